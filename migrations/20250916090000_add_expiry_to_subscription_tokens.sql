@@ -0,0 +1,4 @@
+-- Subscription tokens are only valid for a limited amount of time
+ALTER TABLE subscription_tokens ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
+ALTER TABLE subscription_tokens ADD COLUMN expires_at timestamptz NOT NULL DEFAULT now() + interval '24 hours';
+ALTER TABLE subscription_tokens ALTER COLUMN expires_at DROP DEFAULT;
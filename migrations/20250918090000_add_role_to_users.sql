@@ -0,0 +1,6 @@
+-- Add role and activation flag to users
+-- Existing users keep full access.
+ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'owner'
+  CHECK (role IN ('owner', 'editor', 'viewer'));
+ALTER TABLE users ALTER COLUMN role SET DEFAULT 'viewer';
+ALTER TABLE users ADD COLUMN is_active BOOLEAN NOT NULL DEFAULT TRUE;
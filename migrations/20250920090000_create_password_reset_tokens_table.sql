@@ -0,0 +1,13 @@
+-- Create Password Reset Tokens Table
+ALTER TABLE users ADD COLUMN email TEXT NULL UNIQUE;
+-- Sessions established before this instant are no longer valid.
+ALTER TABLE users ADD COLUMN sessions_invalidated_at timestamptz NULL;
+CREATE TABLE password_reset_tokens(
+  -- SHA-256 of the token, we never store the token itself
+  token_hash TEXT NOT NULL,
+  user_id uuid NOT NULL
+  REFERENCES users (user_id),
+  created_at timestamptz NOT NULL,
+  expires_at timestamptz NOT NULL,
+  PRIMARY KEY (token_hash)
+);
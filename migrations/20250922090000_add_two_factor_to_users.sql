@@ -0,0 +1,13 @@
+-- Add Two Factor Authentication To Users
+-- Base32 encoded TOTP shared secret, NULL until the user enrolls.
+ALTER TABLE users ADD COLUMN totp_secret TEXT NULL;
+-- Last accepted 30 seconds time step, a code cannot be used twice.
+ALTER TABLE users ADD COLUMN totp_last_step BIGINT NULL;
+CREATE TABLE recovery_codes(
+  user_id uuid NOT NULL
+  REFERENCES users (user_id),
+  -- SHA-256 of the code, we never store the code itself
+  code_hash TEXT NOT NULL,
+  used_at timestamptz NULL,
+  PRIMARY KEY (user_id, code_hash)
+);
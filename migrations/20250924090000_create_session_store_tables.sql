@@ -0,0 +1,36 @@
+-- Create Session Store Tables
+-- Used instead of Redis when `session.store` is `postgres`.
+CREATE TABLE sessions(
+  -- SHA-256 of the key in the session cookie, a leaked table does not
+  -- hand out live sessions.
+  session_key_hash TEXT PRIMARY KEY,
+  state TEXT NOT NULL,
+  expires_at timestamptz NOT NULL
+);
+CREATE INDEX sessions_expires_at_idx ON sessions (expires_at);
+
+CREATE TABLE session_registry(
+  session_id uuid PRIMARY KEY,
+  user_id uuid NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+  created_at timestamptz NOT NULL,
+  last_seen_at timestamptz NOT NULL,
+  ip TEXT NULL,
+  user_agent TEXT NULL,
+  expires_at timestamptz NOT NULL
+);
+CREATE INDEX session_registry_user_id_idx ON session_registry (user_id);
+
+CREATE TABLE login_failures(
+  kind TEXT NOT NULL,
+  identifier TEXT NOT NULL,
+  failures BIGINT NOT NULL,
+  expires_at timestamptz NOT NULL,
+  PRIMARY KEY (kind, identifier)
+);
+
+CREATE TABLE login_lockouts(
+  kind TEXT NOT NULL,
+  identifier TEXT NOT NULL,
+  expires_at timestamptz NOT NULL,
+  PRIMARY KEY (kind, identifier)
+);
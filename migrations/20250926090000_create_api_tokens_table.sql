@@ -0,0 +1,13 @@
+-- Create API Tokens Table
+CREATE TABLE api_tokens(
+  token_id uuid PRIMARY KEY,
+  user_id uuid NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+  name TEXT NOT NULL,
+  -- SHA-256 of the token, it is only shown once when created
+  token_hash TEXT NOT NULL UNIQUE,
+  scopes TEXT[] NOT NULL,
+  created_at timestamptz NOT NULL,
+  last_used_at timestamptz NULL,
+  revoked_at timestamptz NULL
+);
+CREATE INDEX api_tokens_user_id_idx ON api_tokens (user_id);
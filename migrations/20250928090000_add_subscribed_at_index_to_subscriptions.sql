@@ -0,0 +1,2 @@
+-- Walked backwards by the keyset pagination of /admin/subscribers
+CREATE INDEX subscriptions_subscribed_at_id_idx ON subscriptions (subscribed_at, id);
@@ -0,0 +1,12 @@
+-- Create Subscriber Imports Table
+CREATE TABLE subscriber_imports(
+  import_id uuid PRIMARY KEY,
+  user_id uuid NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+  -- 'confirmed' or 'double_opt_in'
+  mode TEXT NOT NULL,
+  imported_count INTEGER NOT NULL,
+  -- CSV of the rows that were not imported, with the reason why
+  report TEXT NOT NULL,
+  created_at timestamptz NOT NULL
+);
+CREATE INDEX subscriber_imports_user_id_idx ON subscriber_imports (user_id);
@@ -0,0 +1,16 @@
+-- Addresses erased at the request of their owner, which must never be mailed
+-- again. Only a hash of the lowercased address is kept.
+CREATE TABLE suppressed_emails(
+  email_hash TEXT NOT NULL,
+  suppressed_at timestamptz NOT NULL,
+  PRIMARY KEY (email_hash)
+);
+-- What was done with personal data, kept after the data itself is gone.
+CREATE TABLE audit_log(
+  audit_id uuid NOT NULL,
+  action TEXT NOT NULL,
+  subject_id uuid NOT NULL,
+  created_at timestamptz NOT NULL,
+  PRIMARY KEY (audit_id)
+);
+CREATE INDEX audit_log_subject_id_idx ON audit_log (subject_id);
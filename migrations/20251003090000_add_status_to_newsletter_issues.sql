@@ -0,0 +1,20 @@
+-- Issues can be saved as drafts and scheduled before they are sent:
+-- 'draft', 'scheduled', 'sending' (deliveries queued) or 'sent'
+ALTER TABLE newsletter_issues ADD COLUMN status TEXT NULL;
+UPDATE newsletter_issues i
+SET status = CASE
+    WHEN EXISTS (
+        SELECT 1 FROM issue_delivery_queue q
+        WHERE q.newsletter_issue_id = i.newsletter_issue_id
+    ) THEN 'sending'
+    ELSE 'sent'
+END;
+ALTER TABLE newsletter_issues ALTER COLUMN status SET NOT NULL;
+-- Only set while the issue is scheduled
+ALTER TABLE newsletter_issues ADD COLUMN scheduled_at timestamptz NULL;
+-- Drafts and scheduled issues are not published yet
+ALTER TABLE newsletter_issues ALTER COLUMN published_at DROP NOT NULL;
+ALTER TABLE newsletter_issues ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
+UPDATE newsletter_issues SET created_at = published_at;
+CREATE INDEX newsletter_issues_scheduled_at_idx ON newsletter_issues (scheduled_at)
+  WHERE status = 'scheduled';
@@ -0,0 +1,3 @@
+-- The source of issues authored in Markdown, their text and HTML bodies are
+-- rendered from it unless written by hand.
+ALTER TABLE newsletter_issues ADD COLUMN content_markdown TEXT NULL;
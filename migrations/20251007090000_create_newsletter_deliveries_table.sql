@@ -0,0 +1,12 @@
+-- What was sent to each subscriber, for their data export. The rows go away
+-- with the subscriber.
+CREATE TABLE newsletter_deliveries(
+  newsletter_issue_id uuid NOT NULL
+  REFERENCES newsletter_issues (newsletter_issue_id),
+  subscriber_id uuid NOT NULL
+  REFERENCES subscriptions (id),
+  outcome TEXT NOT NULL,
+  attempted_at timestamptz NOT NULL,
+  PRIMARY KEY (newsletter_issue_id, subscriber_id)
+);
+CREATE INDEX newsletter_deliveries_subscriber_id_idx ON newsletter_deliveries (subscriber_id);
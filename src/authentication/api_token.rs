@@ -0,0 +1,235 @@
+//! src/authentication/api_token.rs
+use crate::authentication::{Permission, Role};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rand::distributions::Alphanumeric;
+use rand::{Rng, thread_rng};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// What an API token may be used for, on top of what the role of its owner
+/// allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiScope {
+    SubscribersRead,
+    SubscribersWrite,
+    NewslettersRead,
+    NewslettersPublish,
+}
+
+impl ApiScope {
+    pub const ALL: [ApiScope; 4] = [
+        ApiScope::SubscribersRead,
+        ApiScope::SubscribersWrite,
+        ApiScope::NewslettersRead,
+        ApiScope::NewslettersPublish,
+    ];
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "subscribers:read" => Ok(ApiScope::SubscribersRead),
+            "subscribers:write" => Ok(ApiScope::SubscribersWrite),
+            "newsletters:read" => Ok(ApiScope::NewslettersRead),
+            "newsletters:publish" => Ok(ApiScope::NewslettersPublish),
+            other => Err(format!("{other} is not a valid scope.")),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::SubscribersRead => "subscribers:read",
+            ApiScope::SubscribersWrite => "subscribers:write",
+            ApiScope::NewslettersRead => "newsletters:read",
+            ApiScope::NewslettersPublish => "newsletters:publish",
+        }
+    }
+
+    /// The permission the owner of the token needs for the scope to be of any use.
+    pub fn permission(&self) -> Permission {
+        match self {
+            ApiScope::SubscribersRead => Permission::ReadSubscribers,
+            ApiScope::SubscribersWrite => Permission::ManageSubscribers,
+            ApiScope::NewslettersRead => Permission::ReadNewsletters,
+            ApiScope::NewslettersPublish => Permission::PublishNewsletters,
+        }
+    }
+}
+
+impl std::fmt::Display for ApiScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An API token as listed to its owner, the token itself is never stored.
+pub struct ApiToken {
+    pub token_id: Uuid,
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// What `reject_invalid_api_tokens` learns from a valid token.
+pub struct ApiTokenOwner {
+    pub token_id: Uuid,
+    pub user_id: Uuid,
+    pub role: Role,
+    pub scopes: Vec<ApiScope>,
+}
+
+fn generate_api_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(40)
+        .collect()
+}
+
+fn hash_api_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn parse_scopes(scopes: &[String]) -> Result<Vec<ApiScope>, anyhow::Error> {
+    scopes
+        .iter()
+        .map(|s| ApiScope::parse(s).map_err(anyhow::Error::msg))
+        .collect()
+}
+
+/// Returns the id of the new token and the token itself, which the caller
+/// must show to the user: it cannot be recovered afterwards.
+#[tracing::instrument(name = "Create API token", skip(pool))]
+pub async fn create_api_token(
+    pool: &PgPool,
+    user_id: Uuid,
+    name: &str,
+    scopes: &[ApiScope],
+) -> Result<(Uuid, String), anyhow::Error> {
+    let token_id = Uuid::new_v4();
+    let token = generate_api_token();
+    let scopes: Vec<String> = scopes.iter().map(|s| s.as_str().to_owned()).collect();
+    sqlx::query!(
+        r#"
+    INSERT INTO api_tokens (token_id, user_id, name, token_hash, scopes, created_at)
+    VALUES ($1, $2, $3, $4, $5, now())
+    "#,
+        token_id,
+        user_id,
+        name,
+        hash_api_token(&token),
+        &scopes
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the API token.")?;
+    Ok((token_id, token))
+}
+
+/// The tokens of `user_id` that have not been revoked, newest first.
+#[tracing::instrument(name = "List API tokens", skip(pool))]
+pub async fn list_api_tokens(pool: &PgPool, user_id: Uuid) -> Result<Vec<ApiToken>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+    SELECT token_id, name, scopes, created_at, last_used_at
+    FROM api_tokens
+    WHERE user_id = $1 AND revoked_at IS NULL
+    ORDER BY created_at DESC
+    "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the API tokens.")?;
+    rows.into_iter()
+        .map(|r| {
+            Ok(ApiToken {
+                token_id: r.token_id,
+                name: r.name,
+                scopes: parse_scopes(&r.scopes)?,
+                created_at: r.created_at,
+                last_used_at: r.last_used_at,
+            })
+        })
+        .collect()
+}
+
+/// Returns `false` if `user_id` has no such token, or it was already revoked.
+#[tracing::instrument(name = "Revoke API token", skip(pool))]
+pub async fn revoke_api_token(
+    pool: &PgPool,
+    user_id: Uuid,
+    token_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"
+    UPDATE api_tokens SET revoked_at = now()
+    WHERE token_id = $1 AND user_id = $2 AND revoked_at IS NULL
+    "#,
+        token_id,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to revoke the API token.")?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Look up the owner of `token` and record that the token was used. Revoked
+/// tokens and tokens of deactivated users are not valid.
+#[tracing::instrument(name = "Authenticate API token", skip_all)]
+pub async fn authenticate_api_token(
+    pool: &PgPool,
+    token: &str,
+) -> Result<Option<ApiTokenOwner>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+    UPDATE api_tokens t SET last_used_at = now()
+    FROM users u
+    WHERE t.token_hash = $1 AND t.revoked_at IS NULL
+      AND u.user_id = t.user_id AND u.is_active
+    RETURNING t.token_id, t.user_id, t.scopes, u.role
+    "#,
+        hash_api_token(token)
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up the API token.")?;
+    row.map(|r| {
+        Ok(ApiTokenOwner {
+            token_id: r.token_id,
+            user_id: r.user_id,
+            role: Role::parse(&r.role).map_err(anyhow::Error::msg)?,
+            scopes: parse_scopes(&r.scopes)?,
+        })
+    })
+    .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ApiScope;
+    use crate::authentication::Role;
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn scopes_round_trip_through_their_string_representation() {
+        for scope in ApiScope::ALL {
+            assert_ok_eq!(ApiScope::parse(scope.as_str()), scope);
+        }
+    }
+
+    #[test]
+    fn unknown_scopes_are_rejected() {
+        assert_err!(ApiScope::parse("newsletters:delete"));
+        assert_err!(ApiScope::parse(""));
+    }
+
+    #[test]
+    fn viewers_cannot_make_use_of_the_publish_scope() {
+        assert!(Role::Viewer.can(ApiScope::SubscribersRead.permission()));
+        assert!(!Role::Viewer.can(ApiScope::NewslettersPublish.permission()));
+        assert!(Role::Editor.can(ApiScope::NewslettersPublish.permission()));
+    }
+}
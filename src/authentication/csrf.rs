@@ -0,0 +1,141 @@
+//! src/authentication/csrf.rs
+use crate::routes::e500;
+use crate::session_state::TypedSession;
+use actix_multipart::Multipart;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::PayloadError;
+use actix_web::http::Method;
+use actix_web::http::header::{CONTENT_TYPE, HeaderMap};
+use actix_web::middleware::Next;
+use actix_web::{FromRequest, HttpMessage, web};
+use futures_util::StreamExt;
+
+/// Name of the hidden field every rendered form carries.
+pub const CSRF_TOKEN_FIELD: &str = "csrf_token";
+/// Alternative to the form field, for requests that are not form posts.
+pub const CSRF_TOKEN_HEADER: &str = "X-CSRF-Token";
+/// Largest `multipart/form-data` body read to find the token, i.e. the
+/// largest file upload, see `routes::import_subscribers`.
+pub const MAX_MULTIPART_BODY_BYTES: usize = 10 * 1024 * 1024;
+
+/// Reject state-changing requests that do not carry the CSRF token stored in
+/// the session, either as the `csrf_token` form field or as the
+/// `X-CSRF-Token` header. Safe methods go through untouched.
+///
+/// The form body is read to find the token, then handed back to the request
+/// so that handlers can still extract it. Both url-encoded and multipart
+/// forms are understood.
+pub async fn require_csrf_token(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return next.call(req).await;
+    }
+
+    let session = {
+        let (http_request, payload) = req.parts_mut();
+        TypedSession::from_request(http_request, payload).await
+    }?;
+    let expected = session.get_csrf_token().map_err(e500)?;
+
+    let submitted = match req.headers().get(CSRF_TOKEN_HEADER) {
+        Some(value) => value.to_str().ok().map(str::to_owned),
+        None if is_multipart(req.headers()) => {
+            let body = read_body(&mut req, MAX_MULTIPART_BODY_BYTES).await?;
+            let token = multipart_field(req.headers(), body.clone(), CSRF_TOKEN_FIELD).await;
+            let (_, mut payload) = actix_http::h1::Payload::create(true);
+            payload.unread_data(body);
+            req.set_payload(payload.into());
+            token
+        }
+        None => {
+            let body = req.extract::<web::Bytes>().await?;
+            let token = serde_urlencoded::from_bytes::<Vec<(String, String)>>(&body)
+                .ok()
+                .and_then(|fields| {
+                    fields
+                        .into_iter()
+                        .find(|(name, _)| name == CSRF_TOKEN_FIELD)
+                        .map(|(_, value)| value)
+                });
+            let (_, mut payload) = actix_http::h1::Payload::create(true);
+            payload.unread_data(body);
+            req.set_payload(payload.into());
+            token
+        }
+    };
+
+    match (expected, submitted) {
+        (Some(expected), Some(submitted))
+            if constant_time_eq(expected.as_bytes(), submitted.as_bytes()) =>
+        {
+            next.call(req).await
+        }
+        _ => Err(actix_web::error::ErrorForbidden(
+            "The form has expired or was not submitted from this site, please try again.",
+        )),
+    }
+}
+
+fn is_multipart(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|h| h.starts_with("multipart/form-data"))
+}
+
+/// `web::Bytes` would stop at the default payload limit, far too small for uploads.
+async fn read_body(req: &mut ServiceRequest, limit: usize) -> Result<web::Bytes, actix_web::Error> {
+    let mut payload = req.take_payload();
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if body.len() + chunk.len() > limit {
+            return Err(PayloadError::Overflow.into());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body.freeze())
+}
+
+/// The value of the first field called `name`, if it is text.
+async fn multipart_field(headers: &HeaderMap, body: web::Bytes, name: &str) -> Option<String> {
+    let stream = futures_util::stream::once(async move { Ok::<_, PayloadError>(body) });
+    let mut multipart = Multipart::new(headers, stream);
+    while let Some(Ok(mut field)) = multipart.next().await {
+        if field.name() != Some(name) {
+            continue;
+        }
+        let mut value = Vec::new();
+        while let Some(chunk) = field.next().await {
+            value.extend_from_slice(&chunk.ok()?);
+        }
+        return String::from_utf8(value).ok();
+    }
+    None
+}
+
+/// Does not stop at the first differing byte, so the response time does not
+/// reveal how much of a guessed token is correct.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::constant_time_eq;
+
+    #[test]
+    fn equal_tokens_match() {
+        assert!(constant_time_eq(b"a-token", b"a-token"));
+    }
+
+    #[test]
+    fn different_tokens_do_not_match() {
+        assert!(!constant_time_eq(b"a-token", b"a-tokem"));
+        assert!(!constant_time_eq(b"a-token", b"a-token-but-longer"));
+        assert!(!constant_time_eq(b"a-token", b""));
+    }
+}
@@ -1,13 +1,24 @@
 //! src/authentication/middleware.rs
 use std::ops::Deref;
 
-use crate::routes::{e500, see_other};
+use crate::authentication::{
+    ApiScope, Permission, Role, SessionRegistry, SessionTimeouts, authenticate_api_token,
+};
+use crate::routes::api::ApiError;
+use crate::routes::{LoginError, e500, see_other};
 use crate::session_state::TypedSession;
-use actix_web::body::MessageBody;
+use crate::startup::client_ip;
+use actix_web::body::{EitherBody, MessageBody};
 use actix_web::dev::{ServiceRequest, ServiceResponse};
 use actix_web::error::InternalError;
+use actix_web::http::StatusCode;
+use actix_web::http::header::{AUTHORIZATION, USER_AGENT};
 use actix_web::middleware::Next;
-use actix_web::{FromRequest, HttpMessage};
+use actix_web::{FromRequest, HttpMessage, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Copy)]
@@ -27,24 +38,238 @@ impl Deref for UserId {
     }
 }
 
-pub async fn reject_anonymous_users(
+/// Only fully authenticated sessions get through: a session waiting for a
+/// second factor has no user id yet, see `TypedSession::insert_pending_second_factor`.
+pub async fn reject_anonymous_users<B: MessageBody>(
     mut req: ServiceRequest,
-    next: Next<impl MessageBody>,
-) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, actix_web::Error> {
     let session = {
         let (http_request, payload) = req.parts_mut();
         TypedSession::from_request(http_request, payload).await
     }?;
 
-    match session.get_user_id().map_err(e500)? {
-        Some(user_id) => {
-            req.extensions_mut().insert(UserId(user_id));
-            next.call(req).await
+    let Some(user_id) = session.get_user_id().map_err(e500)? else {
+        let response = see_other("/login");
+        let e = anyhow::anyhow!("The user has not logged in");
+        return Err(InternalError::from_response(e, response).into());
+    };
+
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .expect("The connection pool is registered as application data.");
+    let registry = req
+        .app_data::<web::Data<SessionRegistry>>()
+        .expect("The session registry is registered as application data.")
+        .clone();
+    let authenticated_at = session.get_authenticated_at().map_err(e500)?;
+    let session_id = session.get_session_id().map_err(e500)?;
+    let timeouts = req
+        .app_data::<web::Data<SessionTimeouts>>()
+        .expect("The session timeouts are registered as application data.");
+    let now = Utc::now().timestamp_micros();
+    let last_seen_at = session.get_last_seen_at().map_err(e500)?;
+    let remember_me = session.get_remember_me().map_err(e500)?.unwrap_or(false);
+    if timeouts.has_expired(authenticated_at, last_seen_at, remember_me, now) {
+        return log_out(
+            req,
+            session,
+            &registry,
+            user_id,
+            "Your session has expired, please log in again.",
+            "The session timed out",
+        )
+        .await;
+    }
+
+    let (message, reason) = match get_active_user(pool, user_id).await.map_err(e500)? {
+        Some(user) if !user.has_invalidated(authenticated_at) => {
+            let is_live = match session_id {
+                Some(session_id) => registry.touch(user_id, session_id).await.map_err(e500)?,
+                None => {
+                    // Sessions created before the registry existed.
+                    let user_agent = req.headers().get(USER_AGENT).and_then(|h| h.to_str().ok());
+                    let ip = client_ip(req.request());
+                    let session_id = registry
+                        .register(user_id, ip, user_agent)
+                        .await
+                        .map_err(e500)?;
+                    session.insert_session_id(session_id).map_err(e500)?;
+                    true
+                }
+            };
+            if is_live {
+                session.insert_last_seen_at(now).map_err(e500)?;
+                req.extensions_mut().insert(UserId(user_id));
+                req.extensions_mut().insert(user.role);
+                return next
+                    .call(req)
+                    .await
+                    .map(ServiceResponse::map_into_left_body);
+            }
+            // Signed out from another session, see `routes::revoke_session`.
+            (
+                "Your session has been signed out, please log in again.",
+                "The session was revoked",
+            )
         }
-        None => {
-            let response = see_other("/login");
-            let e = anyhow::anyhow!("The user has not logged in");
-            Err(InternalError::from_response(e, response).into())
+        Some(_) => (
+            // e.g. the password was reset after this session was created.
+            "Your session is no longer valid, please log in again.",
+            "The session was invalidated",
+        ),
+        None => (
+            // The account was deactivated (or deleted) after the session was created.
+            "Your account has been deactivated.",
+            "The user account is no longer active",
+        ),
+    };
+    log_out(req, session, &registry, user_id, message, reason).await
+}
+
+/// End the session, remove it from the registry and send the user back to
+/// the login page with `message`.
+async fn log_out<B>(
+    req: ServiceRequest,
+    session: TypedSession,
+    registry: &SessionRegistry,
+    user_id: Uuid,
+    message: &'static str,
+    reason: &'static str,
+) -> Result<ServiceResponse<EitherBody<B>>, actix_web::Error> {
+    if let Some(session_id) = session.get_session_id().map_err(e500)? {
+        registry.revoke(user_id, session_id).await.map_err(e500)?;
+    }
+    tracing::info!(reason, "Logging the session out.");
+    session.logout();
+    FlashMessage::error(message).send();
+    // Not an error: the session and flash message middlewares only write
+    // their cookies on successful responses.
+    Ok(req.into_response(see_other("/login")).map_into_right_body())
+}
+
+/// Reject requests from users whose role does not grant `permission`.
+///
+/// Must run after `reject_anonymous_users`, which puts the user's `Role` in the
+/// request extensions. Wrap it in a closure to pick the permission for a route,
+/// see `startup::run`.
+pub async fn require_permission(
+    permission: Permission,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let role = req.extensions().get::<Role>().copied();
+    match role {
+        Some(role) if role.can(permission) => next.call(req).await,
+        _ => Err(actix_web::error::ErrorForbidden(
+            "You are not allowed to perform this action.",
+        )),
+    }
+}
+
+/// The scopes of the API token a request was authenticated with.
+#[derive(Debug, Clone)]
+struct ApiTokenScopes(Vec<ApiScope>);
+
+/// The API counterpart of `reject_anonymous_users`: only requests with a
+/// valid `Authorization: Bearer` token get through, with the same `UserId`
+/// and `Role` in their extensions as a browser session would have.
+pub async fn reject_invalid_api_tokens(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let Some(token) = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+    else {
+        return Err(unauthorized("Missing bearer token."));
+    };
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .expect("The connection pool is registered as application data.");
+    let Some(owner) = authenticate_api_token(pool, token.trim())
+        .await
+        .map_err(|e| ApiError::from(LoginError::UnexpectedError(e)))?
+    else {
+        return Err(unauthorized("Invalid API token."));
+    };
+    tracing::info!(
+        token_id = %owner.token_id,
+        user_id = %owner.user_id,
+        "Authenticated with an API token."
+    );
+    req.extensions_mut().insert(UserId(owner.user_id));
+    req.extensions_mut().insert(owner.role);
+    req.extensions_mut().insert(ApiTokenScopes(owner.scopes));
+    next.call(req).await
+}
+
+fn unauthorized(message: &'static str) -> actix_web::Error {
+    ApiError::from(LoginError::AuthError(anyhow::anyhow!(message))).into()
+}
+
+/// Reject requests whose API token lacks `scope`, or whose owner no longer
+/// has the permission the scope relies on.
+///
+/// Must run after `reject_invalid_api_tokens`, wrap it in a closure like
+/// `require_permission`.
+pub async fn require_scope(
+    scope: ApiScope,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let has_scope = req
+        .extensions()
+        .get::<ApiTokenScopes>()
+        .is_some_and(|scopes| scopes.0.contains(&scope));
+    let role = req.extensions().get::<Role>().copied();
+    match role {
+        Some(role) if has_scope && role.can(scope.permission()) => next.call(req).await,
+        _ => Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            format!("The API token is not allowed to use the {scope} scope."),
+        )
+        .into()),
+    }
+}
+
+struct ActiveUser {
+    role: Role,
+    sessions_invalidated_at: Option<DateTime<Utc>>,
+}
+
+impl ActiveUser {
+    /// `authenticated_at` is the login time stored in the session, sessions
+    /// created before we started recording it are treated as the oldest possible.
+    fn has_invalidated(&self, authenticated_at: Option<i64>) -> bool {
+        match self.sessions_invalidated_at {
+            Some(invalidated_at) => {
+                authenticated_at.unwrap_or(i64::MIN) <= invalidated_at.timestamp_micros()
+            }
+            None => false,
         }
     }
 }
+
+#[tracing::instrument(name = "Get active user", skip(pool))]
+async fn get_active_user(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Option<ActiveUser>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT role, sessions_invalidated_at FROM users WHERE user_id = $1 AND is_active"#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to retrieve the user role.")?;
+    row.map(|r| {
+        Ok(ActiveUser {
+            role: Role::parse(&r.role).map_err(anyhow::Error::msg)?,
+            sessions_invalidated_at: r.sessions_invalidated_at,
+        })
+    })
+    .transpose()
+}
@@ -1,7 +1,28 @@
 //! src/authentication/mod.rs
 
+mod api_token;
+mod csrf;
 mod middleware;
 mod password;
+mod role;
+mod session_registry;
+mod session_timeouts;
+mod throttle;
+mod two_factor;
+pub use api_token::{
+    ApiScope, ApiToken, ApiTokenOwner, authenticate_api_token, create_api_token, list_api_tokens,
+    revoke_api_token,
+};
+pub use csrf::{CSRF_TOKEN_HEADER, MAX_MULTIPART_BODY_BYTES, require_csrf_token};
 pub use middleware::UserId;
-pub use middleware::reject_anonymous_users;
+pub use middleware::{
+    reject_anonymous_users, reject_invalid_api_tokens, require_permission, require_scope,
+};
 pub use password::*;
+pub use role::{Permission, Role};
+pub use session_registry::{SessionMetadata, SessionRegistry};
+pub use session_timeouts::{
+    SessionTimeouts, apply_session_cookie_max_age, pick_session_cookie_max_age,
+};
+pub use throttle::{LockoutKind, LoginAttempt, LoginThrottle, ThrottleDecision};
+pub use two_factor::*;
@@ -1,7 +1,9 @@
 //! src/authentication/password.rs
+use crate::configuration::PasswordHashingSettings;
 use crate::telemetry;
 use anyhow::Context;
-use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use argon2::password_hash::SaltString;
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
 use secrecy::ExposeSecret;
 use secrecy::Secret;
 use sqlx::PgPool;
@@ -39,7 +41,7 @@ async fn get_stored_credentials(
     username: &str,
 ) -> Result<Option<(uuid::Uuid, Secret<String>)>, anyhow::Error> {
     let row = sqlx::query!(
-        r#"SELECT user_id, password_hash FROM users WHERE username = $1;"#,
+        r#"SELECT user_id, password_hash FROM users WHERE username = $1 AND is_active;"#,
         username,
     )
     .fetch_optional(pool)
@@ -49,6 +51,49 @@ async fn get_stored_credentials(
     Ok(row)
 }
 
+/// Argon2id with the cost parameters from `PasswordHashingSettings`, shared by
+/// everything that hashes or verifies passwords.
+pub struct PasswordHashing {
+    params: Params,
+    /// Verified against when the username does not exist, so that unknown
+    /// usernames take as long to reject as wrong passwords.
+    dummy_hash: Secret<String>,
+}
+
+impl PasswordHashing {
+    pub fn new(settings: &PasswordHashingSettings) -> Result<Self, anyhow::Error> {
+        let params = Params::new(
+            settings.memory_size_kib,
+            settings.iterations,
+            settings.parallelism,
+            None,
+        )
+        .map_err(anyhow::Error::msg)
+        .context("Invalid Argon2 parameters.")?;
+        let dummy_password = Secret::new(uuid::Uuid::new_v4().to_string());
+        let dummy_hash = Secret::new(hash_password(&dummy_password, params.clone())?);
+        Ok(Self { params, dummy_hash })
+    }
+
+    /// Whether `hash` was computed with another algorithm, version or cost
+    /// parameters than the ones currently configured.
+    fn needs_rehash(&self, hash: &PasswordHash<'_>) -> bool {
+        if hash.algorithm != Algorithm::Argon2id.ident()
+            || hash.version != Some(Version::V0x13 as u32)
+        {
+            return true;
+        }
+        match Params::try_from(hash) {
+            Ok(params) => {
+                params.m_cost() != self.params.m_cost()
+                    || params.t_cost() != self.params.t_cost()
+                    || params.p_cost() != self.params.p_cost()
+            }
+            Err(_) => true,
+        }
+    }
+}
+
 #[tracing::instrument(name = "Verify password hash", skip(password, expected_password_hash))]
 fn verify_password(
     password: Secret<String>,
@@ -57,24 +102,21 @@ fn verify_password(
     let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())
         .context("Failed to parse hash in PHC format.")
         .map_err(AuthError::UnexpectedError)?;
+    // The algorithm and the cost parameters are read from the PHC string.
     Argon2::default()
         .verify_password(password.expose_secret().as_bytes(), &expected_password_hash)
         .context("Failed to verify password")
         .map_err(AuthError::InvalidCredentials)
 }
 
-#[tracing::instrument(name = "Validate credentials", skip(credentials, pool))]
+#[tracing::instrument(name = "Validate credentials", skip(credentials, pool, hashing))]
 pub async fn validate_credentials(
     credentials: Credentials,
     pool: &PgPool,
+    hashing: &PasswordHashing,
 ) -> Result<uuid::Uuid, AuthError> {
     let mut user_id = None;
-    let mut expected_password_hash = Secret::new(
-        "$argon2id$v=19$m=15000,t=2,p=1$\
-gZiV/M1gPc22ElAH/Jh1Hw$\
-CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
-            .to_string(),
-    );
+    let mut expected_password_hash = hashing.dummy_hash.clone();
     if let Some((stored_user_id, stored_expected_password_hash)) =
         get_stored_credentials(pool, &credentials.username)
             .await
@@ -83,6 +125,8 @@ CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
         user_id = Some(stored_user_id);
         expected_password_hash = stored_expected_password_hash;
     };
+    let password = credentials.password.clone();
+    let stored_password_hash = expected_password_hash.clone();
     telemetry::spawn_blocking_with_tracing(move || {
         verify_password(credentials.password, expected_password_hash)
     })
@@ -90,5 +134,73 @@ CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
     .context("Failed to spawn blocking task.")
     .map_err(AuthError::UnexpectedError)??;
 
-    user_id.ok_or_else(|| AuthError::InvalidCredentials(anyhow::anyhow!("Unknown username.")))
+    let user_id = user_id
+        .ok_or_else(|| AuthError::InvalidCredentials(anyhow::anyhow!("Unknown username.")))?;
+    let parsed_hash = PasswordHash::new(stored_password_hash.expose_secret())
+        .context("Failed to parse hash in PHC format.")
+        .map_err(AuthError::UnexpectedError)?;
+    if hashing.needs_rehash(&parsed_hash) {
+        // The user is not kept waiting for a second hash computation.
+        tokio::spawn(upgrade_password_hash(
+            pool.clone(),
+            hashing.params.clone(),
+            user_id,
+            password,
+            stored_password_hash,
+        ));
+    }
+    Ok(user_id)
+}
+
+/// Rehash the password with the current parameters. The update is skipped if
+/// the password was changed in the meantime.
+#[tracing::instrument(name = "Upgrade password hash", skip(pool, params, password, old_hash))]
+async fn upgrade_password_hash(
+    pool: PgPool,
+    params: Params,
+    user_id: uuid::Uuid,
+    password: Secret<String>,
+    old_hash: Secret<String>,
+) {
+    let result = async {
+        let new_hash =
+            telemetry::spawn_blocking_with_tracing(move || hash_password(&password, params))
+                .await
+                .context("Failed to spawn blocking task.")??;
+        sqlx::query!(
+            r#"UPDATE users SET password_hash = $1 WHERE user_id = $2 AND password_hash = $3"#,
+            new_hash,
+            user_id,
+            old_hash.expose_secret()
+        )
+        .execute(&pool)
+        .await
+        .context("Failed to save the upgraded password hash.")?;
+        Ok::<(), anyhow::Error>(())
+    }
+    .await;
+    if let Err(e) = result {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to upgrade the password hash."
+        );
+    }
+}
+
+fn hash_password(password: &Secret<String>, params: Params) -> Result<String, anyhow::Error> {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let password_hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password(password.expose_secret().as_bytes(), &salt)
+        .context("Failed to hash password.")?
+        .to_string();
+    Ok(password_hash)
+}
+
+/// Hash a password into a PHC string, ready to be stored in `users.password_hash`.
+pub fn compute_password_hash(
+    password: &Secret<String>,
+    hashing: &PasswordHashing,
+) -> Result<String, anyhow::Error> {
+    hash_password(password, hashing.params.clone())
 }
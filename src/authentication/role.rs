@@ -0,0 +1,104 @@
+//! src/authentication/role.rs
+
+/// What an admin user is allowed to do, from least to most privileged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Viewer,
+    Editor,
+    Owner,
+}
+
+/// An action guarded by `require_permission`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    ReadSubscribers,
+    ManageSubscribers,
+    ReadNewsletters,
+    PublishNewsletters,
+    ManageUsers,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Owner, Role::Editor, Role::Viewer];
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "owner" => Ok(Role::Owner),
+            "editor" => Ok(Role::Editor),
+            "viewer" => Ok(Role::Viewer),
+            other => Err(format!("{other} is not a valid role.")),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Owner => "owner",
+            Role::Editor => "editor",
+            Role::Viewer => "viewer",
+        }
+    }
+
+    pub fn can(&self, permission: Permission) -> bool {
+        let required = match permission {
+            Permission::ReadSubscribers | Permission::ReadNewsletters => Role::Viewer,
+            Permission::ManageSubscribers => Role::Editor,
+            Permission::PublishNewsletters => Role::Editor,
+            Permission::ManageUsers => Role::Owner,
+        };
+        *self >= required
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Permission, Role};
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn roles_round_trip_through_their_string_representation() {
+        for role in Role::ALL {
+            assert_ok_eq!(Role::parse(role.as_str()), role);
+        }
+    }
+
+    #[test]
+    fn unknown_roles_are_rejected() {
+        assert_err!(Role::parse("admin"));
+        assert_err!(Role::parse(""));
+    }
+
+    #[test]
+    fn every_role_can_read_subscribers_and_newsletters() {
+        for role in Role::ALL {
+            assert!(role.can(Permission::ReadSubscribers));
+            assert!(role.can(Permission::ReadNewsletters));
+        }
+    }
+
+    #[test]
+    fn only_editors_and_owners_can_manage_subscribers() {
+        assert!(Role::Owner.can(Permission::ManageSubscribers));
+        assert!(Role::Editor.can(Permission::ManageSubscribers));
+        assert!(!Role::Viewer.can(Permission::ManageSubscribers));
+    }
+
+    #[test]
+    fn only_editors_and_owners_can_publish_newsletters() {
+        assert!(Role::Owner.can(Permission::PublishNewsletters));
+        assert!(Role::Editor.can(Permission::PublishNewsletters));
+        assert!(!Role::Viewer.can(Permission::PublishNewsletters));
+    }
+
+    #[test]
+    fn only_owners_can_manage_users() {
+        assert!(Role::Owner.can(Permission::ManageUsers));
+        assert!(!Role::Editor.can(Permission::ManageUsers));
+        assert!(!Role::Viewer.can(Permission::ManageUsers));
+    }
+}
@@ -0,0 +1,167 @@
+//! src/authentication/session_registry/mod.rs
+mod postgres_store;
+mod redis_store;
+
+use chrono::{DateTime, Utc};
+use postgres_store::PgRegistryStore;
+use redis::aio::ConnectionManager;
+use redis_store::RedisRegistryStore;
+use sqlx::PgPool;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// `last_seen_at` is only written back once it is this stale, so that a
+/// burst of requests does not turn into a burst of writes.
+const TOUCH_INTERVAL_SECONDS: i64 = 60;
+
+/// Keeps track of the authenticated sessions of every user, so that they
+/// can be listed and revoked from any other session.
+///
+/// `actix_session` does not expose the key it stores a session under: each
+/// session gets its own id instead, see `TypedSession::insert_session_id`.
+/// A session whose id is no longer in the registry has been revoked and is
+/// turned away by `reject_anonymous_users`.
+#[derive(Clone)]
+pub struct SessionRegistry {
+    store: Arc<dyn RegistryStore>,
+    /// How long an unused entry is kept: as long as the session store keeps
+    /// the session it describes, see `SessionTimeouts::longest`.
+    ttl: Duration,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct SessionMetadata {
+    pub session_id: Uuid,
+    pub user_id: Uuid,
+    /// Seconds since the Unix epoch.
+    created_at: i64,
+    /// Seconds since the Unix epoch.
+    last_seen_at: i64,
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+}
+
+impl SessionMetadata {
+    pub fn created_at(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.created_at, 0).unwrap_or_default()
+    }
+
+    pub fn last_seen_at(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.last_seen_at, 0).unwrap_or_default()
+    }
+}
+
+/// Where registry entries are kept. Expired entries must be ignored, whether
+/// or not they have been cleaned up already.
+#[async_trait::async_trait]
+trait RegistryStore: Send + Sync {
+    async fn insert(&self, metadata: &SessionMetadata, ttl: Duration) -> Result<(), anyhow::Error>;
+
+    async fn get(&self, session_id: Uuid) -> Result<Option<SessionMetadata>, anyhow::Error>;
+
+    /// Overwrite an existing entry and push back its expiration. Returns
+    /// `false`, without writing anything, if the entry is gone: a session
+    /// revoked concurrently must stay revoked.
+    async fn update(
+        &self,
+        metadata: &SessionMetadata,
+        ttl: Duration,
+    ) -> Result<bool, anyhow::Error>;
+
+    async fn list(&self, user_id: Uuid) -> Result<Vec<SessionMetadata>, anyhow::Error>;
+
+    /// Returns `false` if the user has no such session: nobody can remove a
+    /// session that is not theirs.
+    async fn remove(&self, user_id: Uuid, session_id: Uuid) -> Result<bool, anyhow::Error>;
+}
+
+impl SessionRegistry {
+    pub fn redis(redis: ConnectionManager, key_prefix: String, ttl: Duration) -> Self {
+        Self {
+            store: Arc::new(RedisRegistryStore::new(redis, key_prefix)),
+            ttl,
+        }
+    }
+
+    pub fn postgres(pool: PgPool, ttl: Duration) -> Self {
+        Self {
+            store: Arc::new(PgRegistryStore::new(pool)),
+            ttl,
+        }
+    }
+
+    /// Record a freshly authenticated session, returns the id to store in it.
+    #[tracing::instrument(name = "Register session", skip(self))]
+    pub async fn register(
+        &self,
+        user_id: Uuid,
+        ip: Option<IpAddr>,
+        user_agent: Option<&str>,
+    ) -> Result<Uuid, anyhow::Error> {
+        let now = Utc::now().timestamp();
+        let metadata = SessionMetadata {
+            session_id: Uuid::new_v4(),
+            user_id,
+            created_at: now,
+            last_seen_at: now,
+            ip,
+            user_agent: user_agent.map(str::to_owned),
+        };
+        self.store.insert(&metadata, self.ttl).await?;
+        Ok(metadata.session_id)
+    }
+
+    /// Mark the session as used just now.
+    ///
+    /// Returns `false` if the session has been revoked (or has expired), in
+    /// which case it must not be let through.
+    #[tracing::instrument(name = "Touch session", skip(self))]
+    pub async fn touch(&self, user_id: Uuid, session_id: Uuid) -> Result<bool, anyhow::Error> {
+        let Some(mut metadata) = self.store.get(session_id).await? else {
+            return Ok(false);
+        };
+        if metadata.user_id != user_id {
+            return Ok(false);
+        }
+        let now = Utc::now().timestamp();
+        if now - metadata.last_seen_at < TOUCH_INTERVAL_SECONDS {
+            return Ok(true);
+        }
+        metadata.last_seen_at = now;
+        self.store.update(&metadata, self.ttl).await
+    }
+
+    /// The live sessions of a user, most recently used first.
+    #[tracing::instrument(name = "List sessions", skip(self))]
+    pub async fn list(&self, user_id: Uuid) -> Result<Vec<SessionMetadata>, anyhow::Error> {
+        let mut sessions = self.store.list(user_id).await?;
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.last_seen_at));
+        Ok(sessions)
+    }
+
+    /// Returns `false` if the user has no such session.
+    #[tracing::instrument(name = "Revoke session", skip(self))]
+    pub async fn revoke(&self, user_id: Uuid, session_id: Uuid) -> Result<bool, anyhow::Error> {
+        self.store.remove(user_id, session_id).await
+    }
+
+    /// Revoke every session of the user but `keep`, returns how many were revoked.
+    #[tracing::instrument(name = "Revoke other sessions", skip(self))]
+    pub async fn revoke_all_except(
+        &self,
+        user_id: Uuid,
+        keep: Option<Uuid>,
+    ) -> Result<u64, anyhow::Error> {
+        let mut revoked = 0;
+        for session in self.store.list(user_id).await? {
+            if Some(session.session_id) != keep
+                && self.store.remove(user_id, session.session_id).await?
+            {
+                revoked += 1;
+            }
+        }
+        Ok(revoked)
+    }
+}
@@ -0,0 +1,135 @@
+//! src/authentication/session_registry/postgres_store.rs
+use super::{RegistryStore, SessionMetadata};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Expired rows are skipped by every query and deleted in the background
+/// by `session_store::run_cleanup_worker_until_stopped`.
+pub(super) struct PgRegistryStore {
+    pool: PgPool,
+}
+
+impl PgRegistryStore {
+    pub(super) fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+struct Row {
+    session_id: Uuid,
+    user_id: Uuid,
+    created_at: DateTime<Utc>,
+    last_seen_at: DateTime<Utc>,
+    ip: Option<String>,
+    user_agent: Option<String>,
+}
+
+impl From<Row> for SessionMetadata {
+    fn from(row: Row) -> Self {
+        SessionMetadata {
+            session_id: row.session_id,
+            user_id: row.user_id,
+            created_at: row.created_at.timestamp(),
+            last_seen_at: row.last_seen_at.timestamp(),
+            ip: row.ip.and_then(|ip| ip.parse().ok()),
+            user_agent: row.user_agent,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl RegistryStore for PgRegistryStore {
+    async fn insert(&self, metadata: &SessionMetadata, ttl: Duration) -> Result<(), anyhow::Error> {
+        sqlx::query!(
+            r#"
+        INSERT INTO session_registry
+            (session_id, user_id, created_at, last_seen_at, ip, user_agent, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+            metadata.session_id,
+            metadata.user_id,
+            metadata.created_at(),
+            metadata.last_seen_at(),
+            metadata.ip.map(|ip| ip.to_string()),
+            metadata.user_agent,
+            Utc::now() + ttl
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to register the session in Postgres.")?;
+        Ok(())
+    }
+
+    async fn get(&self, session_id: Uuid) -> Result<Option<SessionMetadata>, anyhow::Error> {
+        let row = sqlx::query_as!(
+            Row,
+            r#"
+        SELECT session_id, user_id, created_at, last_seen_at, ip, user_agent
+        FROM session_registry
+        WHERE session_id = $1 AND expires_at > now()
+        "#,
+            session_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to read the session from Postgres.")?;
+        Ok(row.map(SessionMetadata::from))
+    }
+
+    async fn update(
+        &self,
+        metadata: &SessionMetadata,
+        ttl: Duration,
+    ) -> Result<bool, anyhow::Error> {
+        let n_updated = sqlx::query!(
+            r#"
+        UPDATE session_registry
+        SET last_seen_at = $2, expires_at = $3
+        WHERE session_id = $1 AND expires_at > now()
+        "#,
+            metadata.session_id,
+            metadata.last_seen_at(),
+            Utc::now() + ttl
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to update the session in Postgres.")?
+        .rows_affected();
+        Ok(n_updated > 0)
+    }
+
+    async fn list(&self, user_id: Uuid) -> Result<Vec<SessionMetadata>, anyhow::Error> {
+        let rows = sqlx::query_as!(
+            Row,
+            r#"
+        SELECT session_id, user_id, created_at, last_seen_at, ip, user_agent
+        FROM session_registry
+        WHERE user_id = $1 AND expires_at > now()
+        "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to read the sessions from Postgres.")?;
+        Ok(rows.into_iter().map(SessionMetadata::from).collect())
+    }
+
+    async fn remove(&self, user_id: Uuid, session_id: Uuid) -> Result<bool, anyhow::Error> {
+        let n_deleted = sqlx::query!(
+            r#"
+        DELETE FROM session_registry
+        WHERE session_id = $1 AND user_id = $2 AND expires_at > now()
+        "#,
+            session_id,
+            user_id
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to delete the session from Postgres.")?
+        .rows_affected();
+        Ok(n_deleted > 0)
+    }
+}
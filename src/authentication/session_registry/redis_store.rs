@@ -0,0 +1,128 @@
+//! src/authentication/session_registry/redis_store.rs
+use super::{RegistryStore, SessionMetadata};
+use anyhow::Context;
+use redis::AsyncCommands;
+use redis::aio::ConnectionManager;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Each entry is a JSON document under its own key, next to a set of the
+/// session ids of each user.
+pub(super) struct RedisRegistryStore {
+    redis: ConnectionManager,
+    /// Lets several environments share a Redis instance.
+    key_prefix: String,
+}
+
+impl RedisRegistryStore {
+    pub(super) fn new(redis: ConnectionManager, key_prefix: String) -> Self {
+        Self { redis, key_prefix }
+    }
+
+    fn session_key(&self, session_id: Uuid) -> String {
+        format!("{}:session:{session_id}", self.key_prefix)
+    }
+
+    fn user_index_key(&self, user_id: Uuid) -> String {
+        format!("{}:user:{user_id}", self.key_prefix)
+    }
+}
+
+#[async_trait::async_trait]
+impl RegistryStore for RedisRegistryStore {
+    async fn insert(&self, metadata: &SessionMetadata, ttl: Duration) -> Result<(), anyhow::Error> {
+        let index_key = self.user_index_key(metadata.user_id);
+        let mut conn = self.redis.clone();
+        redis::pipe()
+            .atomic()
+            .set_ex(
+                self.session_key(metadata.session_id),
+                serde_json::to_string(metadata)?,
+                ttl.as_secs(),
+            )
+            .ignore()
+            .sadd(&index_key, metadata.session_id.to_string())
+            .ignore()
+            .expire(&index_key, ttl.as_secs() as i64)
+            .ignore()
+            .query_async::<()>(&mut conn)
+            .await
+            .context("Failed to register the session in Redis.")
+    }
+
+    async fn get(&self, session_id: Uuid) -> Result<Option<SessionMetadata>, anyhow::Error> {
+        let mut conn = self.redis.clone();
+        let metadata: Option<String> = conn
+            .get(self.session_key(session_id))
+            .await
+            .context("Failed to read the session from Redis.")?;
+        metadata
+            .map(|m| serde_json::from_str(&m).context("Failed to parse the session metadata."))
+            .transpose()
+    }
+
+    async fn update(
+        &self,
+        metadata: &SessionMetadata,
+        ttl: Duration,
+    ) -> Result<bool, anyhow::Error> {
+        let mut conn = self.redis.clone();
+        let updated: Option<String> = conn
+            .set_options(
+                self.session_key(metadata.session_id),
+                serde_json::to_string(metadata)?,
+                redis::SetOptions::default()
+                    .conditional_set(redis::ExistenceCheck::XX)
+                    .with_expiration(redis::SetExpiry::EX(ttl.as_secs())),
+            )
+            .await
+            .context("Failed to update the session in Redis.")?;
+        conn.expire::<_, ()>(self.user_index_key(metadata.user_id), ttl.as_secs() as i64)
+            .await
+            .context("Failed to extend the session index in Redis.")?;
+        Ok(updated.is_some())
+    }
+
+    async fn list(&self, user_id: Uuid) -> Result<Vec<SessionMetadata>, anyhow::Error> {
+        let index_key = self.user_index_key(user_id);
+        let mut conn = self.redis.clone();
+        let session_ids: Vec<String> = conn
+            .smembers(&index_key)
+            .await
+            .context("Failed to read the session index from Redis.")?;
+
+        let mut sessions = Vec::new();
+        for session_id in session_ids {
+            let metadata = match Uuid::parse_str(&session_id) {
+                Ok(id) => self.get(id).await?,
+                Err(_) => None,
+            };
+            match metadata {
+                Some(metadata) => sessions.push(metadata),
+                // The entry expired, tidy up the index.
+                None => conn
+                    .srem::<_, _, ()>(&index_key, &session_id)
+                    .await
+                    .context("Failed to prune the session index in Redis.")?,
+            }
+        }
+        Ok(sessions)
+    }
+
+    async fn remove(&self, user_id: Uuid, session_id: Uuid) -> Result<bool, anyhow::Error> {
+        let mut conn = self.redis.clone();
+        // Removing the id from the user's own index first makes sure nobody
+        // can revoke a session that is not theirs.
+        let removed: u64 = conn
+            .srem(self.user_index_key(user_id), session_id.to_string())
+            .await
+            .context("Failed to remove the session from the index in Redis.")?;
+        if removed == 0 {
+            return Ok(false);
+        }
+        conn.del::<_, ()>(self.session_key(session_id))
+            .await
+            .context("Failed to delete the session from Redis.")?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,186 @@
+//! src/authentication/session_timeouts.rs
+use crate::configuration::ApplicationSettings;
+use crate::session_state::{SESSION_COOKIE_NAME, TypedSession};
+use actix_web::body::MessageBody;
+use actix_web::cookie::Cookie;
+use actix_web::cookie::time::Duration as CookieDuration;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderValue, SET_COOKIE};
+use actix_web::middleware::Next;
+use actix_web::{FromRequest, HttpMessage, web};
+use std::time::Duration;
+
+/// How long an authenticated session may live, enforced by
+/// `reject_anonymous_users` from the timestamps kept in `TypedSession`.
+#[derive(Debug, Clone, Copy)]
+pub struct SessionTimeouts {
+    pub idle: Duration,
+    pub absolute: Duration,
+    pub remember_me: Duration,
+}
+
+impl SessionTimeouts {
+    pub fn new(settings: &ApplicationSettings) -> Self {
+        Self {
+            idle: Duration::from_secs(settings.session_idle_timeout_seconds),
+            absolute: Duration::from_secs(settings.session_absolute_timeout_seconds),
+            remember_me: Duration::from_secs(settings.remember_me_timeout_seconds),
+        }
+    }
+
+    /// What the session store keeps sessions for: the longest any session may live.
+    pub fn longest(&self) -> Duration {
+        self.absolute.max(self.remember_me)
+    }
+
+    /// How long after logging in the session ends.
+    pub fn lifetime(&self, remember_me: bool) -> Duration {
+        if remember_me {
+            self.remember_me
+        } else {
+            self.absolute
+        }
+    }
+
+    /// All timestamps are in microseconds since the Unix epoch. A session
+    /// without a login time predates the timeouts and is treated as expired.
+    pub fn has_expired(
+        &self,
+        authenticated_at: Option<i64>,
+        last_seen_at: Option<i64>,
+        remember_me: bool,
+        now: i64,
+    ) -> bool {
+        let Some(authenticated_at) = authenticated_at else {
+            return true;
+        };
+        let elapsed = |since: i64| Duration::from_micros(now.saturating_sub(since).max(0) as u64);
+        if elapsed(authenticated_at) > self.lifetime(remember_me) {
+            return true;
+        }
+        // Remembered sessions are meant to survive a long absence.
+        !remember_me && elapsed(last_seen_at.unwrap_or(authenticated_at)) > self.idle
+    }
+}
+
+/// The `Max-Age` the session cookie of the current response should carry.
+#[derive(Debug, Clone, Copy)]
+struct SessionCookieMaxAge(Duration);
+
+/// `actix_session` gives every session cookie the same lifetime: pick the
+/// one that fits the session, depending on "remember me".
+///
+/// Must run inside `SessionMiddleware`, i.e. be registered before it: once
+/// the response has gone through it, the session state is gone.
+pub async fn pick_session_cookie_max_age(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let res = next.call(req).await?;
+    let session = TypedSession::extract(res.request()).await?;
+    let remember_me = session.get_remember_me().ok().flatten().unwrap_or(false);
+    let timeouts = res
+        .request()
+        .app_data::<web::Data<SessionTimeouts>>()
+        .expect("The session timeouts are registered as application data.");
+    let max_age = SessionCookieMaxAge(timeouts.lifetime(remember_me));
+    res.request().extensions_mut().insert(max_age);
+    Ok(res)
+}
+
+/// Rewrite the `Max-Age` of the session cookie set by `SessionMiddleware`
+/// with the one picked by `pick_session_cookie_max_age`.
+///
+/// Must wrap `SessionMiddleware`, i.e. be registered after it.
+pub async fn apply_session_cookie_max_age(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let mut res = next.call(req).await?;
+    let Some(SessionCookieMaxAge(max_age)) = res.request().extensions().get().copied() else {
+        return Ok(res);
+    };
+    let headers = res.headers_mut();
+    let set_cookies: Vec<HeaderValue> = headers.get_all(SET_COOKIE).cloned().collect();
+    headers.remove(SET_COOKIE);
+    for value in set_cookies {
+        let rewritten = value
+            .to_str()
+            .ok()
+            .and_then(|v| Cookie::parse_encoded(v).ok())
+            // A cookie being deleted has a `Max-Age` of zero, leave it be.
+            .filter(|c| {
+                c.name() == SESSION_COOKIE_NAME
+                    && c.max_age().is_some_and(|a| a > CookieDuration::ZERO)
+            })
+            .and_then(|mut c| {
+                c.set_max_age(CookieDuration::seconds(max_age.as_secs() as i64));
+                HeaderValue::from_str(&c.encoded().to_string()).ok()
+            });
+        headers.append(SET_COOKIE, rewritten.unwrap_or(value));
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SessionTimeouts;
+    use std::time::Duration;
+
+    const MINUTE: i64 = 60_000_000;
+
+    fn timeouts() -> SessionTimeouts {
+        SessionTimeouts {
+            idle: Duration::from_secs(30 * 60),
+            absolute: Duration::from_secs(12 * 60 * 60),
+            remember_me: Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
+
+    #[test]
+    fn an_active_session_has_not_expired() {
+        let now = 1000 * MINUTE;
+        assert!(!timeouts().has_expired(Some(now - 60 * MINUTE), Some(now - MINUTE), false, now));
+    }
+
+    #[test]
+    fn a_session_idle_for_too_long_has_expired() {
+        let now = 1000 * MINUTE;
+        assert!(timeouts().has_expired(
+            Some(now - 60 * MINUTE),
+            Some(now - 31 * MINUTE),
+            false,
+            now
+        ));
+        // Never used since logging in.
+        assert!(timeouts().has_expired(Some(now - 31 * MINUTE), None, false, now));
+    }
+
+    #[test]
+    fn a_session_older_than_the_absolute_timeout_has_expired() {
+        let now = 10_000 * MINUTE;
+        assert!(timeouts().has_expired(Some(now - 721 * MINUTE), Some(now - MINUTE), false, now));
+    }
+
+    #[test]
+    fn a_remembered_session_ignores_the_idle_timeout() {
+        let now = 10_000 * MINUTE;
+        assert!(!timeouts().has_expired(
+            Some(now - 2000 * MINUTE),
+            Some(now - 2000 * MINUTE),
+            true,
+            now
+        ));
+    }
+
+    #[test]
+    fn a_remembered_session_expires_eventually() {
+        let now = 100_000 * MINUTE;
+        assert!(timeouts().has_expired(Some(now - 43_201 * MINUTE), Some(now - MINUTE), true, now));
+    }
+
+    #[test]
+    fn a_session_without_a_login_time_has_expired() {
+        assert!(timeouts().has_expired(None, None, false, 0));
+    }
+}
@@ -0,0 +1,285 @@
+//! src/authentication/throttle/mod.rs
+mod postgres_store;
+mod redis_store;
+
+use crate::configuration::LoginThrottleSettings;
+use postgres_store::PgThrottleStore;
+use redis::aio::ConnectionManager;
+use redis_store::RedisThrottleStore;
+use sqlx::PgPool;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Counts failed logins per username and per client IP, slows down
+/// repeated failures and locks the username (or IP) out for a while once a
+/// threshold is reached.
+///
+/// Counters live in the session store (Redis or Postgres), so they are shared
+/// by every instance of the application and the limits hold even when the
+/// login form is load balanced.
+#[derive(Clone)]
+pub struct LoginThrottle {
+    store: Arc<dyn ThrottleStore>,
+    settings: LoginThrottleSettings,
+}
+
+/// Who is trying to log in.
+#[derive(Debug)]
+pub struct LoginAttempt<'a> {
+    pub username: &'a str,
+    pub ip: Option<IpAddr>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ThrottleDecision {
+    /// Go ahead and check the password, after waiting this long.
+    Proceed {
+        delay: Duration,
+    },
+    LockedOut,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockoutKind {
+    Username,
+    Ip,
+}
+
+impl LockoutKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LockoutKind::Username => "username",
+            LockoutKind::Ip => "ip",
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "username" => Ok(LockoutKind::Username),
+            "ip" => Ok(LockoutKind::Ip),
+            other => Err(format!("{other} is not a valid lockout kind.")),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Lockout {
+    pub kind: LockoutKind,
+    pub identifier: String,
+    pub expires_in: Duration,
+}
+
+/// Where failure counters and lockouts are kept. Expired entries must be
+/// ignored, whether or not they have been cleaned up already.
+#[async_trait::async_trait]
+trait ThrottleStore: Send + Sync {
+    async fn is_locked_out(
+        &self,
+        kind: LockoutKind,
+        identifier: &str,
+    ) -> Result<bool, anyhow::Error>;
+
+    async fn failures(&self, kind: LockoutKind, identifier: &str) -> Result<u64, anyhow::Error>;
+
+    /// Count one more failure and restart the window, returns the new count.
+    async fn add_failure(
+        &self,
+        kind: LockoutKind,
+        identifier: &str,
+        window: Duration,
+    ) -> Result<u64, anyhow::Error>;
+
+    /// Lock out for `duration`, the failure counter starts over.
+    async fn lock_out(
+        &self,
+        kind: LockoutKind,
+        identifier: &str,
+        duration: Duration,
+    ) -> Result<(), anyhow::Error>;
+
+    async fn reset_failures(
+        &self,
+        kind: LockoutKind,
+        identifier: &str,
+    ) -> Result<(), anyhow::Error>;
+
+    async fn lockouts(&self) -> Result<Vec<Lockout>, anyhow::Error>;
+
+    /// Lift a lockout and reset the failure counter, returns `false` if
+    /// there was no such lockout.
+    async fn clear_lockout(
+        &self,
+        kind: LockoutKind,
+        identifier: &str,
+    ) -> Result<bool, anyhow::Error>;
+}
+
+impl LoginThrottle {
+    pub fn redis(redis: ConnectionManager, settings: LoginThrottleSettings) -> Self {
+        let store = RedisThrottleStore::new(redis, settings.key_prefix.clone());
+        Self {
+            store: Arc::new(store),
+            settings,
+        }
+    }
+
+    pub fn postgres(pool: PgPool, settings: LoginThrottleSettings) -> Self {
+        Self {
+            store: Arc::new(PgThrottleStore::new(pool)),
+            settings,
+        }
+    }
+
+    fn identifiers(attempt: &LoginAttempt<'_>) -> Vec<(LockoutKind, String)> {
+        let mut identifiers = vec![(LockoutKind::Username, attempt.username.to_string())];
+        if let Some(ip) = attempt.ip {
+            identifiers.push((LockoutKind::Ip, ip.to_string()));
+        }
+        identifiers
+    }
+
+    fn threshold(&self, kind: LockoutKind) -> u64 {
+        match kind {
+            LockoutKind::Username => self.settings.max_failures_per_username,
+            LockoutKind::Ip => self.settings.max_failures_per_ip,
+        }
+    }
+
+    /// To be called before verifying the password: locked out attempts must
+    /// be turned away without spending any time on Argon2.
+    #[tracing::instrument(name = "Check login throttle", skip(self))]
+    pub async fn check(
+        &self,
+        attempt: &LoginAttempt<'_>,
+    ) -> Result<ThrottleDecision, anyhow::Error> {
+        let mut failures = 0;
+        for (kind, identifier) in Self::identifiers(attempt) {
+            if self.store.is_locked_out(kind, &identifier).await? {
+                return Ok(ThrottleDecision::LockedOut);
+            }
+            failures = failures.max(self.store.failures(kind, &identifier).await?);
+        }
+        Ok(ThrottleDecision::Proceed {
+            delay: progressive_delay(
+                failures,
+                Duration::from_millis(self.settings.initial_delay_milliseconds),
+                Duration::from_millis(self.settings.max_delay_milliseconds),
+            ),
+        })
+    }
+
+    /// Count a failed attempt against both the username and the client IP,
+    /// locking out whichever reaches its threshold.
+    ///
+    /// Returns the highest of the two counters.
+    #[tracing::instrument(name = "Record failed login", skip(self))]
+    pub async fn record_failure(&self, attempt: &LoginAttempt<'_>) -> Result<u64, anyhow::Error> {
+        let mut failures = 0;
+        for (kind, identifier) in Self::identifiers(attempt) {
+            // The window restarts with every failure, a slow but steady
+            // attacker is still caught.
+            let n = self
+                .store
+                .add_failure(
+                    kind,
+                    &identifier,
+                    Duration::from_secs(self.settings.failure_window_seconds),
+                )
+                .await?;
+            if n >= self.threshold(kind) {
+                tracing::warn!(
+                    lockout.kind = kind.as_str(),
+                    lockout.identifier = %identifier,
+                    "Too many failed logins, locking out."
+                );
+                self.store
+                    .lock_out(
+                        kind,
+                        &identifier,
+                        Duration::from_secs(self.settings.lockout_seconds),
+                    )
+                    .await?;
+            }
+            failures = failures.max(n);
+        }
+        Ok(failures)
+    }
+
+    /// Forget the failures of a username once it has logged in. The IP
+    /// counter is left alone: a single valid account must not give an
+    /// attacker a way to reset it.
+    #[tracing::instrument(name = "Record successful login", skip(self))]
+    pub async fn record_success(&self, username: &str) -> Result<(), anyhow::Error> {
+        self.store
+            .reset_failures(LockoutKind::Username, username)
+            .await
+    }
+
+    #[tracing::instrument(name = "List lockouts", skip(self))]
+    pub async fn list_lockouts(&self) -> Result<Vec<Lockout>, anyhow::Error> {
+        let mut lockouts = self.store.lockouts().await?;
+        lockouts.sort_by(|a, b| {
+            (a.kind.as_str(), &a.identifier).cmp(&(b.kind.as_str(), &b.identifier))
+        });
+        Ok(lockouts)
+    }
+
+    /// Lift a lockout and reset the matching failure counter.
+    ///
+    /// Returns `false` if there was no such lockout.
+    #[tracing::instrument(name = "Clear lockout", skip(self))]
+    pub async fn clear_lockout(
+        &self,
+        kind: LockoutKind,
+        identifier: &str,
+    ) -> Result<bool, anyhow::Error> {
+        self.store.clear_lockout(kind, identifier).await
+    }
+}
+
+/// No delay on the first attempt, then `initial` doubled for every further
+/// failure, capped at `max`.
+fn progressive_delay(failures: u64, initial: Duration, max: Duration) -> Duration {
+    if failures == 0 {
+        return Duration::ZERO;
+    }
+    let factor = 2u32.saturating_pow((failures - 1).min(31) as u32);
+    initial.saturating_mul(factor).min(max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LockoutKind, progressive_delay};
+    use claims::{assert_err, assert_ok_eq};
+    use std::time::Duration;
+
+    const INITIAL: Duration = Duration::from_millis(250);
+    const MAX: Duration = Duration::from_millis(4000);
+
+    #[test]
+    fn there_is_no_delay_without_previous_failures() {
+        assert_eq!(progressive_delay(0, INITIAL, MAX), Duration::ZERO);
+    }
+
+    #[test]
+    fn the_delay_doubles_with_each_failure() {
+        assert_eq!(progressive_delay(1, INITIAL, MAX), INITIAL);
+        assert_eq!(progressive_delay(2, INITIAL, MAX), INITIAL * 2);
+        assert_eq!(progressive_delay(3, INITIAL, MAX), INITIAL * 4);
+    }
+
+    #[test]
+    fn the_delay_is_capped() {
+        assert_eq!(progressive_delay(5, INITIAL, MAX), MAX);
+        assert_eq!(progressive_delay(u64::MAX, INITIAL, MAX), MAX);
+    }
+
+    #[test]
+    fn lockout_kinds_round_trip_through_their_string_representation() {
+        for kind in [LockoutKind::Username, LockoutKind::Ip] {
+            assert_ok_eq!(LockoutKind::parse(kind.as_str()), kind);
+        }
+        assert_err!(LockoutKind::parse("email"));
+    }
+}
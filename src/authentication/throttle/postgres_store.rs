@@ -0,0 +1,199 @@
+//! src/authentication/throttle/postgres_store.rs
+use super::{Lockout, LockoutKind, ThrottleStore};
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+use std::time::Duration;
+
+/// Expired rows are skipped by every query and deleted in the background
+/// by `session_store::run_cleanup_worker_until_stopped`.
+pub(super) struct PgThrottleStore {
+    pool: PgPool,
+}
+
+impl PgThrottleStore {
+    pub(super) fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl ThrottleStore for PgThrottleStore {
+    async fn is_locked_out(
+        &self,
+        kind: LockoutKind,
+        identifier: &str,
+    ) -> Result<bool, anyhow::Error> {
+        let row = sqlx::query!(
+            r#"
+        SELECT EXISTS(
+            SELECT 1 FROM login_lockouts
+            WHERE kind = $1 AND identifier = $2 AND expires_at > now()
+        ) AS "locked_out!"
+        "#,
+            kind.as_str(),
+            identifier
+        )
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to check for a lockout in Postgres.")?;
+        Ok(row.locked_out)
+    }
+
+    async fn failures(&self, kind: LockoutKind, identifier: &str) -> Result<u64, anyhow::Error> {
+        let row = sqlx::query!(
+            r#"
+        SELECT failures FROM login_failures
+        WHERE kind = $1 AND identifier = $2 AND expires_at > now()
+        "#,
+            kind.as_str(),
+            identifier
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to read the failed login counter from Postgres.")?;
+        Ok(row.map(|r| r.failures as u64).unwrap_or_default())
+    }
+
+    async fn add_failure(
+        &self,
+        kind: LockoutKind,
+        identifier: &str,
+        window: Duration,
+    ) -> Result<u64, anyhow::Error> {
+        let expires_at = Utc::now() + window;
+        // An expired counter that has not been cleaned up yet starts over.
+        let row = sqlx::query!(
+            r#"
+        INSERT INTO login_failures (kind, identifier, failures, expires_at)
+        VALUES ($1, $2, 1, $3)
+        ON CONFLICT (kind, identifier) DO UPDATE SET
+            failures = CASE
+                WHEN login_failures.expires_at > now() THEN login_failures.failures + 1
+                ELSE 1
+            END,
+            expires_at = EXCLUDED.expires_at
+        RETURNING failures
+        "#,
+            kind.as_str(),
+            identifier,
+            expires_at
+        )
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to increment the failed login counter in Postgres.")?;
+        Ok(row.failures as u64)
+    }
+
+    async fn lock_out(
+        &self,
+        kind: LockoutKind,
+        identifier: &str,
+        duration: Duration,
+    ) -> Result<(), anyhow::Error> {
+        let expires_at = Utc::now() + duration;
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool")?;
+        sqlx::query!(
+            r#"
+        INSERT INTO login_lockouts (kind, identifier, expires_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (kind, identifier) DO UPDATE SET expires_at = EXCLUDED.expires_at
+        "#,
+            kind.as_str(),
+            identifier,
+            expires_at
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to store the lockout in Postgres.")?;
+        sqlx::query!(
+            r#"DELETE FROM login_failures WHERE kind = $1 AND identifier = $2"#,
+            kind.as_str(),
+            identifier
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to reset the failed login counter in Postgres.")?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to store a lockout.")
+    }
+
+    async fn reset_failures(
+        &self,
+        kind: LockoutKind,
+        identifier: &str,
+    ) -> Result<(), anyhow::Error> {
+        sqlx::query!(
+            r#"DELETE FROM login_failures WHERE kind = $1 AND identifier = $2"#,
+            kind.as_str(),
+            identifier
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to reset the failed login counter in Postgres.")?;
+        Ok(())
+    }
+
+    async fn lockouts(&self) -> Result<Vec<Lockout>, anyhow::Error> {
+        let rows = sqlx::query!(
+            r#"SELECT kind, identifier, expires_at FROM login_lockouts WHERE expires_at > now()"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to read the lockouts from Postgres.")?;
+        let now = Utc::now();
+        Ok(rows
+            .into_iter()
+            .filter_map(|r| {
+                Some(Lockout {
+                    kind: LockoutKind::parse(&r.kind).ok()?,
+                    identifier: r.identifier,
+                    expires_in: (r.expires_at - now).to_std().unwrap_or_default(),
+                })
+            })
+            .collect())
+    }
+
+    async fn clear_lockout(
+        &self,
+        kind: LockoutKind,
+        identifier: &str,
+    ) -> Result<bool, anyhow::Error> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool")?;
+        let n_deleted = sqlx::query!(
+            r#"
+        DELETE FROM login_lockouts
+        WHERE kind = $1 AND identifier = $2 AND expires_at > now()
+        "#,
+            kind.as_str(),
+            identifier
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to clear the lockout in Postgres.")?
+        .rows_affected();
+        sqlx::query!(
+            r#"DELETE FROM login_failures WHERE kind = $1 AND identifier = $2"#,
+            kind.as_str(),
+            identifier
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to reset the failed login counter in Postgres.")?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to clear a lockout.")?;
+        Ok(n_deleted > 0)
+    }
+}
@@ -0,0 +1,162 @@
+//! src/authentication/throttle/redis_store.rs
+use super::{Lockout, LockoutKind, ThrottleStore};
+use anyhow::Context;
+use redis::AsyncCommands;
+use redis::aio::ConnectionManager;
+use std::time::Duration;
+
+/// Counters and lockouts are plain keys, left to Redis to expire.
+pub(super) struct RedisThrottleStore {
+    redis: ConnectionManager,
+    /// Lets several environments share a Redis instance.
+    key_prefix: String,
+}
+
+impl RedisThrottleStore {
+    pub(super) fn new(redis: ConnectionManager, key_prefix: String) -> Self {
+        Self { redis, key_prefix }
+    }
+
+    fn failures_key(&self, kind: LockoutKind, identifier: &str) -> String {
+        format!(
+            "{}:failures:{}:{identifier}",
+            self.key_prefix,
+            kind.as_str()
+        )
+    }
+
+    fn lockout_key(&self, kind: LockoutKind, identifier: &str) -> String {
+        format!("{}:lockout:{}:{identifier}", self.key_prefix, kind.as_str())
+    }
+}
+
+#[async_trait::async_trait]
+impl ThrottleStore for RedisThrottleStore {
+    async fn is_locked_out(
+        &self,
+        kind: LockoutKind,
+        identifier: &str,
+    ) -> Result<bool, anyhow::Error> {
+        let mut conn = self.redis.clone();
+        conn.exists(self.lockout_key(kind, identifier))
+            .await
+            .context("Failed to check for a lockout in Redis.")
+    }
+
+    async fn failures(&self, kind: LockoutKind, identifier: &str) -> Result<u64, anyhow::Error> {
+        let mut conn = self.redis.clone();
+        let n: Option<u64> = conn
+            .get(self.failures_key(kind, identifier))
+            .await
+            .context("Failed to read the failed login counter from Redis.")?;
+        Ok(n.unwrap_or_default())
+    }
+
+    async fn add_failure(
+        &self,
+        kind: LockoutKind,
+        identifier: &str,
+        window: Duration,
+    ) -> Result<u64, anyhow::Error> {
+        let mut conn = self.redis.clone();
+        let key = self.failures_key(kind, identifier);
+        let (n,): (u64,) = redis::pipe()
+            .atomic()
+            .incr(&key, 1)
+            .expire(&key, window.as_secs() as i64)
+            .ignore()
+            .query_async(&mut conn)
+            .await
+            .context("Failed to increment the failed login counter in Redis.")?;
+        Ok(n)
+    }
+
+    async fn lock_out(
+        &self,
+        kind: LockoutKind,
+        identifier: &str,
+        duration: Duration,
+    ) -> Result<(), anyhow::Error> {
+        let mut conn = self.redis.clone();
+        redis::pipe()
+            .atomic()
+            .set_ex(self.lockout_key(kind, identifier), 1, duration.as_secs())
+            .ignore()
+            .del(self.failures_key(kind, identifier))
+            .ignore()
+            .query_async::<()>(&mut conn)
+            .await
+            .context("Failed to store the lockout in Redis.")
+    }
+
+    async fn reset_failures(
+        &self,
+        kind: LockoutKind,
+        identifier: &str,
+    ) -> Result<(), anyhow::Error> {
+        let mut conn = self.redis.clone();
+        conn.del::<_, ()>(self.failures_key(kind, identifier))
+            .await
+            .context("Failed to reset the failed login counter in Redis.")
+    }
+
+    async fn lockouts(&self) -> Result<Vec<Lockout>, anyhow::Error> {
+        let mut conn = self.redis.clone();
+        let prefix = format!("{}:lockout:", self.key_prefix);
+        let keys: Vec<String> = {
+            let mut iter = conn
+                .scan_match::<_, String>(format!("{prefix}*"))
+                .await
+                .context("Failed to scan Redis for lockouts.")?;
+            let mut keys = Vec::new();
+            while let Some(key) = iter.next_item().await {
+                keys.push(key);
+            }
+            keys
+        };
+
+        let mut lockouts = Vec::new();
+        for key in keys {
+            let Some((kind, identifier)) = key
+                .strip_prefix(&prefix)
+                .and_then(|rest| rest.split_once(':'))
+            else {
+                continue;
+            };
+            let Ok(kind) = LockoutKind::parse(kind) else {
+                continue;
+            };
+            let ttl: i64 = conn
+                .ttl(&key)
+                .await
+                .context("Failed to read the lockout expiration from Redis.")?;
+            // The lockout expired between the scan and now.
+            if ttl < 0 {
+                continue;
+            }
+            lockouts.push(Lockout {
+                kind,
+                identifier: identifier.to_string(),
+                expires_in: Duration::from_secs(ttl as u64),
+            });
+        }
+        Ok(lockouts)
+    }
+
+    async fn clear_lockout(
+        &self,
+        kind: LockoutKind,
+        identifier: &str,
+    ) -> Result<bool, anyhow::Error> {
+        let mut conn = self.redis.clone();
+        let (n_deleted,): (u64,) = redis::pipe()
+            .atomic()
+            .del(self.lockout_key(kind, identifier))
+            .del(self.failures_key(kind, identifier))
+            .ignore()
+            .query_async(&mut conn)
+            .await
+            .context("Failed to clear the lockout in Redis.")?;
+        Ok(n_deleted > 0)
+    }
+}
@@ -0,0 +1,328 @@
+//! src/authentication/two_factor.rs
+use anyhow::Context;
+use chrono::Utc;
+use qrcode::QrCode;
+use qrcode::render::svg;
+use rand::distributions::{Alphanumeric, Standard};
+use rand::{Rng, thread_rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::{Executor, PgPool};
+use totp_rs::{Algorithm, TOTP};
+use uuid::Uuid;
+
+const ISSUER: &str = "zero2prod";
+const TOTP_STEP_SECONDS: u64 = 30;
+const N_RECOVERY_CODES: usize = 10;
+
+/// A fresh base32 encoded shared secret, 160 bits as recommended by RFC 4226.
+pub fn generate_totp_secret() -> Secret<String> {
+    let bytes: Vec<u8> = thread_rng().sample_iter(Standard).take(20).collect();
+    match totp_rs::Secret::Raw(bytes).to_encoded() {
+        totp_rs::Secret::Encoded(encoded) => Secret::new(encoded),
+        totp_rs::Secret::Raw(_) => unreachable!("`to_encoded` always returns an encoded secret"),
+    }
+}
+
+/// RFC 6238 defaults, the only parameters every authenticator app supports.
+fn totp(secret: &Secret<String>, username: &str) -> Result<TOTP, anyhow::Error> {
+    let secret = totp_rs::Secret::Encoded(secret.expose_secret().to_owned())
+        .to_bytes()
+        .map_err(|e| anyhow::anyhow!("{e:?}"))
+        .context("The TOTP secret is not valid base32.")?;
+    // ':' separates the issuer from the account name in the provisioning URI.
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        0,
+        TOTP_STEP_SECONDS,
+        secret,
+        Some(ISSUER.to_string()),
+        username.replace(':', "_"),
+    )
+    .context("Failed to build the TOTP generator.")
+}
+
+/// The `otpauth://` URI authenticator apps import, usually by scanning it.
+pub fn provisioning_uri(secret: &Secret<String>, username: &str) -> Result<String, anyhow::Error> {
+    Ok(totp(secret, username)?.get_url())
+}
+
+/// `data` rendered as an inline SVG QR code, ready to be embedded in a page.
+pub fn qr_code_svg(data: &str) -> Result<String, anyhow::Error> {
+    let svg = QrCode::new(data.as_bytes())
+        .context("Failed to encode the QR code.")?
+        .render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .build();
+    // Drop the XML declaration, it is not allowed inside an HTML document.
+    Ok(match svg.find("<svg") {
+        Some(start) => svg[start..].to_string(),
+        None => svg,
+    })
+}
+
+/// Check `code` against the time steps around `now` (Unix seconds), tolerating
+/// one step of clock drift. Steps up to `last_used_step` are rejected so that
+/// an intercepted code cannot be replayed.
+///
+/// Returns the matching time step, to be stored as the new `last_used_step`.
+pub fn verify_totp_code(
+    secret: &Secret<String>,
+    code: &str,
+    now: u64,
+    last_used_step: Option<i64>,
+) -> Result<Option<i64>, anyhow::Error> {
+    let totp = totp(secret, "")?;
+    let current_step = now / TOTP_STEP_SECONDS;
+    let matching_step = [
+        current_step.saturating_sub(1),
+        current_step,
+        current_step + 1,
+    ]
+    .into_iter()
+    .filter(|step| last_used_step.is_none_or(|last| *step as i64 > last))
+    .find(|step| totp.check(code, step * TOTP_STEP_SECONDS));
+    Ok(matching_step.map(|step| step as i64))
+}
+
+/// One-time codes to get in if the authenticator is lost, formatted as
+/// `xxxx-xxxx-xxxx-xxxx`. They are shown once, only their hashes are stored.
+pub fn generate_recovery_codes() -> Vec<Secret<String>> {
+    let mut rng = thread_rng();
+    (0..N_RECOVERY_CODES)
+        .map(|_| {
+            let code: String = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+                .map(|c| char::from(c).to_ascii_lowercase())
+                .take(16)
+                .collect();
+            let groups: Vec<&str> = (0..4).map(|i| &code[i * 4..(i + 1) * 4]).collect();
+            Secret::new(groups.join("-"))
+        })
+        .collect()
+}
+
+/// Dashes, spaces and casing are ignored, users tend to retype codes loosely.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
+
+fn unix_now() -> u64 {
+    Utc::now().timestamp().try_into().unwrap_or_default()
+}
+
+#[tracing::instrument(name = "Check if the user has two factor authentication", skip(pool))]
+pub async fn has_two_factor(pool: &PgPool, user_id: Uuid) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT totp_secret IS NOT NULL AS "enabled!" FROM users WHERE user_id = $1"#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to retrieve the two factor settings.")?;
+    Ok(row.is_some_and(|r| r.enabled))
+}
+
+/// Save the confirmed secret and replace any previous recovery codes.
+///
+/// `confirmed_step` is the time step of the code used to confirm the
+/// enrollment, so that the same code cannot be used to log in.
+#[tracing::instrument(
+    name = "Enable two factor authentication",
+    skip(pool, secret, recovery_codes)
+)]
+pub async fn enable_two_factor(
+    pool: &PgPool,
+    user_id: Uuid,
+    secret: &Secret<String>,
+    confirmed_step: i64,
+    recovery_codes: &[Secret<String>],
+) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let query = sqlx::query!(
+        r#"UPDATE users SET totp_secret = $1, totp_last_step = $2 WHERE user_id = $3"#,
+        secret.expose_secret(),
+        confirmed_step,
+        user_id
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to save the TOTP secret.")?;
+    let query = sqlx::query!(r#"DELETE FROM recovery_codes WHERE user_id = $1"#, user_id);
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to delete the previous recovery codes.")?;
+    let code_hashes: Vec<String> = recovery_codes
+        .iter()
+        .map(|code| hash_recovery_code(code.expose_secret()))
+        .collect();
+    let query = sqlx::query!(
+        r#"
+    INSERT INTO recovery_codes (user_id, code_hash)
+    SELECT $1, code_hash FROM UNNEST($2::text[]) AS code_hash
+    "#,
+        user_id,
+        &code_hashes
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to store the recovery codes.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to enable two factor authentication.")?;
+    Ok(())
+}
+
+/// Accepts either a code from the authenticator app or an unused recovery
+/// code, which is used up.
+#[tracing::instrument(name = "Verify second factor", skip(pool, code))]
+pub async fn verify_second_factor(
+    pool: &PgPool,
+    user_id: Uuid,
+    code: &Secret<String>,
+) -> Result<bool, anyhow::Error> {
+    let code = code.expose_secret().trim();
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    // Locking the row serializes concurrent attempts with the same code.
+    let row = sqlx::query!(
+        r#"SELECT totp_secret, totp_last_step FROM users WHERE user_id = $1 FOR UPDATE"#,
+        user_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to perform a query to retrieve the TOTP secret.")?;
+    let Some((secret, last_step)) =
+        row.and_then(|r| r.totp_secret.map(|s| (Secret::new(s), r.totp_last_step)))
+    else {
+        return Ok(false);
+    };
+
+    if code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()) {
+        let Some(step) = verify_totp_code(&secret, code, unix_now(), last_step)? else {
+            return Ok(false);
+        };
+        let query = sqlx::query!(
+            r#"UPDATE users SET totp_last_step = $1 WHERE user_id = $2"#,
+            step,
+            user_id
+        );
+        transaction
+            .execute(query)
+            .await
+            .context("Failed to save the last used TOTP step.")?;
+    } else {
+        let query = sqlx::query!(
+            r#"
+        UPDATE recovery_codes SET used_at = now()
+        WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+        "#,
+            user_id,
+            hash_recovery_code(code)
+        );
+        let n_used = transaction
+            .execute(query)
+            .await
+            .context("Failed to use up the recovery code.")?
+            .rows_affected();
+        if n_used == 0 {
+            return Ok(false);
+        }
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to verify the second factor.")?;
+    Ok(true)
+}
+
+/// Enrollment is confirmed with the pending secret, which is not stored yet.
+pub fn verify_enrollment_code(
+    secret: &Secret<String>,
+    code: &Secret<String>,
+) -> Result<Option<i64>, anyhow::Error> {
+    verify_totp_code(secret, code.expose_secret().trim(), unix_now(), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_none, assert_ok, assert_some_eq};
+
+    // The SHA1 test secret from RFC 6238 appendix B, "12345678901234567890".
+    fn rfc_secret() -> Secret<String> {
+        Secret::new("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".to_string())
+    }
+
+    #[test]
+    fn codes_match_the_rfc_6238_test_vectors() {
+        // Appendix B lists 8 digit codes, we use the last 6.
+        let totp = totp(&rfc_secret(), "ursula").unwrap();
+        assert_eq!(totp.generate(59), "287082");
+        assert_eq!(totp.generate(1111111109), "081804");
+        assert_eq!(totp.generate(1234567890), "005924");
+    }
+
+    #[test]
+    fn a_code_from_the_previous_or_next_step_is_accepted() {
+        let totp = totp(&rfc_secret(), "ursula").unwrap();
+        let now = 1234567890;
+        for offset in [-30i64, 0, 30] {
+            let code = totp.generate((now as i64 + offset) as u64);
+            let step = (now as i64 + offset) / 30;
+            assert_some_eq!(
+                verify_totp_code(&rfc_secret(), &code, now, None).unwrap(),
+                step
+            );
+        }
+        let code = totp.generate(now + 90);
+        assert_none!(verify_totp_code(&rfc_secret(), &code, now, None).unwrap());
+    }
+
+    #[test]
+    fn a_code_cannot_be_replayed() {
+        let now = 1234567890;
+        let code = totp(&rfc_secret(), "ursula").unwrap().generate(now);
+        let step = verify_totp_code(&rfc_secret(), &code, now, None)
+            .unwrap()
+            .unwrap();
+        assert_none!(verify_totp_code(&rfc_secret(), &code, now, Some(step)).unwrap());
+    }
+
+    #[test]
+    fn generated_secrets_can_be_used_to_build_a_provisioning_uri() {
+        let uri = assert_ok!(provisioning_uri(&generate_totp_secret(), "ursula:le-guin"));
+        assert!(uri.starts_with("otpauth://totp/zero2prod:ursula_le-guin?"));
+    }
+
+    #[test]
+    fn recovery_codes_are_unique_and_hash_loosely() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), N_RECOVERY_CODES);
+        let hashes: std::collections::HashSet<_> = codes
+            .iter()
+            .map(|c| hash_recovery_code(c.expose_secret()))
+            .collect();
+        assert_eq!(hashes.len(), N_RECOVERY_CODES);
+
+        let code = codes[0].expose_secret();
+        assert_eq!(
+            hash_recovery_code(code),
+            hash_recovery_code(&code.replace('-', " ").to_uppercase())
+        );
+    }
+}
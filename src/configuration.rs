@@ -1,17 +1,30 @@
 //! src/configuration.rs
 
+use actix_web::cookie::SameSite;
 use secrecy::{ExposeSecret, Secret};
 use serde_aux::field_attributes::deserialize_number_from_string;
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
 
 use crate::domain::SubscriberEmail;
+use crate::email_client::{
+    EmailTransport, FileSinkTransport, PostmarkTransport, RetryPolicy, SmtpTls, SmtpTransport,
+};
+use std::net::IpAddr;
+use std::sync::Arc;
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, Clone)]
 pub struct Settings {
     pub database: DatabaseSettings,
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
-    pub redis_uri: Secret<String>,
+    pub idempotency: IdempotencySettings,
+    pub subscriptions: SubscriptionSettings,
+    pub password_reset: PasswordResetSettings,
+    pub login_throttle: LoginThrottleSettings,
+    pub password_hashing: PasswordHashingSettings,
+    pub session: SessionSettings,
+    /// Only needed when `session.store` is `redis`.
+    pub redis_uri: Option<Secret<String>>,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -21,20 +34,227 @@ pub struct ApplicationSettings {
     pub host: String,
     pub base_url: String,
     pub hmac_secret: Secret<String>,
+    /// An admin session is logged out after this long without any request.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub session_idle_timeout_seconds: u64,
+    /// An admin session is logged out this long after logging in, however active it is.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub session_absolute_timeout_seconds: u64,
+    /// Lifetime of sessions that ticked "remember me" on the login form.
+    /// They are not subject to the idle timeout.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub remember_me_timeout_seconds: u64,
+    /// Reverse proxies in front of the application. Requests coming from one
+    /// of them are attributed to the client named in `Forwarded` or
+    /// `X-Forwarded-For`, anyone else could forge those headers.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, Clone)]
 pub struct EmailClientSettings {
+    pub kind: EmailTransportKind,
     pub sender_email: String,
     pub base_url: String,
     pub authorization_token: Secret<String>,
+    /// Timeout applied to each delivery attempt.
     pub timeout_milliseconds: u64,
+    pub max_attempts: u32,
+    pub initial_backoff_milliseconds: u64,
+    pub max_backoff_milliseconds: u64,
+    pub smtp: Option<SmtpSettings>,
+    pub file_sink: Option<FileSinkSettings>,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailTransportKind {
+    Postmark,
+    Smtp,
+    File,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct SmtpSettings {
+    pub host: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub port: u16,
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<Secret<String>>,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct FileSinkSettings {
+    pub directory: String,
 }
 
 impl EmailClientSettings {
+    pub fn client(self) -> Result<Arc<dyn EmailTransport>, anyhow::Error> {
+        let sender_email = self.sender().map_err(|e| anyhow::anyhow!(e))?;
+        let timeout = self.timeout();
+        let retry_policy = self.retry_policy();
+        let transport: Arc<dyn EmailTransport> = match self.kind {
+            EmailTransportKind::Postmark => Arc::new(PostmarkTransport::new(
+                self.base_url,
+                sender_email,
+                self.authorization_token,
+                timeout,
+                retry_policy,
+            )),
+            EmailTransportKind::Smtp => {
+                let smtp = self.smtp.ok_or_else(|| {
+                    anyhow::anyhow!("`email_client.smtp` is required when `kind` is `smtp`.")
+                })?;
+                let credentials = smtp.username.zip(smtp.password);
+                Arc::new(SmtpTransport::new(
+                    &smtp.host,
+                    smtp.port,
+                    smtp.tls,
+                    credentials,
+                    sender_email,
+                    timeout,
+                    retry_policy,
+                )?)
+            }
+            EmailTransportKind::File => {
+                let file_sink = self.file_sink.ok_or_else(|| {
+                    anyhow::anyhow!("`email_client.file_sink` is required when `kind` is `file`.")
+                })?;
+                Arc::new(FileSinkTransport::new(file_sink.directory, sender_email))
+            }
+        };
+        Ok(transport)
+    }
+
     pub fn sender(&self) -> Result<SubscriberEmail, String> {
         SubscriberEmail::parse(self.sender_email.clone())
     }
+
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.timeout_milliseconds)
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::new(
+            self.max_attempts,
+            std::time::Duration::from_millis(self.initial_backoff_milliseconds),
+            std::time::Duration::from_millis(self.max_backoff_milliseconds),
+        )
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct IdempotencySettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub expiration_seconds: u64,
+}
+
+impl IdempotencySettings {
+    pub fn expiration(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.expiration_seconds)
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct SubscriptionSettings {
+    /// How long a confirmation link stays valid after it has been sent.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub token_expiration_seconds: u64,
+}
+
+impl SubscriptionSettings {
+    pub fn token_expiration(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.token_expiration_seconds)
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct PasswordResetSettings {
+    /// How long a password reset link stays valid after it has been sent.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub token_expiration_seconds: u64,
+}
+
+impl PasswordResetSettings {
+    pub fn token_expiration(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.token_expiration_seconds)
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct LoginThrottleSettings {
+    /// Prepended to every Redis key, lets several environments share a Redis instance.
+    pub key_prefix: String,
+    /// Failed attempts for a single username before it is locked out.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_failures_per_username: u64,
+    /// Failed attempts from a single client IP before it is locked out.
+    /// Higher than the username threshold, many users can share an address.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_failures_per_ip: u64,
+    /// Failed attempts older than this are forgotten.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub failure_window_seconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub lockout_seconds: u64,
+    /// Delay added before checking the password after the first failure,
+    /// doubled for each further failure up to `max_delay_milliseconds`.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub initial_delay_milliseconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_delay_milliseconds: u64,
+}
+
+/// Argon2id cost parameters for new password hashes. Existing hashes are
+/// upgraded the next time their owner logs in.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct PasswordHashingSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub memory_size_kib: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub iterations: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub parallelism: u32,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct SessionSettings {
+    /// Only send the session cookie over HTTPS.
+    pub cookie_secure: bool,
+    pub cookie_same_site: CookieSameSite,
+    /// Prepended to the Redis keys of the session registry, see
+    /// `authentication::SessionRegistry`.
+    pub key_prefix: String,
+    /// Where sessions are kept, along with the session registry and the
+    /// login throttle counters.
+    pub store: SessionStoreKind,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionStoreKind {
+    Redis,
+    /// Saves running Redis for small deployments, see `session_store::PgSessionStore`.
+    Postgres,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CookieSameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl From<CookieSameSite> for SameSite {
+    fn from(value: CookieSameSite) -> Self {
+        match value {
+            CookieSameSite::Strict => SameSite::Strict,
+            CookieSameSite::Lax => SameSite::Lax,
+            CookieSameSite::None => SameSite::None,
+        }
+    }
 }
 
 #[derive(serde::Deserialize, Clone)]
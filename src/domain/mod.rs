@@ -1,9 +1,15 @@
 //! src/domain/mod.rs
+mod data_request_token;
 mod new_subscriber;
+mod newsletter_body;
 mod password;
 mod subscriber_email;
 mod subscriber_name;
+mod unsubscribe_token;
+pub use data_request_token::DataRequestToken;
 pub use new_subscriber::NewSubscriber;
+pub use newsletter_body::NewsletterBody;
 pub use password::Password;
 pub use subscriber_email::SubscriberEmail;
 pub use subscriber_name::SubscriberName;
+pub use unsubscribe_token::UnsubscribeToken;
@@ -0,0 +1,253 @@
+//! src/domain/newsletter_body.rs
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd, html};
+
+/// The two bodies of a newsletter email, rendered from the same Markdown.
+#[derive(Debug, PartialEq)]
+pub struct NewsletterBody {
+    pub html: String,
+    pub text: String,
+}
+
+impl NewsletterBody {
+    /// The HTML is sanitized: raw HTML in the Markdown cannot smuggle scripts
+    /// or event handlers into subscribers' inboxes.
+    pub fn from_markdown(markdown: &str) -> Self {
+        let mut unsafe_html = String::new();
+        html::push_html(&mut unsafe_html, parser(markdown));
+        Self {
+            html: ammonia::clean(&unsafe_html),
+            text: PlainText::render(markdown),
+        }
+    }
+}
+
+fn parser(markdown: &str) -> Parser<'_> {
+    Parser::new_ext(markdown, Options::ENABLE_STRIKETHROUGH)
+}
+
+/// Markdown is already readable as text: we mostly drop the markup, and
+/// spell out what a plain-text reader would otherwise miss, like link targets.
+#[derive(Default)]
+struct PlainText {
+    out: String,
+    /// `None` for bullet lists, the number of the next item otherwise.
+    lists: Vec<Option<u64>>,
+    quote_depth: usize,
+    in_code_block: bool,
+    /// Set right after a list marker, the first block of the item goes on the
+    /// same line.
+    at_item_start: bool,
+    /// Where the text of the open heading starts.
+    heading_start: usize,
+    /// Targets of the open links and images, with where their text starts.
+    targets: Vec<(String, usize)>,
+}
+
+impl PlainText {
+    fn render(markdown: &str) -> String {
+        let mut renderer = Self::default();
+        for event in parser(markdown) {
+            renderer.event(event);
+        }
+        let lines: Vec<&str> = renderer.out.lines().map(str::trim_end).collect();
+        lines.join("\n").trim().to_string()
+    }
+
+    fn event(&mut self, event: Event<'_>) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) if self.in_code_block => {
+                for (i, line) in text.trim_end_matches('\n').split('\n').enumerate() {
+                    if i > 0 {
+                        self.new_line();
+                    }
+                    self.out.push_str("    ");
+                    self.out.push_str(line);
+                }
+            }
+            Event::Text(text) | Event::Code(text) => {
+                self.at_item_start = false;
+                self.out.push_str(&text);
+            }
+            Event::SoftBreak | Event::HardBreak => self.new_line(),
+            Event::Rule => {
+                self.new_block();
+                self.out.push_str("----");
+            }
+            // Raw HTML has no plain-text rendering, an override is needed to
+            // keep it.
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag<'_>) {
+        match tag {
+            Tag::Paragraph => self.new_block(),
+            Tag::Heading { .. } => {
+                self.new_block();
+                self.heading_start = self.out.len();
+            }
+            Tag::BlockQuote(_) => {
+                self.new_block();
+                self.quote_depth += 1;
+                self.out.push_str("> ");
+                self.at_item_start = true;
+            }
+            Tag::CodeBlock(_) => {
+                self.new_block();
+                self.in_code_block = true;
+            }
+            Tag::List(start) => {
+                if self.lists.is_empty() {
+                    self.new_block();
+                    // The first item goes on the line the block starts.
+                    self.at_item_start = true;
+                }
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                if !std::mem::take(&mut self.at_item_start) {
+                    // The marker is indented for the enclosing lists only.
+                    self.break_line(self.lists.len() - 1);
+                }
+                match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        self.out.push_str(&format!("{n}. "));
+                        *n += 1;
+                    }
+                    _ => self.out.push_str("- "),
+                }
+                self.at_item_start = true;
+            }
+            Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. } => {
+                self.targets.push((dest_url.into_string(), self.out.len()));
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Heading(level) => {
+                let width = self.out[self.heading_start..].chars().count();
+                let underline = match level {
+                    HeadingLevel::H1 => "=",
+                    HeadingLevel::H2 => "-",
+                    _ => return,
+                };
+                self.new_line();
+                self.out.push_str(&underline.repeat(width));
+            }
+            TagEnd::BlockQuote(_) => self.quote_depth -= 1,
+            TagEnd::CodeBlock => self.in_code_block = false,
+            TagEnd::List(_) => {
+                self.lists.pop();
+            }
+            TagEnd::Link | TagEnd::Image => {
+                let Some((target, start)) = self.targets.pop() else {
+                    return;
+                };
+                let label = &self.out[start..];
+                let bare_target = target.strip_prefix("mailto:").unwrap_or(&target);
+                if !target.is_empty() && label != bare_target {
+                    self.out.push_str(&format!(" ({target})"));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// A blank line before the block, unless it opens a list item or a quote.
+    fn new_block(&mut self) {
+        if std::mem::take(&mut self.at_item_start) || self.out.is_empty() {
+            return;
+        }
+        self.new_line();
+        self.new_line();
+    }
+
+    /// Continuation lines line up with the text of the innermost list item.
+    fn new_line(&mut self) {
+        self.break_line(self.lists.len());
+    }
+
+    fn break_line(&mut self, indent: usize) {
+        self.at_item_start = false;
+        self.out.push('\n');
+        self.out.push_str(&"> ".repeat(self.quote_depth));
+        self.out.push_str(&"  ".repeat(indent));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NewsletterBody;
+
+    fn text(markdown: &str) -> String {
+        NewsletterBody::from_markdown(markdown).text
+    }
+
+    #[test]
+    fn paragraphs_and_emphasis_read_as_plain_text() {
+        assert_eq!(
+            text("Hello *dear* **readers**,\nwelcome.\n\nSee you `soon`."),
+            "Hello dear readers,\nwelcome.\n\nSee you soon."
+        );
+    }
+
+    #[test]
+    fn headings_are_underlined() {
+        assert_eq!(
+            text("# News\n\n## This week\n\n### Details\n\nBody"),
+            "News\n====\n\nThis week\n---------\n\nDetails\n\nBody"
+        );
+    }
+
+    #[test]
+    fn link_targets_are_spelled_out() {
+        assert_eq!(
+            text(
+                "Read [the post](https://example.com/post), <https://example.com> or ![a chart](chart.png)."
+            ),
+            "Read the post (https://example.com/post), https://example.com or a chart (chart.png)."
+        );
+        assert_eq!(text("<editor@example.com>"), "editor@example.com");
+    }
+
+    #[test]
+    fn lists_keep_their_markers_and_nesting() {
+        assert_eq!(
+            text("Intro\n\n- one\n- two\n  - nested\n\n3. three\n4. four"),
+            "Intro\n\n- one\n- two\n  - nested\n\n3. three\n4. four"
+        );
+    }
+
+    #[test]
+    fn quotes_and_code_blocks_are_set_apart() {
+        assert_eq!(
+            text("> Quoted\n> text\n\n```rust\nfn main() {}\n```\n\n---\n\nEnd"),
+            "> Quoted\n> text\n\n    fn main() {}\n\n----\n\nEnd"
+        );
+    }
+
+    #[test]
+    fn the_html_is_sanitized() {
+        let body = NewsletterBody::from_markdown(
+            "Hi <script>alert(1)</script><b onclick=\"steal()\">there</b>\n\n[x](javascript:alert(1))",
+        );
+        assert!(!body.html.contains("script"));
+        assert!(!body.html.contains("onclick"));
+        assert!(!body.html.contains("javascript:"));
+        assert!(body.html.contains("<b>there</b>"));
+    }
+
+    #[test]
+    fn the_html_keeps_the_formatting() {
+        let body = NewsletterBody::from_markdown("# Title\n\nSome *emphasis* and ~~strikes~~.");
+        assert_eq!(
+            body.html,
+            "<h1>Title</h1>\n<p>Some <em>emphasis</em> and <del>strikes</del>.</p>\n"
+        );
+    }
+}
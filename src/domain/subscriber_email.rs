@@ -1,7 +1,7 @@
 //! src/domain/subscriber_email.rs
 use validator::ValidateEmail;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SubscriberEmail(String);
 
 impl SubscriberEmail {
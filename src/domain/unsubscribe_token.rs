@@ -0,0 +1,93 @@
+//! src/domain/unsubscribe_token.rs
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use uuid::Uuid;
+
+/// A signed, per-subscriber token embedded in unsubscribe links.
+///
+/// It has the shape `<subscriber id>.<hex encoded HMAC-SHA256 of the id>`, so
+/// it can be verified without a database round-trip and cannot be forged for
+/// another subscriber without knowing the application's HMAC secret.
+#[derive(Debug)]
+pub struct UnsubscribeToken(String);
+
+impl UnsubscribeToken {
+    pub fn generate(subscriber_id: Uuid, hmac_secret: &Secret<String>) -> Self {
+        let subscriber_id = subscriber_id.simple().to_string();
+        let tag = hex::encode(
+            mac(hmac_secret, &subscriber_id)
+                .finalize()
+                .into_bytes()
+                .as_slice(),
+        );
+        Self(format!("{subscriber_id}.{tag}"))
+    }
+
+    /// Returns the id of the subscriber the token was issued for, if the signature checks out.
+    pub fn verify(token: &str, hmac_secret: &Secret<String>) -> Result<Uuid, String> {
+        let (subscriber_id, tag) = token
+            .split_once('.')
+            .ok_or_else(|| "The unsubscribe token is malformed.".to_string())?;
+        let tag =
+            hex::decode(tag).map_err(|_| "The unsubscribe token is malformed.".to_string())?;
+        mac(hmac_secret, subscriber_id)
+            .verify_slice(&tag)
+            .map_err(|_| "The unsubscribe token signature is invalid.".to_string())?;
+        Uuid::parse_str(subscriber_id).map_err(|e| e.to_string())
+    }
+}
+
+fn mac(hmac_secret: &Secret<String>, subscriber_id: &str) -> Hmac<sha2::Sha256> {
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(hmac_secret.expose_secret().as_bytes())
+        .expect("HMAC can take a key of any size.");
+    mac.update(subscriber_id.as_bytes());
+    mac
+}
+
+impl AsRef<str> for UnsubscribeToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::UnsubscribeToken;
+    use claims::{assert_err, assert_ok_eq};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    fn secret() -> Secret<String> {
+        Secret::new("a-very-secret-key".to_string())
+    }
+
+    #[test]
+    fn a_generated_token_verifies_to_the_subscriber_id() {
+        let subscriber_id = Uuid::new_v4();
+        let token = UnsubscribeToken::generate(subscriber_id, &secret());
+        assert_ok_eq!(
+            UnsubscribeToken::verify(token.as_ref(), &secret()),
+            subscriber_id
+        );
+    }
+
+    #[test]
+    fn a_token_signed_with_another_secret_is_rejected() {
+        let token =
+            UnsubscribeToken::generate(Uuid::new_v4(), &Secret::new("another-secret".to_string()));
+        assert_err!(UnsubscribeToken::verify(token.as_ref(), &secret()));
+    }
+
+    #[test]
+    fn a_token_for_another_subscriber_is_rejected() {
+        let token = UnsubscribeToken::generate(Uuid::new_v4(), &secret());
+        let (_, tag) = token.as_ref().split_once('.').unwrap();
+        let forged = format!("{}.{}", Uuid::new_v4().simple(), tag);
+        assert_err!(UnsubscribeToken::verify(&forged, &secret()));
+    }
+
+    #[test]
+    fn a_malformed_token_is_rejected() {
+        assert_err!(UnsubscribeToken::verify("not-a-token", &secret()));
+    }
+}
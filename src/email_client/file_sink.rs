@@ -0,0 +1,90 @@
+//! src/email_client/file_sink.rs
+use super::{EmailTransport, SendEmailError, build_message};
+use crate::domain::SubscriberEmail;
+use lettre::{AsyncFileTransport, AsyncTransport, Tokio1Executor};
+use std::path::PathBuf;
+
+/// Writes every email as an RFC 5322 `.eml` file in a local folder.
+///
+/// Meant for development and CI, where no real email provider is available.
+#[derive(Debug)]
+pub struct FileSinkTransport {
+    sender: SubscriberEmail,
+    directory: PathBuf,
+    mailer: AsyncFileTransport<Tokio1Executor>,
+}
+
+impl FileSinkTransport {
+    pub fn new(directory: impl Into<PathBuf>, sender: SubscriberEmail) -> Self {
+        let directory = directory.into();
+        Self {
+            sender,
+            mailer: AsyncFileTransport::new(&directory),
+            directory,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for FileSinkTransport {
+    #[tracing::instrument(name = "Write an email to the file sink", skip_all)]
+    async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[(&str, &str)],
+    ) -> Result<(), SendEmailError> {
+        let message = build_message(
+            &self.sender,
+            recipient,
+            subject,
+            html_content,
+            text_content,
+            headers,
+        )?;
+        tokio::fs::create_dir_all(&self.directory)
+            .await
+            .map_err(SendEmailError::transient)?;
+        let id = self
+            .mailer
+            .send(message)
+            .await
+            .map_err(SendEmailError::transient)?;
+        tracing::info!(path = %self.directory.join(format!("{id}.eml")).display(), "Email written to disk.");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{EmailTransport, FileSinkTransport};
+    use claims::assert_ok;
+
+    #[tokio::test]
+    async fn send_email_writes_an_eml_file() {
+        let directory = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let sender = SubscriberEmail::parse("sender@example.com".into()).unwrap();
+        let recipient = SubscriberEmail::parse("recipient@example.com".into()).unwrap();
+        let transport = FileSinkTransport::new(&directory, sender);
+
+        let outcome = transport
+            .send_email(&recipient, "Hello", "<p>Hi there!</p>", "Hi there!")
+            .await;
+
+        assert_ok!(outcome);
+        let files: Vec<_> = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].extension().unwrap(), "eml");
+        let eml = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(eml.contains("To: recipient@example.com"));
+        assert!(eml.contains("Subject: Hello"));
+        assert!(eml.contains("Hi there!"));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
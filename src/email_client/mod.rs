@@ -0,0 +1,200 @@
+//! src/email_client/mod.rs
+mod file_sink;
+mod postmark;
+mod smtp;
+
+pub use file_sink::FileSinkTransport;
+pub use postmark::PostmarkTransport;
+pub use smtp::{SmtpTls, SmtpTransport};
+
+use crate::domain::SubscriberEmail;
+use lettre::message::header::{HeaderName, HeaderValue};
+use rand::Rng;
+use std::future::Future;
+use std::time::Duration;
+
+/// A backend able to deliver an email on behalf of the application.
+///
+/// Routes and background workers only ever see a `dyn EmailTransport`, the
+/// concrete backend is picked at startup from `EmailClientSettings::kind`.
+#[async_trait::async_trait]
+pub trait EmailTransport: Send + Sync + std::fmt::Debug {
+    async fn send_email(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<(), SendEmailError> {
+        self.send_email_with_headers(recipient, subject, html_content, text_content, &[])
+            .await
+    }
+
+    /// Send an email with additional `(name, value)` headers, e.g. `List-Unsubscribe`.
+    async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[(&str, &str)],
+    ) -> Result<(), SendEmailError>;
+}
+
+/// How many times, and how far apart, a transport retries a transient failure.
+///
+/// The delay before attempt `n + 1` is drawn uniformly from
+/// `[0, min(max_backoff, initial_backoff * 2^(n - 1))]` ("full jitter"),
+/// unless the server asked us to wait with a `Retry-After` header.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, initial_backoff: Duration, max_backoff: Duration) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            initial_backoff,
+            max_backoff,
+        }
+    }
+
+    /// A policy that gives up after the first attempt.
+    pub fn no_retries() -> Self {
+        Self::new(1, Duration::ZERO, Duration::ZERO)
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// The delay to wait after `attempt` failed (attempts are counted from 1).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let ceiling = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(exponent))
+            .min(self.max_backoff);
+        if ceiling.is_zero() {
+            return ceiling;
+        }
+        rand::thread_rng().gen_range(Duration::ZERO..=ceiling)
+    }
+
+    /// Run `send` until it succeeds, fails permanently or we run out of attempts.
+    #[tracing::instrument(
+        name = "Send an email",
+        skip_all,
+        fields(attempts = tracing::field::Empty)
+    )]
+    pub async fn run<F, Fut>(&self, mut send: F) -> Result<(), SendEmailError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<(), SendEmailError>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let outcome = send().await;
+            tracing::Span::current().record("attempts", attempt);
+            match outcome {
+                Ok(()) => return Ok(()),
+                Err(SendEmailError::Transient { retry_after, .. })
+                    if attempt < self.max_attempts =>
+                {
+                    let delay = retry_after
+                        .map(|d| d.min(self.max_backoff))
+                        .unwrap_or_else(|| self.backoff(attempt));
+                    tracing::warn!(
+                        attempt,
+                        delay_ms = delay.as_millis() as u64,
+                        "Transient failure while sending an email, retrying."
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Build an RFC 5322 message with a plain text and an HTML alternative.
+fn build_message(
+    sender: &SubscriberEmail,
+    recipient: &SubscriberEmail,
+    subject: &str,
+    html_content: &str,
+    text_content: &str,
+    headers: &[(&str, &str)],
+) -> Result<lettre::Message, SendEmailError> {
+    let parse_mailbox = |email: &SubscriberEmail| {
+        email
+            .as_ref()
+            .parse::<lettre::message::Mailbox>()
+            .map_err(|e| SendEmailError::Permanent(e.into()))
+    };
+    let mut message = lettre::Message::builder()
+        .from(parse_mailbox(sender)?)
+        .to(parse_mailbox(recipient)?)
+        .subject(subject)
+        .multipart(lettre::message::MultiPart::alternative_plain_html(
+            text_content.to_string(),
+            html_content.to_string(),
+        ))
+        .map_err(|e| SendEmailError::Permanent(e.into()))?;
+    for (name, value) in headers {
+        let name = HeaderName::new_from_ascii(name.to_string())
+            .map_err(|e| SendEmailError::Permanent(e.into()))?;
+        message
+            .headers_mut()
+            .insert_raw(HeaderValue::new(name, value.to_string()));
+    }
+    Ok(message)
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SendEmailError {
+    /// The request may succeed if tried again later: timeouts, connection errors,
+    /// `5xx`s and `429 Too Many Requests`.
+    #[error("A transient error was encountered while sending an email.")]
+    Transient {
+        #[source]
+        source: anyhow::Error,
+        retry_after: Option<Duration>,
+    },
+    /// The email provider refused the request (e.g. an invalid recipient), trying
+    /// again will not help.
+    #[error("The email provider rejected the request.")]
+    Permanent(#[source] anyhow::Error),
+}
+
+impl SendEmailError {
+    pub fn transient(source: impl Into<anyhow::Error>) -> Self {
+        SendEmailError::Transient {
+            source: source.into(),
+            retry_after: None,
+        }
+    }
+
+    pub fn is_transient(&self) -> bool {
+        matches!(self, SendEmailError::Transient { .. })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::email_client::RetryPolicy;
+    use std::time::Duration;
+
+    #[test]
+    fn backoff_never_exceeds_the_max_backoff() {
+        let policy = RetryPolicy::new(10, Duration::from_millis(100), Duration::from_secs(1));
+        for attempt in 1..=10 {
+            assert!(policy.backoff(attempt) <= Duration::from_secs(1));
+        }
+        assert!(policy.backoff(1) <= Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,386 @@
+//! src/email_client/postmark.rs
+use super::{EmailTransport, RetryPolicy, SendEmailError};
+use crate::domain::SubscriberEmail;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Client, StatusCode};
+use secrecy::{ExposeSecret, Secret};
+use std::time::Duration;
+
+/// Sends emails through Postmark's HTTP `/email` API.
+#[derive(Debug)]
+pub struct PostmarkTransport {
+    sender: SubscriberEmail,
+    http_client: Client,
+    base_url: String,
+    authorization_token: Secret<String>,
+    retry_policy: RetryPolicy,
+}
+
+fn classify(e: reqwest::Error, retry_after: Option<Duration>) -> SendEmailError {
+    match e.status() {
+        Some(status) if !is_transient_status(status) => SendEmailError::Permanent(e.into()),
+        // No status code: the request timed out or never reached the server.
+        _ => SendEmailError::Transient {
+            source: e.into(),
+            retry_after,
+        },
+    }
+}
+
+fn is_transient_status(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+}
+
+/// Parse a `Retry-After` header expressed in seconds.
+fn parse_retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+impl PostmarkTransport {
+    /// `timeout` is applied to each attempt, not to the whole retry sequence.
+    pub fn new(
+        base_url: String,
+        sender: SubscriberEmail,
+        authorization_token: Secret<String>,
+        timeout: std::time::Duration,
+        retry_policy: RetryPolicy,
+    ) -> Self {
+        Self {
+            http_client: Client::builder()
+                .timeout(timeout)
+                .build()
+                .expect("Failed to build HTTP Client."),
+            sender,
+            base_url,
+            authorization_token,
+            retry_policy,
+        }
+    }
+
+    async fn try_send_email(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[(&str, &str)],
+    ) -> Result<(), SendEmailError> {
+        let request_body = SendEmailRequest {
+            from: self.sender.as_ref(),
+            to: recipient.as_ref(),
+            subject,
+            html_body: html_content,
+            text_body: text_content,
+            headers: headers
+                .iter()
+                .map(|&(name, value)| SendEmailHeader { name, value })
+                .collect(),
+        };
+        let response = self
+            .http_client
+            .post(format!("{}/email", self.base_url))
+            .header("Accept", "application/json")
+            .header(
+                "X-Postmark-Server-Token",
+                self.authorization_token.expose_secret(),
+            )
+            .json(&request_body)
+            .send()
+            .await
+            .map_err(|e| classify(e, None))?;
+
+        let retry_after = parse_retry_after(&response);
+        response
+            .error_for_status()
+            .map_err(|e| classify(e, retry_after))?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for PostmarkTransport {
+    async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[(&str, &str)],
+    ) -> Result<(), SendEmailError> {
+        self.retry_policy
+            .run(|| self.try_send_email(recipient, subject, html_content, text_content, headers))
+            .await
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    html_body: &'a str,
+    text_body: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    headers: Vec<SendEmailHeader<'a>>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailHeader<'a> {
+    name: &'a str,
+    value: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{EmailTransport, PostmarkTransport, RetryPolicy, SendEmailError};
+    use claims::{assert_err, assert_ok};
+    use fake::faker::internet::en::SafeEmail;
+    use fake::faker::lorem::en::{Paragraph, Sentence};
+    use fake::{Fake, Faker};
+    use secrecy::Secret;
+    use wiremock::matchers::{any, header, header_exists, method, path};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    struct SendEmailRequestBodyMatcher;
+
+    /// Generate a random email subject
+    fn subject() -> String {
+        Sentence(1..2).fake()
+    }
+    /// Generate a random email content
+    fn content() -> String {
+        Paragraph(1..10).fake()
+    }
+    /// Generate a random subscriber email
+    fn email() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+
+    /// Get a test instance of `PostmarkTransport`.
+    fn email_client(base_url: String) -> PostmarkTransport {
+        PostmarkTransport::new(
+            base_url,
+            email(),
+            Secret::new(Faker.fake()),
+            Duration::from_millis(200),
+            RetryPolicy::no_retries(),
+        )
+    }
+
+    /// Get a test instance of `PostmarkTransport` that retries transient failures.
+    fn retrying_email_client(base_url: String) -> PostmarkTransport {
+        PostmarkTransport::new(
+            base_url,
+            email(),
+            Secret::new(Faker.fake()),
+            Duration::from_millis(200),
+            RetryPolicy::new(3, Duration::from_millis(10), Duration::from_millis(50)),
+        )
+    }
+
+    impl wiremock::Match for SendEmailRequestBodyMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            let result: Result<serde_json::Value, serde_json::Error> =
+                serde_json::from_slice(&request.body);
+            if let Ok(body) = result {
+                body.get("From").is_some()
+                    && body.get("To").is_some()
+                    && body.get("Subject").is_some()
+                    && body.get("HtmlBody").is_some()
+                    && body.get("TextBody").is_some()
+            } else {
+                false
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn send_email_issues_a_valid_request_to_base_url() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(header_exists("X-Postmark-Server-Token"))
+            .and(header("Content-Type", "application/json"))
+            .and(path("/email"))
+            .and(method("POST"))
+            .and(SendEmailRequestBodyMatcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let _ = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+    }
+
+    #[tokio::test]
+    async fn send_email_succeeds_if_the_server_returns_200() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_if_the_server_returns_500() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert_err!(outcome);
+    }
+    #[tokio::test]
+    async fn send_email_timesout_after_10_seconds_if_response_takes_long() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(180)))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_retries_a_500_and_succeeds() {
+        let mock_server = MockServer::start().await;
+        let email_client = retrying_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_gives_up_with_a_transient_error_after_max_attempts() {
+        let mock_server = MockServer::start().await;
+        let email_client = retrying_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert!(assert_err!(outcome).is_transient());
+    }
+
+    #[tokio::test]
+    async fn send_email_does_not_retry_a_400() {
+        let mock_server = MockServer::start().await;
+        let email_client = retrying_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(422))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert!(matches!(assert_err!(outcome), SendEmailError::Permanent(_)));
+    }
+
+    #[tokio::test]
+    async fn send_email_retries_a_429_honouring_retry_after() {
+        let mock_server = MockServer::start().await;
+        let email_client = retrying_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn a_timeout_is_a_transient_error() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(180)))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert!(assert_err!(outcome).is_transient());
+    }
+}
@@ -0,0 +1,95 @@
+//! src/email_client/smtp.rs
+use super::{EmailTransport, RetryPolicy, SendEmailError, build_message};
+use crate::domain::SubscriberEmail;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use secrecy::{ExposeSecret, Secret};
+use std::time::Duration;
+
+/// How the connection to the SMTP relay is secured.
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Plain text connection, only meant for local relays such as MailHog.
+    None,
+    /// Upgrade a plain text connection with `STARTTLS` (usually port 587).
+    StartTls,
+    /// Implicit TLS from the first byte (usually port 465).
+    Tls,
+}
+
+/// Sends emails through an SMTP relay.
+#[derive(Debug)]
+pub struct SmtpTransport {
+    sender: SubscriberEmail,
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+    retry_policy: RetryPolicy,
+}
+
+impl SmtpTransport {
+    /// `credentials` enables `AUTH` when present; `timeout` is applied to each attempt.
+    pub fn new(
+        host: &str,
+        port: u16,
+        tls: SmtpTls,
+        credentials: Option<(String, Secret<String>)>,
+        sender: SubscriberEmail,
+        timeout: Duration,
+        retry_policy: RetryPolicy,
+    ) -> Result<Self, anyhow::Error> {
+        let builder = match tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+        };
+        let mut builder = builder.port(port).timeout(Some(timeout));
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(
+                username,
+                password.expose_secret().to_owned(),
+            ));
+        }
+        Ok(Self {
+            sender,
+            mailer: builder.build(),
+            retry_policy,
+        })
+    }
+
+    async fn try_send_email(&self, message: lettre::Message) -> Result<(), SendEmailError> {
+        self.mailer.send(message).await.map_err(|e| {
+            // 5xx replies are final, anything else (4xx replies, timeouts,
+            // connection failures) is worth another try.
+            if e.is_permanent() {
+                SendEmailError::Permanent(e.into())
+            } else {
+                SendEmailError::transient(e)
+            }
+        })?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for SmtpTransport {
+    async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[(&str, &str)],
+    ) -> Result<(), SendEmailError> {
+        let message = build_message(
+            &self.sender,
+            recipient,
+            subject,
+            html_content,
+            text_content,
+            headers,
+        )?;
+        self.retry_policy
+            .run(|| self.try_send_email(message.clone()))
+            .await
+    }
+}
@@ -0,0 +1,34 @@
+//! src/idempotency/expiry.rs
+use crate::configuration::Settings;
+use crate::startup::get_connection_pool;
+use chrono::Utc;
+use sqlx::PgPool;
+use std::time::Duration;
+
+pub async fn run_expiry_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database).await;
+    let expiration = configuration.idempotency.expiration();
+    expiry_loop(connection_pool, expiration).await
+}
+
+async fn expiry_loop(pool: PgPool, expiration: Duration) -> Result<(), anyhow::Error> {
+    loop {
+        // Failures are already recorded by the span, we try again on the next tick.
+        let _ = delete_expired_keys(&pool, expiration).await;
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    }
+}
+
+/// Remove the idempotency keys (and their saved responses) that are older than `expiration`.
+#[tracing::instrument(name = "Delete expired idempotency keys", skip(pool), err)]
+pub async fn delete_expired_keys(
+    pool: &PgPool,
+    expiration: Duration,
+) -> Result<u64, anyhow::Error> {
+    let cutoff = Utc::now() - chrono::Duration::from_std(expiration)?;
+    let n_deleted_rows = sqlx::query!(r#"DELETE FROM idempotency WHERE created_at < $1"#, cutoff)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    Ok(n_deleted_rows)
+}
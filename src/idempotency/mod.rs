@@ -0,0 +1,7 @@
+//! src/idempotency/mod.rs
+mod expiry;
+mod key;
+mod persistence;
+pub use expiry::{delete_expired_keys, run_expiry_worker_until_stopped};
+pub use key::IdempotencyKey;
+pub use persistence::{NextAction, save_response, try_processing};
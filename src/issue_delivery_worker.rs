@@ -0,0 +1,327 @@
+//! src/issue_delivery_worker.rs
+use crate::configuration::Settings;
+use crate::domain::{SubscriberEmail, UnsubscribeToken};
+use crate::email_client::{EmailTransport, SendEmailError};
+use crate::startup::get_connection_pool;
+use chrono::Utc;
+use secrecy::Secret;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{Span, field::display};
+use uuid::Uuid;
+
+pub enum ExecutionOutcome {
+    TaskCompleted,
+    EmptyQueue,
+}
+
+/// How many times a delivery task is put back in the queue after a transient
+/// failure before we give up on it.
+const MAX_DELIVERY_RETRIES: i32 = 5;
+/// The delay before the first re-delivery, doubled on every subsequent retry.
+const BASE_REDELIVERY_DELAY: Duration = Duration::from_secs(60);
+
+pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database).await;
+    let email_client = configuration.email_client.client()?;
+    worker_loop(
+        connection_pool,
+        email_client,
+        configuration.application.base_url,
+        configuration.application.hmac_secret,
+    )
+    .await
+}
+
+async fn worker_loop(
+    pool: PgPool,
+    email_client: Arc<dyn EmailTransport>,
+    base_url: String,
+    hmac_secret: Secret<String>,
+) -> Result<(), anyhow::Error> {
+    loop {
+        match try_execute_task(&pool, email_client.as_ref(), &base_url, &hmac_secret).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        newsletter_issue_id=tracing::field::Empty,
+        subscriber_email=tracing::field::Empty
+    ),
+    err
+)]
+pub async fn try_execute_task(
+    pool: &PgPool,
+    email_client: &dyn EmailTransport,
+    base_url: &str,
+    hmac_secret: &Secret<String>,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let task = dequeue_task(pool).await?;
+    if task.is_none() {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    }
+    let (mut transaction, task) = task.unwrap();
+    let DeliveryTask {
+        newsletter_issue_id: issue_id,
+        subscriber_email: email,
+        n_retries,
+    } = task;
+    Span::current()
+        .record("newsletter_issue_id", display(issue_id))
+        .record("subscriber_email", display(&email));
+    let Some(subscriber_id) = get_confirmed_subscriber_id(pool, &email).await? else {
+        tracing::info!("Skipping a subscriber that is no longer confirmed.");
+        delete_task(transaction, issue_id, &email).await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    };
+    let outcome = match SubscriberEmail::parse(email.clone()) {
+        Ok(email) => {
+            let issue = get_issue(pool, issue_id).await?;
+            let unsubscribe_link = unsubscribe_link(base_url, subscriber_id, hmac_secret);
+            let html_content = format!(
+                "{}<hr><p><a href=\"{unsubscribe_link}\">Unsubscribe</a> from this newsletter.</p>",
+                issue.html_content
+            );
+            let text_content = format!(
+                "{}\n\n--\nUnsubscribe from this newsletter: {unsubscribe_link}",
+                issue.text_content
+            );
+            let list_unsubscribe = format!("<{unsubscribe_link}>");
+            match email_client
+                .send_email_with_headers(
+                    &email,
+                    &issue.title,
+                    &html_content,
+                    &text_content,
+                    &[
+                        ("List-Unsubscribe", &list_unsubscribe),
+                        ("List-Unsubscribe-Post", "List-Unsubscribe=One-Click"),
+                    ],
+                )
+                .await
+            {
+                Ok(()) => "delivered",
+                Err(e) if e.is_transient() && n_retries < MAX_DELIVERY_RETRIES => {
+                    tracing::warn!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        n_retries,
+                        "Failed to deliver issue to a confirmed subscriber. \
+                        Retrying later.",
+                    );
+                    reschedule_task(transaction, issue_id, email.as_ref(), n_retries).await?;
+                    return Ok(ExecutionOutcome::TaskCompleted);
+                }
+                Err(e) => {
+                    log_delivery_failure(&e);
+                    "failed"
+                }
+            }
+        }
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Skipping a confirmed subscriber. \
+                Their stored contact details are invalid",
+            );
+            "failed"
+        }
+    };
+    record_delivery(&mut transaction, issue_id, subscriber_id, outcome).await?;
+    delete_task(transaction, issue_id, &email).await?;
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+fn log_delivery_failure(e: &SendEmailError) {
+    tracing::error!(
+        error.cause_chain = ?e,
+        error.message = %e,
+        "Failed to deliver issue to a confirmed subscriber. \
+        Skipping.",
+    );
+}
+
+/// A signed link that lets the subscriber opt out in one click (RFC 8058).
+fn unsubscribe_link(base_url: &str, subscriber_id: Uuid, hmac_secret: &Secret<String>) -> String {
+    let token = UnsubscribeToken::generate(subscriber_id, hmac_secret);
+    format!(
+        "{}/subscriptions/unsubscribe?token={}",
+        base_url,
+        token.as_ref()
+    )
+}
+
+type PgTransaction = Transaction<'static, Postgres>;
+
+struct DeliveryTask {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+    n_retries: i32,
+}
+
+#[tracing::instrument(skip_all)]
+async fn dequeue_task(
+    pool: &PgPool,
+) -> Result<Option<(PgTransaction, DeliveryTask)>, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let task = sqlx::query_as!(
+        DeliveryTask,
+        r#"
+    SELECT newsletter_issue_id, subscriber_email, n_retries
+    FROM issue_delivery_queue
+    WHERE execute_after <= now()
+    FOR UPDATE
+    SKIP LOCKED
+    LIMIT 1
+    "#,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    Ok(task.map(|task| (transaction, task)))
+}
+
+#[tracing::instrument(skip_all)]
+async fn reschedule_task(
+    mut transaction: PgTransaction,
+    issue_id: Uuid,
+    email: &str,
+    n_retries: i32,
+) -> Result<(), anyhow::Error> {
+    let delay = BASE_REDELIVERY_DELAY * 2u32.pow(n_retries as u32);
+    let execute_after = Utc::now() + chrono::Duration::from_std(delay)?;
+    let query = sqlx::query!(
+        r#"
+    UPDATE issue_delivery_queue
+    SET
+        n_retries = n_retries + 1,
+        execute_after = $3
+    WHERE
+        newsletter_issue_id = $1 AND
+        subscriber_email = $2
+    "#,
+        issue_id,
+        email,
+        execute_after
+    );
+    transaction.execute(query).await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Kept for the data export of the subscriber, see `get_subscriber_data`.
+#[tracing::instrument(skip_all)]
+async fn record_delivery(
+    transaction: &mut PgTransaction,
+    issue_id: Uuid,
+    subscriber_id: Uuid,
+    outcome: &str,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+    INSERT INTO newsletter_deliveries (newsletter_issue_id, subscriber_id, outcome, attempted_at)
+    VALUES ($1, $2, $3, now())
+    ON CONFLICT DO NOTHING
+    "#,
+        issue_id,
+        subscriber_id,
+        outcome
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+/// Mark the issue as `sent` along with its last delivery.
+#[tracing::instrument(skip_all)]
+async fn delete_task(
+    mut transaction: PgTransaction,
+    issue_id: Uuid,
+    email: &str,
+) -> Result<(), anyhow::Error> {
+    // Workers finishing the last deliveries of an issue at the same time take
+    // turns, so that the last one to commit sees an empty queue.
+    let query = sqlx::query!(
+        r#"SELECT newsletter_issue_id FROM newsletter_issues WHERE newsletter_issue_id = $1 FOR UPDATE"#,
+        issue_id
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        r#"
+    DELETE FROM issue_delivery_queue
+    WHERE
+        newsletter_issue_id = $1 AND
+        subscriber_email = $2
+    "#,
+        issue_id,
+        email
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        r#"
+    UPDATE newsletter_issues
+    SET status = 'sent'
+    WHERE
+        newsletter_issue_id = $1 AND
+        status = 'sending' AND
+        NOT EXISTS (SELECT 1 FROM issue_delivery_queue WHERE newsletter_issue_id = $1)
+    "#,
+        issue_id
+    );
+    transaction.execute(query).await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Subscribers can opt out between the moment an issue is enqueued and the
+/// moment we get around to delivering it.
+#[tracing::instrument(skip_all)]
+async fn get_confirmed_subscriber_id(
+    pool: &PgPool,
+    email: &str,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let subscriber_id = sqlx::query_scalar!(
+        r#"
+    SELECT id
+    FROM subscriptions
+    WHERE email = $1 AND status = 'confirmed'
+    "#,
+        email
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(subscriber_id)
+}
+
+struct NewsletterIssue {
+    title: String,
+    text_content: String,
+    html_content: String,
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, anyhow::Error> {
+    let issue = sqlx::query_as!(
+        NewsletterIssue,
+        r#"
+    SELECT title, text_content, html_content
+    FROM newsletter_issues
+    WHERE
+        newsletter_issue_id = $1
+    "#,
+        issue_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(issue)
+}
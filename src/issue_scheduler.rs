@@ -0,0 +1,22 @@
+//! src/issue_scheduler.rs
+use crate::configuration::Settings;
+use crate::services::publish_due_issues;
+use crate::startup::get_connection_pool;
+use sqlx::PgPool;
+use std::time::Duration;
+
+/// How often scheduled issues are checked, i.e. how late they can go out.
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+pub async fn run_scheduler_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database).await;
+    scheduler_loop(connection_pool).await
+}
+
+async fn scheduler_loop(pool: PgPool) -> Result<(), anyhow::Error> {
+    loop {
+        // Failures are already recorded by the span, we try again on the next tick.
+        let _ = publish_due_issues(&pool).await;
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
@@ -1,5 +1,11 @@
 //! main.rs
+use std::fmt::{Debug, Display};
+use tokio::task::JoinError;
 use zero2prod::configuration::get_configuration;
+use zero2prod::idempotency::run_expiry_worker_until_stopped;
+use zero2prod::issue_delivery_worker::run_worker_until_stopped;
+use zero2prod::issue_scheduler::run_scheduler_until_stopped;
+use zero2prod::session_store::run_cleanup_worker_until_stopped;
 use zero2prod::startup::Application;
 use zero2prod::telemetry::{get_subscriber, init_subscriber};
 
@@ -13,6 +19,46 @@ async fn main() -> Result<(), anyhow::Error> {
         .await
         .expect("Failed to build application.");
 
-    app.run_until_stopped().await?;
+    // The delivery worker runs next to the HTTP server, draining the queue
+    // that `publish_newsletter` and the scheduler fill up. It is started here
+    // rather than in `Application`: the tests build an `Application` and
+    // drain the queue themselves, one `try_execute_task` at a time.
+    let application_task = tokio::spawn(app.run_until_stopped());
+    let worker_task = tokio::spawn(run_worker_until_stopped(configuration.clone()));
+    let scheduler_task = tokio::spawn(run_scheduler_until_stopped(configuration.clone()));
+    let expiry_task = tokio::spawn(run_expiry_worker_until_stopped(configuration.clone()));
+    let session_cleanup_task = tokio::spawn(run_cleanup_worker_until_stopped(configuration));
+
+    tokio::select! {
+        o = application_task => report_exit("API", o),
+        o = worker_task => report_exit("Background worker", o),
+        o = scheduler_task => report_exit("Issue scheduler", o),
+        o = expiry_task => report_exit("Idempotency expiry worker", o),
+        o = session_cleanup_task => report_exit("Session cleanup worker", o),
+    };
     Ok(())
 }
+
+fn report_exit(task_name: &str, outcome: Result<Result<(), impl Debug + Display>, JoinError>) {
+    match outcome {
+        Ok(Ok(())) => {
+            tracing::info!("{} has exited", task_name)
+        }
+        Ok(Err(e)) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "{} failed",
+                task_name
+            )
+        }
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "'{}' task failed to complete",
+                task_name
+            )
+        }
+    }
+}
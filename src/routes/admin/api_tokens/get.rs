@@ -0,0 +1,112 @@
+//! src/routes/admin/api_tokens/get.rs
+use crate::authentication::{self, ApiScope, UserId};
+use crate::routes::e500;
+use crate::session_state::TypedSession;
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{IncomingFlashMessages, Level};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M UTC";
+
+#[tracing::instrument(
+    name = "List API tokens",
+    skip(pool, flash_messages, session),
+    fields(user_id=%*user_id)
+)]
+#[utoipa::path(
+    get,
+    path = "/admin/api-tokens",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "The API tokens of the user.", content_type = "text/html"),
+        (status = 303, description = "Not logged in: redirects to `/login`.")
+    )
+)]
+pub async fn list_api_tokens(
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = session.get_or_insert_csrf_token().map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages
+        .iter()
+        .filter(|m| m.level() == Level::Error || m.level() == Level::Info)
+    {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let tokens = authentication::list_api_tokens(&pool, **user_id)
+        .await
+        .map_err(e500)?;
+    let mut rows_html = String::new();
+    for t in tokens {
+        let scopes: Vec<&str> = t.scopes.iter().map(ApiScope::as_str).collect();
+        let last_used_at = t
+            .last_used_at
+            .map(|d| d.format(DATE_FORMAT).to_string())
+            .unwrap_or_else(|| "Never".into());
+        writeln!(
+            rows_html,
+            r#"      <tr>
+        <td>{name}</td>
+        <td>{scopes}</td>
+        <td>{created_at}</td>
+        <td>{last_used_at}</td>
+        <td>
+          <form action="/admin/api-tokens/{token_id}/revoke" method="post">
+            <input hidden type="text" name="csrf_token" value="{csrf_token}">
+            <button type="submit">Revoke</button>
+          </form>
+        </td>
+      </tr>"#,
+            name = htmlescape::encode_minimal(&t.name),
+            scopes = scopes.join(" "),
+            created_at = t.created_at.format(DATE_FORMAT),
+            token_id = t.token_id,
+        )
+        .unwrap();
+    }
+    let mut scope_inputs = String::new();
+    for scope in ApiScope::ALL {
+        writeln!(
+            scope_inputs,
+            r#"      <label><input type="checkbox" name="scopes" value="{scope}"> {scope}</label><br>"#
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta http-equiv="content-type" content="text/html; charset=utf-8">
+  <title>API tokens</title>
+</head>
+<body>
+    <h1>API tokens</h1>
+    {msg_html}
+    <table>
+      <tr><th>Name</th><th>Scopes</th><th>Created</th><th>Last used</th><th>Actions</th></tr>
+{rows_html}
+    </table>
+    <h2>New token</h2>
+    <form action="/admin/api-tokens" method="post">
+      <input hidden type="text" name="csrf_token" value="{csrf_token}">
+      <label>Name
+        <input type="text" placeholder="e.g. CI" name="name">
+      </label>
+      <br>
+{scope_inputs}
+      <button type="submit">Create token</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
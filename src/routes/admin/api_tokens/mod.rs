@@ -0,0 +1,9 @@
+//! src/routes/admin/api_tokens/mod.rs
+
+mod get;
+pub use get::{__path_list_api_tokens, list_api_tokens};
+
+mod post;
+pub use post::{
+    __path_create_api_token, __path_revoke_api_token, create_api_token, revoke_api_token,
+};
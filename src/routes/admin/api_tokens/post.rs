@@ -0,0 +1,136 @@
+//! src/routes/admin/api_tokens/post.rs
+use crate::authentication::{self, ApiScope, Role, UserId};
+use crate::routes::admin::helpers::{e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(utoipa::ToSchema)]
+pub struct ApiTokenFormData {
+    name: String,
+    /// Repeated, once per ticked checkbox.
+    scopes: Vec<String>,
+}
+
+impl ApiTokenFormData {
+    /// Read by hand rather than with `web::Form`: `serde_urlencoded` cannot
+    /// collect a repeated field into a `Vec`.
+    fn parse(body: &[u8]) -> Result<Self, String> {
+        let fields = serde_urlencoded::from_bytes::<Vec<(String, String)>>(body)
+            .map_err(|_| "The form could not be read.".to_string())?;
+        let mut form = Self {
+            name: String::new(),
+            scopes: Vec::new(),
+        };
+        for (field, value) in fields {
+            match field.as_str() {
+                "name" => form.name = value.trim().to_owned(),
+                "scopes" => form.scopes.push(value),
+                _ => {}
+            }
+        }
+        Ok(form)
+    }
+
+    fn validate(self) -> Result<(String, Vec<ApiScope>), String> {
+        if self.name.is_empty() {
+            return Err("A name is required.".into());
+        }
+        let mut scopes = Vec::new();
+        for scope in &self.scopes {
+            let scope = ApiScope::parse(scope)?;
+            if !scopes.contains(&scope) {
+                scopes.push(scope);
+            }
+        }
+        if scopes.is_empty() {
+            return Err("Pick at least one scope.".into());
+        }
+        Ok((self.name, scopes))
+    }
+}
+
+/// The token is shown right away instead of going through a redirect, so it
+/// never ends up in a cookie or in the browser history.
+#[tracing::instrument(name = "Create an API token", skip(body, pool), fields(user_id=%*user_id))]
+#[utoipa::path(
+    post,
+    path = "/admin/api-tokens",
+    tag = "admin",
+    request_body(content = ApiTokenFormData, content_type = "application/x-www-form-urlencoded"),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 200, description = "Shows the new token, once.", content_type = "text/html"),
+        (status = 303, description = "The form is invalid: redirects to `/admin/api-tokens` with an error.")
+    )
+)]
+pub async fn create_api_token(
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    role: web::ReqData<Role>,
+    body: web::Bytes,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (name, scopes) = match ApiTokenFormData::parse(&body).and_then(ApiTokenFormData::validate) {
+        Ok(form) => form,
+        Err(e) => {
+            FlashMessage::error(htmlescape::encode_minimal(&e)).send();
+            return Ok(see_other("/admin/api-tokens"));
+        }
+    };
+    if let Some(scope) = scopes.iter().find(|s| !role.can(s.permission())) {
+        FlashMessage::error(format!("Your role does not allow the {scope} scope.")).send();
+        return Ok(see_other("/admin/api-tokens"));
+    }
+    let (_, token) = authentication::create_api_token(&pool, **user_id, &name, &scopes)
+        .await
+        .map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta http-equiv="content-type" content="text/html; charset=utf-8">
+  <title>API token created</title>
+</head>
+<body>
+    <h1>API token created</h1>
+    <p>Copy the token for {name} now, it will not be shown again:</p>
+    <pre><code>{token}</code></pre>
+    <p>Send it in the <code>Authorization: Bearer</code> header of requests to <code>/api/v1</code>.</p>
+    <p><a href="/admin/api-tokens">&lt;- Back</a></p>
+</body>
+</html>"#,
+            name = htmlescape::encode_minimal(&name),
+        )))
+}
+
+#[tracing::instrument(name = "Revoke an API token", skip(pool), fields(user_id=%*user_id))]
+#[utoipa::path(
+    post,
+    path = "/admin/api-tokens/{token_id}/revoke",
+    tag = "admin",
+    params(("token_id" = Uuid, Path, description = "An API token of the user.")),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to `/admin/api-tokens` with the outcome.")
+    )
+)]
+pub async fn revoke_api_token(
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    token_id: web::Path<Uuid>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let revoked = authentication::revoke_api_token(&pool, **user_id, token_id.into_inner())
+        .await
+        .map_err(e500)?;
+    if revoked {
+        FlashMessage::info("The API token has been revoked.").send();
+    } else {
+        FlashMessage::error("There is no such API token.").send();
+    }
+    Ok(see_other("/admin/api-tokens"))
+}
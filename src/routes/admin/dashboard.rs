@@ -1,17 +1,43 @@
 //! src/routes/admin/dashboard.rs
-use crate::authentication::UserId;
+use crate::authentication::{Permission, Role, UserId};
 use crate::routes::{admin::helpers::get_username, e500};
+use crate::session_state::TypedSession;
 use actix_web::web;
 use actix_web::{HttpResponse, http::header::ContentType};
 use sqlx::PgPool;
 
-#[tracing::instrument(name = "Get admin dashboard", skip(pool, user_id))]
+#[tracing::instrument(name = "Get admin dashboard", skip(pool, user_id, role, session))]
+#[utoipa::path(
+    get,
+    path = "/admin/dashboard",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "The actions available to the user.", content_type = "text/html"),
+        (status = 303, description = "Not logged in: redirects to `/login`.")
+    )
+)]
 pub async fn admin_dashboard(
     pool: actix_web::web::Data<PgPool>,
     user_id: web::ReqData<UserId>,
+    role: web::ReqData<Role>,
+    session: TypedSession,
 ) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = session.get_or_insert_csrf_token().map_err(e500)?;
     let user_id = user_id.into_inner();
     let username = get_username(&pool, &user_id).await.map_err(e500)?;
+    let username = htmlescape::encode_minimal(&username);
+    let mut actions = String::new();
+    if role.can(Permission::ReadSubscribers) {
+        actions.push_str(r#"<li><a href="/admin/subscribers">Subscribers</a></li>"#);
+    }
+    if role.can(Permission::PublishNewsletters) {
+        actions.push_str(r#"<li><a href="/admin/newsletters">Publish a newsletter issue</a></li>"#);
+    }
+    if role.can(Permission::ManageUsers) {
+        actions.push_str(r#"<li><a href="/admin/users">Manage users</a></li>"#);
+        actions.push_str(r#"<li><a href="/admin/lockouts">Manage login lockouts</a></li>"#);
+    }
 
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
@@ -23,14 +49,23 @@ pub async fn admin_dashboard(
 <title>Admin dashboard</title>
 </head>
 <body>
-<p>Welcome {username}!</p>
+<p>Welcome {username}! You are signed in as {role}.</p>
 <p>Available actions:</p>
 <ol>
     <li><a href="/admin/password">Change password</a></li>
-    <li><a href="/admin/newsletters">Publish a newsletter issue</a></li>
-    <li><a href="/admin/logout">Logout</a></li>
+    <li><a href="/admin/two-factor">Set up two-factor authentication</a></li>
+    <li><a href="/admin/sessions">Active sessions</a></li>
+    <li><a href="/admin/api-tokens">API tokens</a></li>
+    {actions}
+    <li>
+      <form action="/admin/logout" method="post">
+        <input hidden type="text" name="csrf_token" value="{csrf_token}">
+        <button type="submit">Logout</button>
+      </form>
+    </li>
 </ol>
 </body>
-</html>"#
+</html>"#,
+            role = *role
         )))
 }
@@ -0,0 +1,88 @@
+//! src/routes/admin/lockouts/get.rs
+use crate::authentication::LoginThrottle;
+use crate::routes::e500;
+use crate::session_state::TypedSession;
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{IncomingFlashMessages, Level};
+use std::fmt::Write;
+
+#[tracing::instrument(name = "List login lockouts", skip(throttle, flash_messages, session))]
+#[utoipa::path(
+    get,
+    path = "/admin/lockouts",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "The current login lockouts.", content_type = "text/html"),
+        (status = 303, description = "Not logged in: redirects to `/login`."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
+pub async fn list_lockouts(
+    throttle: web::Data<LoginThrottle>,
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = session.get_or_insert_csrf_token().map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages
+        .iter()
+        .filter(|m| m.level() == Level::Error || m.level() == Level::Info)
+    {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let lockouts = throttle.list_lockouts().await.map_err(e500)?;
+    let mut rows_html = String::new();
+    for lockout in &lockouts {
+        let identifier = htmlescape::encode_minimal(&lockout.identifier);
+        let identifier_attribute = htmlescape::encode_attribute(&lockout.identifier);
+        let kind = lockout.kind.as_str();
+        writeln!(
+            rows_html,
+            r#"      <tr>
+        <td>{kind}</td>
+        <td>{identifier}</td>
+        <td>{minutes} min</td>
+        <td>
+          <form action="/admin/lockouts/clear" method="post">
+            <input hidden type="text" name="csrf_token" value="{csrf_token}">
+            <input hidden type="text" name="kind" value="{kind}">
+            <input hidden type="text" name="identifier" value="{identifier_attribute}">
+            <button type="submit">Clear</button>
+          </form>
+        </td>
+      </tr>"#,
+            minutes = lockout.expires_in.as_secs().div_ceil(60),
+        )
+        .unwrap();
+    }
+    let table_html = if lockouts.is_empty() {
+        "<p>Nobody is locked out.</p>".to_string()
+    } else {
+        format!(
+            r#"<table>
+      <tr><th>Kind</th><th>Username or IP</th><th>Expires in</th><th>Actions</th></tr>
+{rows_html}
+    </table>"#
+        )
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta http-equiv="content-type" content="text/html; charset=utf-8">
+  <title>Login lockouts</title>
+</head>
+<body>
+    <h1>Login lockouts</h1>
+    {msg_html}
+    {table_html}
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
@@ -0,0 +1,7 @@
+//! src/routes/admin/lockouts/mod.rs
+
+mod get;
+pub use get::{__path_list_lockouts, list_lockouts};
+
+mod post;
+pub use post::{__path_clear_lockout, clear_lockout};
@@ -0,0 +1,47 @@
+//! src/routes/admin/lockouts/post.rs
+use crate::authentication::{LockoutKind, LoginThrottle, UserId};
+use crate::routes::admin::helpers::{e500, see_other};
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct ClearLockoutFormData {
+    kind: String,
+    identifier: String,
+}
+
+#[tracing::instrument(name = "Clear a login lockout", skip(form, throttle), fields(user_id=%*user_id))]
+#[utoipa::path(
+    post,
+    path = "/admin/lockouts/clear",
+    tag = "admin",
+    request_body(content = ClearLockoutFormData, content_type = "application/x-www-form-urlencoded"),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to `/admin/lockouts` with the outcome."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
+pub async fn clear_lockout(
+    throttle: web::Data<LoginThrottle>,
+    user_id: web::ReqData<UserId>,
+    web::Form(form): web::Form<ClearLockoutFormData>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let kind = match LockoutKind::parse(&form.kind) {
+        Ok(kind) => kind,
+        Err(e) => {
+            FlashMessage::error(htmlescape::encode_minimal(&e)).send();
+            return Ok(see_other("/admin/lockouts"));
+        }
+    };
+    let cleared = throttle
+        .clear_lockout(kind, &form.identifier)
+        .await
+        .map_err(e500)?;
+    if cleared {
+        FlashMessage::info("The lockout has been cleared.").send();
+    } else {
+        FlashMessage::error("There is no such lockout, it may have expired already.").send();
+    }
+    Ok(see_other("/admin/lockouts"))
+}
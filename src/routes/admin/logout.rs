@@ -1,9 +1,27 @@
 //! src/routes/admin/logout.rs
-use crate::{routes::admin::helpers::see_other, session_state::TypedSession};
-use actix_web::HttpResponse;
+use crate::authentication::{SessionRegistry, UserId};
+use crate::routes::admin::helpers::{e500, see_other};
+use crate::session_state::TypedSession;
+use actix_web::{HttpResponse, web};
 use actix_web_flash_messages::FlashMessage;
 
-pub async fn logout(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
+#[utoipa::path(
+    post,
+    path = "/admin/logout",
+    tag = "admin",
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to `/login`.")
+    )
+)]
+pub async fn logout(
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+    registry: web::Data<SessionRegistry>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let Some(session_id) = session.get_session_id().map_err(e500)? {
+        registry.revoke(**user_id, session_id).await.map_err(e500)?;
+    }
     session.logout();
     FlashMessage::info("You have successfully logged out.").send();
     Ok(see_other("/login"))
@@ -1,12 +1,24 @@
 //! src/routes/admin/mod.rs
+mod api_tokens;
 mod dashboard;
 mod helpers;
+mod lockouts;
 mod logout;
 mod newsletters;
 mod password;
+mod sessions;
+mod subscribers;
+mod two_factor;
+mod users;
 
-pub use dashboard::admin_dashboard;
+pub use api_tokens::*;
+pub use dashboard::{__path_admin_dashboard, admin_dashboard};
 pub use helpers::*;
+pub use lockouts::*;
 pub use logout::*;
 pub use newsletters::*;
 pub use password::*;
+pub use sessions::*;
+pub use subscribers::*;
+pub use two_factor::*;
+pub use users::*;
@@ -0,0 +1,403 @@
+//! src/routes/admin/newsletters/drafts.rs
+use super::post::success_message;
+use crate::routes::admin::helpers::{e500, see_other};
+use crate::services::{self, IssueStateError, NewIssue};
+use crate::session_state::TypedSession;
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages, Level};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct DraftFormData {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    content_markdown: String,
+    #[serde(default)]
+    content_text: String,
+    #[serde(default)]
+    content_html: String,
+}
+
+impl DraftFormData {
+    fn as_issue(&self) -> NewIssue<'_> {
+        NewIssue {
+            title: &self.title,
+            content_markdown: &self.content_markdown,
+            content_text: &self.content_text,
+            content_html: &self.content_html,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct ScheduleFormData {
+    /// In UTC, as submitted by a `datetime-local` input or as RFC 3339.
+    scheduled_at: String,
+}
+
+/// Where the issue can be edited, or at least looked at.
+fn edit_location(issue_id: Uuid) -> String {
+    format!("/admin/newsletters/{issue_id}/edit")
+}
+
+/// Flash why the issue could not change state and go back to `location`,
+/// unless something went wrong on our side.
+fn state_error(e: IssueStateError, location: &str) -> Result<HttpResponse, actix_web::Error> {
+    let location = match e {
+        IssueStateError::UnexpectedError(_) => return Err(e500(e)),
+        IssueStateError::UnknownIssue => "/admin/newsletters",
+        _ => location,
+    };
+    FlashMessage::error(e.to_string()).send();
+    Ok(see_other(location))
+}
+
+/// `datetime-local` inputs submit `2025-10-20T14:30`, without an offset.
+fn parse_send_time(s: &str) -> Result<DateTime<Utc>, String> {
+    let s = s.trim();
+    DateTime::parse_from_rfc3339(s)
+        .map(|t| t.with_timezone(&Utc))
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M").map(|t| t.and_utc()))
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S").map(|t| t.and_utc()))
+        .map_err(|_| "The send time is invalid.".to_string())
+}
+
+#[tracing::instrument(name = "Create a newsletter draft", skip(form, pool))]
+#[utoipa::path(
+    post,
+    path = "/admin/newsletters/drafts",
+    tag = "admin",
+    request_body(content = DraftFormData, content_type = "application/x-www-form-urlencoded"),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to the edit page of the new draft."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
+pub async fn create_newsletter_draft(
+    pool: web::Data<PgPool>,
+    web::Form(form): web::Form<DraftFormData>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = services::create_draft(&pool, &form.as_issue())
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("The draft has been saved.").send();
+    Ok(see_other(&edit_location(issue_id)))
+}
+
+#[tracing::instrument(
+    name = "Get the newsletter draft form",
+    skip(pool, flash_messages, session)
+)]
+#[utoipa::path(
+    get,
+    path = "/admin/newsletters/{issue_id}/edit",
+    tag = "admin",
+    params(("issue_id" = Uuid, Path)),
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "The draft, with the actions its state allows.", content_type = "text/html"),
+        (status = 303, description = "There is no such issue: redirects to `/admin/newsletters`."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
+pub async fn edit_newsletter_draft_form(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(issue) = services::get_issue(&pool, issue_id.into_inner())
+        .await
+        .map_err(e500)?
+    else {
+        return state_error(IssueStateError::UnknownIssue, "/admin/newsletters");
+    };
+    let csrf_token = session.get_or_insert_csrf_token().map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages
+        .iter()
+        .filter(|m| m.level() == Level::Error || m.level() == Level::Info)
+    {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let id = issue.id;
+    let overrides = issue.overrides();
+    let csrf_field =
+        format!(r#"<input hidden type="text" name="csrf_token" value="{csrf_token}">"#);
+    let (readonly, actions) = match (issue.status.as_str(), issue.scheduled_at) {
+        ("draft", _) => (
+            "",
+            format!(
+                r#"<button type="submit">Save</button>
+    </form>
+    <form action="/admin/newsletters/{id}/schedule" method="post">
+      {csrf_field}
+      <label>Send at (UTC) <input type="datetime-local" name="scheduled_at"></label>
+      <button type="submit">Schedule</button>
+    </form>
+    <form action="/admin/newsletters/{id}/publish" method="post">
+      {csrf_field}
+      <button type="submit">Publish now</button>
+    </form>"#
+            ),
+        ),
+        ("scheduled", Some(scheduled_at)) => (
+            " readonly",
+            format!(
+                r#"</form>
+    <p>Scheduled for {}.</p>
+    <form action="/admin/newsletters/{id}/cancel" method="post">
+      {csrf_field}
+      <button type="submit">Cancel and edit</button>
+    </form>"#,
+                scheduled_at.format("%Y-%m-%d %H:%M UTC")
+            ),
+        ),
+        (status, _) => (
+            " readonly",
+            format!("</form>\n    <p>This issue is {status}, it can no longer be edited.</p>"),
+        ),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta http-equiv="content-type" content="text/html; charset=utf-8">
+  <title>Edit Newsletter</title>
+</head>
+<body>
+    <h1>Edit Newsletter</h1>
+    {msg_html}
+    <p><a href="/admin/newsletters/{id}/preview">Preview</a></p>
+    <form action="/admin/newsletters/{id}/edit" method="post">
+      {csrf_field}
+      <label>Title
+        <input type="text" name="title" value="{title}"{readonly}>
+      </label>
+      <br />
+      <label>Markdown
+        <textarea name="content_markdown" rows="15" cols="50"{readonly}>{content_markdown}</textarea>
+      </label>
+      <br />
+      <p>The plain text and HTML versions are rendered from the Markdown, unless you write them below.</p>
+      <label>Plain text override
+        <textarea name="content_text" rows="10" cols="50"{readonly}>{content_text}</textarea>
+      </label>
+      <br />
+      <label>HTML override
+        <textarea name="content_html" rows="10" cols="50"{readonly}>{content_html}</textarea>
+      </label>
+      <br />
+    {actions}
+    <p><a href="/admin/newsletters">&lt;- Back</a></p>
+</body>
+</html>"#,
+            title = htmlescape::encode_attribute(&issue.title),
+            content_markdown =
+                htmlescape::encode_minimal(issue.content_markdown.as_deref().unwrap_or_default()),
+            content_text = htmlescape::encode_minimal(&overrides.text),
+            content_html = htmlescape::encode_minimal(&overrides.html),
+        )))
+}
+
+#[tracing::instrument(name = "Update a newsletter draft", skip(form, pool))]
+#[utoipa::path(
+    post,
+    path = "/admin/newsletters/{issue_id}/edit",
+    tag = "admin",
+    params(("issue_id" = Uuid, Path)),
+    request_body(content = DraftFormData, content_type = "application/x-www-form-urlencoded"),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to the edit page with the outcome."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
+pub async fn update_newsletter_draft(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    web::Form(form): web::Form<DraftFormData>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    let location = edit_location(issue_id);
+    if let Err(e) = services::update_draft(&pool, issue_id, &form.as_issue()).await {
+        return state_error(e, &location);
+    }
+    FlashMessage::info("The draft has been saved.").send();
+    Ok(see_other(&location))
+}
+
+#[tracing::instrument(name = "Preview a newsletter issue", skip(pool))]
+#[utoipa::path(
+    get,
+    path = "/admin/newsletters/{issue_id}/preview",
+    tag = "admin",
+    params(("issue_id" = Uuid, Path)),
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "The issue as subscribers will see it.", content_type = "text/html"),
+        (status = 303, description = "There is no such issue: redirects to `/admin/newsletters`."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
+pub async fn preview_newsletter_issue(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(issue) = services::get_issue(&pool, issue_id.into_inner())
+        .await
+        .map_err(e500)?
+    else {
+        return state_error(IssueStateError::UnknownIssue, "/admin/newsletters");
+    };
+    // The HTML is rendered in a sandboxed frame, scripts in it cannot act on
+    // behalf of the logged-in user.
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta http-equiv="content-type" content="text/html; charset=utf-8">
+  <title>Preview: {title}</title>
+</head>
+<body>
+    <h1>{title}</h1>
+    <h2>HTML</h2>
+    <iframe sandbox srcdoc="{content_html}" width="100%" height="400"></iframe>
+    <h2>Plain text</h2>
+    <pre>{content_text}</pre>
+    <p>An unsubscribe link is added at the bottom of every email.</p>
+    <p><a href="/admin/newsletters/{id}/edit">&lt;- Back</a></p>
+</body>
+</html>"#,
+            id = issue.id,
+            title = htmlescape::encode_minimal(&issue.title),
+            content_html = htmlescape::encode_attribute(&issue.html_content),
+            content_text = htmlescape::encode_minimal(&issue.text_content),
+        )))
+}
+
+#[tracing::instrument(name = "Schedule a newsletter issue", skip(form, pool))]
+#[utoipa::path(
+    post,
+    path = "/admin/newsletters/{issue_id}/schedule",
+    tag = "admin",
+    params(("issue_id" = Uuid, Path)),
+    request_body(content = ScheduleFormData, content_type = "application/x-www-form-urlencoded"),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to the edit page with the outcome."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
+pub async fn schedule_newsletter_issue(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    web::Form(form): web::Form<ScheduleFormData>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    let location = edit_location(issue_id);
+    let scheduled_at = match parse_send_time(&form.scheduled_at) {
+        Ok(scheduled_at) => scheduled_at,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other(&location));
+        }
+    };
+    if let Err(e) = services::schedule_issue(&pool, issue_id, scheduled_at).await {
+        return state_error(e, &location);
+    }
+    FlashMessage::info(format!(
+        "The issue will be sent on {}.",
+        scheduled_at.format("%Y-%m-%d %H:%M UTC")
+    ))
+    .send();
+    Ok(see_other(&location))
+}
+
+#[tracing::instrument(name = "Cancel a scheduled newsletter issue", skip(pool))]
+#[utoipa::path(
+    post,
+    path = "/admin/newsletters/{issue_id}/cancel",
+    tag = "admin",
+    params(("issue_id" = Uuid, Path)),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to the edit page with the outcome, the issue is a draft again."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
+pub async fn cancel_newsletter_issue(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    let location = edit_location(issue_id);
+    if let Err(e) = services::cancel_scheduled_issue(&pool, issue_id).await {
+        return state_error(e, &location);
+    }
+    FlashMessage::info("The issue is no longer scheduled, it is a draft again.").send();
+    Ok(see_other(&location))
+}
+
+#[tracing::instrument(name = "Publish a newsletter draft", skip(pool))]
+#[utoipa::path(
+    post,
+    path = "/admin/newsletters/{issue_id}/publish",
+    tag = "admin",
+    params(("issue_id" = Uuid, Path)),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to `/admin/newsletters` once the deliveries are queued, to the edit page otherwise."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
+pub async fn publish_newsletter_draft(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    if let Err(e) = services::publish_draft(&pool, issue_id).await {
+        return state_error(e, &edit_location(issue_id));
+    }
+    success_message().send();
+    Ok(see_other("/admin/newsletters"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_send_time;
+    use claims::assert_err;
+
+    #[test]
+    fn send_times_are_read_as_utc() {
+        for input in [
+            "2025-10-20T14:30",
+            "2025-10-20T14:30:00",
+            "2025-10-20T16:30:00+02:00",
+        ] {
+            assert_eq!(
+                parse_send_time(input).unwrap().to_rfc3339(),
+                "2025-10-20T14:30:00+00:00",
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_send_times_are_rejected() {
+        assert_err!(parse_send_time(""));
+        assert_err!(parse_send_time("tomorrow"));
+        assert_err!(parse_send_time("2025-02-30T14:30"));
+    }
+}
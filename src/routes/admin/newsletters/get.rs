@@ -1,12 +1,66 @@
 //! src/routes/admin/newsletters/get.rs
-use actix_web::{HttpResponse, http::header::ContentType};
+use crate::routes::e500;
+use crate::services::{self, NewsletterIssue};
+use crate::session_state::TypedSession;
+use actix_web::{HttpResponse, http::header::ContentType, web};
 use actix_web_flash_messages::{IncomingFlashMessages, Level};
+use sqlx::PgPool;
 use std::fmt::Write;
+use uuid::Uuid;
 
-#[tracing::instrument(name = "Get publish newsletter form", skip(flash_messages))]
+/// One row of the issues table, with the actions the state of the issue allows.
+fn issue_row(issue: &NewsletterIssue, csrf_token: &str) -> String {
+    let id = issue.id;
+    let when = issue
+        .published_at
+        .or(issue.scheduled_at)
+        .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default();
+    let actions = match issue.status.as_str() {
+        "draft" => format!(r#"<a href="/admin/newsletters/{id}/edit">Edit</a>"#),
+        "scheduled" => format!(
+            r#"<form action="/admin/newsletters/{id}/cancel" method="post">
+          <input hidden type="text" name="csrf_token" value="{csrf_token}">
+          <button type="submit">Cancel</button>
+        </form>"#
+        ),
+        _ => String::new(),
+    };
+    format!(
+        r#"<tr>
+      <td><a href="/admin/newsletters/{id}/preview">{title}</a></td>
+      <td>{status}</td>
+      <td>{when}</td>
+      <td>{pending}</td>
+      <td>{actions}</td>
+    </tr>"#,
+        title = htmlescape::encode_minimal(&issue.title),
+        status = issue.status,
+        pending = issue.pending_deliveries,
+    )
+}
+
+#[tracing::instrument(
+    name = "Get publish newsletter form",
+    skip(pool, flash_messages, session)
+)]
+#[utoipa::path(
+    get,
+    path = "/admin/newsletters",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "The publish form, and the issues published or in the works.", content_type = "text/html"),
+        (status = 303, description = "Not logged in: redirects to `/login`."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
 pub async fn publish_newsletter_form(
+    pool: web::Data<PgPool>,
     flash_messages: IncomingFlashMessages,
+    session: TypedSession,
 ) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = session.get_or_insert_csrf_token().map_err(e500)?;
     let mut msg_html = String::new();
     for m in flash_messages
         .iter()
@@ -14,6 +68,13 @@ pub async fn publish_newsletter_form(
     {
         writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
     }
+    let idempotency_key = Uuid::new_v4();
+    let issues = services::list_issues(&pool).await.map_err(e500)?;
+    let issue_rows = issues
+        .iter()
+        .map(|issue| issue_row(issue, &csrf_token))
+        .collect::<Vec<_>>()
+        .join("\n    ");
 
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
@@ -30,26 +91,41 @@ pub async fn publish_newsletter_form(
     <h1>Publish Newsletter</h1>
     {msg_html}
     <form action="/admin/newsletters" method="post">
+      <input hidden type="text" name="csrf_token" value="{csrf_token}">
       <label for="title">
       Title:
       </label>
       <input id="title" type="text" name="title" placeholder="Enter a newsletter title" />
       <br />
       <br />
+      <label for="markdown_content">
+      Write your newsletter in Markdown:
+      </label>
+      <textarea id="markdown_content" name="content_markdown" rows="15" cols="50" placeholder="Write your newsletter (markdown)"></textarea>
+      <br />
+      <br />
+      <p>The plain text and HTML versions are rendered from the Markdown, unless you write them below.</p>
       <label for="text_content">
-      Write your newsletter in plain text:
+      Plain text override:
       </label>
       <textarea id="text_content" name="content_text" rows="10" cols="50" placeholder="Write your newsletter (plain text)"></textarea>
       <br />
       <br />
       <label for="html_content">
-      Write your newsletter with HTML: 
+      HTML override:
       </label>
       <textarea id="html_content" name="content_html" rows="10" cols="50" placeholder="Write your newsletter (html)"></textarea>
       <br />
       <br />
+      <input hidden type="text" name="idempotency_key" value="{idempotency_key}">
       <button type="submit">Submit</button>
-     <form>
+      <button type="submit" formaction="/admin/newsletters/drafts">Save as draft</button>
+     </form>
+    <h2>Issues</h2>
+    <table>
+    <tr><th>Title</th><th>Status</th><th>Scheduled or published</th><th>Pending deliveries</th><th></th></tr>
+    {issue_rows}
+    </table>
      <a href="/admin/dashboard">Cancel</a>
 </body>
 </html>
@@ -1,7 +1,17 @@
 //! src/routes/admin/newsletters/mod.rs
 
 mod get;
-pub use get::publish_newsletter_form;
+pub use get::{__path_publish_newsletter_form, publish_newsletter_form};
 
 mod post;
-pub use post::publish_newsletter;
+pub use post::{__path_publish_newsletter, publish_newsletter};
+
+mod drafts;
+pub use drafts::{
+    __path_cancel_newsletter_issue, __path_create_newsletter_draft,
+    __path_edit_newsletter_draft_form, __path_preview_newsletter_issue,
+    __path_publish_newsletter_draft, __path_schedule_newsletter_issue,
+    __path_update_newsletter_draft, cancel_newsletter_issue, create_newsletter_draft,
+    edit_newsletter_draft_form, preview_newsletter_issue, publish_newsletter_draft,
+    schedule_newsletter_issue, update_newsletter_draft,
+};
@@ -1,105 +1,94 @@
 //! src/routes/admin/newsletters/post.rs
 use crate::{
     authentication::UserId,
-    domain::SubscriberEmail,
-    email_client::EmailClient,
-    routes::{admin::helpers::e500, see_other},
+    idempotency::{IdempotencyKey, NextAction, save_response, try_processing},
+    routes::{
+        admin::helpers::{e400, e500},
+        see_other,
+    },
+    services::{self, NewIssue},
 };
 use actix_web::{HttpResponse, web};
 use actix_web_flash_messages::FlashMessage;
-use anyhow::Context;
 use sqlx::PgPool;
 
-#[derive(serde::Deserialize, Debug)]
+#[derive(serde::Deserialize, Debug, utoipa::ToSchema)]
+#[schema(as = PublishNewsletterForm)]
 pub struct FormData {
     title: String,
+    #[serde(default)]
+    content_markdown: String,
+    /// Overrides the text rendered from `content_markdown` when not empty.
     content_text: String,
+    /// Overrides the HTML rendered from `content_markdown` when not empty.
     content_html: String,
+    idempotency_key: String,
+}
+
+pub(super) fn success_message() -> FlashMessage {
+    FlashMessage::info("The newsletter issue has been accepted - emails will go out shortly.")
 }
 
 #[tracing::instrument(name = "Publish a newsletter issue",
-    skip(form, pool, email_client),
+    skip(form, pool),
     fields(user_id=%*user_id)
 )]
+#[utoipa::path(
+    post,
+    path = "/admin/newsletters",
+    tag = "admin",
+    request_body(content = FormData, content_type = "application/x-www-form-urlencoded"),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to `/admin/newsletters` with the outcome."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
 pub async fn publish_newsletter(
     pool: web::Data<PgPool>,
-    email_client: web::Data<EmailClient>,
     user_id: web::ReqData<UserId>,
     web::Form(form): web::Form<FormData>,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let mut is_valid = true;
-    if form.title.is_empty() {
-        FlashMessage::error("Title is required").send();
-        is_valid = false;
-    }
-
-    if form.content_html.is_empty() {
-        FlashMessage::error("HTML content is required.").send();
-        is_valid = false;
-    }
-
-    if form.content_text.is_empty() {
-        FlashMessage::error("Text content is required.").send();
-        is_valid = false;
-    }
-
-    if !is_valid {
+    let FormData {
+        title,
+        content_markdown,
+        content_text,
+        content_html,
+        idempotency_key,
+    } = form;
+    let issue = NewIssue {
+        title: &title,
+        content_markdown: &content_markdown,
+        content_text: &content_text,
+        content_html: &content_html,
+    };
+    let errors = issue.validation_errors();
+    if !errors.is_empty() {
+        for e in errors {
+            FlashMessage::error(e).send();
+        }
         return Ok(see_other("/admin/newsletters"));
     }
 
-    let confirmed_subscribers = get_confirmed_subscribers(&pool)
+    let user_id = user_id.into_inner();
+    let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
+    let mut transaction = match try_processing(&pool, &idempotency_key, *user_id)
+        .await
+        .map_err(e500)?
+    {
+        NextAction::StartProcessing(t) => t,
+        NextAction::ReturnSavedResponse(saved_response) => {
+            success_message().send();
+            return Ok(saved_response);
+        }
+    };
+    services::publish_issue(&mut transaction, &issue)
         .await
-        .context("Failed to get confirmed subscribers")
         .map_err(e500)?;
-
-    for confirmed_subscriber in confirmed_subscribers {
-        match confirmed_subscriber {
-            Ok(subscriber) => email_client
-                .send_email(
-                    &subscriber.email,
-                    &form.title,
-                    &form.content_html,
-                    &form.content_text,
-                )
-                .await
-                .with_context(|| {
-                    format!(
-                        "Failed to send email to subscriber with email: {}",
-                        subscriber.email
-                    )
-                })
-                .map_err(e500)?,
-            Err(e) => {
-                tracing::warn!(e.cause_chain = ?e, "Skipping a confirmed subscriber. \
-                    Their stored contact details are invalid.
-                ");
-            }
-        };
-    }
-    FlashMessage::info("Newsletter published successfully!").send();
-    Ok(see_other("/admin/newsletters"))
-}
-
-#[tracing::instrument(name = "Get confirmed subscribers", skip(pool))]
-async fn get_confirmed_subscribers(
-    pool: &PgPool,
-) -> Result<Vec<Result<ConfirmedSubscriber, anyhow::Error>>, anyhow::Error> {
-    let rows = sqlx::query!(r#"SELECT email FROM subscriptions WHERE status = 'confirmed';"#)
-        .fetch_all(pool)
-        .await?;
-
-    let confirmed_subscribers = rows
-        .into_iter()
-        .map(|r| match SubscriberEmail::parse(r.email) {
-            Ok(subscriber_email) => Ok(ConfirmedSubscriber {
-                email: subscriber_email,
-            }),
-            Err(e) => Err(anyhow::anyhow!(e)),
-        })
-        .collect();
-    Ok(confirmed_subscribers)
-}
-
-struct ConfirmedSubscriber {
-    email: SubscriberEmail,
+    let response = see_other("/admin/newsletters");
+    let response = save_response(transaction, &idempotency_key, *user_id, response)
+        .await
+        .map_err(e500)?;
+    success_message().send();
+    Ok(response)
 }
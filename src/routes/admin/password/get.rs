@@ -1,12 +1,26 @@
 //! src/routes/admin/password/get.rs
+use crate::routes::e500;
+use crate::session_state::TypedSession;
 use actix_web::{HttpResponse, http::header::ContentType};
 use actix_web_flash_messages::{IncomingFlashMessages, Level};
 use std::fmt::Write;
 
-#[tracing::instrument(name = "Get change password form", skip(flash_messages))]
+#[tracing::instrument(name = "Get change password form", skip(flash_messages, session))]
+#[utoipa::path(
+    get,
+    path = "/admin/password",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "The change password form.", content_type = "text/html"),
+        (status = 303, description = "Not logged in: redirects to `/login`.")
+    )
+)]
 pub async fn change_password_form(
     flash_messages: IncomingFlashMessages,
+    session: TypedSession,
 ) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = session.get_or_insert_csrf_token().map_err(e500)?;
     let mut msg_html = String::new();
     for m in flash_messages
         .iter()
@@ -30,6 +44,7 @@ pub async fn change_password_form(
     <h1>Change Password</h1>
     {msg_html}
     <form action="/admin/password" method="post">
+      <input hidden type="text" name="csrf_token" value="{csrf_token}">
       <label for="Password">Current password
       <input type="password" name="current_password" placeholder="Enter your current password">
       </label>
@@ -1,6 +1,6 @@
 //! src/routes/admin/password/mod.rs
 mod get;
-pub use get::change_password_form;
+pub use get::{__path_change_password_form, change_password_form};
 
 mod post;
-pub use post::change_password;
+pub use post::{__path_change_password, change_password, set_new_password};
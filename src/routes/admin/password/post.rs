@@ -1,34 +1,53 @@
 //! src/routes/admin/password/post.rs
-use crate::authentication::{AuthError, Credentials, UserId, validate_credentials};
+use crate::authentication::{
+    AuthError, Credentials, PasswordHashing, SessionRegistry, UserId, compute_password_hash,
+    validate_credentials,
+};
 use crate::domain::Password;
 use crate::routes::admin::helpers::{e500, see_other};
 use crate::routes::get_username;
+use crate::session_state::TypedSession;
 use actix_web::HttpResponse;
 use actix_web::web;
 use actix_web_flash_messages::FlashMessage;
 use anyhow::Context;
-use argon2::password_hash::SaltString;
-use argon2::{Argon2, Params, PasswordHasher};
 use secrecy::{ExposeSecret, Secret};
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 use uuid::Uuid;
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = ChangePasswordForm)]
 pub struct FormData {
+    #[schema(value_type = String, format = Password)]
     current_password: Secret<String>,
+    #[schema(value_type = String, format = Password)]
     new_password: Secret<String>,
+    #[schema(value_type = String, format = Password)]
     verify_new_password: Secret<String>,
 }
 
 #[tracing::instrument(
     name = "Handle password change",
-    skip(form, pool), fields(
+    skip(form, pool, hashing, session, registry), fields(
     user_id=%*user_id
 ))]
+#[utoipa::path(
+    post,
+    path = "/admin/password",
+    tag = "admin",
+    request_body(content = FormData, content_type = "application/x-www-form-urlencoded"),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to `/admin/password` with the outcome.")
+    )
+)]
 pub async fn change_password(
     pool: web::Data<PgPool>,
     web::Form(form): web::Form<FormData>,
     user_id: web::ReqData<UserId>,
+    hashing: web::Data<PasswordHashing>,
+    session: TypedSession,
+    registry: web::Data<SessionRegistry>,
 ) -> Result<HttpResponse, actix_web::Error> {
     // Try to get the user_id from the session (redirect to login on invalid session)
     let user_id = user_id.into_inner();
@@ -51,7 +70,7 @@ pub async fn change_password(
 
     // Attempt to validate the credentials
     let credentials = Credentials::new(username, form.current_password);
-    if let Err(e) = validate_credentials(credentials, &pool).await {
+    if let Err(e) = validate_credentials(credentials, &pool, &hashing).await {
         return match e {
             AuthError::InvalidCredentials(_) => {
                 FlashMessage::error("The current password you entered is invalid").send();
@@ -62,39 +81,37 @@ pub async fn change_password(
     }
 
     // Set the new password
-    match set_new_password(&pool, *user_id, &form.new_password).await {
-        Ok(_) => {
-            FlashMessage::info("Password changed successfully!").send();
-        }
-        Err(e) => {
-            tracing::error!("{e:?}");
-            FlashMessage::error("Password change failed, please try again.").send();
-        }
+    if let Err(e) = set_new_password(pool.get_ref(), *user_id, &form.new_password, &hashing).await {
+        tracing::error!("{e:?}");
+        FlashMessage::error("Password change failed, please try again.").send();
+        return Ok(see_other("/admin/password"));
     }
+
+    // Whoever knew the old password must not stay logged in elsewhere.
+    let current_session_id = session.get_session_id().map_err(e500)?;
+    registry
+        .revoke_all_except(*user_id, current_session_id)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("Password changed successfully! Your other sessions have been signed out.")
+        .send();
     Ok(see_other("/admin/password"))
 }
 
-#[tracing::instrument(name = "Set new password", skip(pool, new_password))]
+#[tracing::instrument(name = "Set new password", skip(executor, new_password, hashing))]
 pub async fn set_new_password(
-    pool: &PgPool,
+    executor: impl PgExecutor<'_>,
     user_id: Uuid,
     new_password: &Secret<String>,
+    hashing: &PasswordHashing,
 ) -> Result<(), anyhow::Error> {
-    let salt = SaltString::generate(&mut rand::thread_rng());
-    let password_hash = Argon2::new(
-        argon2::Algorithm::Argon2id,
-        argon2::Version::V0x13,
-        Params::new(15000, 2, 1, None).expect("Failed to build params."),
-    )
-    .hash_password(new_password.expose_secret().as_bytes(), &salt)
-    .unwrap()
-    .to_string();
+    let password_hash = compute_password_hash(new_password, hashing)?;
     sqlx::query!(
         r#"UPDATE users SET password_hash = $1 WHERE user_id = $2;"#,
         password_hash,
         user_id
     )
-    .execute(pool)
+    .execute(executor)
     .await
     .context("Failed to set password hash")?;
     Ok(())
@@ -0,0 +1,106 @@
+//! src/routes/admin/sessions/get.rs
+use crate::authentication::{SessionRegistry, UserId};
+use crate::routes::e500;
+use crate::session_state::TypedSession;
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{IncomingFlashMessages, Level};
+use std::fmt::Write;
+
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M UTC";
+
+#[tracing::instrument(
+    name = "List active sessions",
+    skip(registry, flash_messages, session),
+    fields(user_id=%*user_id)
+)]
+#[utoipa::path(
+    get,
+    path = "/admin/sessions",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "The active sessions of the user.", content_type = "text/html"),
+        (status = 303, description = "Not logged in: redirects to `/login`.")
+    )
+)]
+pub async fn list_sessions(
+    registry: web::Data<SessionRegistry>,
+    user_id: web::ReqData<UserId>,
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = session.get_or_insert_csrf_token().map_err(e500)?;
+    let current_session_id = session.get_session_id().map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages
+        .iter()
+        .filter(|m| m.level() == Level::Error || m.level() == Level::Info)
+    {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let sessions = registry.list(**user_id).await.map_err(e500)?;
+    let mut rows_html = String::new();
+    for s in &sessions {
+        let action = if Some(s.session_id) == current_session_id {
+            "This session".to_string()
+        } else {
+            format!(
+                r#"<form action="/admin/sessions/{session_id}/revoke" method="post">
+            <input hidden type="text" name="csrf_token" value="{csrf_token}">
+            <button type="submit">Revoke</button>
+          </form>"#,
+                session_id = s.session_id,
+            )
+        };
+        let ip = s.ip.map(|ip| ip.to_string()).unwrap_or_default();
+        let user_agent = htmlescape::encode_minimal(s.user_agent.as_deref().unwrap_or("Unknown"));
+        writeln!(
+            rows_html,
+            r#"      <tr>
+        <td>{created_at}</td>
+        <td>{last_seen_at}</td>
+        <td>{ip}</td>
+        <td>{user_agent}</td>
+        <td>
+          {action}
+        </td>
+      </tr>"#,
+            created_at = s.created_at().format(DATE_FORMAT),
+            last_seen_at = s.last_seen_at().format(DATE_FORMAT),
+        )
+        .unwrap();
+    }
+    let revoke_others_html = if sessions.len() > 1 {
+        format!(
+            r#"<form action="/admin/sessions/revoke-others" method="post">
+      <input hidden type="text" name="csrf_token" value="{csrf_token}">
+      <button type="submit">Sign out all other sessions</button>
+    </form>"#
+        )
+    } else {
+        String::new()
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta http-equiv="content-type" content="text/html; charset=utf-8">
+  <title>Active sessions</title>
+</head>
+<body>
+    <h1>Active sessions</h1>
+    {msg_html}
+    <table>
+      <tr><th>Signed in</th><th>Last seen</th><th>IP</th><th>Browser</th><th>Actions</th></tr>
+{rows_html}
+    </table>
+    {revoke_others_html}
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
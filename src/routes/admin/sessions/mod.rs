@@ -0,0 +1,9 @@
+//! src/routes/admin/sessions/mod.rs
+
+mod get;
+pub use get::{__path_list_sessions, list_sessions};
+
+mod post;
+pub use post::{
+    __path_revoke_other_sessions, __path_revoke_session, revoke_other_sessions, revoke_session,
+};
@@ -0,0 +1,70 @@
+//! src/routes/admin/sessions/post.rs
+use crate::authentication::{SessionRegistry, UserId};
+use crate::routes::admin::helpers::{e500, see_other};
+use crate::session_state::TypedSession;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use uuid::Uuid;
+
+#[tracing::instrument(
+    name = "Revoke a session",
+    skip(registry, session),
+    fields(user_id=%*user_id)
+)]
+#[utoipa::path(
+    post,
+    path = "/admin/sessions/{session_id}/revoke",
+    tag = "admin",
+    params(("session_id" = Uuid, Path, description = "A session of the user, other than the current one.")),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to `/admin/sessions` with the outcome.")
+    )
+)]
+pub async fn revoke_session(
+    registry: web::Data<SessionRegistry>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+    session_id: web::Path<Uuid>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let session_id = session_id.into_inner();
+    if session.get_session_id().map_err(e500)? == Some(session_id) {
+        FlashMessage::error("Use the logout button to end the current session.").send();
+        return Ok(see_other("/admin/sessions"));
+    }
+    let revoked = registry.revoke(**user_id, session_id).await.map_err(e500)?;
+    if revoked {
+        FlashMessage::info("The session has been signed out.").send();
+    } else {
+        FlashMessage::error("There is no such session, it may have expired already.").send();
+    }
+    Ok(see_other("/admin/sessions"))
+}
+
+#[tracing::instrument(
+    name = "Revoke all other sessions",
+    skip(registry, session),
+    fields(user_id=%*user_id)
+)]
+#[utoipa::path(
+    post,
+    path = "/admin/sessions/revoke-others",
+    tag = "admin",
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to `/admin/sessions` with the number of sessions signed out.")
+    )
+)]
+pub async fn revoke_other_sessions(
+    registry: web::Data<SessionRegistry>,
+    user_id: web::ReqData<UserId>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let current_session_id = session.get_session_id().map_err(e500)?;
+    let revoked = registry
+        .revoke_all_except(**user_id, current_session_id)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info(format!("{revoked} other session(s) have been signed out.")).send();
+    Ok(see_other("/admin/sessions"))
+}
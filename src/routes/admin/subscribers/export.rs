@@ -0,0 +1,203 @@
+//! src/routes/admin/subscribers/export.rs
+use super::{non_empty, parse_status};
+use crate::services::{self, Subscriber, SubscriberExportFilter};
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use futures_util::StreamExt;
+use sqlx::PgPool;
+use std::borrow::Cow;
+use tokio::sync::mpsc;
+use tracing::Instrument;
+
+/// Rows are sent to the client in chunks of about this many bytes.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SubscriberExportParameters {
+    /// `csv` (the default) or `ndjson`.
+    format: Option<String>,
+    /// One of `pending_confirmation`, `confirmed` or `unsubscribed`.
+    status: Option<String>,
+    /// Only subscribers who signed up on or after this day, as `YYYY-MM-DD`.
+    from: Option<String>,
+    /// Only subscribers who signed up on or before this day, as `YYYY-MM-DD`.
+    to: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+enum ExportFormat {
+    Csv,
+    Ndjson,
+}
+
+impl ExportFormat {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "ndjson" => Ok(Self::Ndjson),
+            other => Err(format!("{other} is not a valid export format.")),
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Ndjson => "ndjson",
+        }
+    }
+
+    fn header(self) -> &'static [u8] {
+        match self {
+            Self::Csv => b"id,email,name,status,subscribed_at\n",
+            Self::Ndjson => b"",
+        }
+    }
+
+    fn write_row(self, out: &mut Vec<u8>, subscriber: &Subscriber) -> Result<(), anyhow::Error> {
+        match self {
+            Self::Csv => {
+                let mut writer = csv::Writer::from_writer(out);
+                writer.write_record([
+                    subscriber.id.to_string().as_str(),
+                    &neutralize_formula(&subscriber.email),
+                    &neutralize_formula(&subscriber.name),
+                    &subscriber.status,
+                    &subscriber.subscribed_at.to_rfc3339(),
+                ])?;
+                writer.flush()?;
+            }
+            Self::Ndjson => {
+                serde_json::to_writer(&mut *out, subscriber)?;
+                out.push(b'\n');
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Spreadsheets evaluate cells starting with one of these as formulas.
+/// Subscribers pick their own name, so a leading `'` makes such cells text.
+fn neutralize_formula(cell: &str) -> Cow<'_, str> {
+    if cell.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        Cow::Owned(format!("'{cell}"))
+    } else {
+        Cow::Borrowed(cell)
+    }
+}
+
+fn parse_date(s: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map_err(|_| format!("{s} is not a valid date, expected YYYY-MM-DD."))
+}
+
+fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0)
+        .expect("Midnight is a valid time.")
+        .and_utc()
+}
+
+#[tracing::instrument(name = "Export subscribers", skip(parameters, pool))]
+#[utoipa::path(
+    get,
+    path = "/admin/subscribers/export",
+    tag = "admin",
+    params(SubscriberExportParameters),
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "The matching subscribers, oldest first, as CSV or one JSON object per line.", content_type = "text/csv"),
+        (status = 303, description = "Not logged in: redirects to `/login`."),
+        (status = 400, description = "The format, the status or a date is invalid.")
+    )
+)]
+pub async fn export_subscribers(
+    web::Query(parameters): web::Query<SubscriberExportParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let bad_request = |e: String| actix_web::error::ErrorBadRequest(htmlescape::encode_minimal(&e));
+    let format = non_empty(&parameters.format)
+        .map(ExportFormat::parse)
+        .transpose()
+        .map_err(bad_request)?
+        .unwrap_or(ExportFormat::Csv);
+    let status = parse_status(non_empty(&parameters.status))?;
+    let from = non_empty(&parameters.from)
+        .map(parse_date)
+        .transpose()
+        .map_err(bad_request)?;
+    let to = non_empty(&parameters.to)
+        .map(parse_date)
+        .transpose()
+        .map_err(bad_request)?;
+    let filter = SubscriberExportFilter {
+        status: status.map(str::to_owned),
+        subscribed_from: from.map(start_of_day),
+        // `to` includes the whole day.
+        subscribed_before: to
+            .and_then(|to| to.checked_add_days(Days::new(1)))
+            .map(start_of_day),
+    };
+
+    // The rows are read from the database as the client consumes the body;
+    // a full channel pauses the query, a dropped one stops it.
+    let (sender, receiver) = mpsc::channel::<Result<web::Bytes, anyhow::Error>>(4);
+    let pool = pool.get_ref().clone();
+    tokio::spawn(
+        async move {
+            let mut rows = services::stream_subscribers(&pool, &filter);
+            let mut chunk = format.header().to_vec();
+            while let Some(row) = rows.next().await {
+                let written = row.and_then(|subscriber| format.write_row(&mut chunk, &subscriber));
+                if let Err(e) = written {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "The subscriber export stopped before the end.",
+                    );
+                    // Aborts the response, so that the client does not mistake
+                    // a partial export for a complete one.
+                    let _ = sender
+                        .send(Err(e).context("Failed to export the subscribers."))
+                        .await;
+                    return;
+                }
+                if chunk.len() >= CHUNK_SIZE
+                    && sender
+                        .send(Ok(std::mem::take(&mut chunk).into()))
+                        .await
+                        .is_err()
+                {
+                    // The client went away.
+                    return;
+                }
+            }
+            if !chunk.is_empty() {
+                let _ = sender.send(Ok(chunk.into())).await;
+            }
+        }
+        .in_current_span(),
+    );
+    let body = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "subscribers.{}",
+                format.extension()
+            ))],
+        })
+        .streaming(body))
+}
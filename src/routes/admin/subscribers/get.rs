@@ -0,0 +1,235 @@
+//! src/routes/admin/subscribers/get.rs
+use super::{non_empty, parse_status};
+use crate::authentication::{Permission, Role};
+use crate::routes::e500;
+use crate::services::{self, SUBSCRIBER_STATUSES, SubscriberCursor, SubscriberQuery};
+use crate::session_state::TypedSession;
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{IncomingFlashMessages, Level};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+const PAGE_SIZE: i64 = 50;
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SubscriberListParameters {
+    /// Part of the email or the name.
+    search: Option<String>,
+    /// One of `pending_confirmation`, `confirmed` or `unsubscribed`.
+    status: Option<String>,
+    /// From the "Next page" link.
+    after: Option<String>,
+}
+
+#[tracing::instrument(
+    name = "List subscribers",
+    skip(parameters, pool, flash_messages, session)
+)]
+#[utoipa::path(
+    get,
+    path = "/admin/subscribers",
+    tag = "admin",
+    params(SubscriberListParameters),
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "A page of subscribers, newest first.", content_type = "text/html"),
+        (status = 303, description = "Not logged in: redirects to `/login`."),
+        (status = 400, description = "The status or the cursor is invalid.")
+    )
+)]
+pub async fn list_subscribers_page(
+    web::Query(parameters): web::Query<SubscriberListParameters>,
+    pool: web::Data<PgPool>,
+    role: web::ReqData<Role>,
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let search = non_empty(&parameters.search);
+    let status = parse_status(non_empty(&parameters.status))?;
+    let after = non_empty(&parameters.after)
+        .map(SubscriberCursor::parse)
+        .transpose()
+        .map_err(|e| actix_web::error::ErrorBadRequest(htmlescape::encode_minimal(&e)))?;
+    let query = SubscriberQuery {
+        search,
+        status,
+        after,
+        limit: PAGE_SIZE,
+    };
+    let page = services::search_subscribers(&pool, &query)
+        .await
+        .map_err(e500)?;
+
+    let csrf_token = session.get_or_insert_csrf_token().map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages
+        .iter()
+        .filter(|m| m.level() == Level::Error || m.level() == Level::Info)
+    {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let can_manage = role.can(Permission::ManageSubscribers);
+    let mut rows_html = String::new();
+    for subscriber in &page.subscribers {
+        let actions = if can_manage {
+            row_actions(subscriber.id, &subscriber.status, &csrf_token)
+        } else {
+            String::new()
+        };
+        writeln!(
+            rows_html,
+            r#"      <tr>
+        <td>{email}</td>
+        <td>{name}</td>
+        <td>{status}</td>
+        <td>{subscribed_at}</td>
+        <td>
+          {actions}
+        </td>
+      </tr>"#,
+            email = htmlescape::encode_minimal(&subscriber.email),
+            name = htmlescape::encode_minimal(&subscriber.name),
+            status = subscriber.status,
+            subscribed_at = subscriber.subscribed_at.format("%Y-%m-%d %H:%M UTC"),
+        )
+        .unwrap();
+    }
+    if page.subscribers.is_empty() {
+        rows_html.push_str(r#"      <tr><td colspan="5">No subscribers found.</td></tr>"#);
+    }
+
+    let mut status_options = String::from(r#"<option value="">Any status</option>"#);
+    for value in SUBSCRIBER_STATUSES {
+        let selected = if status == Some(value) {
+            " selected"
+        } else {
+            ""
+        };
+        write!(
+            status_options,
+            r#"<option value="{value}"{selected}>{value}</option>"#
+        )
+        .unwrap();
+    }
+
+    let mut export_status_options = String::from(r#"<option value="">Any status</option>"#);
+    for value in SUBSCRIBER_STATUSES {
+        write!(
+            export_status_options,
+            r#"<option value="{value}">{value}</option>"#
+        )
+        .unwrap();
+    }
+
+    let mut pagination = String::new();
+    if after.is_some() {
+        write!(
+            pagination,
+            r#"<a href="/admin/subscribers?{}">First page</a> "#,
+            page_query(search, status, None)
+        )
+        .unwrap();
+    }
+    if let Some(next) = page.next {
+        write!(
+            pagination,
+            r#"<a href="/admin/subscribers?{}">Next page -&gt;</a>"#,
+            page_query(search, status, Some(next))
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta http-equiv="content-type" content="text/html; charset=utf-8">
+  <title>Subscribers</title>
+</head>
+<body>
+    <h1>Subscribers</h1>
+    {msg_html}
+    {import_link}
+    <form action="/admin/subscribers" method="get">
+      <label>Search
+        <input type="search" name="search" value="{search}" placeholder="Email or name">
+      </label>
+      <select name="status">{status_options}</select>
+      <button type="submit">Filter</button>
+    </form>
+    <form action="/admin/subscribers/export" method="get">
+      <select name="format">
+        <option value="csv">CSV</option>
+        <option value="ndjson">Newline-delimited JSON</option>
+      </select>
+      <select name="status">{export_status_options}</select>
+      <label>Signed up from <input type="date" name="from"></label>
+      <label>to <input type="date" name="to"></label>
+      <button type="submit">Export</button>
+    </form>
+    <table>
+      <tr><th>Email</th><th>Name</th><th>Status</th><th>Subscribed at</th><th>Actions</th></tr>
+{rows_html}
+    </table>
+    <p>{pagination}</p>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+            search = htmlescape::encode_minimal(search.unwrap_or_default()),
+            import_link = if can_manage {
+                r#"<p><a href="/admin/subscribers/import">Import from a CSV file</a></p>"#
+            } else {
+                ""
+            },
+        )))
+}
+
+/// The actions that make sense for a subscriber in `status`.
+fn row_actions(id: uuid::Uuid, status: &str, csrf_token: &str) -> String {
+    let mut actions: Vec<(&str, &str)> = Vec::new();
+    if status == "pending_confirmation" {
+        actions.push(("resend-confirmation", "Resend confirmation"));
+        actions.push(("confirm", "Confirm"));
+    }
+    if status != "unsubscribed" {
+        actions.push(("unsubscribe", "Unsubscribe"));
+    }
+    actions.push(("delete", "Delete"));
+    let mut html = String::new();
+    for (action, label) in actions {
+        write!(
+            html,
+            r#"<form action="/admin/subscribers/{id}/{action}" method="post">
+            <input hidden type="text" name="csrf_token" value="{csrf_token}">
+            <button type="submit">{label}</button>
+          </form>"#
+        )
+        .unwrap();
+    }
+    html
+}
+
+/// The query string of another page of the same search.
+fn page_query(
+    search: Option<&str>,
+    status: Option<&str>,
+    after: Option<SubscriberCursor>,
+) -> String {
+    let after = after.map(|c| c.to_string());
+    let parameters = [
+        ("search", search),
+        ("status", status),
+        ("after", after.as_deref()),
+    ];
+    let parameters: Vec<(&str, &str)> = parameters
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value?)))
+        .collect();
+    htmlescape::encode_minimal(
+        &serde_urlencoded::to_string(parameters).expect("Query strings are serializable."),
+    )
+}
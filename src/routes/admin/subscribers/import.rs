@@ -0,0 +1,260 @@
+//! src/routes/admin/subscribers/import.rs
+use crate::authentication::UserId;
+use crate::configuration::SubscriptionSettings;
+use crate::email_client::EmailTransport;
+use crate::routes::admin::helpers::{e500, see_other};
+use crate::services::{self, ImportError, ImportMode, ImportOutcome, PendingConfirmation};
+use crate::session_state::TypedSession;
+use crate::startup::ApplicationBaseUrl;
+use actix_multipart::form::MultipartForm;
+use actix_multipart::form::bytes::Bytes;
+use actix_multipart::form::text::Text;
+use actix_web::http::header::{ContentDisposition, ContentType, DispositionParam, DispositionType};
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages, Level};
+use sqlx::PgPool;
+use std::fmt::Write;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(MultipartForm, utoipa::ToSchema)]
+pub struct ImportFormData {
+    /// A CSV file with a `name` and an `email` column.
+    #[schema(value_type = String, format = Binary)]
+    file: Bytes,
+    /// `confirmed` or `double_opt_in`.
+    #[schema(value_type = String)]
+    mode: Text<String>,
+}
+
+#[tracing::instrument(
+    name = "Show the subscriber import form",
+    skip(flash_messages, session)
+)]
+#[utoipa::path(
+    get,
+    path = "/admin/subscribers/import",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "The CSV upload form.", content_type = "text/html"),
+        (status = 303, description = "Not logged in: redirects to `/login`."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
+pub async fn import_subscribers_form(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = session.get_or_insert_csrf_token().map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages
+        .iter()
+        .filter(|m| m.level() == Level::Error || m.level() == Level::Info)
+    {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta http-equiv="content-type" content="text/html; charset=utf-8">
+  <title>Import subscribers</title>
+</head>
+<body>
+    <h1>Import subscribers</h1>
+    {msg_html}
+    <p>Upload a CSV file whose first line names the columns, with a <code>name</code> and an <code>email</code> column.
+    Addresses already on the list are skipped.</p>
+    <form action="/admin/subscribers/import" method="post" enctype="multipart/form-data">
+      <input hidden type="text" name="csrf_token" value="{csrf_token}">
+      <label>CSV file
+        <input type="file" name="file" accept=".csv,text/csv">
+      </label>
+      <fieldset>
+        <label>
+          <input type="radio" name="mode" value="double_opt_in" checked>
+          Send them a confirmation email
+        </label>
+        <label>
+          <input type="radio" name="mode" value="confirmed">
+          Import them as confirmed, they already opted in elsewhere
+        </label>
+      </fieldset>
+      <button type="submit">Import</button>
+    </form>
+    <p><a href="/admin/subscribers">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+/// The summary is shown right away, the full report is downloaded from
+/// `download_import_report`.
+#[tracing::instrument(
+    name = "Import subscribers from a CSV file",
+    skip(form, pool, email_client, base_url, settings),
+    fields(user_id=%*user_id)
+)]
+#[utoipa::path(
+    post,
+    path = "/admin/subscribers/import",
+    tag = "admin",
+    request_body(content = ImportFormData, content_type = "multipart/form-data"),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 200, description = "How the import went.", content_type = "text/html"),
+        (status = 303, description = "The file or the mode is invalid: redirects to `/admin/subscribers/import` with an error."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
+pub async fn import_subscribers(
+    MultipartForm(form): MultipartForm<ImportFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<dyn EmailTransport>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mode = match ImportMode::parse(&form.mode) {
+        Ok(mode) => mode,
+        Err(e) => {
+            FlashMessage::error(htmlescape::encode_minimal(&e)).send();
+            return Ok(see_other("/admin/subscribers/import"));
+        }
+    };
+    let mut summary = match services::import_subscribers(
+        &pool,
+        settings.token_expiration(),
+        **user_id,
+        mode,
+        &form.file.data,
+    )
+    .await
+    {
+        Ok(summary) => summary,
+        Err(ImportError::InvalidFile(e)) => {
+            FlashMessage::error(htmlescape::encode_minimal(&e)).send();
+            return Ok(see_other("/admin/subscribers/import"));
+        }
+        Err(e) => return Err(e500(e)),
+    };
+    let confirmations = std::mem::take(&mut summary.confirmations);
+    let n_confirmations = confirmations.len();
+    if !confirmations.is_empty() {
+        tokio::spawn(send_confirmations_in_background(
+            pool.get_ref().clone(),
+            email_client.into_inner(),
+            base_url.0.clone(),
+            summary.import_id,
+            confirmations,
+        ));
+    }
+
+    let mut rows_html = String::new();
+    for entry in &summary.entries {
+        writeln!(
+            rows_html,
+            r#"      <tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>"#,
+            entry.line,
+            htmlescape::encode_minimal(&entry.name),
+            htmlescape::encode_minimal(&entry.email),
+            entry.outcome.as_str(),
+            htmlescape::encode_minimal(&entry.reason),
+        )
+        .unwrap();
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta http-equiv="content-type" content="text/html; charset=utf-8">
+  <title>Import report</title>
+</head>
+<body>
+    <h1>Import report</h1>
+    <ul>
+      <li>Imported: {imported}</li>
+      <li>Rejected: {rejected}</li>
+      <li>Skipped duplicates: {skipped}</li>
+      <li>Confirmation emails on their way: {n_confirmations}</li>
+    </ul>
+    <p><a href="/admin/subscribers/import/{import_id}/report">Download the report</a>,
+    the confirmation emails that cannot be sent are added to it.</p>
+    <table>
+      <tr><th>Line</th><th>Name</th><th>Email</th><th>Outcome</th><th>Reason</th></tr>
+{rows_html}
+    </table>
+    <p><a href="/admin/subscribers">&lt;- Back to the subscribers</a></p>
+</body>
+</html>"#,
+            imported = summary.imported,
+            rejected = summary.count(ImportOutcome::Rejected),
+            skipped = summary.count(ImportOutcome::Skipped),
+            import_id = summary.import_id,
+        )))
+}
+
+/// A large import would keep the response waiting for every email.
+#[tracing::instrument(name = "Send import confirmations in the background", skip_all, fields(%import_id))]
+async fn send_confirmations_in_background(
+    pool: PgPool,
+    email_client: Arc<dyn EmailTransport>,
+    base_url: String,
+    import_id: Uuid,
+    confirmations: Vec<PendingConfirmation>,
+) {
+    if let Err(e) = services::send_import_confirmations(
+        &pool,
+        email_client.as_ref(),
+        &base_url,
+        import_id,
+        confirmations,
+    )
+    .await
+    {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to send the confirmation emails of an import."
+        );
+    }
+}
+
+#[tracing::instrument(name = "Download an import report", skip(pool), fields(user_id=%*user_id))]
+#[utoipa::path(
+    get,
+    path = "/admin/subscribers/import/{import_id}/report",
+    tag = "admin",
+    params(("import_id" = Uuid, Path, description = "An import run by the user.")),
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "The rows that were not imported, with the reason why.", content_type = "text/csv"),
+        (status = 404, description = "There is no such import."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
+pub async fn download_import_report(
+    import_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let import_id = import_id.into_inner();
+    let report = services::get_import_report(&pool, **user_id, import_id)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("There is no such import."))?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "import-report-{import_id}.csv"
+            ))],
+        })
+        .body(report))
+}
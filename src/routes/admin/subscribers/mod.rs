@@ -0,0 +1,39 @@
+//! src/routes/admin/subscribers/mod.rs
+use crate::services::SUBSCRIBER_STATUSES;
+
+mod export;
+pub use export::{__path_export_subscribers, export_subscribers};
+
+mod get;
+pub use get::{__path_list_subscribers_page, list_subscribers_page};
+
+mod import;
+pub use import::{
+    __path_download_import_report, __path_import_subscribers, __path_import_subscribers_form,
+    download_import_report, import_subscribers, import_subscribers_form,
+};
+
+mod post;
+pub use post::{
+    __path_confirm_subscriber, __path_remove_subscriber, __path_resend_subscriber_confirmation,
+    __path_unsubscribe_subscriber, confirm_subscriber, remove_subscriber,
+    resend_subscriber_confirmation, unsubscribe_subscriber,
+};
+
+/// Forms submit empty fields as empty strings.
+fn non_empty(field: &Option<String>) -> Option<&str> {
+    field.as_deref().map(str::trim).filter(|s| !s.is_empty())
+}
+
+/// A status filter must be one of `SUBSCRIBER_STATUSES`.
+fn parse_status(status: Option<&str>) -> Result<Option<&str>, actix_web::Error> {
+    match status {
+        Some(status) if !SUBSCRIBER_STATUSES.contains(&status) => {
+            Err(actix_web::error::ErrorBadRequest(format!(
+                "{} is not a valid status.",
+                htmlescape::encode_minimal(status)
+            )))
+        }
+        status => Ok(status),
+    }
+}
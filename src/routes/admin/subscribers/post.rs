@@ -0,0 +1,146 @@
+//! src/routes/admin/subscribers/post.rs
+use crate::configuration::SubscriptionSettings;
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailTransport;
+use crate::routes::admin::helpers::{e500, see_other};
+use crate::services::{self, ConfirmationLinkSettings};
+use crate::startup::ApplicationBaseUrl;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+fn no_such_subscriber() -> HttpResponse {
+    FlashMessage::error("There is no such subscriber.").send();
+    see_other("/admin/subscribers")
+}
+
+#[tracing::instrument(
+    name = "Resend a confirmation email from the admin pages",
+    skip(pool, email_client, base_url, settings)
+)]
+#[utoipa::path(
+    post,
+    path = "/admin/subscribers/{subscriber_id}/resend-confirmation",
+    tag = "admin",
+    params(("subscriber_id" = Uuid, Path)),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to `/admin/subscribers` with the outcome."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
+pub async fn resend_subscriber_confirmation(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<dyn EmailTransport>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(subscriber) = services::get_subscriber(&pool, subscriber_id.into_inner())
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(no_such_subscriber());
+    };
+    if subscriber.status != "pending_confirmation" {
+        FlashMessage::error("The subscriber is not waiting for a confirmation.").send();
+        return Ok(see_other("/admin/subscribers"));
+    }
+    // Validated when they subscribed.
+    let email = SubscriberEmail::parse(subscriber.email).map_err(e500)?;
+    let links = ConfirmationLinkSettings {
+        base_url: &base_url.0,
+        token_expiration: settings.token_expiration(),
+    };
+    services::resend_confirmation_email(&pool, email_client.as_ref(), &links, &email)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info(format!(
+        "A new confirmation email has been sent to {}.",
+        htmlescape::encode_minimal(email.as_ref())
+    ))
+    .send();
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[tracing::instrument(name = "Confirm a subscriber from the admin pages", skip(pool))]
+#[utoipa::path(
+    post,
+    path = "/admin/subscribers/{subscriber_id}/confirm",
+    tag = "admin",
+    params(("subscriber_id" = Uuid, Path)),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to `/admin/subscribers` with the outcome."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
+pub async fn confirm_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let confirmed = services::confirm_subscriber_manually(&pool, subscriber_id.into_inner())
+        .await
+        .map_err(e500)?;
+    if confirmed {
+        FlashMessage::info("The subscriber has been confirmed.").send();
+    } else {
+        FlashMessage::error("There is no pending subscriber with this id.").send();
+    }
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[tracing::instrument(name = "Unsubscribe a subscriber from the admin pages", skip(pool))]
+#[utoipa::path(
+    post,
+    path = "/admin/subscribers/{subscriber_id}/unsubscribe",
+    tag = "admin",
+    params(("subscriber_id" = Uuid, Path)),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to `/admin/subscribers` with the outcome."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
+pub async fn unsubscribe_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let unsubscribed = services::unsubscribe_subscriber(&pool, subscriber_id.into_inner())
+        .await
+        .map_err(e500)?;
+    if unsubscribed {
+        FlashMessage::info("The subscriber has been unsubscribed.").send();
+    } else {
+        FlashMessage::error("There is no subscribed subscriber with this id.").send();
+    }
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[tracing::instrument(name = "Delete a subscriber from the admin pages", skip(pool))]
+#[utoipa::path(
+    post,
+    path = "/admin/subscribers/{subscriber_id}/delete",
+    tag = "admin",
+    params(("subscriber_id" = Uuid, Path)),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to `/admin/subscribers` with the outcome."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
+pub async fn remove_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let deleted = services::delete_subscriber(&pool, subscriber_id.into_inner())
+        .await
+        .map_err(e500)?;
+    if deleted {
+        FlashMessage::info("The subscriber has been deleted.").send();
+        Ok(see_other("/admin/subscribers"))
+    } else {
+        Ok(no_such_subscriber())
+    }
+}
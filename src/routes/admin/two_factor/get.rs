@@ -0,0 +1,96 @@
+//! src/routes/admin/two_factor/get.rs
+use crate::authentication::{
+    UserId, generate_totp_secret, has_two_factor, provisioning_uri, qr_code_svg,
+};
+use crate::routes::admin::helpers::{e500, get_username};
+use crate::session_state::TypedSession;
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+#[tracing::instrument(
+    name = "Get two factor enrollment form",
+    skip(flash_messages, pool, session),
+    fields(user_id=%*user_id)
+)]
+#[utoipa::path(
+    get,
+    path = "/admin/two-factor",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "A new secret to enroll, as a QR code.", content_type = "text/html"),
+        (status = 303, description = "Not logged in: redirects to `/login`.")
+    )
+)]
+pub async fn two_factor_enrollment_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    // Reuse the pending secret, reloading the page must not invalidate what
+    // the user has already scanned.
+    let secret = match session.get_pending_totp_secret().map_err(e500)? {
+        Some(secret) => secret,
+        None => {
+            let secret = generate_totp_secret();
+            session.insert_pending_totp_secret(&secret).map_err(e500)?;
+            secret
+        }
+    };
+    let csrf_token = session.get_or_insert_csrf_token().map_err(e500)?;
+    let username = get_username(&pool, &user_id).await.map_err(e500)?;
+    let uri = provisioning_uri(&secret, &username).map_err(e500)?;
+    let qr_code = qr_code_svg(&uri).map_err(e500)?;
+    let (status, current_code_field) = if has_two_factor(&pool, *user_id).await.map_err(e500)? {
+        (
+            "Two-factor authentication is enabled. Setting it up again replaces \
+            your authenticator and your recovery codes.",
+            r#"<label for="current_code">Code from your current app, or a recovery code
+        <input type="text" name="current_code" autocomplete="one-time-code" placeholder="Enter the current code">
+      </label>"#,
+        )
+    } else {
+        ("Two-factor authentication is not enabled.", "")
+    };
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="UTF-8">
+  <meta http-equiv="content-type" content="text/html; charset=utf-8">
+  <title>Two-factor authentication</title>
+</head>
+<body>
+    <h1>Two-factor authentication</h1>
+    {msg_html}
+    <p>{status}</p>
+    <p>Scan this QR code with your authenticator app:</p>
+    {qr_code}
+    <p>Or enter this key manually: <code id="totp-secret">{secret}</code></p>
+    <form action="/admin/two-factor" method="post">
+      <input hidden type="text" name="csrf_token" value="{csrf_token}">
+      {current_code_field}
+      <label for="code">Code shown by the app
+        <input type="text" name="code" autocomplete="one-time-code" placeholder="Enter the code">
+      </label>
+      <button type="submit">Enable two-factor authentication</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+            secret = secret.expose_secret()
+        )))
+}
@@ -0,0 +1,6 @@
+//! src/routes/admin/two_factor/mod.rs
+mod get;
+pub use get::{__path_two_factor_enrollment_form, two_factor_enrollment_form};
+
+mod post;
+pub use post::{__path_enroll_two_factor, enroll_two_factor};
@@ -0,0 +1,108 @@
+//! src/routes/admin/two_factor/post.rs
+use crate::authentication::{
+    UserId, enable_two_factor, generate_recovery_codes, has_two_factor, verify_enrollment_code,
+    verify_second_factor,
+};
+use crate::routes::admin::helpers::{e500, see_other};
+use crate::session_state::TypedSession;
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::FlashMessage;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct EnrollmentFormData {
+    #[schema(value_type = String, format = Password)]
+    code: Secret<String>,
+    /// Required to replace an enabled second factor: a code from the current
+    /// authenticator app or a recovery code.
+    #[schema(value_type = Option<String>, format = Password)]
+    current_code: Option<Secret<String>>,
+}
+
+/// The recovery codes are rendered straight away rather than flashed: they
+/// are shown exactly once and must not end up in a cookie.
+///
+/// Replacing an enabled second factor requires it, otherwise a hijacked
+/// session would be enough to take it over.
+#[tracing::instrument(
+    name = "Enroll in two factor authentication",
+    skip(form, pool, session),
+    fields(user_id=%*user_id)
+)]
+#[utoipa::path(
+    post,
+    path = "/admin/two-factor",
+    tag = "admin",
+    request_body(content = EnrollmentFormData, content_type = "application/x-www-form-urlencoded"),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects with the outcome.")
+    )
+)]
+pub async fn enroll_two_factor(
+    web::Form(form): web::Form<EnrollmentFormData>,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let Some(secret) = session.get_pending_totp_secret().map_err(e500)? else {
+        FlashMessage::error("The enrollment has expired, please scan the new QR code.").send();
+        return Ok(see_other("/admin/two-factor"));
+    };
+    let Some(confirmed_step) = verify_enrollment_code(&secret, &form.code).map_err(e500)? else {
+        FlashMessage::error("The code is invalid, please try again.").send();
+        return Ok(see_other("/admin/two-factor"));
+    };
+    // Checked last: a valid current code is used up.
+    if has_two_factor(&pool, *user_id).await.map_err(e500)? {
+        let current_factor_verified = match &form.current_code {
+            Some(code) => verify_second_factor(&pool, *user_id, code)
+                .await
+                .map_err(e500)?,
+            None => false,
+        };
+        if !current_factor_verified {
+            FlashMessage::error(
+                "The current code is invalid, enter one from your current authenticator app \
+                or a recovery code.",
+            )
+            .send();
+            return Ok(see_other("/admin/two-factor"));
+        }
+    }
+
+    let recovery_codes = generate_recovery_codes();
+    enable_two_factor(&pool, *user_id, &secret, confirmed_step, &recovery_codes)
+        .await
+        .map_err(e500)?;
+    session.remove_pending_totp_secret();
+
+    let mut codes_html = String::new();
+    for code in &recovery_codes {
+        writeln!(codes_html, "<li><code>{}</code></li>", code.expose_secret()).unwrap();
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="UTF-8">
+  <meta http-equiv="content-type" content="text/html; charset=utf-8">
+  <title>Recovery codes</title>
+</head>
+<body>
+    <h1>Two-factor authentication is enabled</h1>
+    <p>Store these recovery codes somewhere safe. Each of them lets you log in
+    once without your authenticator app. They will not be shown again.</p>
+    <ul id="recovery-codes">
+    {codes_html}
+    </ul>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#
+        )))
+}
@@ -0,0 +1,157 @@
+//! src/routes/admin/users/get.rs
+use crate::authentication::Role;
+use crate::routes::e500;
+use crate::session_state::TypedSession;
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{IncomingFlashMessages, Level};
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+struct UserRow {
+    user_id: Uuid,
+    username: String,
+    role: String,
+    is_active: bool,
+}
+
+#[tracing::instrument(name = "List admin users", skip(pool, flash_messages, session))]
+#[utoipa::path(
+    get,
+    path = "/admin/users",
+    tag = "admin",
+    security(("session_cookie" = [])),
+    responses(
+        (status = 200, description = "The admin users.", content_type = "text/html"),
+        (status = 303, description = "Not logged in: redirects to `/login`."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
+pub async fn list_users(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = session.get_or_insert_csrf_token().map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages
+        .iter()
+        .filter(|m| m.level() == Level::Error || m.level() == Level::Info)
+    {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    let users = get_users(&pool).await.map_err(e500)?;
+    let mut rows_html = String::new();
+    for user in users {
+        let username = htmlescape::encode_minimal(&user.username);
+        let status = if user.is_active {
+            "active"
+        } else {
+            "deactivated"
+        };
+        let mut role_options = String::new();
+        for role in Role::ALL {
+            let selected = if role.as_str() == user.role {
+                " selected"
+            } else {
+                ""
+            };
+            write!(
+                role_options,
+                r#"<option value="{role}"{selected}>{role}</option>"#
+            )
+            .unwrap();
+        }
+        let actions = if user.is_active {
+            format!(
+                r#"<form action="/admin/users/{id}/role" method="post">
+            <input hidden type="text" name="csrf_token" value="{csrf_token}">
+            <select name="role">{role_options}</select>
+            <button type="submit">Change role</button>
+          </form>
+          <form action="/admin/users/{id}/deactivate" method="post">
+            <input hidden type="text" name="csrf_token" value="{csrf_token}">
+            <button type="submit">Deactivate</button>
+          </form>"#,
+                id = user.user_id
+            )
+        } else {
+            String::new()
+        };
+        writeln!(
+            rows_html,
+            r#"      <tr>
+        <td>{username}</td>
+        <td>{role}</td>
+        <td>{status}</td>
+        <td>
+          {actions}
+        </td>
+      </tr>"#,
+            role = user.role,
+        )
+        .unwrap();
+    }
+
+    let mut invite_role_options = String::new();
+    for role in Role::ALL {
+        let selected = if role == Role::Viewer {
+            " selected"
+        } else {
+            ""
+        };
+        write!(
+            invite_role_options,
+            r#"<option value="{role}"{selected}>{role}</option>"#
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta http-equiv="content-type" content="text/html; charset=utf-8">
+  <title>Users</title>
+</head>
+<body>
+    <h1>Users</h1>
+    {msg_html}
+    <table>
+      <tr><th>Username</th><th>Role</th><th>Status</th><th>Actions</th></tr>
+{rows_html}
+    </table>
+    <h2>Invite a user</h2>
+    <form action="/admin/users" method="post">
+      <input hidden type="text" name="csrf_token" value="{csrf_token}">
+      <label>Username
+        <input type="text" name="username" placeholder="Enter a username">
+      </label>
+      <label>Email
+        <input type="email" name="email" placeholder="Used for password resets">
+      </label>
+      <label>Role
+        <select name="role">{invite_role_options}</select>
+      </label>
+      <button type="submit">Invite</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        )))
+}
+
+#[tracing::instrument(name = "Get all users", skip(pool))]
+async fn get_users(pool: &PgPool) -> Result<Vec<UserRow>, anyhow::Error> {
+    sqlx::query_as!(
+        UserRow,
+        r#"SELECT user_id, username, role, is_active FROM users ORDER BY username"#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to perform a query to retrieve users.")
+}
@@ -0,0 +1,10 @@
+//! src/routes/admin/users/mod.rs
+
+mod get;
+pub use get::{__path_list_users, list_users};
+
+mod post;
+pub use post::{
+    __path_change_user_role, __path_deactivate_user, __path_invite_user, change_user_role,
+    deactivate_user, invite_user,
+};
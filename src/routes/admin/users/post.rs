@@ -0,0 +1,244 @@
+//! src/routes/admin/users/post.rs
+use crate::authentication::{PasswordHashing, Role, UserId, compute_password_hash};
+use crate::domain::SubscriberEmail;
+use crate::routes::admin::helpers::{e500, see_other};
+use crate::telemetry::spawn_blocking_with_tracing;
+use actix_web::http::header::{CacheControl, CacheDirective, ContentType};
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use rand::distributions::Alphanumeric;
+use rand::{Rng, thread_rng};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct InviteFormData {
+    username: String,
+    #[serde(default)]
+    email: String,
+    role: String,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct RoleFormData {
+    role: String,
+}
+
+/// Long and random enough to pass `Password::parse`, the invited user is
+/// expected to change it from `/admin/password` after their first login.
+fn generate_temporary_password() -> Secret<String> {
+    let mut rng = thread_rng();
+    Secret::new(
+        std::iter::repeat_with(|| rng.sample(Alphanumeric))
+            .map(char::from)
+            .take(24)
+            .collect(),
+    )
+}
+
+#[tracing::instrument(name = "Invite a user", skip(form, pool, hashing), fields(user_id=%*user_id))]
+#[utoipa::path(
+    post,
+    path = "/admin/users",
+    tag = "admin",
+    request_body(content = InviteFormData, content_type = "application/x-www-form-urlencoded"),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 200, description = "The temporary password of the invited user, shown only once.", content_type = "text/html"),
+        (status = 303, description = "Redirects to `/admin/users` with the error."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
+pub async fn invite_user(
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    web::Form(form): web::Form<InviteFormData>,
+    hashing: web::Data<PasswordHashing>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let username = form.username.trim();
+    if username.is_empty() {
+        FlashMessage::error("Username is required.").send();
+        return Ok(see_other("/admin/users"));
+    }
+    let role = match Role::parse(&form.role) {
+        Ok(role) => role,
+        Err(e) => {
+            FlashMessage::error(htmlescape::encode_minimal(&e)).send();
+            return Ok(see_other("/admin/users"));
+        }
+    };
+
+    let email = match form.email.trim() {
+        "" => None,
+        email => match SubscriberEmail::parse(email.to_string()) {
+            Ok(email) => Some(email),
+            Err(e) => {
+                FlashMessage::error(htmlescape::encode_minimal(&e)).send();
+                return Ok(see_other("/admin/users"));
+            }
+        },
+    };
+
+    let temporary_password = generate_temporary_password();
+    let password_hash = {
+        let password = temporary_password.clone();
+        spawn_blocking_with_tracing(move || compute_password_hash(&password, &hashing))
+            .await
+            .context("Failed to spawn blocking task.")
+            .map_err(e500)?
+            .map_err(e500)?
+    };
+    let inserted = insert_user(&pool, username, email.as_ref(), &password_hash, role)
+        .await
+        .map_err(e500)?;
+    let username = htmlescape::encode_minimal(username);
+    if !inserted {
+        FlashMessage::error(format!(
+            "The username {username} or its email address is already taken."
+        ))
+        .send();
+        return Ok(see_other("/admin/users"));
+    }
+    // In the body rather than in a flash message: those travel in a cookie.
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>User invited</title>
+</head>
+<body>
+    <p>{username} was invited as {role}. Their temporary password is
+    <code>{password}</code> - it will not be shown again.</p>
+    <p><a href="/admin/users">&lt;- Back to the users</a></p>
+</body>
+</html>"#,
+            password = temporary_password.expose_secret(),
+        )))
+}
+
+#[tracing::instrument(
+    name = "Change the role of a user",
+    skip(form, pool),
+    fields(user_id=%*user_id)
+)]
+#[utoipa::path(
+    post,
+    path = "/admin/users/{user_id}/role",
+    tag = "admin",
+    request_body(content = RoleFormData, content_type = "application/x-www-form-urlencoded"),
+    params(("user_id" = Uuid, Path)),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to `/admin/users` with the outcome."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
+pub async fn change_user_role(
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    target_user_id: web::Path<Uuid>,
+    web::Form(form): web::Form<RoleFormData>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let target_user_id = target_user_id.into_inner();
+    // Prevents the last owner from locking everyone out of user management.
+    if target_user_id == **user_id {
+        FlashMessage::error("You cannot change your own role.").send();
+        return Ok(see_other("/admin/users"));
+    }
+    let role = match Role::parse(&form.role) {
+        Ok(role) => role,
+        Err(e) => {
+            FlashMessage::error(htmlescape::encode_minimal(&e)).send();
+            return Ok(see_other("/admin/users"));
+        }
+    };
+    let updated = sqlx::query!(
+        r#"UPDATE users SET role = $1 WHERE user_id = $2 AND is_active"#,
+        role.as_str(),
+        target_user_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to update the user role.")
+    .map_err(e500)?
+    .rows_affected();
+    if updated == 0 {
+        FlashMessage::error("There is no active user with this id.").send();
+    } else {
+        FlashMessage::info("The role has been updated.").send();
+    }
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument(name = "Deactivate a user", skip(pool), fields(user_id=%*user_id))]
+#[utoipa::path(
+    post,
+    path = "/admin/users/{user_id}/deactivate",
+    tag = "admin",
+    params(("user_id" = Uuid, Path)),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to `/admin/users` with the outcome."),
+        (status = 403, description = "The role of the user does not allow it.")
+    )
+)]
+pub async fn deactivate_user(
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    target_user_id: web::Path<Uuid>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let target_user_id = target_user_id.into_inner();
+    if target_user_id == **user_id {
+        FlashMessage::error("You cannot deactivate your own account.").send();
+        return Ok(see_other("/admin/users"));
+    }
+    let updated = sqlx::query!(
+        r#"UPDATE users SET is_active = FALSE WHERE user_id = $1 AND is_active"#,
+        target_user_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to deactivate the user.")
+    .map_err(e500)?
+    .rows_affected();
+    if updated == 0 {
+        FlashMessage::error("There is no active user with this id.").send();
+    } else {
+        FlashMessage::info("The user has been deactivated.").send();
+    }
+    Ok(see_other("/admin/users"))
+}
+
+/// Returns `false` if the username or the email is already taken.
+#[tracing::instrument(name = "Insert a new user", skip(pool, email, password_hash))]
+async fn insert_user(
+    pool: &PgPool,
+    username: &str,
+    email: Option<&SubscriberEmail>,
+    password_hash: &str,
+    role: Role,
+) -> Result<bool, anyhow::Error> {
+    let n_inserted_rows = sqlx::query!(
+        r#"
+    INSERT INTO users (user_id, username, email, password_hash, role)
+    VALUES ($1, $2, $3, $4, $5)
+    ON CONFLICT DO NOTHING
+    "#,
+        Uuid::new_v4(),
+        username,
+        email.map(|e| e.as_ref()),
+        password_hash,
+        role.as_str()
+    )
+    .execute(pool)
+    .await
+    .context("Failed to insert the new user.")?
+    .rows_affected();
+    Ok(n_inserted_rows > 0)
+}
@@ -0,0 +1,13 @@
+//! src/routes/api/mod.rs
+//! The JSON API under `/api/v1`, authenticated with API tokens.
+mod newsletters;
+mod problem;
+mod subscribers;
+
+pub use newsletters::{__path_list_issues, __path_publish_issue, list_issues, publish_issue};
+pub use problem::{ApiError, Problem};
+pub use subscribers::{
+    __path_create_subscriber, __path_delete_subscriber, __path_get_subscriber,
+    __path_list_subscribers, create_subscriber, delete_subscriber, get_subscriber,
+    list_subscribers,
+};
@@ -0,0 +1,106 @@
+//! src/routes/api/newsletters.rs
+use super::{ApiError, Problem};
+use crate::authentication::UserId;
+use crate::idempotency::{IdempotencyKey, NextAction, save_response, try_processing};
+use crate::services::{self, NewIssue, NewsletterIssue};
+use actix_web::{HttpRequest, HttpResponse, web};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Plays the part of the hidden `idempotency_key` field of the publish form.
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+#[derive(serde::Deserialize, Debug, utoipa::ToSchema)]
+pub struct PublishIssueBody {
+    title: String,
+    /// Rendered into both bodies, as sanitized HTML and as plain text.
+    #[serde(default)]
+    content_markdown: String,
+    /// Overrides the text rendered from `content_markdown` when not empty.
+    #[serde(default)]
+    content_text: String,
+    /// Overrides the HTML rendered from `content_markdown` when not empty.
+    #[serde(default)]
+    content_html: String,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct IssueList {
+    issues: Vec<NewsletterIssue>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct PublishedIssue {
+    id: Uuid,
+}
+
+#[tracing::instrument(name = "API: list newsletter issues", skip(pool))]
+#[utoipa::path(
+    get,
+    path = "/api/v1/newsletters",
+    tag = "api",
+    security(("api_token" = ["newsletters:read"])),
+    responses(
+        (status = 200, body = IssueList),
+        (status = 401, description = "The API token is missing or invalid.", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "The API token lacks the scope, or its owner the permission.", body = Problem, content_type = "application/problem+json")
+    )
+)]
+pub async fn list_issues(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
+    let issues = services::list_issues(&pool).await?;
+    Ok(HttpResponse::Ok().json(IssueList { issues }))
+}
+
+#[tracing::instrument(
+    name = "API: publish a newsletter issue",
+    skip(body, pool, request),
+    fields(user_id=%*user_id)
+)]
+#[utoipa::path(
+    post,
+    path = "/api/v1/newsletters",
+    tag = "api",
+    request_body = PublishIssueBody,
+    params(("Idempotency-Key" = String, Header, description = "Retrying with the same key answers with the first response instead of publishing twice.")),
+    security(("api_token" = ["newsletters:publish"])),
+    responses(
+        (status = 202, body = PublishedIssue, description = "The issue is queued for delivery."),
+        (status = 400, description = "The issue is invalid, or the idempotency key is missing.", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "The API token is missing or invalid.", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "The API token lacks the scope, or its owner the permission.", body = Problem, content_type = "application/problem+json")
+    )
+)]
+pub async fn publish_issue(
+    web::Json(body): web::Json<PublishIssueBody>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    request: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let issue = NewIssue {
+        title: &body.title,
+        content_markdown: &body.content_markdown,
+        content_text: &body.content_text,
+        content_html: &body.content_html,
+    };
+    let errors = issue.validation_errors();
+    if !errors.is_empty() {
+        return Err(ApiError::bad_request(errors.join(" ")));
+    }
+    let idempotency_key: IdempotencyKey = request
+        .headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .ok_or_else(|| ApiError::bad_request("The Idempotency-Key header is required."))?
+        .to_string()
+        .try_into()
+        .map_err(|e: anyhow::Error| ApiError::bad_request(e.to_string()))?;
+
+    let user_id = user_id.into_inner();
+    let mut transaction = match try_processing(&pool, &idempotency_key, *user_id).await? {
+        NextAction::StartProcessing(t) => t,
+        NextAction::ReturnSavedResponse(saved_response) => return Ok(saved_response),
+    };
+    let issue_id = services::publish_issue(&mut transaction, &issue).await?;
+    let response = HttpResponse::Accepted().json(PublishedIssue { id: issue_id });
+    Ok(save_response(transaction, &idempotency_key, *user_id, response).await?)
+}
@@ -0,0 +1,128 @@
+//! src/routes/api/problem.rs
+use crate::routes::LoginError;
+use crate::services::{ConfirmationError, SubscribeError};
+use actix_web::http::StatusCode;
+use actix_web::http::header::{ContentType, WWW_AUTHENTICATE};
+use actix_web::{HttpResponse, ResponseError};
+
+/// The body of an API error, as described by RFC 9457.
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct Problem {
+    /// Always `about:blank`: the status code says it all.
+    #[serde(rename = "type")]
+    problem_type: &'static str,
+    title: &'static str,
+    status: u16,
+    detail: String,
+}
+
+/// An error answered with an `application/problem+json` body.
+///
+/// Built from the error enums the HTML routes use, keeping their status codes.
+pub struct ApiError {
+    status: StatusCode,
+    detail: String,
+    source: anyhow::Error,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, detail: impl Into<String>) -> Self {
+        let detail = detail.into();
+        Self {
+            status,
+            source: anyhow::anyhow!(detail.clone()),
+            detail,
+        }
+    }
+
+    pub fn not_found(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, detail)
+    }
+
+    pub fn bad_request(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, detail)
+    }
+
+    fn from_error<E>(e: E) -> Self
+    where
+        E: ResponseError + std::error::Error + Send + Sync + 'static,
+    {
+        let status = e.status_code();
+        // What went wrong on our side is for the logs, not for API clients.
+        let detail = if status.is_server_error() {
+            "Something went wrong.".to_string()
+        } else {
+            e.to_string()
+        };
+        Self {
+            status,
+            detail,
+            source: e.into(),
+        }
+    }
+}
+
+impl From<SubscribeError> for ApiError {
+    fn from(e: SubscribeError) -> Self {
+        Self::from_error(e)
+    }
+}
+
+impl From<ConfirmationError> for ApiError {
+    fn from(e: ConfirmationError) -> Self {
+        Self::from_error(e)
+    }
+}
+
+impl From<LoginError> for ApiError {
+    fn from(e: LoginError) -> Self {
+        Self::from_error(e)
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            detail: "Something went wrong.".to_string(),
+            source: e,
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.detail)
+    }
+}
+
+impl std::fmt::Debug for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.source)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let problem = Problem {
+            problem_type: "about:blank",
+            title: self.status.canonical_reason().unwrap_or("Error"),
+            status: self.status.as_u16(),
+            detail: self.detail.clone(),
+        };
+        let mut response = HttpResponse::build(self.status);
+        response.content_type(ContentType(
+            "application/problem+json"
+                .parse()
+                .expect("A valid media type."),
+        ));
+        if self.status == StatusCode::UNAUTHORIZED {
+            response.insert_header((WWW_AUTHENTICATE, r#"Bearer realm="api""#));
+        }
+        response.body(serde_json::to_string(&problem).expect("Problems are serializable."))
+    }
+}
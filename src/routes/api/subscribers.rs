@@ -0,0 +1,170 @@
+//! src/routes/api/subscribers.rs
+use super::{ApiError, Problem};
+use crate::configuration::SubscriptionSettings;
+use crate::domain::NewSubscriber;
+use crate::email_client::EmailTransport;
+use crate::routes::subscriptions::{FormData, send_confirmation_in_background};
+use crate::services::{self, SubscribeError, Subscriber, SubscriberCursor, SubscriberQuery};
+use crate::startup::ApplicationBaseUrl;
+use actix_web::http::header::LOCATION;
+use actix_web::{HttpResponse, web};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
+#[derive(serde::Deserialize, Debug, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SubscriberListParameters {
+    /// The `next_cursor` of the previous page.
+    cursor: Option<String>,
+    /// How many subscribers per page, 50 by default and at most 500.
+    limit: Option<i64>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct SubscriberList {
+    subscribers: Vec<Subscriber>,
+    /// Where the next page starts, `null` on the last page.
+    next_cursor: Option<String>,
+}
+
+/// A page of subscribers, newest first, see `services::search_subscribers`.
+#[tracing::instrument(name = "API: list subscribers", skip(pool))]
+#[utoipa::path(
+    get,
+    path = "/api/v1/subscribers",
+    tag = "api",
+    params(SubscriberListParameters),
+    security(("api_token" = ["subscribers:read"])),
+    responses(
+        (status = 200, body = SubscriberList),
+        (status = 400, description = "The cursor or the limit is invalid.", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "The API token is missing or invalid.", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "The API token lacks the scope, or its owner the permission.", body = Problem, content_type = "application/problem+json")
+    )
+)]
+pub async fn list_subscribers(
+    web::Query(parameters): web::Query<SubscriberListParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let limit = parameters.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(ApiError::bad_request(format!(
+            "The limit must be between 1 and {MAX_PAGE_SIZE}."
+        )));
+    }
+    let after = parameters
+        .cursor
+        .as_deref()
+        .map(SubscriberCursor::parse)
+        .transpose()
+        .map_err(ApiError::bad_request)?;
+    let query = SubscriberQuery {
+        search: None,
+        status: None,
+        after,
+        limit,
+    };
+    let page = services::search_subscribers(&pool, &query).await?;
+    Ok(HttpResponse::Ok().json(SubscriberList {
+        subscribers: page.subscribers,
+        next_cursor: page.next.map(|c| c.to_string()),
+    }))
+}
+
+#[tracing::instrument(name = "API: get subscriber", skip(pool))]
+#[utoipa::path(
+    get,
+    path = "/api/v1/subscribers/{subscriber_id}",
+    tag = "api",
+    params(("subscriber_id" = Uuid, Path)),
+    security(("api_token" = ["subscribers:read"])),
+    responses(
+        (status = 200, body = Subscriber),
+        (status = 404, description = "There is no such subscriber.", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "The API token is missing or invalid.", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "The API token lacks the scope, or its owner the permission.", body = Problem, content_type = "application/problem+json")
+    )
+)]
+pub async fn get_subscriber(
+    pool: web::Data<PgPool>,
+    subscriber_id: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    match services::get_subscriber(&pool, subscriber_id.into_inner()).await? {
+        Some(subscriber) => Ok(HttpResponse::Ok().json(subscriber)),
+        None => Err(ApiError::not_found("There is no such subscriber.")),
+    }
+}
+
+/// Goes through the same double opt-in as `POST /subscriptions`: the new
+/// subscriber is pending until they follow the link in the confirmation email.
+#[tracing::instrument(
+    name = "API: create subscriber",
+    skip(body, pool, email_client, base_url, settings)
+)]
+#[utoipa::path(
+    post,
+    path = "/api/v1/subscribers",
+    tag = "api",
+    request_body = FormData,
+    security(("api_token" = ["subscribers:write"])),
+    responses(
+        (status = 201, body = Subscriber, headers(("Location" = String))),
+        (status = 400, description = "The name or the email is invalid.", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "The owner of the address asked for their data to be erased.", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "The API token is missing or invalid.", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "The API token lacks the scope, or its owner the permission.", body = Problem, content_type = "application/problem+json")
+    )
+)]
+pub async fn create_subscriber(
+    web::Json(body): web::Json<FormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<dyn EmailTransport>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
+) -> Result<HttpResponse, ApiError> {
+    let new_subscriber: NewSubscriber = body.try_into().map_err(SubscribeError::ValidationError)?;
+    let subscription =
+        services::subscribe(&pool, settings.token_expiration(), &new_subscriber).await?;
+    if let Some(confirmation_email) = subscription.confirmation_email {
+        tokio::spawn(send_confirmation_in_background(
+            email_client.into_inner(),
+            base_url.0.clone(),
+            confirmation_email,
+        ));
+    }
+    let subscriber_id = subscription.subscriber_id;
+    let subscriber = services::get_subscriber(&pool, subscriber_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("The new subscriber is missing."))?;
+    Ok(HttpResponse::Created()
+        .insert_header((LOCATION, format!("/api/v1/subscribers/{subscriber_id}")))
+        .json(subscriber))
+}
+
+#[tracing::instrument(name = "API: delete subscriber", skip(pool))]
+#[utoipa::path(
+    delete,
+    path = "/api/v1/subscribers/{subscriber_id}",
+    tag = "api",
+    params(("subscriber_id" = Uuid, Path)),
+    security(("api_token" = ["subscribers:write"])),
+    responses(
+        (status = 204, description = "The subscriber is deleted."),
+        (status = 404, description = "There is no such subscriber.", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "The API token is missing or invalid.", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "The API token lacks the scope, or its owner the permission.", body = Problem, content_type = "application/problem+json")
+    )
+)]
+pub async fn delete_subscriber(
+    pool: web::Data<PgPool>,
+    subscriber_id: web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    if services::delete_subscriber(&pool, subscriber_id.into_inner()).await? {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(ApiError::not_found("There is no such subscriber."))
+    }
+}
@@ -2,6 +2,14 @@
 
 use actix_web::{HttpResponse, http::header};
 
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "home",
+    responses(
+        (status = 200, description = "The home page.", content_type = "text/html")
+    )
+)]
 pub async fn home() -> HttpResponse {
     HttpResponse::Ok()
         .insert_header(header::ContentType::html())
@@ -1,9 +1,23 @@
+use crate::routes::{e500, see_other};
+use crate::session_state::TypedSession;
 use actix_web::{HttpResponse, http::header::ContentType};
 use std::fmt::Write;
 
 use actix_web_flash_messages::IncomingFlashMessages;
 
-pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+#[utoipa::path(
+    get,
+    path = "/login",
+    tag = "login",
+    responses(
+        (status = 200, description = "The login form.", content_type = "text/html")
+    )
+)]
+pub async fn login_form(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = session.get_or_insert_csrf_token().map_err(e500)?;
     let mut msg_html = String::new();
     for m in flash_messages.iter() {
         writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
@@ -23,6 +37,7 @@ pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
     <h1>Login</h1>
     {msg_html}
     <form action="/login" method="post">
+      <input hidden type="text" name="csrf_token" value="{csrf_token}">
       <label for="username">Username
         <input type="text" name="username" placeholder="Enter username">
       </label>
@@ -30,13 +45,67 @@ pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
       <label for="Password">Password
       <input type="password" name="password" placeholder="Enter password">
       </label>
+      <br />
+      <label for="remember_me">
+        <input type="checkbox" name="remember_me" value="on"> Remember me
+      </label>
       <button type="submit">Login</button>
      <form>
+    <p><a href="/password-reset">Forgot password?</a></p>
 </body>
 </html>
     "#
     );
-    HttpResponse::Ok()
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_template))
+}
+
+#[utoipa::path(
+    get,
+    path = "/login/two-factor",
+    tag = "login",
+    responses(
+        (status = 200, description = "The form for the second factor.", content_type = "text/html"),
+        (status = 303, description = "No login is waiting for a second factor: redirects to `/login`.")
+    )
+)]
+pub async fn two_factor_form(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    if session.get_pending_second_factor().map_err(e500)?.is_none() {
+        return Ok(see_other("/login"));
+    }
+    let csrf_token = session.get_or_insert_csrf_token().map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
-        .body(html_template)
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="UTF-8">
+  <meta http-equiv="content-type" content="text/html; charset=utf-8">
+  <title>Two-factor authentication</title>
+</head>
+<body>
+    <h1>Two-factor authentication</h1>
+    {msg_html}
+    <form action="/login/two-factor" method="post">
+      <input hidden type="text" name="csrf_token" value="{csrf_token}">
+      <label for="code">Authentication code
+        <input type="text" name="code" autocomplete="one-time-code" placeholder="Enter the code from your app">
+      </label>
+      <button type="submit">Verify</button>
+    </form>
+    <p>Lost your device? Enter one of your recovery codes instead.</p>
+    <p><a href="/login">&lt;- Back to login</a></p>
+</body>
+</html>"#
+        )))
 }
@@ -1,5 +1,5 @@
 mod get;
 mod post;
 
-pub use get::login_form;
-pub use post::login;
+pub use get::{__path_login_form, __path_two_factor_form, login_form, two_factor_form};
+pub use post::{__path_login, __path_verify_two_factor, LoginError, login, verify_two_factor};
@@ -1,46 +1,110 @@
 //! src/routes/login/post.rs
-use crate::authentication::{AuthError, Credentials, validate_credentials};
+use crate::authentication::{
+    AuthError, Credentials, LoginAttempt, LoginThrottle, PasswordHashing, SessionRegistry,
+    ThrottleDecision, has_two_factor, validate_credentials, verify_second_factor,
+};
 use crate::routes::error_chain_fmt;
 use crate::session_state::TypedSession;
-use actix_web::HttpResponse;
+use crate::startup::client_ip;
 use actix_web::error::InternalError;
 use actix_web::http::StatusCode;
-use actix_web::http::header::LOCATION;
+use actix_web::http::header::{LOCATION, USER_AGENT};
 use actix_web::web;
+use actix_web::{HttpRequest, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
+use chrono::Utc;
 use secrecy::Secret;
 use sqlx::PgPool;
+use std::net::IpAddr;
+use uuid::Uuid;
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = LoginForm)]
 pub struct FormData {
     username: String,
+    #[schema(value_type = String, format = Password)]
     password: Secret<String>,
+    /// Only sent when the checkbox is ticked.
+    remember_me: Option<String>,
 }
 
 #[tracing::instrument(name = "Login attempt", 
-    skip(pool, form, session),
-    fields(username=tracing::field::Empty, user_id=tracing::field::Empty))]
+    skip(pool, form, session, request, throttle, hashing, registry),
+    fields(
+        username=tracing::field::Empty,
+        user_id=tracing::field::Empty,
+        client_ip=tracing::field::Empty,
+        failed_attempts=tracing::field::Empty
+    ))]
+#[utoipa::path(
+    post,
+    path = "/login",
+    tag = "login",
+    request_body(content = FormData, content_type = "application/x-www-form-urlencoded"),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to `/admin/dashboard`, to `/login/two-factor` if the user has two-factor authentication, or back to `/login` with an error.")
+    )
+)]
 pub async fn login(
     pool: web::Data<PgPool>,
     web::Form(form): web::Form<FormData>,
     session: TypedSession,
+    request: HttpRequest,
+    throttle: web::Data<LoginThrottle>,
+    hashing: web::Data<PasswordHashing>,
+    registry: web::Data<SessionRegistry>,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
-    let credentials = Credentials::new(form.username, form.password);
-    tracing::Span::current().record("username", tracing::field::display(credentials.username()));
-    match validate_credentials(credentials, &pool).await {
+    let username = form.username;
+    let remember_me = form.remember_me.is_some();
+    let attempt = LoginAttempt {
+        username: &username,
+        ip: client_ip(&request),
+    };
+    record_attempt(&attempt);
+    throttle_attempt(&throttle, &attempt).await?;
+
+    let credentials = Credentials::new(username.clone(), form.password);
+    match validate_credentials(credentials, &pool, &hashing).await {
         Ok(user_id) => {
             tracing::Span::current().record("user_id", tracing::field::display(&user_id));
             session.renew();
-            session
-                .insert_user_id(user_id)
-                .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+            if has_two_factor(&pool, user_id)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+            {
+                // The failure counter is only reset once the second factor is verified.
+                session
+                    .insert_pending_second_factor(user_id, username, remember_me)
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+                return Ok(HttpResponse::SeeOther()
+                    .insert_header((LOCATION, "/login/two-factor"))
+                    .finish());
+            }
+            throttle
+                .record_success(&username)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            start_session(
+                &session,
+                &registry,
+                &request,
+                attempt.ip,
+                user_id,
+                remember_me,
+            )
+            .await
+            .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
             Ok(HttpResponse::SeeOther()
                 .insert_header((LOCATION, "/admin/dashboard"))
                 .finish())
         }
         Err(e) => {
             let e = match e {
-                AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
+                AuthError::InvalidCredentials(_) => {
+                    record_failure(&throttle, &attempt).await?;
+                    LoginError::AuthError(e.into())
+                }
                 AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
             };
             Err(login_redirect(e))
@@ -48,6 +112,166 @@ pub async fn login(
     }
 }
 
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct TwoFactorFormData {
+    #[schema(value_type = String, format = Password)]
+    code: Secret<String>,
+}
+
+/// A pending session gets a handful of tries, then has to start over from the password.
+const MAX_SECOND_FACTOR_ATTEMPTS: u32 = 5;
+
+#[tracing::instrument(
+    name = "Second factor attempt",
+    skip(pool, form, session, request, throttle, registry),
+    fields(
+        username=tracing::field::Empty,
+        user_id=tracing::field::Empty,
+        client_ip=tracing::field::Empty,
+        failed_attempts=tracing::field::Empty
+    )
+)]
+#[utoipa::path(
+    post,
+    path = "/login/two-factor",
+    tag = "login",
+    request_body(content = TwoFactorFormData, content_type = "application/x-www-form-urlencoded"),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to `/admin/dashboard`, or back to the form with an error.")
+    )
+)]
+pub async fn verify_two_factor(
+    pool: web::Data<PgPool>,
+    web::Form(form): web::Form<TwoFactorFormData>,
+    session: TypedSession,
+    request: HttpRequest,
+    throttle: web::Data<LoginThrottle>,
+    registry: web::Data<SessionRegistry>,
+) -> Result<HttpResponse, InternalError<LoginError>> {
+    let Some(mut pending) = session
+        .get_pending_second_factor()
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?
+    else {
+        return Ok(HttpResponse::SeeOther()
+            .insert_header((LOCATION, "/login"))
+            .finish());
+    };
+    let user_id = pending.user_id;
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+    // Wrong codes count towards the same lockout as wrong passwords, otherwise
+    // logging in again would give unlimited guesses at the code.
+    let attempt = LoginAttempt {
+        username: &pending.username,
+        ip: client_ip(&request),
+    };
+    record_attempt(&attempt);
+    throttle_attempt(&throttle, &attempt).await?;
+
+    if !verify_second_factor(&pool, user_id, &form.code)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+    {
+        record_failure(&throttle, &attempt).await?;
+        pending.failed_attempts += 1;
+        if pending.failed_attempts >= MAX_SECOND_FACTOR_ATTEMPTS {
+            session.remove_pending_second_factor();
+            return Err(login_redirect(LoginError::TooManySecondFactorAttempts));
+        }
+        session
+            .update_pending_second_factor(pending)
+            .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+        FlashMessage::error("Invalid authentication code.").send();
+        return Ok(HttpResponse::SeeOther()
+            .insert_header((LOCATION, "/login/two-factor"))
+            .finish());
+    }
+
+    throttle
+        .record_success(attempt.username)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+    session.renew();
+    start_session(
+        &session,
+        &registry,
+        &request,
+        attempt.ip,
+        user_id,
+        pending.remember_me,
+    )
+    .await
+    .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+    Ok(HttpResponse::SeeOther()
+        .insert_header((LOCATION, "/admin/dashboard"))
+        .finish())
+}
+
+/// Every factor has been verified: authenticate the session and record it
+/// in the registry, so that it shows up in `/admin/sessions`.
+async fn start_session(
+    session: &TypedSession,
+    registry: &SessionRegistry,
+    request: &HttpRequest,
+    ip: Option<IpAddr>,
+    user_id: Uuid,
+    remember_me: bool,
+) -> Result<(), anyhow::Error> {
+    let user_agent = request
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|h| h.to_str().ok());
+    let session_id = registry.register(user_id, ip, user_agent).await?;
+    session.insert_user_id(user_id)?;
+    let now = Utc::now().timestamp_micros();
+    session.insert_authenticated_at(now)?;
+    session.insert_last_seen_at(now)?;
+    session.insert_remember_me(remember_me)?;
+    session.insert_session_id(session_id)?;
+    Ok(())
+}
+
+fn record_attempt(attempt: &LoginAttempt<'_>) {
+    let span = tracing::Span::current();
+    span.record("username", tracing::field::display(attempt.username));
+    if let Some(ip) = attempt.ip {
+        span.record("client_ip", tracing::field::display(&ip));
+    }
+}
+
+/// Turn locked out attempts away, and slow down the others according to
+/// the number of recent failures.
+async fn throttle_attempt(
+    throttle: &LoginThrottle,
+    attempt: &LoginAttempt<'_>,
+) -> Result<(), InternalError<LoginError>> {
+    match throttle
+        .check(attempt)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+    {
+        ThrottleDecision::LockedOut => Err(login_redirect(LoginError::LockedOut)),
+        ThrottleDecision::Proceed { delay } => {
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn record_failure(
+    throttle: &LoginThrottle,
+    attempt: &LoginAttempt<'_>,
+) -> Result<(), InternalError<LoginError>> {
+    let failed_attempts = throttle
+        .record_failure(attempt)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+    tracing::Span::current().record("failed_attempts", failed_attempts);
+    Ok(())
+}
+
 pub fn login_redirect(e: LoginError) -> InternalError<LoginError> {
     FlashMessage::error(e.to_string()).send();
     let response = HttpResponse::build(StatusCode::SEE_OTHER)
@@ -60,6 +284,10 @@ pub fn login_redirect(e: LoginError) -> InternalError<LoginError> {
 pub enum LoginError {
     #[error("Authentication failed")]
     AuthError(#[source] anyhow::Error),
+    #[error("Too many failed login attempts, please try again later.")]
+    LockedOut,
+    #[error("Too many invalid authentication codes, please log in again.")]
+    TooManySecondFactorAttempts,
     #[error("Something went wrong.")]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -69,3 +297,17 @@ impl std::fmt::Debug for LoginError {
         error_chain_fmt(f, self)
     }
 }
+
+/// Only used by the API: the login form answers every error with a redirect,
+/// see `login_redirect`.
+impl actix_web::ResponseError for LoginError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            LoginError::AuthError(_) | LoginError::TooManySecondFactorAttempts => {
+                StatusCode::UNAUTHORIZED
+            }
+            LoginError::LockedOut => StatusCode::TOO_MANY_REQUESTS,
+            LoginError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
@@ -1,14 +1,25 @@
 //! src/routes/mod.rs
 mod admin;
+pub mod api;
 mod health_check;
 mod home;
 mod login;
+mod openapi;
+mod password_reset;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_my_data;
+mod subscriptions_resend_confirmation;
+mod subscriptions_unsubscribe;
 
 pub use admin::*;
 pub use health_check::*;
 pub use home::*;
 pub use login::*;
+pub use openapi::*;
+pub use password_reset::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use subscriptions_my_data::*;
+pub use subscriptions_resend_confirmation::*;
+pub use subscriptions_unsubscribe::*;
@@ -0,0 +1,147 @@
+//! src/routes/openapi.rs
+//! The OpenAPI document of every route registered in `startup::run`, built
+//! from the `#[utoipa::path]` attribute of each handler.
+use crate::authentication::CSRF_TOKEN_HEADER;
+use crate::routes::{self, api};
+use crate::session_state::SESSION_COOKIE_NAME;
+use actix_web::HttpResponse;
+use actix_web::http::header::ContentType;
+use utoipa::openapi::OpenApi as OpenApiDocument;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, Http, HttpAuthScheme, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "zero2prod",
+        description = "The newsletter delivery service. Routes under `/admin` answer with HTML \
+            pages and expect a logged in session, the JSON API lives under `/api/v1`."
+    ),
+    paths(
+        routes::health_check,
+        routes::home,
+        routes::subscribe,
+        routes::confirm,
+        routes::resend_confirmation,
+        routes::unsubscribe_form,
+        routes::unsubscribe,
+        routes::request_my_data,
+        routes::my_data_page,
+        routes::download_my_data,
+        routes::erase_my_data,
+        routes::login_form,
+        routes::login,
+        routes::two_factor_form,
+        routes::verify_two_factor,
+        routes::password_reset_form,
+        routes::request_password_reset,
+        routes::new_password_form,
+        routes::reset_password,
+        routes::admin_dashboard,
+        routes::change_password_form,
+        routes::change_password,
+        routes::two_factor_enrollment_form,
+        routes::enroll_two_factor,
+        routes::logout,
+        routes::list_sessions,
+        routes::revoke_other_sessions,
+        routes::revoke_session,
+        routes::list_subscribers_page,
+        routes::export_subscribers,
+        routes::import_subscribers_form,
+        routes::import_subscribers,
+        routes::download_import_report,
+        routes::resend_subscriber_confirmation,
+        routes::confirm_subscriber,
+        routes::unsubscribe_subscriber,
+        routes::remove_subscriber,
+        routes::list_api_tokens,
+        routes::create_api_token,
+        routes::revoke_api_token,
+        routes::publish_newsletter_form,
+        routes::publish_newsletter,
+        routes::create_newsletter_draft,
+        routes::edit_newsletter_draft_form,
+        routes::update_newsletter_draft,
+        routes::preview_newsletter_issue,
+        routes::schedule_newsletter_issue,
+        routes::cancel_newsletter_issue,
+        routes::publish_newsletter_draft,
+        routes::list_users,
+        routes::invite_user,
+        routes::change_user_role,
+        routes::deactivate_user,
+        routes::list_lockouts,
+        routes::clear_lockout,
+        api::list_subscribers,
+        api::create_subscriber,
+        api::get_subscriber,
+        api::delete_subscriber,
+        api::list_issues,
+        api::publish_issue,
+        openapi_json,
+        api_docs,
+    ),
+    modifiers(&SecuritySchemes)
+)]
+pub struct ApiDoc;
+
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut OpenApiDocument) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "session_cookie",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::with_description(
+                SESSION_COOKIE_NAME,
+                "Set by `POST /login`.",
+            ))),
+        );
+        components.add_security_scheme(
+            "csrf_token",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                CSRF_TOKEN_HEADER,
+                "The CSRF token of the session, also accepted as the `csrf_token` form field.",
+            ))),
+        );
+        components.add_security_scheme(
+            "api_token",
+            SecurityScheme::Http(
+                Http::builder()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some(
+                        "An API token created from `/admin/api-tokens`, limited to its scopes.",
+                    ))
+                    .build(),
+            ),
+        );
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/openapi.json",
+    tag = "docs",
+    responses(
+        (status = 200, description = "This document.", content_type = "application/json")
+    )
+)]
+pub async fn openapi_json() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+/// Swagger UI, loaded from a CDN rather than bundled in the binary.
+#[utoipa::path(
+    get,
+    path = "/api/docs",
+    tag = "docs",
+    responses(
+        (status = 200, description = "A browsable version of `/api/openapi.json`.", content_type = "text/html")
+    )
+)]
+pub async fn api_docs() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(include_str!("api_docs.html"))
+}
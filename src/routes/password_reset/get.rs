@@ -0,0 +1,122 @@
+//! src/routes/password_reset/get.rs
+use crate::routes::e500;
+use crate::routes::password_reset::token::get_user_id_from_reset_token;
+use crate::routes::see_other;
+use crate::session_state::TypedSession;
+use actix_web::{HttpResponse, http::header::ContentType, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Parameters {
+    token: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/password-reset",
+    tag = "password reset",
+    responses(
+        (status = 200, description = "Asks for a username or email.", content_type = "text/html")
+    )
+)]
+pub async fn password_reset_form(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let csrf_token = session.get_or_insert_csrf_token().map_err(e500)?;
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="UTF-8">
+  <meta http-equiv="content-type" content="text/html; charset=utf-8">
+  <title>Reset your password</title>
+</head>
+<body>
+    <h1>Reset your password</h1>
+    {msg_html}
+    <form action="/password-reset" method="post">
+      <input hidden type="text" name="csrf_token" value="{csrf_token}">
+      <label for="username">Username or email
+        <input type="text" name="username" placeholder="Enter your username or email">
+      </label>
+      <button type="submit">Send me a reset link</button>
+    </form>
+    <p><a href="/login">&lt;- Back to login</a></p>
+</body>
+</html>"#
+        )))
+}
+
+#[tracing::instrument(name = "Get new password form", skip_all)]
+#[utoipa::path(
+    get,
+    path = "/password-reset/confirm",
+    tag = "password reset",
+    params(Parameters),
+    responses(
+        (status = 200, description = "Asks for a new password, or explains that the link is no longer valid.", content_type = "text/html")
+    )
+)]
+pub async fn new_password_form(
+    pool: web::Data<PgPool>,
+    parameters: web::Query<Parameters>,
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    if get_user_id_from_reset_token(&pool, &parameters.token)
+        .await
+        .map_err(e500)?
+        .is_none()
+    {
+        FlashMessage::error("The password reset link is invalid or has expired.").send();
+        return Ok(see_other("/password-reset"));
+    }
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+    let token = htmlescape::encode_attribute(&parameters.token);
+    let csrf_token = session.get_or_insert_csrf_token().map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="UTF-8">
+  <meta http-equiv="content-type" content="text/html; charset=utf-8">
+  <title>Choose a new password</title>
+</head>
+<body>
+    <h1>Choose a new password</h1>
+    {msg_html}
+    <form action="/password-reset/confirm" method="post">
+      <input hidden type="text" name="csrf_token" value="{csrf_token}">
+      <input hidden type="text" name="token" value="{token}">
+      <label for="Password">New password
+      <input type="password" name="new_password" placeholder="Enter a new password">
+      </label>
+      <br />
+      <label for="Password">Confirm new password
+      <input type="password" name="verify_new_password" placeholder="Type the new password again">
+      </label>
+      <br />
+      <button type="submit">Reset password</button>
+    </form>
+</body>
+</html>"#
+        )))
+}
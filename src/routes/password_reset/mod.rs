@@ -0,0 +1,13 @@
+//! src/routes/password_reset/mod.rs
+
+mod get;
+pub use get::{
+    __path_new_password_form, __path_password_reset_form, new_password_form, password_reset_form,
+};
+
+mod post;
+pub use post::{
+    __path_request_password_reset, __path_reset_password, request_password_reset, reset_password,
+};
+
+mod token;
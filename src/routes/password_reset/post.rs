@@ -0,0 +1,267 @@
+//! src/routes/password_reset/post.rs
+use crate::authentication::{PasswordHashing, SessionRegistry};
+use crate::configuration::PasswordResetSettings;
+use crate::domain::{Password, SubscriberEmail};
+use crate::email_client::EmailTransport;
+use crate::routes::password_reset::token::{
+    consume_reset_token, generate_reset_token, store_reset_token,
+};
+use crate::routes::{e500, see_other, set_new_password};
+use crate::startup::ApplicationBaseUrl;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct ResetRequestFormData {
+    username: String,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct NewPasswordFormData {
+    token: String,
+    #[schema(value_type = String, format = Password)]
+    new_password: Secret<String>,
+    #[schema(value_type = String, format = Password)]
+    verify_new_password: Secret<String>,
+}
+
+fn reset_requested_message() -> FlashMessage {
+    FlashMessage::info(
+        "If an account matches what you entered, \
+        we have sent an email with a link to reset its password.",
+    )
+}
+
+/// We answer the same way whether or not the username (or email) exists, and
+/// whether or not we could deliver the email, to avoid leaking who has an account.
+/// Everything that depends on the account happens in the background, so that
+/// the response takes just as long either way.
+#[tracing::instrument(
+    name = "Request a password reset",
+    skip(form, pool, email_client, base_url, settings)
+)]
+#[utoipa::path(
+    post,
+    path = "/password-reset",
+    tag = "password reset",
+    request_body(content = ResetRequestFormData, content_type = "application/x-www-form-urlencoded"),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to `/password-reset`, whether or not the account exists.")
+    )
+)]
+pub async fn request_password_reset(
+    web::Form(form): web::Form<ResetRequestFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<dyn EmailTransport>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<PasswordResetSettings>,
+) -> HttpResponse {
+    tokio::spawn(send_password_reset_in_background(
+        pool.get_ref().clone(),
+        email_client.into_inner(),
+        base_url.0.clone(),
+        settings.token_expiration(),
+        form.username.trim().to_string(),
+    ));
+    reset_requested_message().send();
+    see_other("/password-reset")
+}
+
+#[tracing::instrument(
+    name = "Send a password reset in the background",
+    skip_all,
+    fields(user_id=tracing::field::Empty)
+)]
+async fn send_password_reset_in_background(
+    pool: PgPool,
+    email_client: Arc<dyn EmailTransport>,
+    base_url: String,
+    token_expiration: Duration,
+    username_or_email: String,
+) {
+    let result = async {
+        let Some((user_id, email)) = get_user_with_email(&pool, &username_or_email).await? else {
+            return Ok(());
+        };
+        tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+        let token = generate_reset_token();
+        let mut transaction = pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool")?;
+        store_reset_token(&mut transaction, user_id, &token, token_expiration)
+            .await
+            .context("Failed to store the password reset token.")?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to store a password reset token.")?;
+
+        send_password_reset_email(
+            email_client.as_ref(),
+            email,
+            &base_url,
+            &token,
+            token_expiration,
+        )
+        .await
+    }
+    .await;
+    if let Err(e) = result {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to send a password reset email."
+        );
+    }
+}
+
+#[tracing::instrument(name = "Reset a password", skip_all, fields(user_id=tracing::field::Empty))]
+#[utoipa::path(
+    post,
+    path = "/password-reset/confirm",
+    tag = "password reset",
+    request_body(content = NewPasswordFormData, content_type = "application/x-www-form-urlencoded"),
+    security(("session_cookie" = [], "csrf_token" = [])),
+    responses(
+        (status = 303, description = "Redirects to `/login` once the password is changed, or back to the form with an error.")
+    )
+)]
+pub async fn reset_password(
+    web::Form(form): web::Form<NewPasswordFormData>,
+    pool: web::Data<PgPool>,
+    hashing: web::Data<PasswordHashing>,
+    registry: web::Data<SessionRegistry>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let retry_url = format!(
+        "/password-reset/confirm?token={}",
+        urlencoding::encode(&form.token)
+    );
+    if form.new_password.expose_secret() != form.verify_new_password.expose_secret() {
+        FlashMessage::error("The new passwords need to match").send();
+        return Ok(see_other(&retry_url));
+    }
+    if let Err(e) = Password::parse(form.new_password.clone()) {
+        FlashMessage::error(e).send();
+        return Ok(see_other(&retry_url));
+    }
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let Some(user_id) = consume_reset_token(&mut transaction, &form.token)
+        .await
+        .context("Failed to consume the password reset token.")
+        .map_err(e500)?
+    else {
+        FlashMessage::error("The password reset link is invalid or has expired.").send();
+        return Ok(see_other("/password-reset"));
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+    set_new_password(&mut *transaction, user_id, &form.new_password, &hashing)
+        .await
+        .map_err(e500)?;
+    invalidate_sessions(&mut transaction, user_id)
+        .await
+        .context("Failed to invalidate the user sessions.")
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to reset a password.")
+        .map_err(e500)?;
+    // The sessions are already rejected because of `sessions_invalidated_at`,
+    // this only takes them off `/admin/sessions`.
+    if let Err(e) = registry.revoke_all_except(user_id, None).await {
+        tracing::warn!(error.cause_chain = ?e, "Failed to revoke the user sessions.");
+    }
+
+    FlashMessage::info("Your password has been reset, you can now log in.").send();
+    Ok(see_other("/login"))
+}
+
+#[tracing::instrument(name = "Get user with email", skip(pool))]
+async fn get_user_with_email(
+    pool: &PgPool,
+    username_or_email: &str,
+) -> Result<Option<(Uuid, SubscriberEmail)>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+    SELECT user_id, email AS "email!"
+    FROM users
+    WHERE (username = $1 OR email = $1) AND is_active AND email IS NOT NULL
+    ORDER BY username = $1 DESC
+    LIMIT 1
+    "#,
+        username_or_email
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to retrieve the user.")?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    match SubscriberEmail::parse(row.email) {
+        Ok(email) => Ok(Some((row.user_id, email))),
+        Err(e) => {
+            tracing::warn!(error.message = %e, "The stored user email is invalid.");
+            Ok(None)
+        }
+    }
+}
+
+/// Every session created before now will be rejected by `reject_anonymous_users`.
+#[tracing::instrument(name = "Invalidate user sessions", skip(transaction))]
+async fn invalidate_sessions(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"UPDATE users SET sessions_invalidated_at = now() WHERE user_id = $1"#,
+        user_id
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Send password reset email", skip_all)]
+async fn send_password_reset_email(
+    email_client: &dyn EmailTransport,
+    recipient: SubscriberEmail,
+    base_url: &str,
+    token: &str,
+    expiration: Duration,
+) -> Result<(), anyhow::Error> {
+    let reset_link = format!("{base_url}/password-reset/confirm?token={token}");
+    let minutes = expiration.as_secs() / 60;
+    let text_content = format!(
+        "Someone asked to reset the password of your account.\n\
+        Visit {reset_link} to choose a new one, the link expires in {minutes} minutes.\n\
+        If it wasn't you, you can ignore this email."
+    );
+    let html_content = format!(
+        "Someone asked to reset the password of your account.<br/>\
+        <a href=\"{reset_link}\">Click here</a> to choose a new one, \
+        the link expires in {minutes} minutes.<br/>\
+        If it wasn't you, you can ignore this email."
+    );
+    email_client
+        .send_email(
+            &recipient,
+            "Reset your password",
+            &html_content,
+            &text_content,
+        )
+        .await
+        .context("Failed to send the password reset email.")
+}
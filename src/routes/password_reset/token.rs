@@ -0,0 +1,86 @@
+//! src/routes/password_reset/token.rs
+use chrono::Utc;
+use rand::distributions::Alphanumeric;
+use rand::{Rng, thread_rng};
+use sha2::{Digest, Sha256};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+pub(super) fn generate_reset_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(32)
+        .collect()
+}
+
+/// Only the hash is stored, a leaked `password_reset_tokens` table cannot be
+/// used to take over accounts.
+pub(super) fn hash_reset_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[tracing::instrument(name = "Store password reset token", skip_all)]
+pub(super) async fn store_reset_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    token: &str,
+    expiration: std::time::Duration,
+) -> Result<(), anyhow::Error> {
+    // Only the most recent link should work.
+    let query = sqlx::query!(
+        r#"DELETE FROM password_reset_tokens WHERE user_id = $1"#,
+        user_id
+    );
+    transaction.execute(query).await?;
+    let now = Utc::now();
+    let query = sqlx::query!(
+        r#"
+    INSERT INTO password_reset_tokens (token_hash, user_id, created_at, expires_at)
+    VALUES ($1, $2, $3, $4)
+    "#,
+        hash_reset_token(token),
+        user_id,
+        now,
+        now + chrono::Duration::from_std(expiration)?
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+/// The user the token was issued for, if it exists and has not expired.
+#[tracing::instrument(name = "Look up password reset token", skip_all)]
+pub(super) async fn get_user_id_from_reset_token(
+    pool: &PgPool,
+    token: &str,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+    SELECT user_id
+    FROM password_reset_tokens
+    WHERE token_hash = $1 AND expires_at > now()
+    "#,
+        hash_reset_token(token)
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Atomically use up the token so it cannot be replayed.
+#[tracing::instrument(name = "Consume password reset token", skip_all)]
+pub(super) async fn consume_reset_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    token: &str,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+    DELETE FROM password_reset_tokens
+    WHERE token_hash = $1
+    RETURNING user_id, expires_at
+    "#,
+        hash_reset_token(token)
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    Ok(row.filter(|r| r.expires_at > Utc::now()).map(|r| r.user_id))
+}
@@ -1,16 +1,13 @@
 use crate::{
+    configuration::SubscriptionSettings,
     domain::{NewSubscriber, SubscriberEmail, SubscriberName},
-    email_client::EmailClient,
+    email_client::EmailTransport,
+    services::{self, ConfirmationEmail, SubscribeError},
     startup::ApplicationBaseUrl,
 };
 use actix_web::{HttpResponse, http::StatusCode, web};
-use anyhow::Context;
-use chrono::Utc;
-use rand::distributions::Alphanumeric;
-use rand::{Rng, thread_rng};
-use sqlx::{Executor, PgPool, Postgres, Transaction};
-use thiserror::Error;
-use uuid::Uuid;
+use sqlx::PgPool;
+use std::sync::Arc;
 
 pub fn error_chain_fmt(
     f: &mut std::fmt::Formatter<'_>,
@@ -25,21 +22,13 @@ pub fn error_chain_fmt(
     Ok(())
 }
 
-#[derive(serde::Deserialize)]
-#[allow(unused)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = SubscribeForm)]
 pub struct FormData {
     name: String,
     email: String,
 }
 
-fn generate_subscription_token() -> String {
-    let mut rng = thread_rng();
-    std::iter::repeat_with(|| rng.sample(Alphanumeric))
-        .map(char::from)
-        .take(25)
-        .collect()
-}
-
 impl TryFrom<FormData> for NewSubscriber {
     type Error = String;
     fn try_from(form: FormData) -> Result<Self, Self::Error> {
@@ -49,59 +38,57 @@ impl TryFrom<FormData> for NewSubscriber {
     }
 }
 
-#[tracing::instrument(
-    name = "Adding a new subscriber",
-    skip(form, pool, email_client, base_url),
-    fields(
-        subscriber_email = %form.email,
-        subscriber_name = %form.name
+#[utoipa::path(
+    post,
+    path = "/subscriptions",
+    tag = "subscriptions",
+    request_body(content = FormData, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "A confirmation email is on its way, unless the address is already confirmed."),
+        (status = 400, description = "The name or the email is invalid.")
     )
 )]
 pub async fn subscribe(
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
-    email_client: web::Data<EmailClient>,
+    email_client: web::Data<dyn EmailTransport>,
     base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
 ) -> Result<HttpResponse, SubscribeError> {
-    let mut transaction = pool
-        .begin()
-        .await
-        .context("Failed to acquire Postgres connection from the pool")?;
     // A fallible conversion that consumes (moves) the input value.
     let new_subscriber = form.0.try_into().map_err(SubscribeError::ValidationError)?;
-    let subscriber_id = insert_subscription(&mut transaction, &new_subscriber)
-        .await
-        .context("Failed to insert new subscriber in the database.")?;
-    let subscription_token = generate_subscription_token();
-    insert_subscription_token(&mut transaction, &subscriber_id, &subscription_token)
-        .await
-        .context("Failed to insert subscription toke in the database.")?;
-    transaction
-        .commit()
-        .await
-        .context("Failed to commit SQL transaction to store a new subscriber.")?;
-    send_confirmation_email(
-        &email_client,
-        new_subscriber,
-        &base_url.0,
-        &subscription_token,
-    )
-    .await
-    .context("Failed to send confirmation email.")?;
-    Ok(HttpResponse::Ok().finish())
-}
-
-#[derive(Error)]
-pub enum SubscribeError {
-    #[error("{0}")]
-    ValidationError(String),
-    #[error(transparent)]
-    UnexpectedError(#[from] anyhow::Error),
+    match services::subscribe(&pool, settings.token_expiration(), &new_subscriber).await {
+        // Answered like any other address, see `services::subscribe`.
+        Ok(subscription) => {
+            if let Some(confirmation_email) = subscription.confirmation_email {
+                tokio::spawn(send_confirmation_in_background(
+                    email_client.into_inner(),
+                    base_url.0.clone(),
+                    confirmation_email,
+                ));
+            }
+            Ok(HttpResponse::Ok().finish())
+        }
+        Err(SubscribeError::Suppressed) => Ok(HttpResponse::Ok().finish()),
+        Err(e) => Err(e),
+    }
 }
 
-impl std::fmt::Debug for SubscribeError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        error_chain_fmt(f, self)
+#[tracing::instrument(name = "Send a confirmation email in the background", skip_all)]
+pub(crate) async fn send_confirmation_in_background(
+    email_client: Arc<dyn EmailTransport>,
+    base_url: String,
+    confirmation_email: ConfirmationEmail,
+) {
+    if let Err(e) = confirmation_email
+        .send(email_client.as_ref(), &base_url)
+        .await
+    {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to send a confirmation email."
+        );
     }
 }
 
@@ -109,94 +96,8 @@ impl actix_web::ResponseError for SubscribeError {
     fn status_code(&self) -> StatusCode {
         match self {
             SubscribeError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            SubscribeError::Suppressed => StatusCode::CONFLICT,
             SubscribeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
-
-#[tracing::instrument(
-    name = "Saving new subscriber details in database",
-    skip(transaction, new_subscriber)
-)]
-async fn insert_subscription(
-    transaction: &mut Transaction<'_, Postgres>,
-    new_subscriber: &NewSubscriber,
-) -> Result<Uuid, sqlx::Error> {
-    let subscriber_id = Uuid::new_v4();
-    let query = sqlx::query!(
-        r#"
-INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-VALUES ($1, $2, $3, $4, 'pending_confirmation')
-"#,
-        subscriber_id,
-        new_subscriber.email.as_ref(),
-        new_subscriber.name.as_ref(),
-        Utc::now()
-    );
-    transaction.execute(query).await?;
-
-    Ok(subscriber_id)
-}
-
-#[tracing::instrument(
-    name = "Send confirmation email to new subscriber",
-    skip(email_client, new_subscriber, base_url, subscription_token)
-)]
-async fn send_confirmation_email(
-    email_client: &EmailClient,
-    new_subscriber: NewSubscriber,
-    base_url: &str,
-    subscription_token: &str,
-) -> Result<(), reqwest::Error> {
-    let confirmation_link = format!(
-        "{base_url}/subscriptions/confirm?subscription_token={}",
-        subscription_token
-    );
-    let text_content = format!(
-        "Welcome to our newsletter!\nVisit {} to confirm your subscription.",
-        confirmation_link
-    );
-    let html_content = format!(
-        "Welcome to our newsletter!<br/>\
-            <a href=\"{}\">Click here</a> to confirm your subscription.",
-        confirmation_link
-    );
-    email_client
-        .send_email(
-            &new_subscriber.email,
-            "Welcome!",
-            &html_content,
-            &text_content,
-        )
-        .await
-}
-
-#[tracing::instrument(
-    name = "Store subscription token in the database",
-    skip(transaction, subscriber_id, subscription_token)
-)]
-async fn insert_subscription_token(
-    transaction: &mut Transaction<'_, Postgres>,
-    subscriber_id: &Uuid,
-    subscription_token: &str,
-) -> Result<(), StoreTokenError> {
-    let query = sqlx::query!(
-        r#"
-    INSERT INTO subscription_tokens (subscription_token, subscriber_id) VALUES ($1, $2)
-    "#,
-        subscription_token,
-        subscriber_id
-    );
-    transaction.execute(query).await.map_err(StoreTokenError)?;
-    Ok(())
-}
-
-#[derive(Error)]
-#[error("A database error was encounter while trying to store a subscription token.")]
-pub struct StoreTokenError(#[source] sqlx::Error);
-
-impl std::fmt::Debug for StoreTokenError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        error_chain_fmt(f, self)
-    }
-}
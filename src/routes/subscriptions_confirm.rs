@@ -1,75 +1,39 @@
-use crate::routes::error_chain_fmt;
+use crate::services::{self, ConfirmationError};
 use actix_web::{HttpResponse, http::StatusCode, web};
-use anyhow::Context;
 use sqlx::PgPool;
-use uuid::Uuid;
 
-#[derive(serde::Deserialize, Debug)]
+#[derive(serde::Deserialize, Debug, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct Parameters {
     subscription_token: String,
 }
 
 #[tracing::instrument(name = "Handle subscription confirmation", skip(pool, parameters))]
+#[utoipa::path(
+    get,
+    path = "/subscriptions/confirm",
+    tag = "subscriptions",
+    params(Parameters),
+    responses(
+        (status = 200, description = "The subscription is confirmed."),
+        (status = 401, description = "There is no subscriber associated with the token."),
+        (status = 410, description = "The token has expired.")
+    )
+)]
 pub async fn confirm(
     pool: web::Data<PgPool>,
     parameters: web::Query<Parameters>,
 ) -> Result<HttpResponse, ConfirmationError> {
-    let subscriber_id = get_subscriber_id_from_token(&pool, &parameters.subscription_token)
-        .await
-        .context("Failed to get subscriber associated with the provided token.")?
-        .ok_or(ConfirmationError::UnknownToken)?;
-    confirm_subscriber(&pool, subscriber_id)
-        .await
-        .context("Failed to update subscriber status to confirmed.")?;
+    services::confirm_subscription(&pool, &parameters.subscription_token).await?;
     Ok(HttpResponse::Ok().finish())
 }
 
-#[derive(thiserror::Error)]
-pub enum ConfirmationError {
-    #[error("There is no subscriber associated with the provided token.")]
-    UnknownToken,
-    #[error(transparent)]
-    UnexpectedError(#[from] anyhow::Error),
-}
-
 impl actix_web::ResponseError for ConfirmationError {
     fn status_code(&self) -> StatusCode {
         match self {
             ConfirmationError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             ConfirmationError::UnknownToken => StatusCode::UNAUTHORIZED,
+            ConfirmationError::ExpiredToken => StatusCode::GONE,
         }
     }
 }
-
-impl std::fmt::Debug for ConfirmationError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        error_chain_fmt(f, self)
-    }
-}
-
-#[tracing::instrument(name = "Get subscriber_id from subscription token", skip(pool, token))]
-async fn get_subscriber_id_from_token(
-    pool: &PgPool,
-    token: &str,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    let result = sqlx::query!(
-        r#"
-    SELECT subscriber_id FROM subscription_tokens WHERE subscription_token = $1
-    "#,
-        token
-    )
-    .fetch_optional(pool)
-    .await?;
-    Ok(result.map(|r| r.subscriber_id))
-}
-
-#[tracing::instrument(name = "Mark subscriber as confirmed", skip(subscriber_id, pool))]
-async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
-        subscriber_id
-    )
-    .execute(pool)
-    .await?;
-    Ok(())
-}
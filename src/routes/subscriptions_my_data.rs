@@ -0,0 +1,252 @@
+//! src/routes/subscriptions_my_data.rs
+use crate::configuration::SubscriptionSettings;
+use crate::domain::{DataRequestToken, SubscriberEmail};
+use crate::email_client::EmailTransport;
+use crate::routes::error_chain_fmt;
+use crate::services::{self, DataRequestLinkSettings, SubscriberData};
+use crate::startup::{ApplicationBaseUrl, HmacSecret};
+use actix_web::http::header::{ContentDisposition, ContentType, DispositionParam, DispositionType};
+use actix_web::{HttpResponse, http::StatusCode, web};
+use chrono::Utc;
+use secrecy::Secret;
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct MyDataFormData {
+    email: String,
+}
+
+#[derive(serde::Deserialize, Debug, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct MyDataParameters {
+    /// From the link in the data request email.
+    token: String,
+}
+
+/// Email a link to download or erase everything we store about an address.
+///
+/// We answer `200 OK` whether or not the address is on the list, and just as
+/// fast: the lookup and the email happen in the background, so the endpoint
+/// cannot be used to find out who is on the list.
+#[tracing::instrument(
+    name = "Request access to subscriber data",
+    skip(form, pool, email_client, base_url, secret, settings)
+)]
+#[utoipa::path(
+    post,
+    path = "/subscriptions/my-data",
+    tag = "subscriptions",
+    request_body(content = MyDataFormData, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "A link is on its way, if the address is on the list."),
+        (status = 400, description = "The email is invalid.")
+    )
+)]
+pub async fn request_my_data(
+    form: web::Form<MyDataFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<dyn EmailTransport>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    secret: web::Data<HmacSecret>,
+    settings: web::Data<SubscriptionSettings>,
+) -> Result<HttpResponse, DataRequestError> {
+    let email = SubscriberEmail::parse(form.0.email).map_err(DataRequestError::ValidationError)?;
+    tokio::spawn(send_data_request_in_background(
+        pool.get_ref().clone(),
+        email_client.into_inner(),
+        base_url.0.clone(),
+        secret.0.clone(),
+        settings.token_expiration(),
+        email,
+    ));
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[tracing::instrument(
+    name = "Send a data request email in the background",
+    skip_all,
+    fields(subscriber_email = %email.as_ref())
+)]
+async fn send_data_request_in_background(
+    pool: PgPool,
+    email_client: Arc<dyn EmailTransport>,
+    base_url: String,
+    hmac_secret: Secret<String>,
+    token_expiration: Duration,
+    email: SubscriberEmail,
+) {
+    let links = DataRequestLinkSettings {
+        base_url: &base_url,
+        hmac_secret: &hmac_secret,
+        token_expiration,
+    };
+    if let Err(e) =
+        services::send_data_request_email(&pool, email_client.as_ref(), &links, &email).await
+    {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to send a data request email."
+        );
+    }
+}
+
+/// Landing page for the link in the data request email.
+///
+/// As for unsubscribing, a `GET` never erases anything: mail scanners follow
+/// links.
+#[tracing::instrument(name = "Show the subscriber data page", skip(parameters, pool, secret))]
+#[utoipa::path(
+    get,
+    path = "/subscriptions/my-data",
+    tag = "subscriptions",
+    params(MyDataParameters),
+    responses(
+        (status = 200, description = "Offers to download or erase the data.", content_type = "text/html"),
+        (status = 401, description = "The token is invalid."),
+        (status = 404, description = "The data was already erased."),
+        (status = 410, description = "The token has expired.")
+    )
+)]
+pub async fn my_data_page(
+    parameters: web::Query<MyDataParameters>,
+    pool: web::Data<PgPool>,
+    secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, DataRequestError> {
+    let subscriber_id = verify_token(&parameters.token, &secret)?;
+    let subscriber = services::get_subscriber(&pool, subscriber_id)
+        .await?
+        .ok_or(DataRequestError::UnknownSubscriber)?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Your data</title>
+</head>
+<body>
+    <p>This is what we can do with the data we hold about {email}.</p>
+    <p><a href="/subscriptions/my-data/export?token={token}">Download it</a> as a JSON file.</p>
+    <form action="/subscriptions/my-data/erase?token={token}" method="post">
+        <p>Erase it: you will stop receiving our newsletter, and this address
+        cannot be subscribed again.</p>
+        <button type="submit">Erase my data</button>
+    </form>
+</body>
+</html>"#,
+            email = htmlescape::encode_minimal(&subscriber.email),
+            token = htmlescape::encode_attribute(&parameters.token),
+        )))
+}
+
+#[tracing::instrument(name = "Download subscriber data", skip(parameters, pool, secret))]
+#[utoipa::path(
+    get,
+    path = "/subscriptions/my-data/export",
+    tag = "subscriptions",
+    params(MyDataParameters),
+    responses(
+        (status = 200, body = SubscriberData, description = "Everything we store about the subscriber."),
+        (status = 401, description = "The token is invalid."),
+        (status = 404, description = "The data was already erased."),
+        (status = 410, description = "The token has expired.")
+    )
+)]
+pub async fn download_my_data(
+    parameters: web::Query<MyDataParameters>,
+    pool: web::Data<PgPool>,
+    secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, DataRequestError> {
+    let subscriber_id = verify_token(&parameters.token, &secret)?;
+    let data = services::get_subscriber_data(&pool, subscriber_id)
+        .await?
+        .ok_or(DataRequestError::UnknownSubscriber)?;
+    Ok(HttpResponse::Ok()
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("my-data.json".into())],
+        })
+        .json(data))
+}
+
+#[tracing::instrument(name = "Erase subscriber data", skip(parameters, pool, secret))]
+#[utoipa::path(
+    post,
+    path = "/subscriptions/my-data/erase",
+    tag = "subscriptions",
+    params(MyDataParameters),
+    responses(
+        (status = 200, description = "The data is erased.", content_type = "text/html"),
+        (status = 401, description = "The token is invalid."),
+        (status = 404, description = "The data was already erased."),
+        (status = 410, description = "The token has expired.")
+    )
+)]
+pub async fn erase_my_data(
+    parameters: web::Query<MyDataParameters>,
+    pool: web::Data<PgPool>,
+    secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, DataRequestError> {
+    let subscriber_id = verify_token(&parameters.token, &secret)?;
+    if !services::erase_subscriber(&pool, subscriber_id).await? {
+        return Err(DataRequestError::UnknownSubscriber);
+    }
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Data erased</title>
+</head>
+<body>
+    <p>Your data has been erased, we will not email you again.</p>
+</body>
+</html>"#,
+    ))
+}
+
+fn verify_token(token: &str, secret: &HmacSecret) -> Result<Uuid, DataRequestError> {
+    let (subscriber_id, expires_at) =
+        DataRequestToken::verify(token, &secret.0).map_err(|_| DataRequestError::InvalidToken)?;
+    if expires_at <= Utc::now() {
+        return Err(DataRequestError::ExpiredToken);
+    }
+    Ok(subscriber_id)
+}
+
+#[derive(thiserror::Error)]
+pub enum DataRequestError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("The link is invalid.")]
+    InvalidToken,
+    #[error("The link has expired, please ask for a new one.")]
+    ExpiredToken,
+    #[error("We do not hold any data about you anymore.")]
+    UnknownSubscriber,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl actix_web::ResponseError for DataRequestError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            DataRequestError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            DataRequestError::InvalidToken => StatusCode::UNAUTHORIZED,
+            DataRequestError::ExpiredToken => StatusCode::GONE,
+            DataRequestError::UnknownSubscriber => StatusCode::NOT_FOUND,
+            DataRequestError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl std::fmt::Debug for DataRequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(f, self)
+    }
+}
@@ -0,0 +1,78 @@
+//! src/routes/subscriptions_resend_confirmation.rs
+use crate::{
+    configuration::SubscriptionSettings,
+    domain::SubscriberEmail,
+    email_client::EmailTransport,
+    services::{self, ConfirmationLinkSettings, SubscribeError},
+    startup::ApplicationBaseUrl,
+};
+use actix_web::{HttpResponse, web};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct ResendConfirmationFormData {
+    email: String,
+}
+
+/// Send a fresh confirmation link to a subscriber who lost (or let expire) the first one.
+///
+/// We answer `200 OK` whether or not a pending subscription exists for the
+/// address, so the endpoint cannot be used to find out who is on the list.
+/// The lookup and the email happen after the response, which would otherwise
+/// take longer, or fail, only for pending addresses.
+#[utoipa::path(
+    post,
+    path = "/subscriptions/resend-confirmation",
+    tag = "subscriptions",
+    request_body(content = ResendConfirmationFormData, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "A new confirmation email is on its way, if the address has a pending subscription."),
+        (status = 400, description = "The email is invalid.")
+    )
+)]
+pub async fn resend_confirmation(
+    form: web::Form<ResendConfirmationFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<dyn EmailTransport>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
+) -> Result<HttpResponse, SubscribeError> {
+    let email = SubscriberEmail::parse(form.0.email).map_err(SubscribeError::ValidationError)?;
+    tokio::spawn(resend_confirmation_in_background(
+        pool.get_ref().clone(),
+        email_client.into_inner(),
+        base_url.0.clone(),
+        settings.token_expiration(),
+        email,
+    ));
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[tracing::instrument(
+    name = "Resend a confirmation email in the background",
+    skip_all,
+    fields(subscriber_email = %email.as_ref())
+)]
+async fn resend_confirmation_in_background(
+    pool: PgPool,
+    email_client: Arc<dyn EmailTransport>,
+    base_url: String,
+    token_expiration: Duration,
+    email: SubscriberEmail,
+) {
+    let links = ConfirmationLinkSettings {
+        base_url: &base_url,
+        token_expiration,
+    };
+    if let Err(e) =
+        services::resend_confirmation_email(&pool, email_client.as_ref(), &links, &email).await
+    {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to resend a confirmation email."
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! src/routes/subscriptions_unsubscribe.rs
+use crate::domain::UnsubscribeToken;
+use crate::routes::error_chain_fmt;
+use crate::startup::HmacSecret;
+use actix_web::{HttpResponse, http::StatusCode, http::header::ContentType, web};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize, Debug, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UnsubscribeParameters {
+    token: String,
+}
+
+/// Landing page for the link in the newsletter footer.
+///
+/// Mail scanners follow links, so a `GET` never changes anything: it asks the
+/// subscriber to confirm with a `POST` to the same URL.
+#[tracing::instrument(name = "Show unsubscribe form", skip(parameters, secret))]
+#[utoipa::path(
+    get,
+    path = "/subscriptions/unsubscribe",
+    tag = "subscriptions",
+    params(UnsubscribeParameters),
+    responses(
+        (status = 200, description = "Asks the subscriber to confirm.", content_type = "text/html"),
+        (status = 401, description = "The token is invalid.")
+    )
+)]
+pub async fn unsubscribe_form(
+    parameters: web::Query<UnsubscribeParameters>,
+    secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, UnsubscribeError> {
+    UnsubscribeToken::verify(&parameters.token, &secret.0)
+        .map_err(|_| UnsubscribeError::InvalidToken)?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Unsubscribe</title>
+</head>
+<body>
+    <p>Do you want to stop receiving our newsletter?</p>
+    <form action="/subscriptions/unsubscribe?token={}" method="post">
+        <input type="hidden" name="List-Unsubscribe" value="One-Click">
+        <button type="submit">Unsubscribe</button>
+    </form>
+</body>
+</html>"#,
+            parameters.token
+        )))
+}
+
+/// Handles both the confirmation form and RFC 8058 one-click requests
+/// (`List-Unsubscribe=One-Click` in the body), the token alone is enough.
+#[tracing::instrument(name = "Unsubscribe a subscriber", skip(parameters, pool, secret))]
+#[utoipa::path(
+    post,
+    path = "/subscriptions/unsubscribe",
+    tag = "subscriptions",
+    params(UnsubscribeParameters),
+    responses(
+        (status = 200, description = "The subscriber is unsubscribed.", content_type = "text/html"),
+        (status = 401, description = "The token is invalid.")
+    )
+)]
+pub async fn unsubscribe(
+    parameters: web::Query<UnsubscribeParameters>,
+    pool: web::Data<PgPool>,
+    secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, UnsubscribeError> {
+    let subscriber_id = UnsubscribeToken::verify(&parameters.token, &secret.0)
+        .map_err(|_| UnsubscribeError::InvalidToken)?;
+    mark_subscriber_as_unsubscribed(&pool, subscriber_id)
+        .await
+        .context("Failed to update subscriber status to unsubscribed.")?;
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Unsubscribed</title>
+</head>
+<body>
+    <p>You have been unsubscribed from our newsletter.</p>
+</body>
+</html>"#,
+    ))
+}
+
+#[derive(thiserror::Error)]
+pub enum UnsubscribeError {
+    #[error("The unsubscribe token is invalid.")]
+    InvalidToken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl actix_web::ResponseError for UnsubscribeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            UnsubscribeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            UnsubscribeError::InvalidToken => StatusCode::UNAUTHORIZED,
+        }
+    }
+}
+
+impl std::fmt::Debug for UnsubscribeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(f, self)
+    }
+}
+
+#[tracing::instrument(name = "Mark subscriber as unsubscribed", skip(pool))]
+async fn mark_subscriber_as_unsubscribed(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE subscriptions SET status = 'unsubscribed' WHERE id = $1"#,
+        subscriber_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,12 @@
+//! src/services/mod.rs
+//! What the admin pages and the JSON API do, independently of how requests
+//! come in and responses go out.
+mod newsletters;
+mod subscriber_data;
+mod subscriber_import;
+mod subscribers;
+
+pub use newsletters::*;
+pub use subscriber_data::*;
+pub use subscriber_import::*;
+pub use subscribers::*;
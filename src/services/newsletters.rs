@@ -0,0 +1,486 @@
+//! src/services/newsletters.rs
+use crate::domain::NewsletterBody;
+use crate::routes::error_chain_fmt;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(serde::Serialize, utoipa::ToSchema, Debug)]
+pub struct NewsletterIssue {
+    pub id: Uuid,
+    pub title: String,
+    /// `draft`, `scheduled`, `sending` or `sent`.
+    pub status: String,
+    /// When a scheduled issue goes out.
+    pub scheduled_at: Option<DateTime<Utc>>,
+    /// When the deliveries were queued, unset until then.
+    pub published_at: Option<DateTime<Utc>>,
+    /// Deliveries still waiting in the queue.
+    pub pending_deliveries: i64,
+}
+
+/// An issue along with its content, as stored.
+#[derive(Debug)]
+pub struct IssueContent {
+    pub id: Uuid,
+    pub title: String,
+    pub text_content: String,
+    pub html_content: String,
+    pub content_markdown: Option<String>,
+    pub status: String,
+    pub scheduled_at: Option<DateTime<Utc>>,
+}
+
+impl IssueContent {
+    /// The bodies that were written by hand rather than rendered from the
+    /// Markdown, empty when they were rendered.
+    pub fn overrides(&self) -> NewsletterBody {
+        let Some(markdown) = &self.content_markdown else {
+            return NewsletterBody {
+                html: self.html_content.clone(),
+                text: self.text_content.clone(),
+            };
+        };
+        let rendered = NewsletterBody::from_markdown(markdown);
+        let manual = |stored: &str, rendered: &str| {
+            if stored == rendered {
+                String::new()
+            } else {
+                stored.to_string()
+            }
+        };
+        NewsletterBody {
+            html: manual(&self.html_content, &rendered.html),
+            text: manual(&self.text_content, &rendered.text),
+        }
+    }
+}
+
+/// Why an issue could not move to the requested state.
+#[derive(Error)]
+pub enum IssueStateError {
+    #[error("There is no such newsletter issue.")]
+    UnknownIssue,
+    #[error("Only drafts can be edited, scheduled or published.")]
+    NotADraft,
+    #[error("Only scheduled issues can be cancelled, before their send time.")]
+    NotScheduled,
+    #[error("The send time must be in the future.")]
+    SendTimeInThePast,
+    #[error("{}", .0.join(" "))]
+    Incomplete(Vec<&'static str>),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for IssueStateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(f, self)
+    }
+}
+
+pub struct NewIssue<'a> {
+    pub title: &'a str,
+    /// Renders both bodies, each can still be overridden by hand.
+    pub content_markdown: &'a str,
+    pub content_text: &'a str,
+    pub content_html: &'a str,
+}
+
+impl NewIssue<'_> {
+    /// The bodies that go out: written by hand, or else rendered from the
+    /// Markdown.
+    pub fn body(&self) -> NewsletterBody {
+        let rendered = NewsletterBody::from_markdown(self.content_markdown);
+        let pick = |manual: &str, rendered: String| {
+            if manual.is_empty() {
+                rendered
+            } else {
+                manual.to_string()
+            }
+        };
+        NewsletterBody {
+            html: pick(self.content_html, rendered.html),
+            text: pick(self.content_text, rendered.text),
+        }
+    }
+
+    /// Everything wrong with the issue, empty if it can be published.
+    pub fn validation_errors(&self) -> Vec<&'static str> {
+        let body = self.body();
+        let mut errors = Vec::new();
+        if self.title.is_empty() {
+            errors.push("Title is required");
+        }
+        if body.html.is_empty() {
+            errors.push("HTML content is required.");
+        }
+        if body.text.is_empty() {
+            errors.push("Text content is required.");
+        }
+        errors
+    }
+
+    fn markdown(&self) -> Option<&str> {
+        Some(self.content_markdown).filter(|markdown| !markdown.trim().is_empty())
+    }
+}
+
+#[tracing::instrument(name = "List newsletter issues", skip(pool))]
+pub async fn list_issues(pool: &PgPool) -> Result<Vec<NewsletterIssue>, anyhow::Error> {
+    sqlx::query_as!(
+        NewsletterIssue,
+        r#"
+    SELECT
+        i.newsletter_issue_id AS id,
+        i.title,
+        i.status,
+        i.scheduled_at,
+        i.published_at,
+        COUNT(q.subscriber_email) AS "pending_deliveries!"
+    FROM newsletter_issues i
+    LEFT JOIN issue_delivery_queue q ON q.newsletter_issue_id = i.newsletter_issue_id
+    GROUP BY i.newsletter_issue_id
+    ORDER BY COALESCE(i.published_at, i.scheduled_at, i.created_at) DESC
+    "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the newsletter issues.")
+}
+
+#[tracing::instrument(name = "Get a newsletter issue", skip(pool))]
+pub async fn get_issue(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<Option<IssueContent>, anyhow::Error> {
+    sqlx::query_as!(
+        IssueContent,
+        r#"
+    SELECT
+        newsletter_issue_id AS id,
+        title,
+        text_content,
+        html_content,
+        content_markdown,
+        status,
+        scheduled_at
+    FROM newsletter_issues
+    WHERE newsletter_issue_id = $1
+    "#,
+        issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the newsletter issue.")
+}
+
+/// Store the issue and queue a delivery to every confirmed subscriber, as
+/// part of `transaction` so that it commits along with the idempotency record.
+#[tracing::instrument(name = "Publish a newsletter issue", skip_all, fields(title = %issue.title))]
+pub async fn publish_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue: &NewIssue<'_>,
+) -> Result<Uuid, anyhow::Error> {
+    let issue_id = insert_newsletter_issue(transaction, issue, "sending")
+        .await
+        .context("Failed to store newsletter issue details")?;
+    start_sending(transaction, issue_id).await?;
+    Ok(issue_id)
+}
+
+/// Store an issue to finish later, none of its fields is required yet.
+#[tracing::instrument(name = "Create a newsletter draft", skip_all, fields(title = %issue.title))]
+pub async fn create_draft(pool: &PgPool, issue: &NewIssue<'_>) -> Result<Uuid, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let issue_id = insert_newsletter_issue(&mut transaction, issue, "draft")
+        .await
+        .context("Failed to store the newsletter draft.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a newsletter draft.")?;
+    Ok(issue_id)
+}
+
+#[tracing::instrument(name = "Update a newsletter draft", skip(pool, issue))]
+pub async fn update_draft(
+    pool: &PgPool,
+    issue_id: Uuid,
+    issue: &NewIssue<'_>,
+) -> Result<(), IssueStateError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    lock_draft(&mut transaction, issue_id).await?;
+    let body = issue.body();
+    let query = sqlx::query!(
+        r#"
+    UPDATE newsletter_issues
+    SET title = $2, text_content = $3, html_content = $4, content_markdown = $5
+    WHERE newsletter_issue_id = $1
+    "#,
+        issue_id,
+        issue.title,
+        body.text,
+        body.html,
+        issue.markdown()
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to update the newsletter draft.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to update a newsletter draft.")?;
+    Ok(())
+}
+
+/// Have the scheduler publish a complete draft at `scheduled_at`, see
+/// `publish_due_issues`.
+#[tracing::instrument(name = "Schedule a newsletter issue", skip(pool))]
+pub async fn schedule_issue(
+    pool: &PgPool,
+    issue_id: Uuid,
+    scheduled_at: DateTime<Utc>,
+) -> Result<(), IssueStateError> {
+    if scheduled_at <= Utc::now() {
+        return Err(IssueStateError::SendTimeInThePast);
+    }
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    lock_draft(&mut transaction, issue_id)
+        .await?
+        .ensure_complete()?;
+    let query = sqlx::query!(
+        r#"
+    UPDATE newsletter_issues
+    SET status = 'scheduled', scheduled_at = $2
+    WHERE newsletter_issue_id = $1
+    "#,
+        issue_id,
+        scheduled_at
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to schedule the newsletter issue.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to schedule a newsletter issue.")?;
+    Ok(())
+}
+
+/// Turn a scheduled issue back into a draft. Too late once the scheduler
+/// picked it up, or once its send time has passed.
+#[tracing::instrument(name = "Cancel a scheduled newsletter issue", skip(pool))]
+pub async fn cancel_scheduled_issue(pool: &PgPool, issue_id: Uuid) -> Result<(), IssueStateError> {
+    // Waits for `publish_due_issues` if it holds the row, and then sees
+    // that the issue is no longer scheduled.
+    let n_updated = sqlx::query!(
+        r#"
+    UPDATE newsletter_issues
+    SET status = 'draft', scheduled_at = NULL
+    WHERE newsletter_issue_id = $1 AND status = 'scheduled' AND scheduled_at > now()
+    "#,
+        issue_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to cancel the scheduled newsletter issue.")?
+    .rows_affected();
+    if n_updated == 0 {
+        return Err(match get_issue(pool, issue_id).await? {
+            Some(_) => IssueStateError::NotScheduled,
+            None => IssueStateError::UnknownIssue,
+        });
+    }
+    Ok(())
+}
+
+/// Queue the deliveries of a complete draft right away.
+#[tracing::instrument(name = "Publish a newsletter draft", skip(pool))]
+pub async fn publish_draft(pool: &PgPool, issue_id: Uuid) -> Result<(), IssueStateError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    lock_draft(&mut transaction, issue_id)
+        .await?
+        .ensure_complete()?;
+    start_sending(&mut transaction, issue_id).await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to publish a newsletter draft.")?;
+    Ok(())
+}
+
+/// Queue the deliveries of every scheduled issue whose send time has come,
+/// returning how many were published.
+#[tracing::instrument(name = "Publish due newsletter issues", skip(pool), err)]
+pub async fn publish_due_issues(pool: &PgPool) -> Result<usize, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let due = sqlx::query_scalar!(
+        r#"
+    SELECT newsletter_issue_id
+    FROM newsletter_issues
+    WHERE status = 'scheduled' AND scheduled_at <= now()
+    FOR UPDATE
+    SKIP LOCKED
+    "#
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .context("Failed to retrieve the due newsletter issues.")?;
+    for issue_id in &due {
+        start_sending(&mut transaction, *issue_id).await?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to publish due newsletter issues.")?;
+    Ok(due.len())
+}
+
+struct LockedDraft {
+    title: String,
+    text_content: String,
+    html_content: String,
+    status: String,
+}
+
+impl LockedDraft {
+    /// Drafts can be saved half-written, but not scheduled or published.
+    fn ensure_complete(&self) -> Result<(), IssueStateError> {
+        // The stored bodies are already rendered.
+        let errors = NewIssue {
+            title: &self.title,
+            content_markdown: "",
+            content_text: &self.text_content,
+            content_html: &self.html_content,
+        }
+        .validation_errors();
+        if !errors.is_empty() {
+            return Err(IssueStateError::Incomplete(errors));
+        }
+        Ok(())
+    }
+}
+
+/// Lock the issue for the rest of `transaction`, making sure it is a draft.
+async fn lock_draft(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue_id: Uuid,
+) -> Result<LockedDraft, IssueStateError> {
+    let draft = sqlx::query_as!(
+        LockedDraft,
+        r#"
+    SELECT title, text_content, html_content, status
+    FROM newsletter_issues
+    WHERE newsletter_issue_id = $1
+    FOR UPDATE
+    "#,
+        issue_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to retrieve the newsletter issue.")?
+    .ok_or(IssueStateError::UnknownIssue)?;
+    if draft.status != "draft" {
+        return Err(IssueStateError::NotADraft);
+    }
+    Ok(draft)
+}
+
+#[tracing::instrument(skip_all)]
+async fn insert_newsletter_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue: &NewIssue<'_>,
+    status: &str,
+) -> Result<Uuid, sqlx::Error> {
+    let newsletter_issue_id = Uuid::new_v4();
+    let now = Utc::now();
+    let body = issue.body();
+    let query = sqlx::query!(
+        r#"
+    INSERT INTO newsletter_issues (
+        newsletter_issue_id,
+        title,
+        text_content,
+        html_content,
+        content_markdown,
+        status,
+        published_at,
+        created_at
+    )
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+    "#,
+        newsletter_issue_id,
+        issue.title,
+        body.text,
+        body.html,
+        issue.markdown(),
+        status,
+        (status == "sending").then_some(now),
+        now
+    );
+    transaction.execute(query).await?;
+    Ok(newsletter_issue_id)
+}
+
+/// Queue a delivery to every confirmed subscriber and mark the issue as
+/// `sending`, or straight away as `sent` if there is nobody to send it to.
+#[tracing::instrument(skip(transaction))]
+async fn start_sending(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+    INSERT INTO issue_delivery_queue (
+        newsletter_issue_id,
+        subscriber_email
+    )
+    SELECT $1, email
+    FROM subscriptions
+    WHERE status = 'confirmed'
+    "#,
+        newsletter_issue_id,
+    );
+    let n_enqueued = transaction
+        .execute(query)
+        .await
+        .context("Failed to enqueue delivery tasks")?
+        .rows_affected();
+    let query = sqlx::query!(
+        r#"
+    UPDATE newsletter_issues
+    SET
+        status = $2,
+        scheduled_at = NULL,
+        published_at = COALESCE(published_at, now())
+    WHERE newsletter_issue_id = $1
+    "#,
+        newsletter_issue_id,
+        if n_enqueued == 0 { "sent" } else { "sending" }
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to update the newsletter issue status.")?;
+    Ok(())
+}
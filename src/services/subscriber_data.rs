@@ -0,0 +1,263 @@
+//! src/services/subscriber_data.rs
+//! Access and erasure requests from subscribers, about the data we hold on them.
+use super::subscriber_import::{
+    ImportReportMention, import_report_mentions, redact_import_reports,
+};
+use super::subscribers::{Subscriber, delete_subscriber_rows, get_subscriber};
+use crate::domain::{DataRequestToken, SubscriberEmail};
+use crate::email_client::EmailTransport;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use secrecy::Secret;
+use sha2::{Digest, Sha256};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Where data request emails send subscribers, and how long the link in them works.
+pub struct DataRequestLinkSettings<'a> {
+    pub base_url: &'a str,
+    pub hmac_secret: &'a Secret<String>,
+    pub token_expiration: Duration,
+}
+
+/// Everything we store about a subscriber.
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct SubscriberData {
+    pub subscriber: Subscriber,
+    pub subscription_tokens: Vec<SubscriptionTokenRecord>,
+    /// Issues sent to the subscriber, or that could not be.
+    pub deliveries: Vec<DeliveryRecord>,
+    /// Issues published but not delivered yet.
+    pub pending_deliveries: Vec<PendingDelivery>,
+    /// Rows of subscriber imports that were not imported as planned.
+    pub import_reports: Vec<ImportReportMention>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct SubscriptionTokenRecord {
+    pub subscription_token: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct DeliveryRecord {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    /// `delivered` or `failed`.
+    pub outcome: String,
+    pub attempted_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct PendingDelivery {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub n_retries: i32,
+    pub execute_after: DateTime<Utc>,
+}
+
+/// Email `email` a link to download or erase their data. Does nothing if the
+/// address is not on the list.
+#[tracing::instrument(
+    name = "Send a data request email",
+    skip(pool, email_client, links, email),
+    fields(subscriber_email = %email.as_ref())
+)]
+pub async fn send_data_request_email(
+    pool: &PgPool,
+    email_client: &dyn EmailTransport,
+    links: &DataRequestLinkSettings<'_>,
+    email: &SubscriberEmail,
+) -> Result<(), anyhow::Error> {
+    let subscriber_id = sqlx::query_scalar!(
+        r#"SELECT id FROM subscriptions WHERE email = $1"#,
+        email.as_ref()
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up the subscriber.")?;
+    let Some(subscriber_id) = subscriber_id else {
+        return Ok(());
+    };
+    let expires_at = Utc::now()
+        + chrono::Duration::from_std(links.token_expiration)
+            .expect("The token expiration is out of range.");
+    let token = DataRequestToken::generate(subscriber_id, expires_at, links.hmac_secret);
+    let link = format!(
+        "{}/subscriptions/my-data?token={}",
+        links.base_url,
+        token.as_ref()
+    );
+    let text_content = format!(
+        "We received a request for the data we hold about this address.\n\
+        Visit {link} to download or erase it.\n\
+        If you did not ask for it, you can ignore this email."
+    );
+    let html_content = format!(
+        "We received a request for the data we hold about this address.<br/>\
+        <a href=\"{link}\">Click here</a> to download or erase it.<br/>\
+        If you did not ask for it, you can ignore this email."
+    );
+    email_client
+        .send_email(email, "Your data", &html_content, &text_content)
+        .await
+        .context("Failed to send the data request email.")?;
+    Ok(())
+}
+
+/// Everything we store about a subscriber, `None` if there is no such
+/// subscriber. The access is recorded in the audit log.
+#[tracing::instrument(name = "Export the data of a subscriber", skip(pool))]
+pub async fn get_subscriber_data(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriberData>, anyhow::Error> {
+    let Some(subscriber) = get_subscriber(pool, subscriber_id).await? else {
+        return Ok(None);
+    };
+    let subscription_tokens = sqlx::query_as!(
+        SubscriptionTokenRecord,
+        r#"
+    SELECT subscription_token, created_at, expires_at
+    FROM subscription_tokens
+    WHERE subscriber_id = $1
+    ORDER BY created_at
+    "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the subscription tokens.")?;
+    let deliveries = sqlx::query_as!(
+        DeliveryRecord,
+        r#"
+    SELECT d.newsletter_issue_id, i.title, d.outcome, d.attempted_at
+    FROM newsletter_deliveries d
+    JOIN newsletter_issues i ON i.newsletter_issue_id = d.newsletter_issue_id
+    WHERE d.subscriber_id = $1
+    ORDER BY d.attempted_at
+    "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the delivery history.")?;
+    let pending_deliveries = sqlx::query_as!(
+        PendingDelivery,
+        r#"
+    SELECT q.newsletter_issue_id, i.title, q.n_retries, q.execute_after
+    FROM issue_delivery_queue q
+    JOIN newsletter_issues i ON i.newsletter_issue_id = q.newsletter_issue_id
+    WHERE q.subscriber_email = $1
+    ORDER BY i.published_at
+    "#,
+        subscriber.email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the pending deliveries.")?;
+    let import_reports = import_report_mentions(pool, &subscriber.email).await?;
+    record_audit_entry(pool, "subscriber_data_exported", subscriber_id).await?;
+    Ok(Some(SubscriberData {
+        subscriber,
+        subscription_tokens,
+        deliveries,
+        pending_deliveries,
+        import_reports,
+    }))
+}
+
+/// Delete a subscriber and everything that refers to them, and remove them
+/// from the import reports. Only a hash of the address is kept, so that it is
+/// never mailed again, see `is_suppressed`.
+///
+/// Returns `false` if there is no such subscriber.
+#[tracing::instrument(name = "Erase a subscriber", skip(pool))]
+pub async fn erase_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let Some(email) = delete_subscriber_rows(&mut transaction, subscriber_id).await? else {
+        return Ok(false);
+    };
+    redact_import_reports(&mut transaction, &email).await?;
+    let query = sqlx::query!(
+        r#"
+    INSERT INTO suppressed_emails (email_hash, suppressed_at)
+    VALUES ($1, now())
+    ON CONFLICT DO NOTHING
+    "#,
+        suppression_hash(&email)
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to suppress the email address.")?;
+    record_audit_entry(&mut *transaction, "subscriber_erased", subscriber_id).await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to erase a subscriber.")?;
+    Ok(true)
+}
+
+/// Whether `email` belonged to a subscriber who asked to be erased.
+pub(super) async fn is_suppressed(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &str,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM suppressed_emails WHERE email_hash = $1) AS "suppressed!""#,
+        suppression_hash(email)
+    )
+    .fetch_one(&mut **transaction)
+    .await
+}
+
+/// Addresses differing only in case reach the same inbox.
+pub(super) fn suppression_hash(email: &str) -> String {
+    hex::encode(Sha256::digest(email.to_lowercase().as_bytes()))
+}
+
+async fn record_audit_entry<'c, E>(
+    executor: E,
+    action: &str,
+    subject_id: Uuid,
+) -> Result<(), anyhow::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query!(
+        r#"
+    INSERT INTO audit_log (audit_id, action, subject_id, created_at)
+    VALUES ($1, $2, $3, now())
+    "#,
+        Uuid::new_v4(),
+        action,
+        subject_id
+    )
+    .execute(executor)
+    .await
+    .context("Failed to write the audit log entry.")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::suppression_hash;
+
+    #[test]
+    fn the_suppression_hash_ignores_case() {
+        assert_eq!(
+            suppression_hash("Ursula@Example.com"),
+            suppression_hash("ursula@example.com")
+        );
+        assert_ne!(
+            suppression_hash("ursula@example.com"),
+            suppression_hash("octavia@example.com")
+        );
+    }
+}
@@ -0,0 +1,623 @@
+//! src/services/subscriber_import.rs
+use super::subscriber_data::suppression_hash;
+use super::subscribers::{generate_subscription_token, send_confirmation_email};
+use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+use crate::email_client::EmailTransport;
+use crate::routes::error_chain_fmt;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// What imported subscribers start as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// They already confirmed with the previous provider.
+    Confirmed,
+    /// They get a confirmation email, as if they had subscribed themselves.
+    DoubleOptIn,
+}
+
+impl ImportMode {
+    pub const ALL: [ImportMode; 2] = [ImportMode::DoubleOptIn, ImportMode::Confirmed];
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "confirmed" => Ok(Self::Confirmed),
+            "double_opt_in" => Ok(Self::DoubleOptIn),
+            other => Err(format!("{other} is not a valid import mode.")),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Confirmed => "confirmed",
+            Self::DoubleOptIn => "double_opt_in",
+        }
+    }
+}
+
+/// Why a row of the file did not make it in as planned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportOutcome {
+    /// The name or the email is invalid.
+    Rejected,
+    /// The email is already on the list, or earlier in the file.
+    Skipped,
+    /// Imported, but still waiting for a confirmation email.
+    EmailNotSent,
+}
+
+impl ImportOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Rejected => "rejected",
+            Self::Skipped => "skipped",
+            Self::EmailNotSent => "email_not_sent",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ReportEntry {
+    /// In the uploaded file, the header being line 1.
+    pub line: u64,
+    pub name: String,
+    pub email: String,
+    pub outcome: ImportOutcome,
+    pub reason: String,
+}
+
+pub struct ImportSummary {
+    pub import_id: Uuid,
+    pub imported: usize,
+    pub entries: Vec<ReportEntry>,
+    /// For `send_import_confirmations`, empty unless the mode asks for them.
+    pub confirmations: Vec<PendingConfirmation>,
+}
+
+impl ImportSummary {
+    pub fn count(&self, outcome: ImportOutcome) -> usize {
+        self.entries.iter().filter(|e| e.outcome == outcome).count()
+    }
+}
+
+#[derive(Error)]
+pub enum ImportError {
+    /// Nothing was imported.
+    #[error("{0}")]
+    InvalidFile(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(f, self)
+    }
+}
+
+/// A confirmation email to send to an imported subscriber.
+pub struct PendingConfirmation {
+    row: ValidRow,
+    token: String,
+}
+
+/// A row that passed validation.
+struct ValidRow {
+    line: u64,
+    subscriber: NewSubscriber,
+}
+
+/// Read a CSV file with a `name` and an `email` column, in any order, and
+/// run every row through the same validation as `POST /subscriptions`.
+///
+/// Rows that fail it, or repeat an earlier email, are reported rather than
+/// failing the whole file.
+fn read_rows(data: &[u8]) -> Result<(Vec<ValidRow>, Vec<ReportEntry>), ImportError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(data);
+    let headers = reader
+        .headers()
+        .map_err(|e| ImportError::InvalidFile(format!("The file could not be read: {e}.")))?
+        .clone();
+    let column = |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+    let (Some(name_column), Some(email_column)) = (column("name"), column("email")) else {
+        return Err(ImportError::InvalidFile(
+            "The first line must name the columns, with a name and an email column.".into(),
+        ));
+    };
+
+    let mut rows = Vec::new();
+    let mut entries = Vec::new();
+    // Lowercased, as two addresses differing only in case reach the same inbox.
+    let mut seen: HashMap<String, u64> = HashMap::new();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                entries.push(ReportEntry {
+                    line: e.position().map_or(0, |p| p.line()),
+                    name: String::new(),
+                    email: String::new(),
+                    outcome: ImportOutcome::Rejected,
+                    reason: format!("The row could not be read: {e}."),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |p| p.line());
+        let name = record.get(name_column).unwrap_or_default().to_string();
+        let email = record.get(email_column).unwrap_or_default().to_string();
+        let validated = SubscriberName::parse(name.clone()).and_then(|name| {
+            let email = SubscriberEmail::parse(email.clone())?;
+            Ok(NewSubscriber { name, email })
+        });
+        let (outcome, reason) = match validated {
+            Ok(subscriber) => match seen.get(&email.to_lowercase()) {
+                Some(first_line) => (
+                    ImportOutcome::Skipped,
+                    format!("Same email as line {first_line}."),
+                ),
+                None => {
+                    seen.insert(email.to_lowercase(), line);
+                    rows.push(ValidRow { line, subscriber });
+                    continue;
+                }
+            },
+            Err(e) => (ImportOutcome::Rejected, e),
+        };
+        entries.push(ReportEntry {
+            line,
+            name,
+            email,
+            outcome,
+            reason,
+        });
+    }
+    Ok((rows, entries))
+}
+
+/// Import every valid row of a CSV file in a single transaction. Addresses
+/// already on the list are left alone, whatever their status.
+///
+/// The confirmation emails that `mode` may ask for are not sent yet, a large
+/// file would keep the caller waiting: see `send_import_confirmations`.
+/// The report of the rows that were not imported is kept, see `get_import_report`.
+#[tracing::instrument(name = "Import subscribers", skip(pool, token_expiration, data))]
+pub async fn import_subscribers(
+    pool: &PgPool,
+    token_expiration: std::time::Duration,
+    user_id: Uuid,
+    mode: ImportMode,
+    data: &[u8],
+) -> Result<ImportSummary, ImportError> {
+    let (rows, mut entries) = read_rows(data)?;
+    let now = Utc::now();
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let hashes: Vec<String> = rows
+        .iter()
+        .map(|r| suppression_hash(r.subscriber.email.as_ref()))
+        .collect();
+    let suppressed: HashSet<String> = sqlx::query_scalar!(
+        r#"SELECT email_hash FROM suppressed_emails WHERE email_hash = ANY($1)"#,
+        &hashes
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .context("Failed to look up the suppressed addresses.")?
+    .into_iter()
+    .collect();
+    let (erased, rows): (Vec<_>, Vec<_>) = rows
+        .into_iter()
+        .zip(hashes)
+        .partition(|(_, hash)| suppressed.contains(hash));
+    let rows: Vec<ValidRow> = rows.into_iter().map(|(row, _)| row).collect();
+    let ids: Vec<Uuid> = rows.iter().map(|_| Uuid::new_v4()).collect();
+    let emails: Vec<String> = rows
+        .iter()
+        .map(|r| r.subscriber.email.as_ref().to_owned())
+        .collect();
+    let names: Vec<String> = rows
+        .iter()
+        .map(|r| r.subscriber.name.as_ref().to_owned())
+        .collect();
+    let status = match mode {
+        ImportMode::Confirmed => "confirmed",
+        ImportMode::DoubleOptIn => "pending_confirmation",
+    };
+
+    let inserted: HashSet<Uuid> = sqlx::query_scalar!(
+        r#"
+    INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+    SELECT id, email, name, $4, $5
+    FROM UNNEST($1::uuid[], $2::text[], $3::text[]) AS t(id, email, name)
+    ON CONFLICT (email) DO NOTHING
+    RETURNING id
+    "#,
+        &ids,
+        &emails,
+        &names,
+        now,
+        status
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .context("Failed to insert the imported subscribers.")?
+    .into_iter()
+    .collect();
+
+    for (row, _) in &erased {
+        // The report must not bring the address back.
+        entries.push(ReportEntry {
+            line: row.line,
+            name: String::new(),
+            email: String::new(),
+            outcome: ImportOutcome::Skipped,
+            reason: "The owner of this address asked for their data to be erased.".into(),
+        });
+    }
+    let mut confirmations = Vec::new();
+    let mut subscriber_ids = Vec::new();
+    for (row, id) in rows.into_iter().zip(&ids) {
+        if !inserted.contains(id) {
+            entries.push(report_entry(
+                &row,
+                ImportOutcome::Skipped,
+                "Already on the list.",
+            ));
+        } else if mode == ImportMode::DoubleOptIn {
+            subscriber_ids.push(*id);
+            confirmations.push(PendingConfirmation {
+                row,
+                token: generate_subscription_token(),
+            });
+        }
+    }
+    if !confirmations.is_empty() {
+        let tokens: Vec<String> = confirmations.iter().map(|c| c.token.clone()).collect();
+        let expires_at = now
+            + chrono::Duration::from_std(token_expiration)
+                .expect("The token expiration is out of range.");
+        sqlx::query!(
+            r#"
+    INSERT INTO subscription_tokens (subscription_token, subscriber_id, created_at, expires_at)
+    SELECT token, subscriber_id, $3, $4
+    FROM UNNEST($1::text[], $2::uuid[]) AS t(token, subscriber_id)
+    "#,
+            &tokens,
+            &subscriber_ids,
+            now,
+            expires_at
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to insert the subscription tokens of the imported subscribers.")?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to import subscribers.")?;
+
+    entries.sort_by_key(|e| e.line);
+    let import_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+    INSERT INTO subscriber_imports (import_id, user_id, mode, imported_count, report, created_at)
+    VALUES ($1, $2, $3, $4, $5, now())
+    "#,
+        import_id,
+        user_id,
+        mode.as_str(),
+        inserted.len() as i32,
+        write_report(&entries, true)?
+    )
+    .execute(pool)
+    .await
+    .context("Failed to save the import report.")?;
+    Ok(ImportSummary {
+        import_id,
+        imported: inserted.len(),
+        entries,
+        confirmations,
+    })
+}
+
+/// Send the confirmation emails of an import, once it is committed so that
+/// the links work by the time the emails arrive. The rows whose email could
+/// not be sent are added to the report of the import.
+#[tracing::instrument(
+    name = "Send the confirmation emails of an import",
+    skip(pool, email_client, base_url, confirmations),
+    fields(n_emails = confirmations.len())
+)]
+pub async fn send_import_confirmations(
+    pool: &PgPool,
+    email_client: &dyn EmailTransport,
+    base_url: &str,
+    import_id: Uuid,
+    confirmations: Vec<PendingConfirmation>,
+) -> Result<(), anyhow::Error> {
+    let mut not_sent = Vec::new();
+    for PendingConfirmation { row, token } in confirmations {
+        if let Err(e) =
+            send_confirmation_email(email_client, &row.subscriber.email, base_url, &token).await
+        {
+            tracing::warn!(error.cause_chain = ?e, line = row.line, "Failed to send a confirmation email.");
+            not_sent.push(report_entry(
+                &row,
+                ImportOutcome::EmailNotSent,
+                "The confirmation email could not be sent, resend it from the subscribers page.",
+            ));
+        }
+    }
+    if not_sent.is_empty() {
+        return Ok(());
+    }
+    sqlx::query!(
+        r#"UPDATE subscriber_imports SET report = report || $2 WHERE import_id = $1"#,
+        import_id,
+        write_report(&not_sent, false)?
+    )
+    .execute(pool)
+    .await
+    .context("Failed to add the unsent emails to the import report.")?;
+    Ok(())
+}
+
+fn report_entry(row: &ValidRow, outcome: ImportOutcome, reason: &str) -> ReportEntry {
+    ReportEntry {
+        line: row.line,
+        name: row.subscriber.name.as_ref().to_owned(),
+        email: row.subscriber.email.as_ref().to_owned(),
+        outcome,
+        reason: reason.to_owned(),
+    }
+}
+
+/// Without the header, the rows can be appended to an existing report.
+fn write_report(entries: &[ReportEntry], with_header: bool) -> Result<String, anyhow::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    if with_header {
+        writer.write_record(["line", "name", "email", "outcome", "reason"])?;
+    }
+    for e in entries {
+        writer.write_record([
+            e.line.to_string().as_str(),
+            &e.name,
+            &e.email,
+            e.outcome.as_str(),
+            &e.reason,
+        ])?;
+    }
+    let report = writer
+        .into_inner()
+        .context("Failed to write the import report.")?;
+    Ok(String::from_utf8(report)?)
+}
+
+/// The report of an import run by `user_id`, as a CSV file.
+#[tracing::instrument(name = "Get an import report", skip(pool))]
+pub async fn get_import_report(
+    pool: &PgPool,
+    user_id: Uuid,
+    import_id: Uuid,
+) -> Result<Option<String>, anyhow::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT report FROM subscriber_imports WHERE import_id = $1 AND user_id = $2"#,
+        import_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the import report.")
+}
+
+/// A row of an import report about a given subscriber.
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ImportReportMention {
+    pub import_id: Uuid,
+    pub imported_at: DateTime<Utc>,
+    pub line: String,
+    pub name: String,
+    pub outcome: String,
+    pub reason: String,
+}
+
+struct StoredReport {
+    import_id: Uuid,
+    created_at: DateTime<Utc>,
+    report: String,
+}
+
+/// The reports with a row about `email`, and likely a few more: the filter is
+/// only a substring match.
+async fn reports_mentioning<'c, E>(
+    executor: E,
+    email: &str,
+) -> Result<Vec<StoredReport>, anyhow::Error>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    sqlx::query_as!(
+        StoredReport,
+        r#"
+    SELECT import_id, created_at, report
+    FROM subscriber_imports
+    WHERE strpos(lower(report), lower($1)) > 0
+    ORDER BY created_at
+    "#,
+        email
+    )
+    .fetch_all(executor)
+    .await
+    .context("Failed to retrieve the import reports.")
+}
+
+/// The column of the email in the reports written by `write_report`.
+const REPORT_EMAIL_COLUMN: usize = 2;
+
+/// Every row about `email` across the import reports.
+pub(super) async fn import_report_mentions(
+    pool: &PgPool,
+    email: &str,
+) -> Result<Vec<ImportReportMention>, anyhow::Error> {
+    let mut mentions = Vec::new();
+    for stored in reports_mentioning(pool, email).await? {
+        let mut reader = csv::Reader::from_reader(stored.report.as_bytes());
+        for record in reader.records() {
+            let record = record.context("Failed to read an import report.")?;
+            if record
+                .get(REPORT_EMAIL_COLUMN)
+                .is_some_and(|e| e.eq_ignore_ascii_case(email))
+            {
+                let field = |i: usize| record.get(i).unwrap_or_default().to_owned();
+                mentions.push(ImportReportMention {
+                    import_id: stored.import_id,
+                    imported_at: stored.created_at,
+                    line: field(0),
+                    name: field(1),
+                    outcome: field(3),
+                    reason: field(4),
+                });
+            }
+        }
+    }
+    Ok(mentions)
+}
+
+/// Blank out the name and the email of the rows about `email` in the import
+/// reports, keeping the rows themselves so the counts still add up.
+pub(super) async fn redact_import_reports(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &str,
+) -> Result<(), anyhow::Error> {
+    for stored in reports_mentioning(&mut **transaction, email).await? {
+        let Some(report) = redact_report(&stored.report, email)? else {
+            continue;
+        };
+        let query = sqlx::query!(
+            r#"UPDATE subscriber_imports SET report = $1 WHERE import_id = $2"#,
+            report,
+            stored.import_id
+        );
+        transaction
+            .execute(query)
+            .await
+            .context("Failed to update an import report.")?;
+    }
+    Ok(())
+}
+
+/// `None` if no row is about `email`.
+fn redact_report(report: &str, email: &str) -> Result<Option<String>, anyhow::Error> {
+    let mut reader = csv::Reader::from_reader(report.as_bytes());
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(reader.headers()?)?;
+    let mut redacted = false;
+    for record in reader.records() {
+        let record = record?;
+        if record
+            .get(REPORT_EMAIL_COLUMN)
+            .is_some_and(|e| e.eq_ignore_ascii_case(email))
+        {
+            redacted = true;
+            let fields: Vec<&str> = record
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    if i == 1 || i == REPORT_EMAIL_COLUMN {
+                        ""
+                    } else {
+                        field
+                    }
+                })
+                .collect();
+            writer.write_record(fields)?;
+        } else {
+            writer.write_record(&record)?;
+        }
+    }
+    if !redacted {
+        return Ok(None);
+    }
+    let report = writer
+        .into_inner()
+        .context("Failed to write the import report.")?;
+    Ok(Some(String::from_utf8(report)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ImportMode, ImportOutcome, read_rows, redact_report};
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn import_modes_round_trip_through_their_string_representation() {
+        for mode in ImportMode::ALL {
+            assert_ok_eq!(ImportMode::parse(mode.as_str()), mode);
+        }
+        assert_err!(ImportMode::parse("pending"));
+    }
+
+    #[test]
+    fn columns_are_found_by_name() {
+        let (rows, entries) = read_rows(b"Email,Name\nursula@example.com,Ursula\n").unwrap();
+        assert!(entries.is_empty());
+        assert_eq!(rows[0].subscriber.name.as_ref(), "Ursula");
+        assert_eq!(rows[0].subscriber.email.as_ref(), "ursula@example.com");
+        assert_eq!(rows[0].line, 2);
+    }
+
+    #[test]
+    fn a_file_without_a_name_and_an_email_column_is_invalid() {
+        assert!(read_rows(b"ursula@example.com,Ursula\n").is_err());
+        assert!(read_rows(b"").is_err());
+    }
+
+    #[test]
+    fn invalid_rows_are_rejected_with_the_reason() {
+        let data = b"name,email\nUrsula,not-an-email\n,octavia@example.com\nOctavia\n";
+        let (rows, entries) = read_rows(data).unwrap();
+        assert!(rows.is_empty());
+        assert_eq!(entries.len(), 3);
+        assert!(entries.iter().all(|e| e.outcome == ImportOutcome::Rejected));
+        assert_eq!(entries[0].line, 2);
+        assert_eq!(
+            entries[0].reason,
+            "not-an-email is an invalid email address."
+        );
+    }
+
+    #[test]
+    fn repeated_emails_are_skipped() {
+        let data = b"name,email\nUrsula,ursula@example.com\nUrsula,URSULA@example.com\n";
+        let (rows, entries) = read_rows(data).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(entries[0].outcome, ImportOutcome::Skipped);
+        assert_eq!(entries[0].reason, "Same email as line 2.");
+    }
+
+    #[test]
+    fn only_the_rows_about_the_email_are_redacted() {
+        let report = "line,name,email,outcome,reason\n\
+            2,Ursula,ursula@example.com,skipped,Already on the list.\n\
+            3,Octavia,octavia@example.com,skipped,Already on the list.\n";
+        assert_eq!(
+            redact_report(report, "URSULA@example.com")
+                .unwrap()
+                .unwrap(),
+            "line,name,email,outcome,reason\n\
+            2,,,skipped,Already on the list.\n\
+            3,Octavia,octavia@example.com,skipped,Already on the list.\n"
+        );
+        assert!(redact_report(report, "ursula@example").unwrap().is_none());
+    }
+}
@@ -0,0 +1,751 @@
+//! src/services/subscribers.rs
+use super::subscriber_data::is_suppressed;
+use crate::domain::{NewSubscriber, SubscriberEmail};
+use crate::email_client::{EmailTransport, SendEmailError};
+use crate::routes::error_chain_fmt;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use futures_util::{Stream, StreamExt};
+use rand::distributions::Alphanumeric;
+use rand::{Rng, thread_rng};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use std::time::Duration;
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(serde::Serialize, utoipa::ToSchema, Debug)]
+pub struct Subscriber {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub status: String,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+/// Where confirmation emails send subscribers, and how long the link in them works.
+pub struct ConfirmationLinkSettings<'a> {
+    pub base_url: &'a str,
+    pub token_expiration: Duration,
+}
+
+#[derive(Error)]
+pub enum SubscribeError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error(
+        "The owner of this address asked for their data to be erased, it cannot be subscribed again."
+    )]
+    Suppressed,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for SubscribeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(f, self)
+    }
+}
+
+#[derive(Error)]
+pub enum ConfirmationError {
+    #[error("There is no subscriber associated with the provided token.")]
+    UnknownToken,
+    #[error("The subscription token has expired, please request a new confirmation email.")]
+    ExpiredToken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ConfirmationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(f, self)
+    }
+}
+
+#[tracing::instrument(name = "Get subscriber", skip(pool))]
+pub async fn get_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<Subscriber>, anyhow::Error> {
+    sqlx::query_as!(
+        Subscriber,
+        r#"SELECT id, email, name, status, subscribed_at FROM subscriptions WHERE id = $1"#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the subscriber.")
+}
+
+/// Every value of `subscriptions.status`.
+pub const SUBSCRIBER_STATUSES: [&str; 3] = ["pending_confirmation", "confirmed", "unsubscribed"];
+
+/// Where a page of `search_subscribers` starts: right after the subscriber
+/// with this `subscribed_at` and `id`, newest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscriberCursor {
+    subscribed_at: DateTime<Utc>,
+    id: Uuid,
+}
+
+impl SubscriberCursor {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let invalid = || format!("{s} is not a valid cursor.");
+        let (micros, id) = s.split_once('_').ok_or_else(invalid)?;
+        let subscribed_at = micros
+            .parse()
+            .ok()
+            .and_then(DateTime::from_timestamp_micros)
+            .ok_or_else(invalid)?;
+        let id = Uuid::parse_str(id).map_err(|_| invalid())?;
+        Ok(Self { subscribed_at, id })
+    }
+}
+
+impl std::fmt::Display for SubscriberCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}_{}", self.subscribed_at.timestamp_micros(), self.id)
+    }
+}
+
+pub struct SubscriberQuery<'a> {
+    /// Matched anywhere in the email or the name, ignoring case.
+    pub search: Option<&'a str>,
+    /// One of `SUBSCRIBER_STATUSES`.
+    pub status: Option<&'a str>,
+    pub after: Option<SubscriberCursor>,
+    pub limit: i64,
+}
+
+pub struct SubscriberPage {
+    pub subscribers: Vec<Subscriber>,
+    /// `None` on the last page.
+    pub next: Option<SubscriberCursor>,
+}
+
+/// Keyset pagination over `(subscribed_at, id)`: pages stay consistent while
+/// subscribers come and go, and deep pages are as cheap as the first one.
+#[tracing::instrument(name = "Search subscribers", skip(pool, query))]
+pub async fn search_subscribers(
+    pool: &PgPool,
+    query: &SubscriberQuery<'_>,
+) -> Result<SubscriberPage, anyhow::Error> {
+    let pattern = query.search.map(|s| format!("%{}%", escape_like(s)));
+    let mut subscribers = sqlx::query_as!(
+        Subscriber,
+        r#"
+    SELECT id, email, name, status, subscribed_at
+    FROM subscriptions
+    WHERE ($1::text IS NULL OR email ILIKE $1 OR name ILIKE $1)
+        AND ($2::text IS NULL OR status = $2)
+        AND ($3::timestamptz IS NULL OR (subscribed_at, id) < ($3, $4::uuid))
+    ORDER BY subscribed_at DESC, id DESC
+    LIMIT $5
+    "#,
+        pattern,
+        query.status,
+        query.after.map(|c| c.subscribed_at),
+        query.after.map(|c| c.id),
+        // One more to find out whether there is a next page.
+        query.limit + 1
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to search the subscribers.")?;
+    let next = if subscribers.len() as i64 > query.limit {
+        subscribers.truncate(query.limit as usize);
+        subscribers.last().map(|s| SubscriberCursor {
+            subscribed_at: s.subscribed_at,
+            id: s.id,
+        })
+    } else {
+        None
+    };
+    Ok(SubscriberPage { subscribers, next })
+}
+
+/// `%` and `_` in a search are matched literally.
+fn escape_like(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Confirm a pending subscriber without going through the confirmation
+/// link. Returns `false` if there is no pending subscriber with this id.
+#[tracing::instrument(name = "Confirm a subscriber manually", skip(pool))]
+pub async fn confirm_subscriber_manually(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let query = sqlx::query!(
+        r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1 AND status = 'pending_confirmation'"#,
+        subscriber_id
+    );
+    let n_updated = transaction
+        .execute(query)
+        .await
+        .context("Failed to update subscriber status to confirmed.")?
+        .rows_affected();
+    if n_updated == 0 {
+        return Ok(false);
+    }
+    // The links we sent are of no use anymore.
+    delete_subscription_tokens(&mut transaction, subscriber_id)
+        .await
+        .context("Failed to delete the subscription tokens.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to confirm a subscriber.")?;
+    Ok(true)
+}
+
+/// Returns `false` if there is no such subscriber, or if they already unsubscribed.
+#[tracing::instrument(name = "Unsubscribe a subscriber", skip(pool))]
+pub async fn unsubscribe_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE subscriptions SET status = 'unsubscribed' WHERE id = $1 AND status <> 'unsubscribed'"#,
+        subscriber_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to update subscriber status to unsubscribed.")?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Narrows down `stream_subscribers`, every field is optional.
+#[derive(Debug, Default)]
+pub struct SubscriberExportFilter {
+    /// One of `SUBSCRIBER_STATUSES`.
+    pub status: Option<String>,
+    /// Inclusive.
+    pub subscribed_from: Option<DateTime<Utc>>,
+    /// Exclusive.
+    pub subscribed_before: Option<DateTime<Utc>>,
+}
+
+/// Every subscriber matching `filter`, oldest first, read from the database
+/// as the stream is polled rather than loaded all at once.
+pub fn stream_subscribers<'a>(
+    pool: &'a PgPool,
+    filter: &'a SubscriberExportFilter,
+) -> impl Stream<Item = Result<Subscriber, anyhow::Error>> + 'a {
+    sqlx::query_as!(
+        Subscriber,
+        r#"
+    SELECT id, email, name, status, subscribed_at
+    FROM subscriptions
+    WHERE ($1::text IS NULL OR status = $1)
+        AND ($2::timestamptz IS NULL OR subscribed_at >= $2)
+        AND ($3::timestamptz IS NULL OR subscribed_at < $3)
+    ORDER BY subscribed_at, id
+    "#,
+        filter.status,
+        filter.subscribed_from,
+        filter.subscribed_before
+    )
+    .fetch(pool)
+    .map(|row| row.context("Failed to read a subscriber to export."))
+}
+
+/// Remove a subscriber along with their tokens and the issues still waiting
+/// to be delivered to them. Returns `false` if there is no such subscriber.
+#[tracing::instrument(name = "Delete subscriber", skip(pool))]
+pub async fn delete_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    if delete_subscriber_rows(&mut transaction, subscriber_id)
+        .await?
+        .is_none()
+    {
+        return Ok(false);
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to delete a subscriber.")?;
+    Ok(true)
+}
+
+/// The deletions of `delete_subscriber`, returning the email of the deleted
+/// subscriber if there was one.
+pub(super) async fn delete_subscriber_rows(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<Option<String>, anyhow::Error> {
+    delete_subscription_tokens(transaction, subscriber_id)
+        .await
+        .context("Failed to delete the subscription tokens.")?;
+    let query = sqlx::query!(
+        r#"DELETE FROM newsletter_deliveries WHERE subscriber_id = $1"#,
+        subscriber_id
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to delete the delivery history.")?;
+    let email = sqlx::query_scalar!(
+        r#"DELETE FROM subscriptions WHERE id = $1 RETURNING email"#,
+        subscriber_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to delete the subscriber.")?;
+    let Some(email) = email else {
+        return Ok(None);
+    };
+    let query = sqlx::query!(
+        r#"DELETE FROM issue_delivery_queue WHERE subscriber_email = $1"#,
+        email
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to delete the pending deliveries.")?;
+    Ok(Some(email))
+}
+
+/// A confirmation email whose token is stored, ready to be sent.
+pub struct ConfirmationEmail {
+    recipient: SubscriberEmail,
+    subscription_token: String,
+}
+
+impl ConfirmationEmail {
+    pub async fn send(
+        &self,
+        email_client: &dyn EmailTransport,
+        base_url: &str,
+    ) -> Result<(), SendEmailError> {
+        send_confirmation_email(
+            email_client,
+            &self.recipient,
+            base_url,
+            &self.subscription_token,
+        )
+        .await
+    }
+}
+
+pub struct Subscription {
+    pub subscriber_id: Uuid,
+    /// `None` if the address is already confirmed.
+    pub confirmation_email: Option<ConfirmationEmail>,
+}
+
+/// Start the double opt-in for `new_subscriber`. The confirmation email is
+/// left to the caller, to be sent off the request path: waiting for it would
+/// tell apart the addresses that get one.
+///
+/// An address that is already confirmed is left untouched and gets no email:
+/// callers must answer exactly as for a new address, so that the list cannot
+/// be probed. The same goes for `SubscribeError::Suppressed`, for an address
+/// that was erased.
+#[tracing::instrument(
+    name = "Adding a new subscriber",
+    skip(pool, token_expiration, new_subscriber),
+    fields(
+        subscriber_email = %new_subscriber.email.as_ref(),
+        subscriber_name = %new_subscriber.name.as_ref()
+    )
+)]
+pub async fn subscribe(
+    pool: &PgPool,
+    token_expiration: Duration,
+    new_subscriber: &NewSubscriber,
+) -> Result<Subscription, SubscribeError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire Postgres connection from the pool")?;
+    if is_suppressed(&mut transaction, new_subscriber.email.as_ref())
+        .await
+        .context("Failed to check whether the address is suppressed.")?
+    {
+        return Err(SubscribeError::Suppressed);
+    }
+    let subscriber_id = match insert_subscription(&mut transaction, new_subscriber)
+        .await
+        .context("Failed to insert new subscriber in the database.")?
+    {
+        Some(subscriber_id) => subscriber_id,
+        None => {
+            let existing = get_existing_subscription(&mut transaction, &new_subscriber.email)
+                .await
+                .context("Failed to fetch the existing subscriber from the database.")?;
+            match existing.status.as_str() {
+                "confirmed" => {
+                    return Ok(Subscription {
+                        subscriber_id: existing.id,
+                        confirmation_email: None,
+                    });
+                }
+                "unsubscribed" => {
+                    restart_double_opt_in(&mut transaction, existing.id, new_subscriber)
+                        .await
+                        .context("Failed to reset the subscriber status.")?;
+                }
+                _ => {
+                    // Still pending: rotate the token so only the latest email works.
+                    delete_subscription_tokens(&mut transaction, existing.id)
+                        .await
+                        .context("Failed to delete previous subscription tokens.")?;
+                }
+            }
+            existing.id
+        }
+    };
+    let subscription_token = generate_subscription_token();
+    insert_subscription_token(
+        &mut transaction,
+        &subscriber_id,
+        &subscription_token,
+        token_expiration,
+    )
+    .await
+    .context("Failed to insert subscription toke in the database.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a new subscriber.")?;
+    Ok(Subscription {
+        subscriber_id,
+        confirmation_email: Some(ConfirmationEmail {
+            recipient: new_subscriber.email.clone(),
+            subscription_token,
+        }),
+    })
+}
+
+/// Send a fresh confirmation link to a subscriber who lost (or let expire)
+/// the first one. Does nothing if `email` has no pending subscription.
+#[tracing::instrument(
+    name = "Resend a confirmation email",
+    skip(pool, email_client, links, email),
+    fields(subscriber_email = %email.as_ref())
+)]
+pub async fn resend_confirmation_email(
+    pool: &PgPool,
+    email_client: &dyn EmailTransport,
+    links: &ConfirmationLinkSettings<'_>,
+    email: &SubscriberEmail,
+) -> Result<(), SubscribeError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire Postgres connection from the pool")?;
+    let Some(subscriber_id) = get_pending_subscriber_id(&mut transaction, email)
+        .await
+        .context("Failed to look up the pending subscriber.")?
+    else {
+        return Ok(());
+    };
+    // Only the most recent link should work.
+    delete_subscription_tokens(&mut transaction, subscriber_id)
+        .await
+        .context("Failed to delete previous subscription tokens.")?;
+    let subscription_token = generate_subscription_token();
+    insert_subscription_token(
+        &mut transaction,
+        &subscriber_id,
+        &subscription_token,
+        links.token_expiration,
+    )
+    .await
+    .context("Failed to insert subscription token in the database.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a new subscription token.")?;
+    send_confirmation_email(email_client, email, links.base_url, &subscription_token)
+        .await
+        .context("Failed to send confirmation email.")?;
+    Ok(())
+}
+
+/// Confirm the subscriber `subscription_token` was sent to.
+#[tracing::instrument(name = "Confirm a subscription", skip_all)]
+pub async fn confirm_subscription(
+    pool: &PgPool,
+    subscription_token: &str,
+) -> Result<(), ConfirmationError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let token = get_token(&mut transaction, subscription_token)
+        .await
+        .context("Failed to get subscriber associated with the provided token.")?
+        .ok_or(ConfirmationError::UnknownToken)?;
+    if token.expires_at <= Utc::now() {
+        return Err(ConfirmationError::ExpiredToken);
+    }
+    confirm_subscriber(&mut transaction, token.subscriber_id)
+        .await
+        .context("Failed to update subscriber status to confirmed.")?;
+    // Tokens are single-use: once confirmed, none of the links we sent are needed anymore.
+    delete_subscription_tokens(&mut transaction, token.subscriber_id)
+        .await
+        .context("Failed to delete used subscription tokens.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to confirm a subscriber.")?;
+    Ok(())
+}
+
+pub(super) fn generate_subscription_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(25)
+        .collect()
+}
+
+/// Returns `None` if there already is a subscriber with the same email.
+#[tracing::instrument(
+    name = "Saving new subscriber details in database",
+    skip(transaction, new_subscriber)
+)]
+async fn insert_subscription(
+    transaction: &mut Transaction<'_, Postgres>,
+    new_subscriber: &NewSubscriber,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let subscriber_id = Uuid::new_v4();
+    let query = sqlx::query!(
+        r#"
+INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+VALUES ($1, $2, $3, $4, 'pending_confirmation')
+ON CONFLICT (email) DO NOTHING
+"#,
+        subscriber_id,
+        new_subscriber.email.as_ref(),
+        new_subscriber.name.as_ref(),
+        Utc::now()
+    );
+    let n_inserted_rows = transaction.execute(query).await?.rows_affected();
+
+    Ok((n_inserted_rows > 0).then_some(subscriber_id))
+}
+
+struct ExistingSubscription {
+    id: Uuid,
+    status: String,
+}
+
+#[tracing::instrument(name = "Get existing subscriber by email", skip_all)]
+async fn get_existing_subscription(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &SubscriberEmail,
+) -> Result<ExistingSubscription, sqlx::Error> {
+    sqlx::query_as!(
+        ExistingSubscription,
+        r#"SELECT id, status FROM subscriptions WHERE email = $1 FOR UPDATE"#,
+        email.as_ref()
+    )
+    .fetch_one(&mut **transaction)
+    .await
+}
+
+/// Put a subscriber who opted out back at the start of the double opt-in flow.
+#[tracing::instrument(
+    name = "Restart double opt-in for an unsubscribed subscriber",
+    skip(transaction, new_subscriber)
+)]
+async fn restart_double_opt_in(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    new_subscriber: &NewSubscriber,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+UPDATE subscriptions
+SET status = 'pending_confirmation', name = $2, subscribed_at = $3
+WHERE id = $1
+"#,
+        subscriber_id,
+        new_subscriber.name.as_ref(),
+        Utc::now()
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Get pending subscriber by email", skip_all)]
+async fn get_pending_subscriber_id(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &SubscriberEmail,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+    SELECT id
+    FROM subscriptions
+    WHERE email = $1 AND status = 'pending_confirmation'
+    FOR UPDATE
+    "#,
+        email.as_ref()
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+}
+
+#[tracing::instrument(
+    name = "Send confirmation email to new subscriber",
+    skip(email_client, recipient, base_url, subscription_token)
+)]
+pub(super) async fn send_confirmation_email(
+    email_client: &dyn EmailTransport,
+    recipient: &SubscriberEmail,
+    base_url: &str,
+    subscription_token: &str,
+) -> Result<(), SendEmailError> {
+    let confirmation_link = format!(
+        "{base_url}/subscriptions/confirm?subscription_token={}",
+        subscription_token
+    );
+    let text_content = format!(
+        "Welcome to our newsletter!\nVisit {} to confirm your subscription.",
+        confirmation_link
+    );
+    let html_content = format!(
+        "Welcome to our newsletter!<br/>\
+            <a href=\"{}\">Click here</a> to confirm your subscription.",
+        confirmation_link
+    );
+    email_client
+        .send_email(recipient, "Welcome!", &html_content, &text_content)
+        .await
+}
+
+#[tracing::instrument(
+    name = "Store subscription token in the database",
+    skip(transaction, subscriber_id, subscription_token)
+)]
+async fn insert_subscription_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: &Uuid,
+    subscription_token: &str,
+    expiration: Duration,
+) -> Result<(), StoreTokenError> {
+    let now = Utc::now();
+    let expires_at = now
+        + chrono::Duration::from_std(expiration).expect("The token expiration is out of range.");
+    let query = sqlx::query!(
+        r#"
+    INSERT INTO subscription_tokens (subscription_token, subscriber_id, created_at, expires_at)
+    VALUES ($1, $2, $3, $4)
+    "#,
+        subscription_token,
+        subscriber_id,
+        now,
+        expires_at
+    );
+    transaction.execute(query).await.map_err(StoreTokenError)?;
+    Ok(())
+}
+
+#[derive(Error)]
+#[error("A database error was encounter while trying to store a subscription token.")]
+pub struct StoreTokenError(#[source] sqlx::Error);
+
+impl std::fmt::Debug for StoreTokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(f, self)
+    }
+}
+
+struct SubscriptionToken {
+    subscriber_id: Uuid,
+    expires_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "Get subscriber_id from subscription token", skip_all)]
+async fn get_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    token: &str,
+) -> Result<Option<SubscriptionToken>, sqlx::Error> {
+    sqlx::query_as!(
+        SubscriptionToken,
+        r#"
+    SELECT subscriber_id, expires_at
+    FROM subscription_tokens
+    WHERE subscription_token = $1
+    FOR UPDATE
+    "#,
+        token
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+}
+
+#[tracing::instrument(name = "Mark subscriber as confirmed", skip(transaction))]
+async fn confirm_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
+        subscriber_id
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Delete subscription tokens", skip(transaction))]
+async fn delete_subscription_tokens(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscriber_id
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SubscriberCursor, escape_like};
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn cursors_round_trip_through_their_string_representation() {
+        let cursor = SubscriberCursor {
+            subscribed_at: chrono::DateTime::from_timestamp_micros(1_758_000_000_123_456).unwrap(),
+            id: uuid::Uuid::new_v4(),
+        };
+        assert_ok_eq!(SubscriberCursor::parse(&cursor.to_string()), cursor);
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        assert_err!(SubscriberCursor::parse(""));
+        assert_err!(SubscriberCursor::parse("1758000000123456"));
+        assert_err!(SubscriberCursor::parse(
+            "yesterday_6f1c3c1e-6d5b-4f43-9a3e-2b4a4f6f9d10"
+        ));
+        assert_err!(SubscriberCursor::parse("1758000000123456_not-a-uuid"));
+    }
+
+    #[test]
+    fn like_wildcards_are_escaped() {
+        assert_eq!(escape_like("100%_off\\"), "100\\%\\_off\\\\");
+        assert_eq!(escape_like("ursula"), "ursula");
+    }
+}
@@ -3,19 +3,43 @@
 use actix_session::{Session, SessionExt, SessionGetError, SessionInsertError};
 use actix_web::dev::Payload;
 use actix_web::{FromRequest, HttpRequest};
+use rand::distributions::Alphanumeric;
+use rand::{Rng, thread_rng};
+use secrecy::{ExposeSecret, Secret};
 use std::future::{Ready, ready};
 use uuid::Uuid;
 
+/// Name of the cookie holding the session key.
+pub const SESSION_COOKIE_NAME: &str = "id";
+
 pub struct TypedSession(Session);
 
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct PendingSecondFactor {
+    pub user_id: Uuid,
+    pub username: String,
+    /// Whether "remember me" was ticked on the login form.
+    pub remember_me: bool,
+    pub failed_attempts: u32,
+}
+
 impl TypedSession {
     const USER_ID_KEY: &'static str = "user_id";
+    const AUTHENTICATED_AT_KEY: &'static str = "authenticated_at";
+    const PENDING_SECOND_FACTOR_KEY: &'static str = "pending_second_factor";
+    const PENDING_TOTP_SECRET_KEY: &'static str = "pending_totp_secret";
+    const CSRF_TOKEN_KEY: &'static str = "csrf_token";
+    const SESSION_ID_KEY: &'static str = "session_id";
+    const LAST_SEEN_AT_KEY: &'static str = "last_seen_at";
+    const REMEMBER_ME_KEY: &'static str = "remember_me";
 
     pub fn renew(&self) {
         self.0.renew();
     }
 
+    /// The user is fully authenticated, any pending second factor is cleared.
     pub fn insert_user_id(&self, user_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.remove(Self::PENDING_SECOND_FACTOR_KEY);
         self.0.insert(Self::USER_ID_KEY, user_id)
     }
 
@@ -23,6 +47,118 @@ impl TypedSession {
         self.0.get(Self::USER_ID_KEY)
     }
 
+    /// When the user logged in, as microseconds since the Unix epoch.
+    pub fn insert_authenticated_at(&self, timestamp: i64) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::AUTHENTICATED_AT_KEY, timestamp)
+    }
+
+    pub fn get_authenticated_at(&self) -> Result<Option<i64>, SessionGetError> {
+        self.0.get(Self::AUTHENTICATED_AT_KEY)
+    }
+
+    /// When the session was last used, as microseconds since the Unix epoch.
+    pub fn insert_last_seen_at(&self, timestamp: i64) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::LAST_SEEN_AT_KEY, timestamp)
+    }
+
+    pub fn get_last_seen_at(&self) -> Result<Option<i64>, SessionGetError> {
+        self.0.get(Self::LAST_SEEN_AT_KEY)
+    }
+
+    /// Picks the longer lifetime of `SessionTimeouts` for this session.
+    pub fn insert_remember_me(&self, remember_me: bool) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::REMEMBER_ME_KEY, remember_me)
+    }
+
+    pub fn get_remember_me(&self) -> Result<Option<bool>, SessionGetError> {
+        self.0.get(Self::REMEMBER_ME_KEY)
+    }
+
+    /// The id this session is known by in the `SessionRegistry`.
+    pub fn insert_session_id(&self, session_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::SESSION_ID_KEY, session_id)
+    }
+
+    pub fn get_session_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::SESSION_ID_KEY)
+    }
+
+    /// The password was verified but the user still has to enter a TOTP or
+    /// recovery code. The session is not authenticated until then.
+    pub fn insert_pending_second_factor(
+        &self,
+        user_id: Uuid,
+        username: String,
+        remember_me: bool,
+    ) -> Result<(), SessionInsertError> {
+        self.0.remove(Self::USER_ID_KEY);
+        self.0.insert(
+            Self::PENDING_SECOND_FACTOR_KEY,
+            PendingSecondFactor {
+                user_id,
+                username,
+                remember_me,
+                failed_attempts: 0,
+            },
+        )
+    }
+
+    pub fn get_pending_second_factor(
+        &self,
+    ) -> Result<Option<PendingSecondFactor>, SessionGetError> {
+        self.0.get(Self::PENDING_SECOND_FACTOR_KEY)
+    }
+
+    pub fn update_pending_second_factor(
+        &self,
+        pending: PendingSecondFactor,
+    ) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::PENDING_SECOND_FACTOR_KEY, pending)
+    }
+
+    pub fn remove_pending_second_factor(&self) {
+        self.0.remove(Self::PENDING_SECOND_FACTOR_KEY);
+    }
+
+    /// The TOTP secret shown during enrollment, until the user confirms it with a code.
+    pub fn insert_pending_totp_secret(
+        &self,
+        secret: &Secret<String>,
+    ) -> Result<(), SessionInsertError> {
+        self.0
+            .insert(Self::PENDING_TOTP_SECRET_KEY, secret.expose_secret())
+    }
+
+    pub fn get_pending_totp_secret(&self) -> Result<Option<Secret<String>>, SessionGetError> {
+        Ok(self
+            .0
+            .get::<String>(Self::PENDING_TOTP_SECRET_KEY)?
+            .map(Secret::new))
+    }
+
+    pub fn remove_pending_totp_secret(&self) {
+        self.0.remove(Self::PENDING_TOTP_SECRET_KEY);
+    }
+
+    /// The synchronizer token every rendered form must send back, created
+    /// the first time a form is rendered for this session.
+    pub fn get_or_insert_csrf_token(&self) -> Result<String, anyhow::Error> {
+        if let Some(token) = self.get_csrf_token()? {
+            return Ok(token);
+        }
+        let mut rng = thread_rng();
+        let token: String = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+            .map(char::from)
+            .take(32)
+            .collect();
+        self.0.insert(Self::CSRF_TOKEN_KEY, &token)?;
+        Ok(token)
+    }
+
+    pub fn get_csrf_token(&self) -> Result<Option<String>, SessionGetError> {
+        self.0.get(Self::CSRF_TOKEN_KEY)
+    }
+
     pub fn logout(self) {
         self.0.purge();
     }
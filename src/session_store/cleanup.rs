@@ -0,0 +1,48 @@
+//! src/session_store/cleanup.rs
+use crate::configuration::{SessionStoreKind, Settings};
+use crate::startup::get_connection_pool;
+use sqlx::PgPool;
+use std::time::Duration;
+
+/// Delete expired sessions, registry entries and login throttle counters
+/// when they are kept in Postgres. Redis expires them on its own, there is
+/// nothing to do for it.
+pub async fn run_cleanup_worker_until_stopped(
+    configuration: Settings,
+) -> Result<(), anyhow::Error> {
+    if configuration.session.store != SessionStoreKind::Postgres {
+        return std::future::pending().await;
+    }
+    let connection_pool = get_connection_pool(&configuration.database).await;
+    cleanup_loop(connection_pool).await
+}
+
+async fn cleanup_loop(pool: PgPool) -> Result<(), anyhow::Error> {
+    loop {
+        // Failures are already recorded by the span, we try again on the next tick.
+        let _ = delete_expired_entries(&pool).await;
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    }
+}
+
+#[tracing::instrument(name = "Delete expired session store entries", skip(pool), err)]
+pub async fn delete_expired_entries(pool: &PgPool) -> Result<u64, anyhow::Error> {
+    let mut n_deleted_rows = 0;
+    n_deleted_rows += sqlx::query!(r#"DELETE FROM sessions WHERE expires_at <= now()"#)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    n_deleted_rows += sqlx::query!(r#"DELETE FROM session_registry WHERE expires_at <= now()"#)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    n_deleted_rows += sqlx::query!(r#"DELETE FROM login_failures WHERE expires_at <= now()"#)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    n_deleted_rows += sqlx::query!(r#"DELETE FROM login_lockouts WHERE expires_at <= now()"#)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    Ok(n_deleted_rows)
+}
@@ -0,0 +1,142 @@
+//! src/session_store/postgres.rs
+use actix_session::storage::{LoadError, SaveError, SessionKey, SessionStore, UpdateError};
+use actix_web::cookie::time::Duration;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rand::distributions::{Alphanumeric, DistString};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+/// Keeps session state in the `sessions` table, for deployments that would
+/// rather not run Redis.
+///
+/// Expired sessions are never loaded, and are deleted in the background by
+/// `run_cleanup_worker_until_stopped`.
+#[derive(Clone)]
+pub struct PgSessionStore {
+    pool: PgPool,
+}
+
+impl PgSessionStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+/// Only a hash of the key is stored: reading the table does not give access
+/// to the sessions in it.
+fn hash_session_key(session_key: &SessionKey) -> String {
+    hex::encode(Sha256::digest(session_key.as_ref().as_bytes()))
+}
+
+/// Same shape as the keys generated by `actix_session` for its own stores.
+fn generate_session_key() -> SessionKey {
+    Alphanumeric
+        .sample_string(&mut rand::thread_rng(), 64)
+        .try_into()
+        .expect("64 characters is a valid session key length.")
+}
+
+fn expires_at(ttl: &Duration) -> DateTime<Utc> {
+    Utc::now() + chrono::Duration::seconds(ttl.whole_seconds())
+}
+
+impl SessionStore for PgSessionStore {
+    async fn load(
+        &self,
+        session_key: &SessionKey,
+    ) -> Result<Option<HashMap<String, String>>, LoadError> {
+        let row = sqlx::query!(
+            r#"SELECT state FROM sessions WHERE session_key_hash = $1 AND expires_at > now()"#,
+            hash_session_key(session_key)
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to load the session from Postgres.")
+        .map_err(LoadError::Other)?;
+        row.map(|r| serde_json::from_str(&r.state))
+            .transpose()
+            .context("Failed to deserialize the session state.")
+            .map_err(LoadError::Deserialization)
+    }
+
+    async fn save(
+        &self,
+        session_state: HashMap<String, String>,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        let state = serde_json::to_string(&session_state)
+            .context("Failed to serialize the session state.")
+            .map_err(SaveError::Serialization)?;
+        let session_key = generate_session_key();
+        sqlx::query!(
+            r#"INSERT INTO sessions (session_key_hash, state, expires_at) VALUES ($1, $2, $3)"#,
+            hash_session_key(&session_key),
+            state,
+            expires_at(ttl)
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to save the session in Postgres.")
+        .map_err(SaveError::Other)?;
+        Ok(session_key)
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: HashMap<String, String>,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        let state = serde_json::to_string(&session_state)
+            .context("Failed to serialize the session state.")
+            .map_err(UpdateError::Serialization)?;
+        let n_updated = sqlx::query!(
+            r#"
+        UPDATE sessions SET state = $2, expires_at = $3
+        WHERE session_key_hash = $1 AND expires_at > now()
+        "#,
+            hash_session_key(&session_key),
+            state,
+            expires_at(ttl)
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to update the session in Postgres.")
+        .map_err(UpdateError::Other)?
+        .rows_affected();
+        if n_updated > 0 {
+            return Ok(session_key);
+        }
+        // The session expired while the request was being handled, start a
+        // new one like `RedisSessionStore` does.
+        self.save(session_state, ttl).await.map_err(|e| match e {
+            SaveError::Serialization(e) => UpdateError::Serialization(e),
+            SaveError::Other(e) => UpdateError::Other(e),
+        })
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> anyhow::Result<()> {
+        sqlx::query!(
+            r#"UPDATE sessions SET expires_at = $2 WHERE session_key_hash = $1"#,
+            hash_session_key(session_key),
+            expires_at(ttl)
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to update the session expiration in Postgres.")?;
+        Ok(())
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> anyhow::Result<()> {
+        sqlx::query!(
+            r#"DELETE FROM sessions WHERE session_key_hash = $1"#,
+            hash_session_key(session_key)
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to delete the session from Postgres.")?;
+        Ok(())
+    }
+}
@@ -1,23 +1,46 @@
 //! src/startup.rs
-use crate::authentication::reject_anonymous_users;
-use crate::configuration::{DatabaseSettings, Settings};
-use crate::email_client::EmailClient;
+use crate::authentication::{
+    ApiScope, LoginThrottle, MAX_MULTIPART_BODY_BYTES, PasswordHashing, Permission,
+    SessionRegistry, SessionTimeouts, apply_session_cookie_max_age, pick_session_cookie_max_age,
+    reject_anonymous_users, reject_invalid_api_tokens, require_csrf_token, require_permission,
+    require_scope,
+};
+use crate::configuration::{DatabaseSettings, SessionStoreKind, Settings};
+use crate::email_client::EmailTransport;
+use crate::routes::api;
 use crate::routes::{
-    admin_dashboard, change_password, change_password_form, confirm, health_check, home, login,
-    login_form, logout, publish_newsletter, publish_newsletter_form, subscribe,
+    admin_dashboard, api_docs, cancel_newsletter_issue, change_password, change_password_form,
+    change_user_role, clear_lockout, confirm, confirm_subscriber, create_api_token,
+    create_newsletter_draft, deactivate_user, download_import_report, download_my_data,
+    edit_newsletter_draft_form, enroll_two_factor, erase_my_data, export_subscribers, health_check,
+    home, import_subscribers, import_subscribers_form, invite_user, list_api_tokens, list_lockouts,
+    list_sessions, list_subscribers_page, list_users, login, login_form, logout, my_data_page,
+    new_password_form, openapi_json, password_reset_form, preview_newsletter_issue,
+    publish_newsletter, publish_newsletter_draft, publish_newsletter_form, remove_subscriber,
+    request_my_data, request_password_reset, resend_confirmation, resend_subscriber_confirmation,
+    reset_password, revoke_api_token, revoke_other_sessions, revoke_session,
+    schedule_newsletter_issue, subscribe, two_factor_enrollment_form, two_factor_form, unsubscribe,
+    unsubscribe_form, unsubscribe_subscriber, update_newsletter_draft, verify_two_factor,
 };
+use crate::session_state::SESSION_COOKIE_NAME;
+use crate::session_store::{AnySessionStore, PgSessionStore};
+use actix_multipart::form::MultipartFormConfig;
 use actix_session::SessionMiddleware;
+use actix_session::config::PersistentSession;
 use actix_session::storage::RedisSessionStore;
 use actix_web::cookie::Key;
+use actix_web::cookie::time::Duration as CookieDuration;
 use actix_web::dev::Server;
 use actix_web::middleware::from_fn;
-use actix_web::{App, HttpServer, web};
+use actix_web::{App, HttpRequest, HttpServer, web};
 use actix_web_flash_messages::FlashMessagesFramework;
 use actix_web_flash_messages::storage::CookieMessageStore;
+use anyhow::Context;
 use secrecy::ExposeSecret;
 use secrecy::Secret;
 use sqlx::{PgPool, postgres::PgPoolOptions};
-use std::net::TcpListener;
+use std::net::{IpAddr, SocketAddr, TcpListener};
+use std::sync::Arc;
 use tracing_actix_web::TracingLogger;
 
 pub struct Application {
@@ -34,28 +57,11 @@ impl Application {
         );
 
         // Configure email client
-        let sender_email = configuration
-            .email_client
-            .sender()
-            .expect("Failed to parse email.");
-        let email_client = EmailClient::new(
-            configuration.email_client.base_url.clone(),
-            sender_email,
-            configuration.email_client.authorization_token.clone(),
-            std::time::Duration::from_millis(configuration.email_client.timeout_milliseconds),
-        );
+        let email_client = configuration.email_client.clone().client()?;
 
         let listener = TcpListener::bind(address).expect("Failed to bind port 8000.");
         let port = listener.local_addr()?.port();
-        let server = run(
-            listener,
-            connection_pool,
-            email_client,
-            &configuration.application.base_url,
-            configuration.application.hmac_secret.clone(),
-            &configuration.redis_uri,
-        )
-        .await?;
+        let server = run(listener, connection_pool, email_client, configuration).await?;
 
         Ok(Self { port, server })
     }
@@ -75,51 +81,312 @@ pub async fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
 
 pub struct ApplicationBaseUrl(pub String);
 
+/// See `ApplicationSettings::trusted_proxies`.
+pub struct TrustedProxies(pub Vec<IpAddr>);
+
+/// The IP of the client behind `request`: the peer itself, unless it is one
+/// of our `TrustedProxies`, in which case the address it forwarded.
+pub fn client_ip(request: &HttpRequest) -> Option<IpAddr> {
+    let peer = request.peer_addr()?.ip();
+    let trusted_proxies = request
+        .app_data::<web::Data<TrustedProxies>>()
+        .expect("The trusted proxies are registered as application data.");
+    if !trusted_proxies.0.contains(&peer) {
+        return Some(peer);
+    }
+    let forwarded = request
+        .connection_info()
+        .realip_remote_addr()
+        .and_then(|addr| {
+            addr.trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse::<IpAddr>()
+                .or_else(|_| addr.parse::<SocketAddr>().map(|addr| addr.ip()))
+                .ok()
+        });
+    Some(forwarded.unwrap_or(peer))
+}
+
+/// Sessions, the session registry and the login throttle all live in the
+/// store picked by `session.store`.
+async fn build_session_backends(
+    configuration: &Settings,
+    pool: &PgPool,
+    session_timeouts: &SessionTimeouts,
+) -> Result<(AnySessionStore, LoginThrottle, SessionRegistry), anyhow::Error> {
+    let throttle_settings = configuration.login_throttle.clone();
+    match configuration.session.store {
+        SessionStoreKind::Redis => {
+            let redis_uri = configuration
+                .redis_uri
+                .as_ref()
+                .context("redis_uri is required when session.store is redis")?;
+            let redis_store = RedisSessionStore::new(redis_uri.expose_secret()).await?;
+            let client = redis::Client::open(redis_uri.expose_secret().as_str())?;
+            let redis = redis::aio::ConnectionManager::new(client).await?;
+            Ok((
+                AnySessionStore::Redis(Box::new(redis_store)),
+                LoginThrottle::redis(redis.clone(), throttle_settings),
+                SessionRegistry::redis(
+                    redis,
+                    configuration.session.key_prefix.clone(),
+                    session_timeouts.longest(),
+                ),
+            ))
+        }
+        SessionStoreKind::Postgres => Ok((
+            AnySessionStore::Postgres(PgSessionStore::new(pool.clone())),
+            LoginThrottle::postgres(pool.clone(), throttle_settings),
+            SessionRegistry::postgres(pool.clone(), session_timeouts.longest()),
+        )),
+    }
+}
+
 pub async fn run(
     listener: TcpListener,
     pool: PgPool,
-    email_client: EmailClient,
-    base_url: &str,
-    hmac_secret: Secret<String>,
-    redis_uri: &Secret<String>,
+    email_client: Arc<dyn EmailTransport>,
+    configuration: &Settings,
 ) -> Result<Server, anyhow::Error> {
+    let hmac_secret = configuration.application.hmac_secret.clone();
+    let session_timeouts = SessionTimeouts::new(&configuration.application);
+    let (session_store, login_throttle, session_registry) =
+        build_session_backends(configuration, &pool, &session_timeouts).await?;
+    let login_throttle = web::Data::new(login_throttle);
+    let session_registry = web::Data::new(session_registry);
     let pool = web::Data::new(pool);
-    let email_client = web::Data::new(email_client);
-    let base_url = web::Data::new(ApplicationBaseUrl(base_url.into()));
+    let subscription_settings = web::Data::new(configuration.subscriptions.clone());
+    let password_reset_settings = web::Data::new(configuration.password_reset.clone());
+    let password_hashing = web::Data::new(PasswordHashing::new(&configuration.password_hashing)?);
+    let email_client = web::Data::from(email_client);
+    let base_url = web::Data::new(ApplicationBaseUrl(
+        configuration.application.base_url.clone(),
+    ));
+    let trusted_proxies = web::Data::new(TrustedProxies(
+        configuration.application.trusted_proxies.clone(),
+    ));
 
     let secret_key = Key::from(hmac_secret.expose_secret().as_bytes());
     let message_store = CookieMessageStore::builder(secret_key.clone()).build();
     let message_framework = FlashMessagesFramework::builder(message_store).build();
 
-    let redis_store = RedisSessionStore::new(redis_uri.expose_secret()).await?;
+    let session_settings = configuration.session.clone();
+    let session_timeouts = web::Data::new(session_timeouts);
 
     let server = HttpServer::new(move || {
         App::new()
             .wrap(TracingLogger::default())
             .wrap(message_framework.clone())
-            .wrap(SessionMiddleware::new(
-                redis_store.clone(),
-                secret_key.clone(),
-            ))
+            .wrap(from_fn(pick_session_cookie_max_age))
+            .wrap(
+                SessionMiddleware::builder(session_store.clone(), secret_key.clone())
+                    .cookie_name(SESSION_COOKIE_NAME.into())
+                    .cookie_secure(session_settings.cookie_secure)
+                    .cookie_same_site(session_settings.cookie_same_site.into())
+                    // Cut down to the lifetime of each session by `apply_session_cookie_max_age`.
+                    .session_lifecycle(PersistentSession::default().session_ttl(
+                        CookieDuration::seconds(session_timeouts.longest().as_secs() as i64),
+                    ))
+                    .build(),
+            )
+            .wrap(from_fn(apply_session_cookie_max_age))
             .route("/health_check", web::get().to(health_check))
             .route("/subscriptions", web::post().to(subscribe))
             .route("/subscriptions/confirm", web::get().to(confirm))
+            .route(
+                "/subscriptions/resend-confirmation",
+                web::post().to(resend_confirmation),
+            )
+            .route(
+                "/subscriptions/unsubscribe",
+                web::get().to(unsubscribe_form),
+            )
+            .route("/subscriptions/unsubscribe", web::post().to(unsubscribe))
+            .route("/subscriptions/my-data", web::post().to(request_my_data))
+            .route("/subscriptions/my-data", web::get().to(my_data_page))
+            .route(
+                "/subscriptions/my-data/export",
+                web::get().to(download_my_data),
+            )
+            .route(
+                "/subscriptions/my-data/erase",
+                web::post().to(erase_my_data),
+            )
             .route("/", web::get().to(home))
-            .route("/login", web::get().to(login_form))
-            .route("/login", web::post().to(login))
+            .service(
+                web::scope("/login")
+                    .wrap(from_fn(require_csrf_token))
+                    .route("", web::get().to(login_form))
+                    .route("", web::post().to(login))
+                    .route("/two-factor", web::get().to(two_factor_form))
+                    .route("/two-factor", web::post().to(verify_two_factor)),
+            )
+            .service(
+                web::scope("/password-reset")
+                    .wrap(from_fn(require_csrf_token))
+                    .route("", web::get().to(password_reset_form))
+                    .route("", web::post().to(request_password_reset))
+                    .route("/confirm", web::get().to(new_password_form))
+                    .route("/confirm", web::post().to(reset_password)),
+            )
             .service(
                 web::scope("/admin")
+                    .wrap(from_fn(require_csrf_token))
                     .wrap(from_fn(reject_anonymous_users))
                     .route("/dashboard", web::get().to(admin_dashboard))
                     .route("/password", web::get().to(change_password_form))
                     .route("/password", web::post().to(change_password))
-                    .route("/logout", web::get().to(logout))
-                    .route("/newsletters", web::post().to(publish_newsletter))
-                    .route("/newsletters", web::get().to(publish_newsletter_form)),
+                    .route("/two-factor", web::get().to(two_factor_enrollment_form))
+                    .route("/two-factor", web::post().to(enroll_two_factor))
+                    .route("/logout", web::post().to(logout))
+                    .route("/sessions", web::get().to(list_sessions))
+                    .route(
+                        "/sessions/revoke-others",
+                        web::post().to(revoke_other_sessions),
+                    )
+                    .route(
+                        "/sessions/{session_id}/revoke",
+                        web::post().to(revoke_session),
+                    )
+                    .service(
+                        web::scope("/subscribers")
+                            .wrap(from_fn(|req, next| {
+                                require_permission(Permission::ReadSubscribers, req, next)
+                            }))
+                            .route("", web::get().to(list_subscribers_page))
+                            .route("/export", web::get().to(export_subscribers))
+                            .service(
+                                web::scope("/import")
+                                    .wrap(from_fn(|req, next| {
+                                        require_permission(Permission::ManageSubscribers, req, next)
+                                    }))
+                                    .app_data(
+                                        MultipartFormConfig::default()
+                                            .total_limit(MAX_MULTIPART_BODY_BYTES)
+                                            .memory_limit(MAX_MULTIPART_BODY_BYTES),
+                                    )
+                                    .route("", web::get().to(import_subscribers_form))
+                                    .route("", web::post().to(import_subscribers))
+                                    .route(
+                                        "/{import_id}/report",
+                                        web::get().to(download_import_report),
+                                    ),
+                            )
+                            .service(
+                                web::scope("/{subscriber_id}")
+                                    .wrap(from_fn(|req, next| {
+                                        require_permission(Permission::ManageSubscribers, req, next)
+                                    }))
+                                    .route(
+                                        "/resend-confirmation",
+                                        web::post().to(resend_subscriber_confirmation),
+                                    )
+                                    .route("/confirm", web::post().to(confirm_subscriber))
+                                    .route("/unsubscribe", web::post().to(unsubscribe_subscriber))
+                                    .route("/delete", web::post().to(remove_subscriber)),
+                            ),
+                    )
+                    .route("/api-tokens", web::get().to(list_api_tokens))
+                    .route("/api-tokens", web::post().to(create_api_token))
+                    .route(
+                        "/api-tokens/{token_id}/revoke",
+                        web::post().to(revoke_api_token),
+                    )
+                    .service(
+                        web::scope("/newsletters")
+                            .wrap(from_fn(|req, next| {
+                                require_permission(Permission::PublishNewsletters, req, next)
+                            }))
+                            .route("", web::post().to(publish_newsletter))
+                            .route("", web::get().to(publish_newsletter_form))
+                            .route("/drafts", web::post().to(create_newsletter_draft))
+                            .service(
+                                web::scope("/{issue_id}")
+                                    .route("/edit", web::get().to(edit_newsletter_draft_form))
+                                    .route("/edit", web::post().to(update_newsletter_draft))
+                                    .route("/preview", web::get().to(preview_newsletter_issue))
+                                    .route("/schedule", web::post().to(schedule_newsletter_issue))
+                                    .route("/cancel", web::post().to(cancel_newsletter_issue))
+                                    .route("/publish", web::post().to(publish_newsletter_draft)),
+                            ),
+                    )
+                    .service(
+                        web::scope("/users")
+                            .wrap(from_fn(|req, next| {
+                                require_permission(Permission::ManageUsers, req, next)
+                            }))
+                            .route("", web::get().to(list_users))
+                            .route("", web::post().to(invite_user))
+                            .route("/{user_id}/role", web::post().to(change_user_role))
+                            .route("/{user_id}/deactivate", web::post().to(deactivate_user)),
+                    )
+                    .service(
+                        web::scope("/lockouts")
+                            .wrap(from_fn(|req, next| {
+                                require_permission(Permission::ManageUsers, req, next)
+                            }))
+                            .route("", web::get().to(list_lockouts))
+                            .route("/clear", web::post().to(clear_lockout)),
+                    ),
+            )
+            .route("/api/openapi.json", web::get().to(openapi_json))
+            .route("/api/docs", web::get().to(api_docs))
+            .service(
+                web::scope("/api/v1")
+                    .wrap(from_fn(reject_invalid_api_tokens))
+                    .app_data(
+                        web::JsonConfig::default()
+                            .error_handler(|e, _| api::ApiError::bad_request(e.to_string()).into()),
+                    )
+                    .app_data(
+                        web::QueryConfig::default()
+                            .error_handler(|e, _| api::ApiError::bad_request(e.to_string()).into()),
+                    )
+                    .app_data(web::PathConfig::default().error_handler(|_, _| {
+                        api::ApiError::not_found("There is no such resource.").into()
+                    }))
+                    .service(
+                        web::resource("/subscribers")
+                            .route(web::get().to(api::list_subscribers).wrap(from_fn(
+                                |req, next| require_scope(ApiScope::SubscribersRead, req, next),
+                            )))
+                            .route(web::post().to(api::create_subscriber).wrap(from_fn(
+                                |req, next| require_scope(ApiScope::SubscribersWrite, req, next),
+                            ))),
+                    )
+                    .service(
+                        web::resource("/subscribers/{subscriber_id}")
+                            .route(
+                                web::get()
+                                    .to(api::get_subscriber)
+                                    .wrap(from_fn(|req, next| {
+                                        require_scope(ApiScope::SubscribersRead, req, next)
+                                    })),
+                            )
+                            .route(web::delete().to(api::delete_subscriber).wrap(from_fn(
+                                |req, next| require_scope(ApiScope::SubscribersWrite, req, next),
+                            ))),
+                    )
+                    .service(
+                        web::resource("/newsletters")
+                            .route(web::get().to(api::list_issues).wrap(from_fn(|req, next| {
+                                require_scope(ApiScope::NewslettersRead, req, next)
+                            })))
+                            .route(web::post().to(api::publish_issue).wrap(from_fn(
+                                |req, next| require_scope(ApiScope::NewslettersPublish, req, next),
+                            ))),
+                    ),
             )
             .app_data(pool.clone())
             .app_data(email_client.clone())
             .app_data(base_url.clone())
+            .app_data(trusted_proxies.clone())
+            .app_data(subscription_settings.clone())
+            .app_data(password_reset_settings.clone())
+            .app_data(login_throttle.clone())
+            .app_data(session_registry.clone())
+            .app_data(session_timeouts.clone())
+            .app_data(password_hashing.clone())
             .app_data(web::Data::new(HmacSecret(hmac_secret.clone())))
     })
     .listen(listener)?
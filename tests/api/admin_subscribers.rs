@@ -0,0 +1,258 @@
+//! tests/api/admin_subscribers.rs
+use crate::helpers::{TestApp, TestUser, assert_is_redirect_to, spawn_app};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Straight into the database, `minutes_ago` orders them on the page.
+async fn insert_subscriber(
+    app: &TestApp,
+    email: &str,
+    name: &str,
+    status: &str,
+    minutes_ago: i64,
+) -> Uuid {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        r#"INSERT INTO subscriptions (id, email, name, subscribed_at, status) VALUES ($1, $2, $3, $4, $5)"#,
+        id,
+        email,
+        name,
+        Utc::now() - Duration::minutes(minutes_ago),
+        status
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    id
+}
+
+async fn subscriber_status(app: &TestApp, id: Uuid) -> Option<String> {
+    sqlx::query_scalar!("SELECT status FROM subscriptions WHERE id = $1", id)
+        .fetch_optional(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+/// The `after` parameter of the "Next page" link, if there is one.
+fn next_page_cursor(html: &str) -> Option<String> {
+    let (before_link, _) = html.split_once("\">Next page")?;
+    let (_, cursor) = before_link.rsplit_once("after=")?;
+    Some(cursor.to_string())
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_admin_subscribers("").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn subscribers_are_listed_newest_first() {
+    // Arrange
+    let app = spawn_app().await;
+    insert_subscriber(&app, "old@example.com", "Old", "confirmed", 10).await;
+    insert_subscriber(&app, "new@example.com", "New", "pending_confirmation", 1).await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let html = app.get_admin_subscribers_html("").await;
+
+    // Assert
+    let new = html.find("new@example.com").unwrap();
+    let old = html.find("old@example.com").unwrap();
+    assert!(new < old);
+    assert!(html.contains("pending_confirmation"));
+    assert!(next_page_cursor(&html).is_none());
+}
+
+#[tokio::test]
+async fn the_dashboard_links_to_the_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let html = app.get_admin_dashboard_html().await;
+
+    // Assert
+    assert!(html.contains(r#"<a href="/admin/subscribers">"#));
+}
+
+#[tokio::test]
+async fn subscribers_can_be_searched_by_email_or_name() {
+    // Arrange
+    let app = spawn_app().await;
+    insert_subscriber(&app, "ursula@example.com", "Le Guin", "confirmed", 3).await;
+    insert_subscriber(&app, "octavia@example.com", "Butler", "confirmed", 2).await;
+    insert_subscriber(&app, "100%@example.com", "Percent", "confirmed", 1).await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let by_email = app.get_admin_subscribers_html("search=URSULA").await;
+    let by_name = app.get_admin_subscribers_html("search=butl").await;
+    let wildcard = app.get_admin_subscribers_html("search=%25").await;
+
+    // Assert
+    assert!(by_email.contains("ursula@example.com"));
+    assert!(!by_email.contains("octavia@example.com"));
+    assert!(by_name.contains("octavia@example.com"));
+    assert!(!by_name.contains("ursula@example.com"));
+    // `%` is not a wildcard.
+    assert!(wildcard.contains("100%@example.com"));
+    assert!(!wildcard.contains("ursula@example.com"));
+}
+
+#[tokio::test]
+async fn subscribers_can_be_filtered_by_status() {
+    // Arrange
+    let app = spawn_app().await;
+    insert_subscriber(&app, "confirmed@example.com", "A", "confirmed", 2).await;
+    insert_subscriber(&app, "pending@example.com", "B", "pending_confirmation", 1).await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let html = app.get_admin_subscribers_html("status=confirmed").await;
+    let invalid = app.get_admin_subscribers("status=banned").await;
+
+    // Assert
+    assert!(html.contains("confirmed@example.com"));
+    assert!(!html.contains("pending@example.com"));
+    assert_eq!(invalid.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn subscribers_are_paginated() {
+    // Arrange
+    let app = spawn_app().await;
+    for i in 0..60 {
+        insert_subscriber(
+            &app,
+            &format!("subscriber{i:02}@example.com"),
+            "Name",
+            "confirmed",
+            i,
+        )
+        .await;
+    }
+    app.login_as(&app.user).await;
+
+    // Act - Part 1 - First page
+    let first_page = app.get_admin_subscribers_html("").await;
+
+    // Assert
+    assert!(first_page.contains("subscriber00@example.com"));
+    assert!(first_page.contains("subscriber49@example.com"));
+    assert!(!first_page.contains("subscriber50@example.com"));
+
+    // Act - Part 2 - Follow the "Next page" link
+    let cursor = next_page_cursor(&first_page).expect("There is a second page.");
+    let second_page = app
+        .get_admin_subscribers_html(&format!("after={cursor}"))
+        .await;
+
+    // Assert
+    assert!(!second_page.contains("subscriber49@example.com"));
+    assert!(second_page.contains("subscriber50@example.com"));
+    assert!(second_page.contains("subscriber59@example.com"));
+    assert!(next_page_cursor(&second_page).is_none());
+}
+
+#[tokio::test]
+async fn a_pending_subscriber_can_be_confirmed_manually() {
+    // Arrange
+    let app = spawn_app().await;
+    let id = insert_subscriber(&app, "pending@example.com", "B", "pending_confirmation", 1).await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let response = app.post_admin_subscriber_action(id, "confirm").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    assert_eq!(subscriber_status(&app, id).await.unwrap(), "confirmed");
+    let html = app.get_admin_subscribers_html("").await;
+    assert!(html.contains("<p><i>The subscriber has been confirmed.</i></p>"));
+}
+
+#[tokio::test]
+async fn a_subscriber_can_be_unsubscribed_and_deleted() {
+    // Arrange
+    let app = spawn_app().await;
+    let id = insert_subscriber(&app, "confirmed@example.com", "A", "confirmed", 1).await;
+    app.login_as(&app.user).await;
+
+    // Act - Part 1 - Unsubscribe
+    app.post_admin_subscriber_action(id, "unsubscribe").await;
+
+    // Assert
+    assert_eq!(subscriber_status(&app, id).await.unwrap(), "unsubscribed");
+
+    // Act - Part 2 - Delete
+    let response = app.post_admin_subscriber_action(id, "delete").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    assert_eq!(subscriber_status(&app, id).await, None);
+    let html = app.get_admin_subscribers_html("").await;
+    assert!(html.contains("<p><i>The subscriber has been deleted.</i></p>"));
+}
+
+#[tokio::test]
+async fn the_confirmation_email_can_be_resent_to_a_pending_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let pending =
+        insert_subscriber(&app, "pending@example.com", "B", "pending_confirmation", 1).await;
+    let confirmed = insert_subscriber(&app, "confirmed@example.com", "A", "confirmed", 2).await;
+    app.login_as(&app.user).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_admin_subscriber_action(pending, "resend-confirmation")
+        .await;
+    app.post_admin_subscriber_action(confirmed, "resend-confirmation")
+        .await;
+
+    // Assert
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], "pending@example.com");
+    let html = app.get_admin_subscribers_html("").await;
+    assert!(html.contains("The subscriber is not waiting for a confirmation."));
+}
+
+#[tokio::test]
+async fn viewers_can_list_subscribers_but_not_change_them() {
+    // Arrange
+    let app = spawn_app().await;
+    let id = insert_subscriber(&app, "pending@example.com", "B", "pending_confirmation", 1).await;
+    let viewer = TestUser::generate_with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    app.login_as(&viewer).await;
+
+    // Act
+    let html = app.get_admin_subscribers_html("").await;
+    let response = app.post_admin_subscriber_action(id, "confirm").await;
+
+    // Assert
+    assert!(html.contains("pending@example.com"));
+    assert!(!html.contains("/confirm\""));
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(
+        subscriber_status(&app, id).await.unwrap(),
+        "pending_confirmation"
+    );
+}
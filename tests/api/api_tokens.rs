@@ -0,0 +1,255 @@
+//! tests/api/api_tokens.rs
+use crate::helpers::{TestApp, TestUser, assert_is_redirect_to, spawn_app};
+use serde_json::json;
+
+/// The token shown on the page returned after creating one.
+fn shown_token(html_page: &str) -> String {
+    html_page
+        .split("<code>")
+        .nth(1)
+        .and_then(|rest| rest.split("</code>").next())
+        .expect("No token on the page.")
+        .to_string()
+}
+
+/// The ids of the tokens that can be revoked from the API tokens page.
+fn token_ids(html_page: &str) -> Vec<String> {
+    html_page
+        .split(r#"action="/admin/api-tokens/"#)
+        .skip(1)
+        .filter_map(|rest| rest.split_once("/revoke\""))
+        .map(|(token_id, _)| token_id.to_string())
+        .collect()
+}
+
+async fn create_token(app: &TestApp, name: &str, scopes: &[&str]) -> String {
+    let response = app.post_api_tokens(name, scopes).await;
+    assert_eq!(response.status().as_u16(), 200);
+    shown_token(&response.text().await.unwrap())
+}
+
+fn newsletter_body() -> serde_json::Value {
+    json!({
+        "title": "Newsletter title",
+        "content_text": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>"
+    })
+}
+
+async fn publish(app: &TestApp, token: &str) -> reqwest::Response {
+    app.post_api(token, "/api/v1/newsletters", &newsletter_body())
+        .await
+}
+
+#[tokio::test]
+async fn the_dashboard_links_to_the_api_tokens_page() {
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+
+    let html_page = app.get_admin_dashboard_html().await;
+
+    assert!(html_page.contains(r#"href="/admin/api-tokens""#));
+}
+
+#[tokio::test]
+async fn a_new_token_is_shown_once_and_stored_hashed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let token = create_token(&app, "CI", &["newsletters:publish", "subscribers:read"]).await;
+
+    // Assert
+    assert_eq!(token.len(), 40);
+    let html_page = app.get_api_tokens_html().await;
+    assert!(html_page.contains("CI"));
+    assert!(html_page.contains("newsletters:publish subscribers:read"));
+    assert!(!html_page.contains(&token));
+    let token_hash: String = sqlx::query_scalar("SELECT token_hash FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_ne!(token_hash, token);
+}
+
+#[tokio::test]
+async fn a_token_needs_a_name_and_a_scope() {
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+
+    let response = app.post_api_tokens("", &["subscribers:read"]).await;
+    assert_is_redirect_to(&response, "/admin/api-tokens");
+    assert!(
+        app.get_api_tokens_html()
+            .await
+            .contains("A name is required.")
+    );
+
+    let response = app.post_api_tokens("CI", &[]).await;
+    assert_is_redirect_to(&response, "/admin/api-tokens");
+    assert!(
+        app.get_api_tokens_html()
+            .await
+            .contains("Pick at least one scope.")
+    );
+
+    let response = app.post_api_tokens("CI", &["newsletters:delete"]).await;
+    assert_is_redirect_to(&response, "/admin/api-tokens");
+    assert!(
+        app.get_api_tokens_html()
+            .await
+            .contains("newsletters:delete is not a valid scope.")
+    );
+}
+
+#[tokio::test]
+async fn viewers_cannot_create_tokens_that_publish() {
+    // Arrange
+    let app = spawn_app().await;
+    let viewer = TestUser::generate_with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    app.login_as(&viewer).await;
+
+    // Act
+    let response = app.post_api_tokens("CI", &["newsletters:publish"]).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/api-tokens");
+    let html_page = app.get_api_tokens_html().await;
+    assert!(html_page.contains("Your role does not allow the newsletters:publish scope."));
+    assert!(token_ids(&html_page).is_empty());
+}
+
+#[tokio::test]
+async fn a_token_with_the_publish_scope_can_publish_a_newsletter() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+    let token = create_token(&app, "CI", &["newsletters:publish"]).await;
+
+    // Act
+    let response = publish(&app, &token).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 202);
+    let n_issues: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_issues, 1);
+    let html_page = app.get_api_tokens_html().await;
+    assert!(!html_page.contains("Never"));
+}
+
+#[tokio::test]
+async fn requests_without_a_valid_token_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let missing = reqwest::Client::new()
+        .post(format!("{}/api/v1/newsletters", app.address))
+        .json(&newsletter_body())
+        .send()
+        .await
+        .unwrap();
+    let invalid = publish(&app, "not-a-real-token").await;
+
+    // Assert
+    for response in [missing, invalid] {
+        assert_eq!(response.status().as_u16(), 401);
+        assert_eq!(
+            response.headers()["WWW-Authenticate"],
+            r#"Bearer realm="api""#
+        );
+    }
+}
+
+#[tokio::test]
+async fn a_token_without_the_scope_is_forbidden() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+    let token = create_token(&app, "Reports", &["subscribers:read"]).await;
+
+    // Act
+    let response = publish(&app, &token).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn a_revoked_token_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+    let token = create_token(&app, "CI", &["newsletters:publish"]).await;
+    let token_id = token_ids(&app.get_api_tokens_html().await).remove(0);
+
+    // Act
+    let response = app.post_revoke_api_token(&token_id).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/api-tokens");
+    let html_page = app.get_api_tokens_html().await;
+    assert!(html_page.contains("The API token has been revoked."));
+    assert!(token_ids(&html_page).is_empty());
+    let response = publish(&app, &token).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn tokens_of_other_users_cannot_be_revoked() {
+    // Arrange
+    let mut app = spawn_app().await;
+    app.login_as(&app.user).await;
+    let token = create_token(&app, "CI", &["newsletters:publish"]).await;
+    let token_id = token_ids(&app.get_api_tokens_html().await).remove(0);
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    app.api_client = crate::helpers::new_browser();
+    app.login_as(&editor).await;
+
+    // Act
+    let response = app.post_revoke_api_token(&token_id).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/api-tokens");
+    assert!(
+        app.get_api_tokens_html()
+            .await
+            .contains("There is no such API token.")
+    );
+    let response = publish(&app, &token).await;
+    assert_eq!(response.status().as_u16(), 202);
+}
+
+#[tokio::test]
+async fn tokens_follow_the_role_and_status_of_their_owner() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+    let token = create_token(&app, "CI", &["newsletters:publish"]).await;
+
+    // Act - Part 1 - Demoted to viewer
+    sqlx::query("UPDATE users SET role = 'viewer' WHERE user_id = $1")
+        .bind(app.user.user_id)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let response = publish(&app, &token).await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    // Act - Part 2 - Deactivated
+    sqlx::query("UPDATE users SET is_active = false WHERE user_id = $1")
+        .bind(app.user.user_id)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let response = publish(&app, &token).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+}
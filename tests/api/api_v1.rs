@@ -0,0 +1,401 @@
+//! tests/api/api_v1.rs
+use crate::helpers::{TestApp, TestUser, spawn_app};
+use serde_json::{Value, json};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::authentication::{self, ApiScope};
+
+async fn mock_email_delivery(app: &TestApp, n_emails: u64) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(n_emails)
+        .mount(&app.email_server)
+        .await;
+}
+
+async fn create_subscriber(app: &TestApp, token: &str, email: &str) -> Value {
+    let n_emails = app.email_server.received_requests().await.unwrap().len();
+    let response = app
+        .post_api(
+            token,
+            "/api/v1/subscribers",
+            &json!({"name": "Le Guin", "email": email}),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    // The confirmation email is sent after the response.
+    app.wait_for_email_requests(n_emails + 1).await;
+    response.json().await.unwrap()
+}
+
+/// Checks the response is a problem+json body with `status`, and returns it.
+async fn assert_problem(response: reqwest::Response, status: u16) -> Value {
+    assert_eq!(response.status().as_u16(), status);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/problem+json"
+    );
+    let problem: Value = response.json().await.unwrap();
+    assert_eq!(problem["status"], status);
+    assert_eq!(problem["type"], "about:blank");
+    problem
+}
+
+fn issue_body() -> Value {
+    json!({
+        "title": "Newsletter title",
+        "content_text": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>"
+    })
+}
+
+#[tokio::test]
+async fn subscribers_can_be_created_listed_fetched_and_deleted() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = app.create_api_token(&ApiScope::ALL).await;
+    mock_email_delivery(&app, 1).await;
+
+    // Act - Part 1 - Create
+    let response = app
+        .post_api(
+            &token,
+            "/api/v1/subscribers",
+            &json!({"name": "Le Guin", "email": "ursula_le_guin@gmail.com"}),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    let location = response.headers()["Location"].to_str().unwrap().to_owned();
+    let subscriber: Value = response.json().await.unwrap();
+    assert_eq!(subscriber["email"], "ursula_le_guin@gmail.com");
+    assert_eq!(subscriber["status"], "pending_confirmation");
+    app.wait_for_email_requests(1).await;
+    assert_eq!(
+        location,
+        format!("/api/v1/subscribers/{}", subscriber["id"].as_str().unwrap())
+    );
+
+    // Act - Part 2 - List
+    let list: Value = app
+        .get_api(&token, "/api/v1/subscribers")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(list["subscribers"].as_array().unwrap().len(), 1);
+
+    // Act - Part 3 - Get
+    let response = app.get_api(&token, &location).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let fetched: Value = response.json().await.unwrap();
+    assert_eq!(fetched, subscriber);
+
+    // Act - Part 4 - Delete
+    let response = app.delete_api(&token, &location).await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    // Assert
+    assert_problem(app.get_api(&token, &location).await, 404).await;
+    assert_problem(app.delete_api(&token, &location).await, 404).await;
+}
+
+#[tokio::test]
+async fn subscribers_are_listed_one_page_at_a_time() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = app.create_api_token(&ApiScope::ALL).await;
+    mock_email_delivery(&app, 3).await;
+    for email in ["a@example.com", "b@example.com", "c@example.com"] {
+        create_subscriber(&app, &token, email).await;
+    }
+
+    // Act - Part 1 - The first page
+    let page: Value = app
+        .get_api(&token, "/api/v1/subscribers?limit=2")
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // Assert - Newest first
+    let emails: Vec<&str> = page["subscribers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["email"].as_str().unwrap())
+        .collect();
+    assert_eq!(emails, vec!["c@example.com", "b@example.com"]);
+
+    // Act - Part 2 - The next page
+    let cursor = page["next_cursor"].as_str().unwrap();
+    let page: Value = app
+        .get_api(
+            &token,
+            &format!("/api/v1/subscribers?limit=2&cursor={cursor}"),
+        )
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(page["subscribers"].as_array().unwrap().len(), 1);
+    assert_eq!(page["subscribers"][0]["email"], "a@example.com");
+    assert!(page["next_cursor"].is_null());
+
+    // Act - Part 3 - Invalid parameters
+    for query in ["limit=0", "limit=501", "limit=many", "cursor=nonsense"] {
+        let response = app
+            .get_api(&token, &format!("/api/v1/subscribers?{query}"))
+            .await;
+        assert_problem(response, 400).await;
+    }
+}
+
+#[tokio::test]
+async fn invalid_subscribers_are_rejected_with_a_problem() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = app.create_api_token(&[ApiScope::SubscribersWrite]).await;
+    mock_email_delivery(&app, 0).await;
+
+    // Act
+    let response = app
+        .post_api(
+            &token,
+            "/api/v1/subscribers",
+            &json!({"name": "Le Guin", "email": "not-an-email"}),
+        )
+        .await;
+
+    // Assert
+    let problem = assert_problem(response, 400).await;
+    assert_eq!(
+        problem["detail"],
+        "not-an-email is an invalid email address."
+    );
+}
+
+#[tokio::test]
+async fn malformed_bodies_and_ids_are_answered_with_a_problem() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = app.create_api_token(&ApiScope::ALL).await;
+
+    // Act
+    let missing_field = app
+        .post_api(&token, "/api/v1/subscribers", &json!({"name": "Le Guin"}))
+        .await;
+    let invalid_id = app.get_api(&token, "/api/v1/subscribers/not-a-uuid").await;
+
+    // Assert
+    assert_problem(missing_field, 400).await;
+    assert_problem(invalid_id, 404).await;
+}
+
+#[tokio::test]
+async fn deleting_a_subscriber_drops_their_pending_deliveries() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = app.create_api_token(&ApiScope::ALL).await;
+    mock_email_delivery(&app, 1).await;
+    let subscriber = create_subscriber(&app, &token, "ursula_le_guin@gmail.com").await;
+    sqlx::query("UPDATE subscriptions SET status = 'confirmed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let response = app
+        .post_api(&token, "/api/v1/newsletters", &issue_body())
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    // Act
+    let response = app
+        .delete_api(
+            &token,
+            &format!("/api/v1/subscribers/{}", subscriber["id"].as_str().unwrap()),
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 204);
+    let n_deliveries: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_deliveries, 0);
+}
+
+#[tokio::test]
+async fn published_issues_are_listed_with_their_pending_deliveries() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = app.create_api_token(&ApiScope::ALL).await;
+    mock_email_delivery(&app, 1).await;
+    create_subscriber(&app, &token, "ursula_le_guin@gmail.com").await;
+    sqlx::query("UPDATE subscriptions SET status = 'confirmed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = app
+        .post_api(&token, "/api/v1/newsletters", &issue_body())
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let published: Value = response.json().await.unwrap();
+    let list: Value = app
+        .get_api(&token, "/api/v1/newsletters")
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // Assert
+    let issues = list["issues"].as_array().unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0]["id"], published["id"]);
+    assert_eq!(issues[0]["title"], "Newsletter title");
+    assert_eq!(issues[0]["pending_deliveries"], 1);
+}
+
+#[tokio::test]
+async fn publishing_is_idempotent() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = app.create_api_token(&[ApiScope::NewslettersPublish]).await;
+    let client = reqwest::Client::new();
+    let idempotency_key = Uuid::new_v4().to_string();
+
+    // Act
+    let mut bodies = Vec::new();
+    for _ in 0..2 {
+        let response = client
+            .post(format!("{}/api/v1/newsletters", app.address))
+            .bearer_auth(&token)
+            .header("Idempotency-Key", &idempotency_key)
+            .json(&issue_body())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 202);
+        bodies.push(response.text().await.unwrap());
+    }
+
+    // Assert
+    assert_eq!(bodies[0], bodies[1]);
+    let n_issues: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_issues, 1);
+}
+
+#[tokio::test]
+async fn publishing_requires_a_valid_issue_and_an_idempotency_key() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = app.create_api_token(&[ApiScope::NewslettersPublish]).await;
+
+    // Act
+    let invalid_issue = app
+        .post_api(
+            &token,
+            "/api/v1/newsletters",
+            &json!({"title": "", "content_text": "text", "content_html": ""}),
+        )
+        .await;
+    let no_idempotency_key = reqwest::Client::new()
+        .post(format!("{}/api/v1/newsletters", app.address))
+        .bearer_auth(&token)
+        .json(&issue_body())
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    let problem = assert_problem(invalid_issue, 400).await;
+    assert_eq!(
+        problem["detail"],
+        "Title is required HTML content is required."
+    );
+    let problem = assert_problem(no_idempotency_key, 400).await;
+    assert_eq!(problem["detail"], "The Idempotency-Key header is required.");
+}
+
+#[tokio::test]
+async fn authentication_failures_are_answered_with_a_problem() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_api("not-a-real-token", "/api/v1/subscribers").await;
+
+    // Assert
+    assert_eq!(
+        response.headers()["WWW-Authenticate"],
+        r#"Bearer realm="api""#
+    );
+    let problem = assert_problem(response, 401).await;
+    assert_eq!(problem["title"], "Unauthorized");
+}
+
+#[tokio::test]
+async fn each_endpoint_requires_its_scope() {
+    // Arrange
+    let app = spawn_app().await;
+    let read_only = app
+        .create_api_token(&[ApiScope::SubscribersRead, ApiScope::NewslettersRead])
+        .await;
+    let subscriber = format!("/api/v1/subscribers/{}", Uuid::new_v4());
+
+    // Act
+    let responses = [
+        app.post_api(&read_only, "/api/v1/subscribers", &json!({}))
+            .await,
+        app.delete_api(&read_only, &subscriber).await,
+        app.post_api(&read_only, "/api/v1/newsletters", &issue_body())
+            .await,
+    ];
+
+    // Assert
+    for response in responses {
+        assert_problem(response, 403).await;
+    }
+    let response = app.get_api(&read_only, "/api/v1/subscribers").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app.get_api(&read_only, "/api/v1/newsletters").await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn viewers_cannot_write_even_with_the_scope() {
+    // Arrange
+    let app = spawn_app().await;
+    let viewer = TestUser::generate_with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    let (_, token) = authentication::create_api_token(
+        &app.db_pool,
+        viewer.user_id,
+        "test",
+        &[ApiScope::SubscribersRead, ApiScope::SubscribersWrite],
+    )
+    .await
+    .unwrap();
+
+    // Act
+    let response = app
+        .post_api(
+            &token,
+            "/api/v1/subscribers",
+            &json!({"name": "Le Guin", "email": "ursula_le_guin@gmail.com"}),
+        )
+        .await;
+
+    // Assert
+    assert_problem(response, 403).await;
+    let response = app.get_api(&token, "/api/v1/subscribers").await;
+    assert_eq!(response.status().as_u16(), 200);
+}
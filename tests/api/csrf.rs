@@ -0,0 +1,169 @@
+//! tests/api/csrf.rs
+use crate::helpers::{assert_is_redirect_to, new_browser, spawn_app};
+use serde_json::json;
+
+#[tokio::test]
+async fn every_form_carries_a_csrf_token() {
+    // Arrange
+    let app = spawn_app().await;
+    let login_page = app.get_login_html().await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let pages = [
+        login_page,
+        app.get_admin_dashboard_html().await,
+        app.get_change_password_html().await,
+        app.get_newsletters_form_html().await,
+        app.get_users_html().await,
+        app.get_two_factor_enrollment_html().await,
+    ];
+
+    // Assert
+    let csrf_token = app.get_csrf_token().await;
+    for page in pages {
+        assert_eq!(
+            page.matches("<form ").count(),
+            page.matches(&format!(r#"name="csrf_token" value="{csrf_token}""#))
+                .count()
+        );
+    }
+}
+
+#[tokio::test]
+async fn a_login_without_a_csrf_token_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.get_login_html().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/login", app.address))
+        .form(&json!({"username": app.user.username, "password": app.user.password}))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn an_admin_form_with_a_wrong_csrf_token_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+    let new_password = uuid::Uuid::new_v4().to_string();
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/password", app.address))
+        .form(&json!({
+            "current_password": app.user.password,
+            "new_password": &new_password,
+            "verify_new_password": &new_password,
+            "csrf_token": "not-the-token",
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    app.post_logout().await;
+    let response = app.login_as(&app.user).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn the_csrf_token_can_be_sent_as_a_header() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+    let csrf_token = app.get_csrf_token().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/logout", app.address))
+        .header("X-CSRF-Token", csrf_token)
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_csrf_token_from_another_session_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let other_session_token = app.get_csrf_token().await;
+    let attacker = new_browser();
+    attacker
+        .get(format!("{}/login", app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // Act
+    let response = attacker
+        .post(format!("{}/login", app.address))
+        .form(&json!({
+            "username": app.user.username,
+            "password": app.user.password,
+            "csrf_token": other_session_token,
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn logout_is_no_longer_a_get() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/admin/logout", app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn the_session_cookie_uses_the_configured_attributes() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/login", app.address))
+        .send()
+        .await
+        .unwrap();
+
+    let session_cookie = response
+        .headers()
+        .get_all("Set-Cookie")
+        .iter()
+        .map(|h| h.to_str().unwrap())
+        .find(|h| h.starts_with("id="))
+        .expect("No session cookie was set.");
+    // `configuration/base.yaml`, with `local.yaml` turning `Secure` off.
+    assert!(session_cookie.contains("SameSite=Lax"));
+    assert!(!session_cookie.contains("Secure"));
+}
@@ -1,19 +1,27 @@
 //! tests/api/helper.rs
-use argon2::password_hash::SaltString;
-use argon2::{Argon2, Params, PasswordHasher};
 use reqwest::Url;
 use secrecy::Secret;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
+use std::sync::Arc;
 use std::sync::LazyLock;
 use uuid::Uuid;
 use wiremock::MockServer;
+use zero2prod::authentication::{self, ApiScope, PasswordHashing, compute_password_hash};
+use zero2prod::email_client::EmailTransport;
+use zero2prod::issue_delivery_worker::{ExecutionOutcome, try_execute_task};
 use zero2prod::startup::get_connection_pool;
 use zero2prod::telemetry::{get_subscriber, init_subscriber};
 use zero2prod::{
-    configuration::{DatabaseSettings, get_configuration},
+    configuration::{DatabaseSettings, Settings, get_configuration},
     startup::Application,
 };
 
+/// Test users are hashed with the same parameters as the application.
+static PASSWORD_HASHING: LazyLock<PasswordHashing> = LazyLock::new(|| {
+    let configuration = get_configuration().expect("Failed to read configuration.");
+    PasswordHashing::new(&configuration.password_hashing).expect("Invalid Argon2 parameters.")
+});
+
 static TRACING: LazyLock<()> = LazyLock::new(|| {
     let default_filter_level = "info".to_string();
     let subscriber_name = "test".to_string();
@@ -32,37 +40,38 @@ pub struct ConfirmationLinks {
     pub text: Url,
 }
 
+#[derive(Clone)]
 pub struct TestUser {
     pub user_id: Uuid,
     pub username: String,
     pub password: String,
+    pub role: &'static str,
 }
 
 impl TestUser {
     pub fn generate() -> Self {
+        Self::generate_with_role("owner")
+    }
+
+    pub fn generate_with_role(role: &'static str) -> Self {
         TestUser {
             username: Uuid::new_v4().to_string(),
             password: Uuid::new_v4().to_string(),
             user_id: Uuid::new_v4(),
+            role,
         }
     }
 
-    async fn store(&self, pool: &PgPool) {
-        let salt = SaltString::generate(&mut rand::thread_rng());
-        let password_hash = Argon2::new(
-            argon2::Algorithm::Argon2id,
-            argon2::Version::V0x13,
-            Params::new(15000, 2, 1, None).expect("Failed to build params."),
-        )
-        .hash_password(self.password.as_bytes(), &salt)
-        .unwrap()
-        .to_string();
+    pub async fn store(&self, pool: &PgPool) {
+        let password_hash =
+            compute_password_hash(&Secret::new(self.password.clone()), &PASSWORD_HASHING).unwrap();
         sqlx::query!(
-            "INSERT INTO users (user_id, username, password_hash)
-VALUES ($1, $2, $3)",
+            "INSERT INTO users (user_id, username, password_hash, role)
+VALUES ($1, $2, $3, $4)",
             self.user_id,
             self.username,
-            password_hash
+            password_hash,
+            self.role
         )
         .execute(pool)
         .await
@@ -76,17 +85,36 @@ pub struct TestApp {
     pub db_pool: PgPool,
     pub email_server: MockServer,
     pub port: u16,
-    api_client: reqwest::Client,
+    pub email_client: Arc<dyn EmailTransport>,
+    pub base_url: String,
+    pub hmac_secret: Secret<String>,
+    pub api_client: reqwest::Client,
 }
 
 impl TestApp {
+    pub async fn dispatch_all_pending_emails(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                &self.db_pool,
+                self.email_client.as_ref(),
+                &self.base_url,
+                &self.hmac_secret,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+    }
+
     pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
         self.api_client
             .post(format!("{}/admin/password", self.address))
-            .form(body)
+            .form(&self.with_csrf_token(body).await)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -104,21 +132,64 @@ impl TestApp {
         self.get_change_password().await.text().await.unwrap()
     }
 
-    pub async fn get_logout(&self) -> reqwest::Response {
+    pub async fn post_logout(&self) -> reqwest::Response {
         self.api_client
-            .get(format!("{}/admin/logout", self.address))
+            .post(format!("{}/admin/logout", self.address))
+            .form(&self.with_csrf_token(&serde_json::json!({})).await)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    /// The CSRF token of the current session, as rendered in every form.
+    pub async fn get_csrf_token(&self) -> String {
+        let html_page = self.get_login_html().await;
+        html_page
+            .split(r#"name="csrf_token" value=""#)
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .expect("The login form has no CSRF token.")
+            .to_string()
+    }
+
+    /// `body` with the CSRF token of the current session added, ready to be
+    /// posted as a form.
+    async fn with_csrf_token<Body>(&self, body: &Body) -> serde_json::Value
+    where
+        Body: serde::Serialize,
+    {
+        let mut body = serde_json::to_value(body).unwrap();
+        body.as_object_mut()
+            .expect("Form bodies are JSON objects.")
+            .insert("csrf_token".into(), self.get_csrf_token().await.into());
+        body
+    }
+
     pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
         self.api_client
             .post(format!("{}/login", self.address))
-            .form(body)
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// `post_login`, relayed by a reverse proxy on behalf of `client_ip`.
+    pub async fn post_login_forwarded_for<Body>(
+        &self,
+        client_ip: &str,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/login", self.address))
+            .header("X-Forwarded-For", client_ip)
+            .form(&self.with_csrf_token(body).await)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -147,6 +218,327 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn login_as(&self, user: &TestUser) -> reqwest::Response {
+        self.post_login(&serde_json::json!({
+            "username": user.username,
+            "password": user.password
+        }))
+        .await
+    }
+
+    pub async fn get_login_two_factor_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/login/two-factor", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_login_two_factor<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/login/two-factor", self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_two_factor_enrollment_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/two-factor", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_two_factor_enrollment<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/two-factor", self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_users(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/users", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_users_html(&self) -> String {
+        self.get_users().await.text().await.unwrap()
+    }
+
+    pub async fn post_users<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/users{}", self.address, path))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_lockouts(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/lockouts", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_lockouts_html(&self) -> String {
+        self.get_lockouts().await.text().await.unwrap()
+    }
+
+    pub async fn post_clear_lockout<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/lockouts/clear", self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sessions_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/sessions", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_revoke_session(&self, session_id: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/sessions/{session_id}/revoke",
+                self.address
+            ))
+            .form(&self.with_csrf_token(&serde_json::json!({})).await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke_other_sessions(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/sessions/revoke-others", self.address))
+            .form(&self.with_csrf_token(&serde_json::json!({})).await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// `query` is the query string, without the leading `?`.
+    pub async fn get_admin_subscribers(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/subscribers?{query}", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscriber_export(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/subscribers/export?{query}", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_subscribers_html(&self, query: &str) -> String {
+        self.get_admin_subscribers(query)
+            .await
+            .text()
+            .await
+            .unwrap()
+    }
+
+    /// `action` is one of the row actions of `/admin/subscribers`, e.g. `confirm`.
+    pub async fn post_admin_subscriber_action(
+        &self,
+        subscriber_id: Uuid,
+        action: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/subscribers/{subscriber_id}/{action}",
+                self.address
+            ))
+            .form(&self.with_csrf_token(&serde_json::json!({})).await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Uploads `csv` the way the import form does, as `multipart/form-data`.
+    pub async fn post_subscriber_import(&self, csv: &str, mode: &str) -> reqwest::Response {
+        let form = reqwest::multipart::Form::new()
+            .text("csrf_token", self.get_csrf_token().await)
+            .text("mode", mode.to_string())
+            .part(
+                "file",
+                reqwest::multipart::Part::text(csv.to_string())
+                    .file_name("subscribers.csv")
+                    .mime_str("text/csv")
+                    .unwrap(),
+            );
+        self.api_client
+            .post(format!("{}/admin/subscribers/import", self.address))
+            .multipart(form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscriber_import_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/subscribers/import", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_api_tokens_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/api-tokens", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    /// `scopes` is sent as a repeated field, like the checkboxes of the form.
+    pub async fn post_api_tokens(&self, name: &str, scopes: &[&str]) -> reqwest::Response {
+        let csrf_token = self.get_csrf_token().await;
+        let mut form = vec![("csrf_token", csrf_token.as_str()), ("name", name)];
+        form.extend(scopes.iter().map(|scope| ("scopes", *scope)));
+        self.api_client
+            .post(format!("{}/admin/api-tokens", self.address))
+            .form(&form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke_api_token(&self, token_id: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/api-tokens/{token_id}/revoke",
+                self.address
+            ))
+            .form(&self.with_csrf_token(&serde_json::json!({})).await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// An API token of the test user, created without going through the admin pages.
+    pub async fn create_api_token(&self, scopes: &[ApiScope]) -> String {
+        let (_, token) =
+            authentication::create_api_token(&self.db_pool, self.user.user_id, "test", scopes)
+                .await
+                .unwrap();
+        token
+    }
+
+    /// Outside of any browser session: API requests only carry `token`.
+    pub async fn get_api(&self, token: &str, path: &str) -> reqwest::Response {
+        new_browser()
+            .get(format!("{}{path}", self.address))
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Every request gets a new `Idempotency-Key`.
+    pub async fn post_api<Body>(&self, token: &str, path: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        new_browser()
+            .post(format!("{}{path}", self.address))
+            .bearer_auth(token)
+            .header("Idempotency-Key", Uuid::new_v4().to_string())
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_api(&self, token: &str, path: &str) -> reqwest::Response {
+        new_browser()
+            .delete(format!("{}{path}", self.address))
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/password-reset", self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_password_reset_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/password-reset", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_password_reset_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/password-reset/confirm", self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
         self.api_client
             .post(format!("{}/subscriptions", self.address))
@@ -157,6 +549,29 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_resend_confirmation(&self, body: String) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/subscriptions/resend-confirmation",
+                &self.address
+            ))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_my_data_request(&self, body: String) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/subscriptions/my-data", &self.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn get_newsletters_form(&self) -> reqwest::Response {
         self.api_client
             .get(format!("{}/admin/newsletters", self.address))
@@ -175,12 +590,52 @@ impl TestApp {
     {
         self.api_client
             .post(format!("{}/admin/newsletters", self.address))
-            .form(&body)
+            .form(&self.with_csrf_token(&body).await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// `path` is relative to `/admin/newsletters`, e.g. `/drafts` or `/{id}/schedule`.
+    pub async fn post_newsletter_action<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/newsletters{path}", self.address))
+            .form(&self.with_csrf_token(body).await)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_newsletter_page_html(&self, path: &str) -> String {
+        self.api_client
+            .get(format!("{}/admin/newsletters{path}", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    /// Wait until the email server has received `n` requests, for emails sent
+    /// by a background task after the response.
+    pub async fn wait_for_email_requests(&self, n: usize) -> Vec<wiremock::Request> {
+        for _ in 0..50 {
+            let requests = self.email_server.received_requests().await.unwrap();
+            if requests.len() >= n {
+                // The mock server records a request before matching it
+                // against the mocks, let it finish.
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                return requests;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        panic!("The email server did not receive {n} requests.");
+    }
+
     pub fn get_confirmation_links(&self, email_request: &wiremock::Request) -> ConfirmationLinks {
         let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
         let get_link = |s: &str| {
@@ -201,10 +656,47 @@ impl TestApp {
             text: get_link(body["TextBody"].as_str().unwrap()),
         }
     }
+
+    /// Extract the one-click unsubscribe link from the `List-Unsubscribe` header
+    /// of a newsletter issue sent through Postmark.
+    pub fn get_unsubscribe_link(&self, email_request: &wiremock::Request) -> Url {
+        let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+        let header = body["Headers"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|h| h["Name"] == "List-Unsubscribe")
+            .expect("No List-Unsubscribe header");
+        let raw_link = header["Value"]
+            .as_str()
+            .unwrap()
+            .trim_start_matches('<')
+            .trim_end_matches('>');
+        let mut link = Url::parse(raw_link).unwrap();
+        assert_eq!(link.host_str().unwrap(), "127.0.0.1");
+        link.set_port(Some(self.port)).unwrap();
+        link
+    }
+
+    pub async fn post_unsubscribe(&self, link: &Url) -> reqwest::Response {
+        self.api_client
+            .post(link.clone())
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("List-Unsubscribe=One-Click")
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
 }
 
 #[allow(clippy::let_underscore_future)]
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+/// `spawn_app`, with `configure` applied to the configuration on top of the
+/// test defaults.
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
     LazyLock::force(&TRACING);
     let email_server = MockServer::start().await;
 
@@ -213,6 +705,11 @@ pub async fn spawn_app() -> TestApp {
         c.database.database_name = Uuid::new_v4().to_string();
         c.application.port = 0;
         c.email_client.base_url = email_server.uri();
+        // Tests share a Redis instance and all log in from 127.0.0.1
+        c.login_throttle.key_prefix = Uuid::new_v4().to_string();
+        c.login_throttle.initial_delay_milliseconds = 0;
+        c.session.key_prefix = Uuid::new_v4().to_string();
+        configure(&mut c);
         c
     };
 
@@ -226,17 +723,13 @@ pub async fn spawn_app() -> TestApp {
     configure_database(&configuration.database, &connection_pool).await;
 
     let address = format!("http://127.0.0.1:{}", app.port());
-    let _ = tokio::spawn(app.run_until_stopped());
+    tokio::spawn(app.run_until_stopped());
 
     // Create test user and store them in the database
     let user = TestUser::generate();
     user.store(&connection_pool).await;
 
-    let client = reqwest::Client::builder()
-        .redirect(reqwest::redirect::Policy::none())
-        .cookie_store(true)
-        .build()
-        .unwrap();
+    let client = new_browser();
 
     TestApp {
         address,
@@ -244,10 +737,23 @@ pub async fn spawn_app() -> TestApp {
         email_server,
         port: application_port,
         user,
+        email_client: configuration.email_client.clone().client().unwrap(),
+        base_url: configuration.application.base_url.clone(),
+        hmac_secret: configuration.application.hmac_secret.clone(),
         api_client: client,
     }
 }
 
+/// An HTTP client with its own cookie jar: swap it into `TestApp::api_client`
+/// to act from a second browser.
+pub fn new_browser() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap()
+}
+
 pub async fn configure_database(config: &DatabaseSettings, connection_pool: &PgPool) {
     let maintenance_settings = DatabaseSettings {
         database_name: "postgres".to_string(),
@@ -1,7 +1,10 @@
 //! src/tests/api/login.rs
 use crate::helpers::assert_is_redirect_to;
-use crate::helpers::spawn_app;
+use crate::helpers::{TestApp, spawn_app};
+use argon2::password_hash::SaltString;
+use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
 use serde_json::json;
+use std::time::Duration;
 
 #[tokio::test]
 async fn error_cookie_should_be_set_on_failed_login_attempt() {
@@ -42,3 +45,120 @@ async fn redirect_to_admin_dashboard_on_login_success() {
 
     assert!(html_page.contains(&format!("Welcome {}!", app.user.username)));
 }
+
+async fn store_password_hash_with(app: &TestApp, algorithm: Algorithm, params: Params) -> String {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let password_hash = Argon2::new(algorithm, Version::V0x13, params)
+        .hash_password(app.user.password.as_bytes(), &salt)
+        .unwrap()
+        .to_string();
+    sqlx::query!(
+        "UPDATE users SET password_hash = $1 WHERE user_id = $2",
+        password_hash,
+        app.user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    password_hash
+}
+
+async fn get_password_hash(app: &TestApp) -> String {
+    sqlx::query!(
+        "SELECT password_hash FROM users WHERE user_id = $1",
+        app.user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .password_hash
+}
+
+/// The upgrade happens in the background, after the response has been sent.
+async fn wait_for_password_hash_change(app: &TestApp, old_hash: &str) -> String {
+    for _ in 0..50 {
+        let password_hash = get_password_hash(app).await;
+        if password_hash != old_hash {
+            return password_hash;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("The password hash was not upgraded.");
+}
+
+#[tokio::test]
+async fn password_hashes_with_outdated_parameters_are_upgraded_on_login() {
+    // Arrange
+    let app = spawn_app().await;
+    let old_hash = store_password_hash_with(
+        &app,
+        Algorithm::Argon2id,
+        Params::new(4096, 1, 1, None).unwrap(),
+    )
+    .await;
+
+    // Act
+    let response = app.login_as(&app.user).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let new_hash = wait_for_password_hash_change(&app, &old_hash).await;
+    // `password_hashing` in `configuration/base.yaml`.
+    assert!(new_hash.starts_with("$argon2id$v=19$m=15000,t=2,p=1$"));
+    app.post_logout().await;
+    let response = app.login_as(&app.user).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn password_hashes_with_an_outdated_algorithm_are_upgraded_on_login() {
+    // Arrange
+    let app = spawn_app().await;
+    let old_hash = store_password_hash_with(
+        &app,
+        Algorithm::Argon2i,
+        Params::new(15000, 2, 1, None).unwrap(),
+    )
+    .await;
+
+    // Act
+    app.login_as(&app.user).await;
+
+    // Assert
+    let new_hash = wait_for_password_hash_change(&app, &old_hash).await;
+    assert!(new_hash.starts_with("$argon2id$"));
+}
+
+#[tokio::test]
+async fn up_to_date_password_hashes_are_left_alone() {
+    // Arrange
+    let app = spawn_app().await;
+    let old_hash = get_password_hash(&app).await;
+
+    // Act
+    app.login_as(&app.user).await;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // Assert
+    assert_eq!(get_password_hash(&app).await, old_hash);
+}
+
+#[tokio::test]
+async fn a_failed_login_does_not_upgrade_the_password_hash() {
+    // Arrange
+    let app = spawn_app().await;
+    let old_hash = store_password_hash_with(
+        &app,
+        Algorithm::Argon2id,
+        Params::new(4096, 1, 1, None).unwrap(),
+    )
+    .await;
+
+    // Act
+    app.post_login(&json!({"username": app.user.username, "password": "wrong-password"}))
+        .await;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // Assert
+    assert_eq!(get_password_hash(&app).await, old_hash);
+}
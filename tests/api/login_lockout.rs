@@ -0,0 +1,175 @@
+//! tests/api/login_lockout.rs
+use crate::helpers::{TestApp, TestUser, assert_is_redirect_to, spawn_app, spawn_app_with};
+use serde_json::json;
+
+/// `max_failures_per_username` in `configuration/base.yaml`.
+const MAX_FAILURES_PER_USERNAME: usize = 5;
+
+/// Low enough for the tests to reach quickly.
+const MAX_FAILURES_PER_IP: u64 = 3;
+
+async fn fail_logins_from(app: &TestApp, client_ip: &str) {
+    for _ in 0..MAX_FAILURES_PER_IP {
+        // A new username each time, so that only the IP gets locked out
+        let username = uuid::Uuid::new_v4().to_string();
+        let response = app
+            .post_login_forwarded_for(
+                client_ip,
+                &json!({"username": username, "password": "wrong-password"}),
+            )
+            .await;
+        assert_is_redirect_to(&response, "/login");
+    }
+}
+
+async fn fail_logins(app: &TestApp, username: &str, n: usize) {
+    for _ in 0..n {
+        let response = app
+            .post_login(&json!({"username": username, "password": "wrong-password"}))
+            .await;
+        assert_is_redirect_to(&response, "/login");
+    }
+}
+
+#[tokio::test]
+async fn a_username_is_locked_out_after_too_many_failures() {
+    // Arrange
+    let app = spawn_app().await;
+    fail_logins(&app, &app.user.username, MAX_FAILURES_PER_USERNAME).await;
+
+    // Act - Even the right password is turned away
+    let response = app.login_as(&app.user).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Too many failed login attempts, please try again later."));
+}
+
+#[tokio::test]
+async fn other_usernames_are_not_affected_by_a_lockout() {
+    // Arrange
+    let app = spawn_app().await;
+    let other = TestUser::generate_with_role("viewer");
+    other.store(&app.db_pool).await;
+    fail_logins(&app, &other.username, MAX_FAILURES_PER_USERNAME).await;
+
+    // Act
+    let response = app.login_as(&app.user).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_successful_login_resets_the_failure_count() {
+    // Arrange
+    let app = spawn_app().await;
+    fail_logins(&app, &app.user.username, MAX_FAILURES_PER_USERNAME - 1).await;
+    let response = app.login_as(&app.user).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    app.post_logout().await;
+
+    // Act
+    fail_logins(&app, &app.user.username, MAX_FAILURES_PER_USERNAME - 1).await;
+    let response = app.login_as(&app.user).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn owners_can_see_and_clear_lockouts() {
+    // Arrange
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    fail_logins(&app, &editor.username, MAX_FAILURES_PER_USERNAME).await;
+    app.login_as(&app.user).await;
+
+    // Act - Part 1 - List
+    let html_page = app.get_lockouts_html().await;
+    assert!(html_page.contains(&editor.username));
+
+    // Act - Part 2 - Clear
+    let response = app
+        .post_clear_lockout(&json!({"kind": "username", "identifier": editor.username}))
+        .await;
+    assert_is_redirect_to(&response, "/admin/lockouts");
+    let html_page = app.get_lockouts_html().await;
+    assert!(html_page.contains("The lockout has been cleared."));
+    assert!(!html_page.contains(&editor.username));
+
+    // Act - Part 3 - The editor can log in again
+    app.post_logout().await;
+    let response = app.login_as(&editor).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn only_owners_can_manage_lockouts() {
+    // Arrange
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    app.login_as(&editor).await;
+
+    // Act
+    let list_response = app.get_lockouts().await;
+    let clear_response = app
+        .post_clear_lockout(&json!({"kind": "username", "identifier": app.user.username}))
+        .await;
+
+    // Assert
+    assert_eq!(list_response.status().as_u16(), 403);
+    assert_eq!(clear_response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn behind_a_trusted_proxy_each_client_ip_is_locked_out_on_its_own() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.application.trusted_proxies = vec!["127.0.0.1".parse().unwrap()];
+        c.login_throttle.max_failures_per_ip = MAX_FAILURES_PER_IP;
+    })
+    .await;
+    fail_logins_from(&app, "203.0.113.1").await;
+    let credentials = json!({"username": app.user.username, "password": app.user.password});
+
+    // Act - Part 1 - The attacker's address is locked out
+    let response = app
+        .post_login_forwarded_for("203.0.113.1", &credentials)
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Too many failed login attempts, please try again later."));
+
+    // Act - Part 2 - Everyone else behind the proxy can still log in
+    let response = app
+        .post_login_forwarded_for("203.0.113.2", &credentials)
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn forwarded_client_ips_are_ignored_unless_the_proxy_is_trusted() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.login_throttle.max_failures_per_ip = MAX_FAILURES_PER_IP;
+    })
+    .await;
+    fail_logins_from(&app, "203.0.113.1").await;
+
+    // Act - A forged header does not get around the lockout of the peer
+    let response = app
+        .post_login_forwarded_for(
+            "203.0.113.2",
+            &json!({"username": app.user.username, "password": app.user.password}),
+        )
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Too many failed login attempts, please try again later."));
+}
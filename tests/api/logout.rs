@@ -19,7 +19,7 @@ async fn you_should_have_a_logout_action_in_admin_dashboard() {
 
     let response = app.get_admin_dashboard_html().await;
 
-    assert!(response.contains(r#"<a href="/admin/logout">Logout</a>"#));
+    assert!(response.contains(r#"<form action="/admin/logout" method="post">"#));
 }
 
 #[tokio::test]
@@ -37,7 +37,7 @@ async fn you_should_be_redirected_to_login_on_logout_with_success_message() {
 
     assert_is_redirect_to(&response, "/admin/dashboard");
 
-    let response = app.get_logout().await;
+    let response = app.post_logout().await;
 
     assert_is_redirect_to(&response, "/login");
 
@@ -61,7 +61,7 @@ async fn you_should_lose_access_to_admin_dashboard_after_logout() {
 
     assert_is_redirect_to(&response, "/admin/dashboard");
 
-    let response = app.get_logout().await;
+    let response = app.post_logout().await;
 
     assert_is_redirect_to(&response, "/login");
 
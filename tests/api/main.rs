@@ -1,10 +1,27 @@
 //! tests/api/main.rs
 mod admin_dashboard;
+mod admin_subscribers;
+mod api_tokens;
+mod api_v1;
+mod csrf;
 mod health_check;
 mod helpers;
 mod login;
+mod login_lockout;
 mod logout;
 mod newsletter;
+mod newsletter_drafts;
+mod openapi;
 mod password;
+mod password_reset;
+mod postgres_sessions;
+mod session_timeouts;
+mod sessions;
+mod subscriber_export;
+mod subscriber_import;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_my_data;
+mod subscriptions_unsubscribe;
+mod two_factor;
+mod users;
@@ -2,8 +2,10 @@
 use crate::helpers::assert_is_redirect_to;
 use crate::helpers::{TestApp, spawn_app};
 use serde_json::json;
+use std::time::Duration;
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
+use zero2prod::idempotency::delete_expired_keys;
 
 #[tokio::test]
 async fn newsletters_are_not_delivered_to_unconfirmed_subscribers() {
@@ -35,12 +37,14 @@ async fn newsletters_are_not_delivered_to_unconfirmed_subscribers() {
         .post_newsletters(json!({
             "title": "Newsletter 1",
             "content_html": "<p>newsletter content html</p>",
-            "content_text": "newsletter content text"
+            "content_text": "newsletter content text",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
         }))
         .await;
 
     // Assert
     assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
 }
 
 #[tokio::test]
@@ -71,12 +75,14 @@ async fn newsletters_are_delivered_to_confirmed_subscriber() {
         .post_newsletters(json!({
             "title": "Newsletter 1",
             "content_html": "<p>newsletter content html</p>",
-            "content_text": "newsletter content text"
+            "content_text": "newsletter content text",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
         }))
         .await;
 
     // Assert
     assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
 }
 
 #[tokio::test]
@@ -197,7 +203,8 @@ async fn submitting_with_empty_fields_shows_validation_error_flash_message() {
         .post_newsletters(json!({
             "title": "",
             "content_html": "",
-            "content_text": ""
+            "content_text": "",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
         }))
         .await;
 
@@ -243,12 +250,420 @@ async fn you_can_publish_a_newsletter_when_logged_in() {
         .post_newsletters(json!({
             "title": "Newsletter 1",
             "content_html": "<p>newsletter content html</p>",
-            "content_text": "newsletter content text"
+            "content_text": "newsletter content text",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
         }))
         .await;
 
     // Assert
     assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn newsletter_delivery_is_deferred_to_the_issue_delivery_queue() {
+    let app = spawn_app().await;
+    create_subscriber(&app, true).await;
+
+    let response = app
+        .post_login(&json!(
+            {
+                "username": app.user.username,
+                "password": app.user.password
+            }
+        ))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    // Act 1 - Publish the issue, nothing is sent within the request
+    let response = app
+        .post_newsletters(json!({
+            "title": "Newsletter 1",
+            "content_html": "<p>newsletter content html</p>",
+            "content_text": "newsletter content text",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let page_html = app.get_newsletters_form_html().await;
+    assert!(page_html.contains("The newsletter issue has been accepted"));
+
+    let issue = sqlx::query!("SELECT newsletter_issue_id, title FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved newsletter issue.");
+    assert_eq!(issue.title, "Newsletter 1");
+
+    let queued = sqlx::query!(
+        "SELECT subscriber_email FROM issue_delivery_queue WHERE newsletter_issue_id = $1",
+        issue.newsletter_issue_id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .expect("Failed to fetch delivery tasks.");
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].subscriber_email, "ursula_le_guin@gmail.com");
+
+    // Act 2 - Drain the queue
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    let remaining = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to count delivery tasks.");
+    assert_eq!(remaining.count, 0);
+}
+
+#[tokio::test]
+async fn newsletter_creation_is_idempotent() {
+    // Arrange
+    let app = spawn_app().await;
+    create_subscriber(&app, true).await;
+    let response = app
+        .post_login(&json!(
+            {
+                "username": app.user.username,
+                "password": app.user.password
+            }
+        ))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Submit newsletter form
+    let newsletter_request_body = json!({
+        "title": "Newsletter title",
+        "content_text": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_newsletters(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_newsletters_form_html().await;
+    assert!(html_page.contains("The newsletter issue has been accepted"));
+
+    // Act - Part 3 - Submit newsletter form **again**
+    let response = app.post_newsletters(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Act - Part 4 - Follow the redirect
+    let html_page = app.get_newsletters_form_html().await;
+    assert!(html_page.contains("The newsletter issue has been accepted"));
+
+    app.dispatch_all_pending_emails().await;
+    // Mock verifies on Drop that we have sent the newsletter email **once**
+}
+
+#[tokio::test]
+async fn concurrent_form_submission_is_handled_gracefully() {
+    // Arrange
+    let app = spawn_app().await;
+    create_subscriber(&app, true).await;
+    let response = app
+        .post_login(&json!(
+            {
+                "username": app.user.username,
+                "password": app.user.password
+            }
+        ))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Submit two newsletter forms concurrently
+    let newsletter_request_body = json!({
+        "title": "Newsletter title",
+        "content_text": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response1 = app.post_newsletters(&newsletter_request_body);
+    let response2 = app.post_newsletters(&newsletter_request_body);
+    let (response1, response2) = tokio::join!(response1, response2);
+
+    assert_eq!(response1.status(), response2.status());
+    assert_eq!(
+        response1.text().await.unwrap(),
+        response2.text().await.unwrap()
+    );
+
+    app.dispatch_all_pending_emails().await;
+    // Mock verifies on Drop that we have sent the newsletter email **once**
+}
+
+#[tokio::test]
+async fn newsletter_form_carries_an_idempotency_key() {
+    let app = spawn_app().await;
+    let response = app
+        .post_login(&json!(
+            {
+                "username": app.user.username,
+                "password": app.user.password
+            }
+        ))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let page_html = app.get_newsletters_form_html().await;
+
+    assert!(page_html.contains(r#"name="idempotency_key""#));
+}
+
+#[tokio::test]
+async fn newsletters_are_rejected_with_an_invalid_idempotency_key() {
+    let app = spawn_app().await;
+    let response = app
+        .post_login(&json!(
+            {
+                "username": app.user.username,
+                "password": app.user.password
+            }
+        ))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let response = app
+        .post_newsletters(json!({
+            "title": "Newsletter 1",
+            "content_html": "<p>newsletter content html</p>",
+            "content_text": "newsletter content text",
+            "idempotency_key": ""
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn expired_idempotency_keys_are_deleted() {
+    // Arrange
+    let app = spawn_app().await;
+    let response = app
+        .post_login(&json!(
+            {
+                "username": app.user.username,
+                "password": app.user.password
+            }
+        ))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let newsletter_request_body = json!({
+        "title": "Newsletter title",
+        "content_text": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_newsletters(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Act 1 - Keys younger than the expiration are kept
+    let deleted = delete_expired_keys(&app.db_pool, Duration::from_secs(3600))
+        .await
+        .unwrap();
+    assert_eq!(deleted, 0);
+
+    // Act 2 - Age the key past the expiration
+    sqlx::query!("UPDATE idempotency SET created_at = created_at - INTERVAL '2 hours'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let deleted = delete_expired_keys(&app.db_pool, Duration::from_secs(3600))
+        .await
+        .unwrap();
+    assert_eq!(deleted, 1);
+
+    // Act 3 - The same key is processed as a brand new request
+    let response = app.post_newsletters(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let issues = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issues.len(), 2);
+}
+
+#[tokio::test]
+async fn transient_delivery_failures_are_rescheduled() {
+    // Arrange
+    let app = spawn_app().await;
+    create_subscriber(&app, true).await;
+    app.post_login(&json!({
+        "username": app.user.username,
+        "password": app.user.password
+    }))
+    .await;
+    app.post_newsletters(json!({
+        "title": "Newsletter 1",
+        "content_html": "<p>newsletter content html</p>",
+        "content_text": "newsletter content text",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let task = sqlx::query!(
+        "SELECT n_retries, execute_after > now() AS \"in_the_future!\" FROM issue_delivery_queue"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("The delivery task should still be queued.");
+    assert_eq!(task.n_retries, 1);
+    assert!(task.in_the_future);
+}
+
+#[tokio::test]
+async fn permanent_delivery_failures_are_dropped() {
+    // Arrange
+    let app = spawn_app().await;
+    create_subscriber(&app, true).await;
+    app.post_login(&json!({
+        "username": app.user.username,
+        "password": app.user.password
+    }))
+    .await;
+    app.post_newsletters(json!({
+        "title": "Newsletter 1",
+        "content_html": "<p>newsletter content html</p>",
+        "content_text": "newsletter content text",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(422))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let remaining = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining.count, 0);
+}
+
+/// Publish `body` to a confirmed subscriber and return the email they get.
+async fn deliver_issue(app: &TestApp, body: serde_json::Value) -> serde_json::Value {
+    create_subscriber(app, true).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.login_as(&app.user).await;
+    let response = app.post_newsletters(body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    email_request.body_json().unwrap()
+}
+
+#[tokio::test]
+async fn markdown_is_rendered_into_both_bodies() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let email = deliver_issue(
+        &app,
+        json!({
+            "title": "Newsletter 1",
+            "content_markdown": "# This week\n\nRead [the post](https://example.com/post).\n\n<script>alert(1)</script>",
+            "content_text": "",
+            "content_html": "",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }),
+    )
+    .await;
+
+    // Assert
+    let html = email["HtmlBody"].as_str().unwrap();
+    let text = email["TextBody"].as_str().unwrap();
+    assert!(html.contains("<h1>This week</h1>"));
+    assert!(
+        html.contains(
+            r#"<a href="https://example.com/post" rel="noopener noreferrer">the post</a>"#
+        )
+    );
+    assert!(!html.contains("<script"));
+    assert!(text.starts_with("This week\n=========\n\nRead the post (https://example.com/post)."));
+}
+
+#[tokio::test]
+async fn hand_written_bodies_override_the_rendered_markdown() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let email = deliver_issue(
+        &app,
+        json!({
+            "title": "Newsletter 1",
+            "content_markdown": "Hello *readers*",
+            "content_text": "Hand-written text",
+            "content_html": "",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }),
+    )
+    .await;
+
+    // Assert
+    assert!(
+        email["HtmlBody"]
+            .as_str()
+            .unwrap()
+            .contains("<p>Hello <em>readers</em></p>")
+    );
+    assert!(
+        email["TextBody"]
+            .as_str()
+            .unwrap()
+            .starts_with("Hand-written text")
+    );
 }
 
 async fn create_subscriber(app: &TestApp, is_confirmed: bool) {
@@ -266,9 +681,11 @@ async fn create_subscriber(app: &TestApp, is_confirmed: bool) {
         .await
         .error_for_status()
         .unwrap();
+    // Sent after the response, before the mock goes away.
+    let email_requests = app.wait_for_email_requests(1).await;
 
     if is_confirmed {
-        let email_request = &app.email_server.received_requests().await.unwrap()[0];
+        let email_request = &email_requests[0];
 
         let confirmation_links = app.get_confirmation_links(email_request);
 
@@ -0,0 +1,332 @@
+//! tests/api/newsletter_drafts.rs
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+use chrono::{Duration, Utc};
+use serde_json::json;
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::services;
+
+async fn create_confirmed_subscriber(app: &TestApp) {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = &app.wait_for_email_requests(1).await[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.text)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+/// Save a draft and return its id, read from the redirect to its edit page.
+async fn create_draft(app: &TestApp, body: serde_json::Value) -> Uuid {
+    let response = app.post_newsletter_action("/drafts", &body).await;
+    assert_eq!(response.status().as_u16(), 303);
+    let location = response.headers()["location"].to_str().unwrap();
+    location
+        .strip_prefix("/admin/newsletters/")
+        .and_then(|rest| rest.strip_suffix("/edit"))
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+async fn complete_draft(app: &TestApp) -> Uuid {
+    create_draft(
+        app,
+        json!({
+            "title": "Newsletter title",
+            "content_text": "Newsletter body as plain text",
+            "content_html": "<p>Newsletter body as HTML</p>",
+        }),
+    )
+    .await
+}
+
+async fn status_of(app: &TestApp, issue_id: Uuid) -> String {
+    sqlx::query_scalar!(
+        "SELECT status FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+/// Pretend the send time of a scheduled issue has come.
+async fn make_due(app: &TestApp, issue_id: Uuid) {
+    sqlx::query!(
+        "UPDATE newsletter_issues SET scheduled_at = now() - interval '1 minute' \
+        WHERE newsletter_issue_id = $1",
+        issue_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+fn in_an_hour() -> String {
+    (Utc::now() + Duration::hours(1))
+        .format("%Y-%m-%dT%H:%M")
+        .to_string()
+}
+
+#[tokio::test]
+async fn an_incomplete_draft_can_be_saved_edited_and_previewed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+
+    // Act - Part 1 - Save a draft with a title only
+    let issue_id = create_draft(&app, json!({ "title": "Work in progress" })).await;
+
+    // Assert
+    assert_eq!(status_of(&app, issue_id).await, "draft");
+    let html = app
+        .get_newsletter_page_html(&format!("/{issue_id}/edit"))
+        .await;
+    assert!(html.contains("The draft has been saved."));
+    assert!(html.contains(r#"value="Work&#x20;in&#x20;progress""#));
+
+    // Act - Part 2 - Fill it in
+    let response = app
+        .post_newsletter_action(
+            &format!("/{issue_id}/edit"),
+            &json!({
+                "title": "Finished",
+                "content_text": "Plain <text>",
+                "content_html": "<p>Some HTML</p>",
+            }),
+        )
+        .await;
+    assert_is_redirect_to(&response, &format!("/admin/newsletters/{issue_id}/edit"));
+
+    // Assert
+    let html = app
+        .get_newsletter_page_html(&format!("/{issue_id}/preview"))
+        .await;
+    assert!(html.contains("<h1>Finished</h1>"));
+    assert!(html.contains(r#"srcdoc="&lt;p&gt;Some&#x20;HTML&lt;&#x2F;p&gt;""#));
+    assert!(html.contains("<pre>Plain &lt;text&gt;</pre>"));
+    let html = app.get_newsletters_form_html().await;
+    assert!(html.contains(&format!(
+        r#"<a href="/admin/newsletters/{issue_id}/edit">Edit</a>"#
+    )));
+}
+
+#[tokio::test]
+async fn an_incomplete_draft_cannot_be_published_or_scheduled() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+    let issue_id = create_draft(&app, json!({ "title": "Work in progress" })).await;
+
+    for (action, body) in [
+        ("publish", json!({})),
+        ("schedule", json!({ "scheduled_at": in_an_hour() })),
+    ] {
+        // Act
+        let response = app
+            .post_newsletter_action(&format!("/{issue_id}/{action}"), &body)
+            .await;
+
+        // Assert
+        assert_is_redirect_to(&response, &format!("/admin/newsletters/{issue_id}/edit"));
+        let html = app
+            .get_newsletter_page_html(&format!("/{issue_id}/edit"))
+            .await;
+        assert!(html.contains("Text content is required."), "{action}");
+        assert_eq!(status_of(&app, issue_id).await, "draft");
+    }
+}
+
+#[tokio::test]
+async fn issues_cannot_be_scheduled_in_the_past() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+    let issue_id = complete_draft(&app).await;
+
+    // Act
+    app.post_newsletter_action(
+        &format!("/{issue_id}/schedule"),
+        &json!({ "scheduled_at": "2020-01-01T09:00" }),
+    )
+    .await;
+
+    // Assert
+    let html = app
+        .get_newsletter_page_html(&format!("/{issue_id}/edit"))
+        .await;
+    assert!(html.contains("The send time must be in the future."));
+    assert_eq!(status_of(&app, issue_id).await, "draft");
+}
+
+#[tokio::test]
+async fn a_scheduled_issue_is_sent_once_it_is_due() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login_as(&app.user).await;
+    let issue_id = complete_draft(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Schedule it
+    app.post_newsletter_action(
+        &format!("/{issue_id}/schedule"),
+        &json!({ "scheduled_at": in_an_hour() }),
+    )
+    .await;
+
+    // Assert - Nothing goes out before the send time
+    assert_eq!(status_of(&app, issue_id).await, "scheduled");
+    assert_eq!(services::publish_due_issues(&app.db_pool).await.unwrap(), 0);
+    let html = app
+        .get_newsletter_page_html(&format!("/{issue_id}/edit"))
+        .await;
+    assert!(html.contains("Scheduled for"));
+    assert!(html.contains(r#"readonly"#));
+
+    // Act - Part 2 - The send time comes
+    make_due(&app, issue_id).await;
+    assert_eq!(services::publish_due_issues(&app.db_pool).await.unwrap(), 1);
+
+    // Assert
+    assert_eq!(status_of(&app, issue_id).await, "sending");
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(status_of(&app, issue_id).await, "sent");
+}
+
+#[tokio::test]
+async fn a_scheduled_issue_can_be_cancelled_until_its_send_time() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+    let issue_id = complete_draft(&app).await;
+    app.post_newsletter_action(
+        &format!("/{issue_id}/schedule"),
+        &json!({ "scheduled_at": in_an_hour() }),
+    )
+    .await;
+
+    // Act - Part 1 - Cancel before the send time
+    app.post_newsletter_action(&format!("/{issue_id}/cancel"), &json!({}))
+        .await;
+
+    // Assert
+    assert_eq!(status_of(&app, issue_id).await, "draft");
+
+    // Act - Part 2 - Cancel once it is due
+    app.post_newsletter_action(
+        &format!("/{issue_id}/schedule"),
+        &json!({ "scheduled_at": in_an_hour() }),
+    )
+    .await;
+    make_due(&app, issue_id).await;
+    app.post_newsletter_action(&format!("/{issue_id}/cancel"), &json!({}))
+        .await;
+
+    // Assert
+    assert_eq!(status_of(&app, issue_id).await, "scheduled");
+    let html = app
+        .get_newsletter_page_html(&format!("/{issue_id}/edit"))
+        .await;
+    assert!(html.contains("Only scheduled issues can be cancelled, before their send time."));
+}
+
+#[tokio::test]
+async fn publishing_a_draft_without_subscribers_marks_it_as_sent() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+    let issue_id = complete_draft(&app).await;
+
+    // Act
+    let response = app
+        .post_newsletter_action(&format!("/{issue_id}/publish"), &json!({}))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    assert_eq!(status_of(&app, issue_id).await, "sent");
+    let html = app
+        .get_newsletter_page_html(&format!("/{issue_id}/edit"))
+        .await;
+    assert!(html.contains("This issue is sent, it can no longer be edited."));
+
+    // Act - Part 2 - It cannot be edited anymore
+    app.post_newsletter_action(
+        &format!("/{issue_id}/edit"),
+        &json!({ "title": "Changed", "content_text": "x", "content_html": "x" }),
+    )
+    .await;
+
+    // Assert
+    let html = app
+        .get_newsletter_page_html(&format!("/{issue_id}/edit"))
+        .await;
+    assert!(html.contains(r#"value="Newsletter&#x20;title""#));
+}
+
+#[tokio::test]
+async fn the_edit_form_only_shows_the_hand_written_bodies() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let issue_id = create_draft(
+        &app,
+        json!({
+            "title": "Newsletter title",
+            "content_markdown": "Hello *readers*",
+            "content_html": "<p>Hand-written</p>",
+        }),
+    )
+    .await;
+
+    // Assert
+    let html = app
+        .get_newsletter_page_html(&format!("/{issue_id}/edit"))
+        .await;
+    assert!(
+        html.contains(r#"name="content_markdown" rows="15" cols="50">Hello *readers*</textarea>"#)
+    );
+    assert!(html.contains(r#"name="content_text" rows="10" cols="50"></textarea>"#));
+    assert!(html.contains(
+        r#"name="content_html" rows="10" cols="50">&lt;p&gt;Hand-written&lt;/p&gt;</textarea>"#
+    ));
+    let html = app
+        .get_newsletter_page_html(&format!("/{issue_id}/preview"))
+        .await;
+    assert!(html.contains("<pre>Hello readers</pre>"));
+}
+
+#[tokio::test]
+async fn an_unknown_issue_redirects_to_the_newsletters_page() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let response = app
+        .post_newsletter_action(&format!("/{}/publish", Uuid::new_v4()), &json!({}))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+}
@@ -0,0 +1,175 @@
+//! tests/api/openapi.rs
+use crate::helpers::spawn_app;
+use std::collections::BTreeSet;
+use syn::visit::Visit;
+use zero2prod::routes::ApiDoc;
+
+#[tokio::test]
+async fn the_openapi_document_is_served() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(format!("{}/api/openapi.json", app.address))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let document: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(document["openapi"], "3.1.0");
+    assert!(document["paths"]["/subscriptions"]["post"].is_object());
+    assert!(document["components"]["schemas"]["SubscribeForm"].is_object());
+    assert!(document["components"]["securitySchemes"]["api_token"].is_object());
+}
+
+#[tokio::test]
+async fn the_docs_page_browses_the_openapi_document() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(format!("{}/api/docs", app.address))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .contains(r#"url: "/api/openapi.json""#)
+    );
+}
+
+#[test]
+fn the_openapi_document_lists_exactly_the_routes_registered_in_startup() {
+    let source = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/src/startup.rs"))
+        .expect("Failed to read src/startup.rs.");
+    let file = syn::parse_file(&source).expect("Failed to parse src/startup.rs.");
+    let mut registered = RegisteredRoutes::default();
+    registered.visit_file(&file);
+    assert!(!registered.0.is_empty(), "No route found in startup::run.");
+
+    let document = <ApiDoc as utoipa::OpenApi>::openapi();
+    let documented: BTreeSet<(String, String)> = serde_json::to_value(&document.paths)
+        .unwrap()
+        .as_object()
+        .unwrap()
+        .iter()
+        .flat_map(|(path, operations)| {
+            operations
+                .as_object()
+                .unwrap()
+                .keys()
+                .map(move |method| (method.clone(), path.clone()))
+        })
+        .collect();
+
+    let undocumented: Vec<_> = registered.0.difference(&documented).collect();
+    let unregistered: Vec<_> = documented.difference(&registered.0).collect();
+    assert!(
+        undocumented.is_empty() && unregistered.is_empty(),
+        "The OpenAPI document has drifted from startup::run.\n\
+        Registered but not documented: {undocumented:?}\n\
+        Documented but not registered: {unregistered:?}"
+    );
+}
+
+/// The `(method, path)` of every route of the `App` built in `startup::run`,
+/// read from its `.route(..)` and `.service(web::scope(..)|web::resource(..))` calls.
+#[derive(Default)]
+struct RegisteredRoutes(BTreeSet<(String, String)>);
+
+impl<'ast> Visit<'ast> for RegisteredRoutes {
+    fn visit_expr(&mut self, expr: &'ast syn::Expr) {
+        if is_app_builder(expr) {
+            self.collect(expr, "");
+        } else {
+            syn::visit::visit_expr(self, expr);
+        }
+    }
+}
+
+impl RegisteredRoutes {
+    fn collect(&mut self, expr: &syn::Expr, prefix: &str) {
+        let syn::Expr::MethodCall(call) = expr else {
+            return;
+        };
+        self.collect(&call.receiver, prefix);
+        let args: Vec<&syn::Expr> = call.args.iter().collect();
+        match (call.method.to_string().as_str(), args.as_slice()) {
+            ("route", [path, route]) => {
+                let path = string_literal(path).expect("Route paths are literals.");
+                self.insert(route, format!("{prefix}{path}"));
+            }
+            ("route", [route]) => self.insert(route, prefix.to_string()),
+            ("service", [service]) => match root_call(service) {
+                Some(("scope" | "resource", path)) => {
+                    let path = path.expect("Scope and resource paths are literals.");
+                    self.collect(service, &format!("{prefix}{path}"));
+                }
+                _ => panic!("Unexpected service in startup::run."),
+            },
+            _ => {}
+        }
+    }
+
+    /// `route` is a `web::get().to(..)` chain.
+    fn insert(&mut self, route: &syn::Expr, path: String) {
+        let Some((method, _)) = root_call(route) else {
+            panic!("Unexpected route in startup::run.");
+        };
+        self.0.insert((method.to_string(), path));
+    }
+}
+
+/// The function called at the start of a method chain, with its first
+/// argument if it is a string literal: `("scope", Some("/admin"))` for
+/// `web::scope("/admin").wrap(..)`.
+fn root_call(expr: &syn::Expr) -> Option<(&'static str, Option<String>)> {
+    let mut expr = expr;
+    while let syn::Expr::MethodCall(call) = expr {
+        expr = &call.receiver;
+    }
+    let syn::Expr::Call(call) = expr else {
+        return None;
+    };
+    let syn::Expr::Path(function) = call.func.as_ref() else {
+        return None;
+    };
+    let name = function.path.segments.last()?.ident.to_string();
+    let name = ["scope", "resource", "get", "post", "put", "patch", "delete"]
+        .into_iter()
+        .find(|n| *n == name)?;
+    Some((name, call.args.first().and_then(string_literal)))
+}
+
+/// A method chain starting with `App::new()`.
+fn is_app_builder(expr: &syn::Expr) -> bool {
+    let mut receiver = expr;
+    while let syn::Expr::MethodCall(call) = receiver {
+        receiver = &call.receiver;
+    }
+    let syn::Expr::Call(call) = receiver else {
+        return false;
+    };
+    let syn::Expr::Path(function) = call.func.as_ref() else {
+        return false;
+    };
+    !std::ptr::eq(receiver, expr)
+        && function.path.segments.len() == 2
+        && function.path.segments[0].ident == "App"
+}
+
+fn string_literal(expr: &syn::Expr) -> Option<String> {
+    match expr {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(s),
+            ..
+        }) => Some(s.value()),
+        _ => None,
+    }
+}
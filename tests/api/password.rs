@@ -133,7 +133,7 @@ async fn you_should_be_able_to_login_with_the_new_passwords_on_success() {
 
     assert!(page_html.contains("Password changed successfully!"));
 
-    let response = app.get_logout().await;
+    let response = app.post_logout().await;
 
     assert_is_redirect_to(&response, "/login");
 
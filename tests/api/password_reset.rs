@@ -0,0 +1,288 @@
+//! tests/api/password_reset.rs
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+use reqwest::Url;
+use serde_json::json;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn set_test_user_email(app: &TestApp) {
+    sqlx::query!(
+        "UPDATE users SET email = 'admin@example.com' WHERE user_id = $1",
+        app.user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+/// Request a reset for the test user and return the link from the email.
+async fn get_reset_link(app: &TestApp) -> Url {
+    set_test_user_email(app).await;
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_password_reset(&json!({"username": app.user.username}))
+        .await;
+    let email_request = app.wait_for_email_requests(1).await.pop().unwrap();
+    app.get_confirmation_links(&email_request).html
+}
+
+fn token_from(link: &Url) -> String {
+    link.query_pairs()
+        .find(|(k, _)| k == "token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+#[tokio::test]
+async fn the_login_form_links_to_the_password_reset_form() {
+    let app = spawn_app().await;
+
+    let html_page = app.get_login_html().await;
+
+    assert!(html_page.contains(r#"href="/password-reset""#));
+}
+
+#[tokio::test]
+async fn requesting_a_reset_for_a_known_user_sends_an_email() {
+    // Arrange
+    let app = spawn_app().await;
+    set_test_user_email(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_password_reset(&json!({"username": app.user.username}))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/password-reset");
+    let html_page = app.get_password_reset_html().await;
+    assert!(html_page.contains("If an account matches what you entered"));
+    app.wait_for_email_requests(1).await;
+}
+
+#[tokio::test]
+async fn requesting_a_reset_by_email_address_works_too() {
+    // Arrange
+    let app = spawn_app().await;
+    set_test_user_email(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_password_reset(&json!({"username": "admin@example.com"}))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/password-reset");
+    app.wait_for_email_requests(1).await;
+}
+
+#[tokio::test]
+async fn requesting_a_reset_for_an_unknown_user_looks_the_same_but_sends_nothing() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_password_reset(&json!({"username": "nobody"}))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/password-reset");
+    let html_page = app.get_password_reset_html().await;
+    assert!(html_page.contains("If an account matches what you entered"));
+}
+
+#[tokio::test]
+async fn a_failing_email_provider_looks_the_same_as_an_unknown_user() {
+    // Arrange
+    let app = spawn_app().await;
+    set_test_user_email(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        // Permanent, a retry could still be running once the test is over.
+        .respond_with(ResponseTemplate::new(422))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_password_reset(&json!({"username": app.user.username}))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/password-reset");
+    let html_page = app.get_password_reset_html().await;
+    assert!(html_page.contains("If an account matches what you entered"));
+    app.wait_for_email_requests(1).await;
+}
+
+#[tokio::test]
+async fn a_password_can_be_reset_with_the_emailed_link() {
+    // Arrange
+    let app = spawn_app().await;
+    let link = get_reset_link(&app).await;
+    let new_password = uuid::Uuid::new_v4().to_string();
+
+    // Act - Part 1 - Follow the link
+    let response = reqwest::get(link.clone()).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .contains("Choose a new password")
+    );
+
+    // Act - Part 2 - Submit the new password
+    let response = app
+        .post_password_reset_confirm(&json!({
+            "token": token_from(&link),
+            "new_password": &new_password,
+            "verify_new_password": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 3 - The old password no longer works
+    let response = app
+        .post_login(&json!({"username": app.user.username, "password": app.user.password}))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 4 - The new one does
+    let response = app
+        .post_login(&json!({"username": app.user.username, "password": new_password}))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_reset_link_can_only_be_used_once() {
+    // Arrange
+    let app = spawn_app().await;
+    let link = get_reset_link(&app).await;
+    let new_password = uuid::Uuid::new_v4().to_string();
+    let body = json!({
+        "token": token_from(&link),
+        "new_password": &new_password,
+        "verify_new_password": &new_password,
+    });
+    app.post_password_reset_confirm(&body).await;
+
+    // Act
+    let response = app.post_password_reset_confirm(&body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/password-reset");
+    let html_page = app.get_password_reset_html().await;
+    assert!(html_page.contains("invalid or has expired"));
+}
+
+#[tokio::test]
+async fn an_expired_reset_link_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let link = get_reset_link(&app).await;
+    sqlx::query!("UPDATE password_reset_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let new_password = uuid::Uuid::new_v4().to_string();
+
+    // Act
+    let get_response = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .get(link.clone())
+        .send()
+        .await
+        .unwrap();
+    let post_response = app
+        .post_password_reset_confirm(&json!({
+            "token": token_from(&link),
+            "new_password": &new_password,
+            "verify_new_password": &new_password,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&get_response, "/password-reset");
+    assert_is_redirect_to(&post_response, "/password-reset");
+}
+
+#[tokio::test]
+async fn a_weak_new_password_is_rejected_without_using_up_the_link() {
+    // Arrange
+    let app = spawn_app().await;
+    let link = get_reset_link(&app).await;
+
+    // Act
+    let response = app
+        .post_password_reset_confirm(&json!({
+            "token": token_from(&link),
+            "new_password": "password",
+            "verify_new_password": "password",
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(
+        &response,
+        &format!("/password-reset/confirm?token={}", token_from(&link)),
+    );
+    let n_tokens = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM password_reset_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_tokens, 1);
+}
+
+#[tokio::test]
+async fn resetting_the_password_invalidates_existing_sessions() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let link = get_reset_link(&app).await;
+    let new_password = uuid::Uuid::new_v4().to_string();
+
+    // Act
+    app.post_password_reset_confirm(&json!({
+        "token": token_from(&link),
+        "new_password": &new_password,
+        "verify_new_password": &new_password,
+    }))
+    .await;
+
+    // Assert
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,157 @@
+//! tests/api/postgres_sessions.rs
+use crate::helpers::{TestApp, assert_is_redirect_to, new_browser, spawn_app_with};
+use serde_json::json;
+use zero2prod::configuration::SessionStoreKind;
+use zero2prod::session_store::delete_expired_entries;
+
+/// No Redis at all: sessions, the session registry and the login throttle
+/// all live in Postgres.
+async fn spawn_postgres_app() -> TestApp {
+    spawn_app_with(|c| {
+        c.session.store = SessionStoreKind::Postgres;
+        c.redis_uri = None;
+    })
+    .await
+}
+
+async fn count_rows(app: &TestApp, table: &str) -> i64 {
+    sqlx::query_scalar::<_, i64>(&format!("SELECT COUNT(*) FROM {table}"))
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn a_session_stored_in_postgres_survives_across_requests() {
+    // Arrange
+    let app = spawn_postgres_app().await;
+
+    // Act
+    let response = app.login_as(&app.user).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let html_page = app.get_admin_dashboard_html().await;
+
+    // Assert
+    assert!(html_page.contains(&format!("Welcome {}", app.user.username)));
+    assert_eq!(count_rows(&app, "session_registry").await, 1);
+}
+
+#[tokio::test]
+async fn session_keys_are_stored_hashed() {
+    // Arrange
+    let app = spawn_postgres_app().await;
+
+    // Act
+    app.login_as(&app.user).await;
+
+    // Assert
+    let key_hashes: Vec<String> = sqlx::query_scalar("SELECT session_key_hash FROM sessions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(!key_hashes.is_empty());
+    for key_hash in key_hashes {
+        assert_eq!(key_hash.len(), 64);
+        assert!(key_hash.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+}
+
+#[tokio::test]
+async fn logging_out_deletes_the_session_from_postgres() {
+    // Arrange
+    let app = spawn_postgres_app().await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let response = app.post_logout().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+    assert_eq!(count_rows(&app, "sessions").await, 0);
+    assert_eq!(count_rows(&app, "session_registry").await, 0);
+}
+
+#[tokio::test]
+async fn sessions_stored_in_postgres_can_be_revoked() {
+    // Arrange
+    let mut app = spawn_postgres_app().await;
+    app.login_as(&app.user).await;
+    let first_browser = std::mem::replace(&mut app.api_client, new_browser());
+    app.login_as(&app.user).await;
+    let html_page = app.get_sessions_html().await;
+    assert_eq!(html_page.matches("This session").count(), 1);
+
+    // Act
+    let response = app.post_revoke_other_sessions().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/sessions");
+    let html_page = app.get_sessions_html().await;
+    assert!(html_page.contains("1 other session(s) have been signed out."));
+    app.api_client = first_browser;
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn login_lockouts_work_with_postgres() {
+    // Arrange
+    let app = spawn_postgres_app().await;
+    for _ in 0..5 {
+        app.post_login(&json!({"username": app.user.username, "password": "wrong-password"}))
+            .await;
+    }
+
+    // Act - Part 1 - Locked out
+    let response = app.login_as(&app.user).await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Too many failed login attempts, please try again later."));
+
+    // Act - Part 2 - Cleared by hand
+    sqlx::query("DELETE FROM login_lockouts")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let response = app.login_as(&app.user).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let html_page = app.get_lockouts_html().await;
+    assert!(!html_page.contains(&app.user.username));
+}
+
+#[tokio::test]
+async fn expired_entries_are_cleaned_up() {
+    // Arrange
+    let app = spawn_postgres_app().await;
+    app.login_as(&app.user).await;
+    app.post_login(&json!({"username": "someone-else", "password": "wrong-password"}))
+        .await;
+    for table in ["sessions", "session_registry", "login_failures"] {
+        sqlx::query(&format!(
+            "UPDATE {table} SET expires_at = now() - interval '1 second'"
+        ))
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+
+    // Act
+    let n_deleted_rows = delete_expired_entries(&app.db_pool).await.unwrap();
+
+    // Assert
+    assert!(n_deleted_rows > 0);
+    for table in ["sessions", "session_registry", "login_failures"] {
+        assert_eq!(
+            count_rows(&app, table).await,
+            0,
+            "{table} was not cleaned up"
+        );
+    }
+    // Expired sessions are not loaded, even before being cleaned up.
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,134 @@
+//! tests/api/session_timeouts.rs
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app, spawn_app_with};
+use serde_json::json;
+use std::time::Duration;
+
+/// The `Max-Age` of the session cookie set by `response`, in seconds.
+fn session_cookie_max_age(response: &reqwest::Response) -> Option<u64> {
+    response
+        .headers()
+        .get_all("Set-Cookie")
+        .iter()
+        .map(|h| h.to_str().unwrap())
+        .find(|h| h.starts_with("id="))?
+        .split(';')
+        .find_map(|attribute| attribute.trim().strip_prefix("Max-Age="))
+        .map(|max_age| max_age.parse().unwrap())
+}
+
+async fn login_remembered(app: &TestApp) -> reqwest::Response {
+    app.post_login(&json!({
+        "username": app.user.username,
+        "password": app.user.password,
+        "remember_me": "on",
+    }))
+    .await
+}
+
+#[tokio::test]
+async fn the_login_form_has_a_remember_me_checkbox() {
+    let app = spawn_app().await;
+
+    let html_page = app.get_login_html().await;
+
+    assert!(html_page.contains(r#"<input type="checkbox" name="remember_me""#));
+}
+
+#[tokio::test]
+async fn an_idle_session_expires() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.session_idle_timeout_seconds = 1).await;
+    app.login_as(&app.user).await;
+
+    // Act
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Your session has expired, please log in again."));
+}
+
+#[tokio::test]
+async fn activity_keeps_a_session_alive() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.session_idle_timeout_seconds = 2).await;
+    app.login_as(&app.user).await;
+
+    // Act - Every request comes before the idle timeout, but the last one
+    // comes after the idle timeout has elapsed since logging in.
+    for _ in 0..3 {
+        tokio::time::sleep(Duration::from_millis(1200)).await;
+        let response = app.get_admin_dashboard().await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 200);
+    }
+}
+
+#[tokio::test]
+async fn a_session_expires_after_the_absolute_timeout_however_active() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.session_absolute_timeout_seconds = 2).await;
+    app.login_as(&app.user).await;
+
+    // Act
+    tokio::time::sleep(Duration::from_millis(1200)).await;
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+    tokio::time::sleep(Duration::from_millis(1200)).await;
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Your session has expired, please log in again."));
+}
+
+#[tokio::test]
+async fn a_remembered_session_survives_the_idle_timeout() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.session_idle_timeout_seconds = 1).await;
+    login_remembered(&app).await;
+
+    // Act
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn a_remembered_session_expires_after_the_remember_me_timeout() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.remember_me_timeout_seconds = 1).await;
+    login_remembered(&app).await;
+
+    // Act
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn remember_me_picks_a_longer_lived_cookie() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.application.session_absolute_timeout_seconds = 3600;
+        c.application.remember_me_timeout_seconds = 86400;
+    })
+    .await;
+
+    // Act - Part 1 - Without remember me
+    let response = app.login_as(&app.user).await;
+    assert_eq!(session_cookie_max_age(&response), Some(3600));
+    app.post_logout().await;
+
+    // Act - Part 2 - With remember me
+    let response = login_remembered(&app).await;
+    assert_eq!(session_cookie_max_age(&response), Some(86400));
+}
@@ -0,0 +1,233 @@
+//! tests/api/sessions.rs
+use crate::helpers::{TestApp, TestUser, assert_is_redirect_to, new_browser, spawn_app};
+use serde_json::json;
+
+/// The ids of the sessions that can be revoked from the sessions page, i.e.
+/// every session but the current one.
+fn revocable_session_ids(html_page: &str) -> Vec<String> {
+    html_page
+        .split(r#"action="/admin/sessions/"#)
+        .skip(1)
+        .filter_map(|rest| rest.split_once("/revoke\""))
+        .map(|(session_id, _)| session_id.to_string())
+        .collect()
+}
+
+/// Log `user` in from a first browser, then from a second one. `app` is left
+/// acting from the second browser, the first one is returned.
+async fn log_in_twice(app: &mut TestApp, user: &TestUser) -> reqwest::Client {
+    let response = app.login_as(user).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let first_browser = std::mem::replace(&mut app.api_client, new_browser());
+    let response = app.login_as(user).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    first_browser
+}
+
+#[tokio::test]
+async fn the_dashboard_links_to_the_sessions_page() {
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+
+    let html_page = app.get_admin_dashboard_html().await;
+
+    assert!(html_page.contains(r#"href="/admin/sessions""#));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_your_sessions() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/sessions", app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_sessions_page_lists_every_session_of_the_user() {
+    // Arrange
+    let mut app = spawn_app().await;
+    let user = app.user.clone();
+    log_in_twice(&mut app, &user).await;
+
+    // Act
+    let html_page = app.get_sessions_html().await;
+
+    // Assert
+    assert_eq!(html_page.matches("This session").count(), 1);
+    assert_eq!(revocable_session_ids(&html_page).len(), 1);
+    assert!(html_page.contains("127.0.0.1"));
+}
+
+#[tokio::test]
+async fn sessions_of_other_users_are_not_listed() {
+    // Arrange
+    let mut app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    app.login_as(&editor).await;
+    app.api_client = new_browser();
+    app.login_as(&app.user).await;
+
+    // Act
+    let html_page = app.get_sessions_html().await;
+
+    // Assert
+    assert!(revocable_session_ids(&html_page).is_empty());
+}
+
+#[tokio::test]
+async fn a_revoked_session_is_logged_out() {
+    // Arrange
+    let mut app = spawn_app().await;
+    let user = app.user.clone();
+    let first_browser = log_in_twice(&mut app, &user).await;
+    let session_id = revocable_session_ids(&app.get_sessions_html().await).remove(0);
+
+    // Act - Part 1 - Revoke the first session from the second one
+    let response = app.post_revoke_session(&session_id).await;
+    assert_is_redirect_to(&response, "/admin/sessions");
+    let html_page = app.get_sessions_html().await;
+    assert!(html_page.contains("The session has been signed out."));
+    assert!(revocable_session_ids(&html_page).is_empty());
+
+    // Act - Part 2 - Back to the first browser
+    let second_browser = std::mem::replace(&mut app.api_client, first_browser);
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Your session has been signed out, please log in again."));
+
+    // Act - Part 3 - The second session is still alive
+    app.api_client = second_browser;
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn all_other_sessions_can_be_revoked_at_once() {
+    // Arrange
+    let mut app = spawn_app().await;
+    let user = app.user.clone();
+    let first_browser = log_in_twice(&mut app, &user).await;
+    let second_browser = std::mem::replace(&mut app.api_client, new_browser());
+    app.login_as(&app.user).await;
+
+    // Act
+    let response = app.post_revoke_other_sessions().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/sessions");
+    let html_page = app.get_sessions_html().await;
+    assert!(html_page.contains("2 other session(s) have been signed out."));
+    assert!(revocable_session_ids(&html_page).is_empty());
+    for browser in [first_browser, second_browser] {
+        let response = browser
+            .get(format!("{}/admin/dashboard", app.address))
+            .send()
+            .await
+            .unwrap();
+        assert_is_redirect_to(&response, "/login");
+    }
+}
+
+#[tokio::test]
+async fn the_current_session_cannot_be_revoked_from_the_sessions_page() {
+    // Arrange
+    let mut app = spawn_app().await;
+    let user = app.user.clone();
+    let first_browser = log_in_twice(&mut app, &user).await;
+    let first_session_id = revocable_session_ids(&app.get_sessions_html().await).remove(0);
+    app.api_client = first_browser;
+
+    // Act
+    let response = app.post_revoke_session(&first_session_id).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/sessions");
+    let html_page = app.get_sessions_html().await;
+    assert!(html_page.contains("Use the logout button to end the current session."));
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn users_cannot_revoke_sessions_of_other_users() {
+    // Arrange
+    let mut app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    let first_editor_browser = log_in_twice(&mut app, &editor).await;
+    let session_id = revocable_session_ids(&app.get_sessions_html().await).remove(0);
+    app.api_client = new_browser();
+    app.login_as(&app.user).await;
+
+    // Act
+    let response = app.post_revoke_session(&session_id).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/sessions");
+    let html_page = app.get_sessions_html().await;
+    assert!(html_page.contains("There is no such session"));
+    let response = first_editor_browser
+        .get(format!("{}/admin/dashboard", app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn logging_out_removes_the_session() {
+    // Arrange
+    let mut app = spawn_app().await;
+    let user = app.user.clone();
+    let first_browser = log_in_twice(&mut app, &user).await;
+    let second_browser = std::mem::replace(&mut app.api_client, first_browser);
+
+    // Act
+    app.post_logout().await;
+
+    // Assert
+    app.api_client = second_browser;
+    let html_page = app.get_sessions_html().await;
+    assert!(revocable_session_ids(&html_page).is_empty());
+}
+
+#[tokio::test]
+async fn changing_password_signs_out_the_other_sessions() {
+    // Arrange
+    let mut app = spawn_app().await;
+    let user = app.user.clone();
+    let first_browser = log_in_twice(&mut app, &user).await;
+    let new_password = uuid::Uuid::new_v4().to_string();
+
+    // Act
+    let response = app
+        .post_change_password(&json!({
+            "current_password": &app.user.password,
+            "new_password": &new_password,
+            "verify_new_password": &new_password,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/password");
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("Your other sessions have been signed out."));
+    // The session the password was changed from is kept...
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+    // ...the other one is not.
+    let response = first_browser
+        .get(format!("{}/admin/dashboard", app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/login");
+}
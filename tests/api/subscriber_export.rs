@@ -0,0 +1,271 @@
+//! tests/api/subscriber_export.rs
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+use chrono::{DateTime, TimeZone, Utc};
+use uuid::Uuid;
+
+async fn insert_subscriber(
+    app: &TestApp,
+    email: &str,
+    name: &str,
+    status: &str,
+    subscribed_at: DateTime<Utc>,
+) -> Uuid {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        r#"INSERT INTO subscriptions (id, email, name, subscribed_at, status) VALUES ($1, $2, $3, $4, $5)"#,
+        id,
+        email,
+        name,
+        subscribed_at,
+        status
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    id
+}
+
+fn day(year: i32, month: u32, day: u32, hour: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(year, month, day, hour, 0, 0).unwrap()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_export_the_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_subscriber_export("").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn subscribers_are_exported_as_csv_filtered_by_status() {
+    // Arrange
+    let app = spawn_app().await;
+    let id = insert_subscriber(
+        &app,
+        "ursula@example.com",
+        "Le Guin, Ursula",
+        "confirmed",
+        day(2025, 3, 1, 12),
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "octavia@example.com",
+        "Butler",
+        "pending_confirmation",
+        day(2025, 3, 2, 12),
+    )
+    .await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let response = app.get_subscriber_export("status=confirmed").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["content-type"],
+        "text/csv; charset=utf-8"
+    );
+    assert!(
+        response.headers()["content-disposition"]
+            .to_str()
+            .unwrap()
+            .contains("subscribers.csv")
+    );
+    let body = response.text().await.unwrap();
+    let mut reader = csv::Reader::from_reader(body.as_bytes());
+    assert_eq!(
+        reader.headers().unwrap(),
+        vec!["id", "email", "name", "status", "subscribed_at"]
+    );
+    let records: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+    assert_eq!(records.len(), 1);
+    assert_eq!(
+        records[0],
+        vec![
+            id.to_string().as_str(),
+            "ursula@example.com",
+            "Le Guin, Ursula",
+            "confirmed",
+            "2025-03-01T12:00:00+00:00",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn csv_cells_that_spreadsheets_would_evaluate_are_exported_as_text() {
+    // Arrange
+    let app = spawn_app().await;
+    for (email, name) in [
+        ("equals@example.com", "=HYPERLINK(\"https://evil.example\")"),
+        ("plus@example.com", "+1"),
+        ("minus@example.com", "-1"),
+        ("@at@example.com", "@SUM(A1)"),
+    ] {
+        insert_subscriber(&app, email, name, "confirmed", day(2025, 3, 1, 12)).await;
+    }
+    app.login_as(&app.user).await;
+
+    // Act
+    let response = app.get_subscriber_export("").await;
+
+    // Assert
+    let body = response.text().await.unwrap();
+    let mut reader = csv::Reader::from_reader(body.as_bytes());
+    let mut cells: Vec<(String, String)> = reader
+        .records()
+        .map(|record| {
+            let record = record.unwrap();
+            (record[1].to_owned(), record[2].to_owned())
+        })
+        .collect();
+    cells.sort();
+    assert_eq!(
+        cells,
+        vec![
+            ("'@at@example.com".to_owned(), "'@SUM(A1)".to_owned()),
+            (
+                "equals@example.com".to_owned(),
+                "'=HYPERLINK(\"https://evil.example\")".to_owned()
+            ),
+            ("minus@example.com".to_owned(), "'-1".to_owned()),
+            ("plus@example.com".to_owned(), "'+1".to_owned()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn subscribers_are_exported_as_ndjson_filtered_by_signup_date() {
+    // Arrange
+    let app = spawn_app().await;
+    insert_subscriber(
+        &app,
+        "before@example.com",
+        "Before",
+        "confirmed",
+        day(2025, 2, 28, 23),
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "first@example.com",
+        "First",
+        "confirmed",
+        day(2025, 3, 1, 0),
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "last@example.com",
+        "Last",
+        "unsubscribed",
+        day(2025, 3, 31, 23),
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "after@example.com",
+        "After",
+        "confirmed",
+        day(2025, 4, 1, 0),
+    )
+    .await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let response = app
+        .get_subscriber_export("format=ndjson&from=2025-03-01&to=2025-03-31")
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+    let body = response.text().await.unwrap();
+    let emails: Vec<String> = body
+        .lines()
+        .map(|line| {
+            let subscriber: serde_json::Value = serde_json::from_str(line).unwrap();
+            subscriber["email"].as_str().unwrap().to_owned()
+        })
+        .collect();
+    // `to` includes the whole day, oldest first.
+    assert_eq!(emails, vec!["first@example.com", "last@example.com"]);
+}
+
+#[tokio::test]
+async fn empty_filters_export_every_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        SELECT gen_random_uuid(), 'subscriber-' || n || '@example.com', 'Subscriber ' || n,
+            now() - n * interval '1 minute', 'confirmed'
+        FROM generate_series(1, 1000) AS n
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.login_as(&app.user).await;
+
+    // Act - what the form on the subscribers page submits
+    let response = app
+        .get_subscriber_export("format=csv&status=&from=&to=")
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.text().await.unwrap();
+    let mut reader = csv::Reader::from_reader(body.as_bytes());
+    let emails: Vec<String> = reader
+        .records()
+        .map(|record| record.unwrap()[1].to_owned())
+        .collect();
+    assert_eq!(emails.len(), 1000);
+    assert_eq!(emails[0], "subscriber-1000@example.com");
+    assert_eq!(emails[999], "subscriber-1@example.com");
+}
+
+#[tokio::test]
+async fn invalid_filters_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+
+    for query in [
+        "format=xml",
+        "status=bounced",
+        "from=yesterday",
+        "to=2025-02-30",
+    ] {
+        // Act
+        let response = app.get_subscriber_export(query).await;
+
+        // Assert
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The export did not fail with {query}."
+        );
+    }
+}
+
+#[tokio::test]
+async fn the_subscribers_page_has_an_export_form() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let html = app.get_admin_subscribers_html("").await;
+
+    // Assert
+    assert!(html.contains(r#"<form action="/admin/subscribers/export" method="get">"#));
+}
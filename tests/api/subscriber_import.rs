@@ -0,0 +1,257 @@
+//! tests/api/subscriber_import.rs
+use crate::helpers::{TestApp, TestUser, assert_is_redirect_to, spawn_app};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn subscriber_statuses(app: &TestApp) -> Vec<(String, String)> {
+    sqlx::query!("SELECT email, status FROM subscriptions ORDER BY email")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| (r.email, r.status))
+        .collect()
+}
+
+/// The link of the "Download the report" button of the result page.
+fn report_link(html: &str) -> String {
+    let (before, _) = html.split_once(r#"">Download the report"#).unwrap();
+    let (_, link) = before.rsplit_once(r#"href=""#).unwrap();
+    link.to_string()
+}
+
+#[tokio::test]
+async fn valid_rows_are_imported_as_confirmed_and_the_others_reported() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+    sqlx::query!(
+        "INSERT INTO subscriptions (id, email, name, subscribed_at, status) \
+        VALUES (gen_random_uuid(), 'existing@example.com', 'Existing', now(), 'unsubscribed')"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let csv = "name,email\n\
+        Ursula,ursula@example.com\n\
+        Octavia,not-an-email\n\
+        Existing,existing@example.com\n\
+        Ursula again,URSULA@example.com\n\
+        Octavia,octavia@example.com\n";
+
+    // Act
+    let response = app.post_subscriber_import(csv, "confirmed").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains("<li>Imported: 2</li>"));
+    assert!(html.contains("<li>Rejected: 1</li>"));
+    assert!(html.contains("<li>Skipped duplicates: 2</li>"));
+    assert_eq!(
+        subscriber_statuses(&app).await,
+        vec![
+            ("existing@example.com".into(), "unsubscribed".into()),
+            ("octavia@example.com".into(), "confirmed".into()),
+            ("ursula@example.com".into(), "confirmed".into()),
+        ]
+    );
+
+    // Act - Part 2 - Download the report
+    let report = app
+        .api_client
+        .get(format!("{}{}", app.address, report_link(&html)))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(report.status().as_u16(), 200);
+    assert_eq!(report.headers()["Content-Type"], "text/csv; charset=utf-8");
+    assert_eq!(
+        report.text().await.unwrap(),
+        "line,name,email,outcome,reason\n\
+        3,Octavia,not-an-email,rejected,not-an-email is an invalid email address.\n\
+        4,Existing,existing@example.com,skipped,Already on the list.\n\
+        5,Ursula again,URSULA@example.com,skipped,Same email as line 2.\n"
+    );
+}
+
+#[tokio::test]
+async fn double_opt_in_imports_send_working_confirmation_links() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    let csv = "email,name\nursula@example.com,Ursula\noctavia@example.com,Octavia\n";
+
+    // Act
+    let response = app.post_subscriber_import(csv, "double_opt_in").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        subscriber_statuses(&app)
+            .await
+            .iter()
+            .all(|(_, status)| status == "pending_confirmation")
+    );
+    let email_requests = app.wait_for_email_requests(2).await;
+    let confirmation_links = app.get_confirmation_links(&email_requests[0]);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let confirmed = subscriber_statuses(&app)
+        .await
+        .into_iter()
+        .filter(|(_, status)| status == "confirmed")
+        .count();
+    assert_eq!(confirmed, 1);
+}
+
+#[tokio::test]
+async fn confirmation_emails_that_cannot_be_sent_are_added_to_the_report() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        // Permanent, a retry could still be running once the test is over.
+        .respond_with(ResponseTemplate::new(422))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriber_import(
+            "name,email
+Ursula,ursula@example.com
+",
+            "double_opt_in",
+        )
+        .await;
+
+    // Assert - The response does not wait for the emails
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains("<li>Confirmation emails on their way: 1</li>"));
+    let report_url = format!("{}{}", app.address, report_link(&html));
+    for _ in 0..50 {
+        let report = app
+            .api_client
+            .get(&report_url)
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        if report.lines().count() > 1 {
+            assert_eq!(
+                report,
+                "line,name,email,outcome,reason\n\
+                2,Ursula,ursula@example.com,email_not_sent,\
+                \"The confirmation email could not be sent, resend it from the subscribers page.\"\n"
+            );
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    panic!("The unsent email was not added to the report.");
+}
+
+#[tokio::test]
+async fn a_file_without_the_expected_columns_imports_nothing() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let response = app
+        .post_subscriber_import("ursula@example.com,Ursula\n", "confirmed")
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/subscribers/import");
+    let html = app.get_subscriber_import_html().await;
+    assert!(html.contains(
+        "<p><i>The first line must name the columns, with a name and an email column.</i></p>"
+    ));
+    assert!(subscriber_statuses(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn imports_without_a_csrf_token_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+    let form = reqwest::multipart::Form::new()
+        .text("mode", "confirmed")
+        .text("file", "name,email\nUrsula,ursula@example.com\n");
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/subscribers/import", app.address))
+        .multipart(form)
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(subscriber_statuses(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn viewers_cannot_import_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    let viewer = TestUser::generate_with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    app.login_as(&viewer).await;
+
+    // Act
+    let response = app
+        .post_subscriber_import("name,email\nUrsula,ursula@example.com\n", "confirmed")
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(subscriber_statuses(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn import_reports_are_only_available_to_whoever_ran_the_import() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+    let html = app
+        .post_subscriber_import("name,email\nUrsula,ursula@example.com\n", "confirmed")
+        .await
+        .text()
+        .await
+        .unwrap();
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    app.login_as(&editor).await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}{}", app.address, report_link(&html)))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
@@ -33,7 +33,7 @@ async fn the_link_returned_by_subscribe_returns_a_200_if_called() {
     let body = "name=Kevin%20Kenyon&email=kvkenyon%40gmail.com";
     test_app.post_subscriptions(body.into()).await;
 
-    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let email_request = &test_app.wait_for_email_requests(1).await[0];
 
     let confirmation_links = test_app.get_confirmation_links(email_request);
 
@@ -55,7 +55,7 @@ async fn clicking_confirmation_link_confirms_the_subscriber() {
     let body = "name=Kevin%20Kenyon&email=kvkenyon%40gmail.com";
     test_app.post_subscriptions(body.into()).await;
 
-    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let email_request = &test_app.wait_for_email_requests(1).await[0];
 
     let confirmation_links = test_app.get_confirmation_links(email_request);
 
@@ -85,7 +85,7 @@ async fn confirm_fails_if_there_is_a_fatal_database_error() {
     let body = "name=Kevin%20Kenyon&email=kvkenyon%40gmail.com";
     test_app.post_subscriptions(body.into()).await;
 
-    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let email_request = &test_app.wait_for_email_requests(1).await[0];
 
     let confirmation_links = test_app.get_confirmation_links(email_request);
 
@@ -97,3 +97,193 @@ async fn confirm_fails_if_there_is_a_fatal_database_error() {
     let response = reqwest::get(confirmation_links.text).await.unwrap();
     assert_eq!(response.status().as_u16(), 500);
 }
+
+#[tokio::test]
+async fn an_expired_confirmation_link_is_rejected_with_a_410() {
+    let test_app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let body = "name=Kevin%20Kenyon&email=kvkenyon%40gmail.com";
+    test_app.post_subscriptions(body.into()).await;
+
+    let email_request = &test_app.wait_for_email_requests(1).await[0];
+    let confirmation_links = test_app.get_confirmation_links(email_request);
+
+    sqlx::query!("UPDATE subscription_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+
+    let response = reqwest::get(confirmation_links.text).await.unwrap();
+    assert_eq!(response.status().as_u16(), 410);
+
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .expect("Failed to query for subscriber.");
+    assert_eq!(saved.status, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn a_confirmation_link_can_only_be_used_once() {
+    let test_app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let body = "name=Kevin%20Kenyon&email=kvkenyon%40gmail.com";
+    test_app.post_subscriptions(body.into()).await;
+
+    let email_request = &test_app.wait_for_email_requests(1).await[0];
+    let confirmation_links = test_app.get_confirmation_links(email_request);
+
+    let response = reqwest::get(confirmation_links.text.clone()).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let response = reqwest::get(confirmation_links.text).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn resending_the_confirmation_email_issues_a_fresh_link() {
+    let test_app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&test_app.email_server)
+        .await;
+
+    let body = "name=Kevin%20Kenyon&email=kvkenyon%40gmail.com";
+    test_app.post_subscriptions(body.into()).await;
+    test_app.wait_for_email_requests(1).await;
+
+    let response = test_app
+        .post_resend_confirmation("email=kvkenyon%40gmail.com".into())
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let email_requests = test_app.wait_for_email_requests(2).await;
+    let first_links = test_app.get_confirmation_links(&email_requests[0]);
+    let fresh_links = test_app.get_confirmation_links(&email_requests[1]);
+    assert_ne!(first_links.text, fresh_links.text);
+
+    // The previous link is superseded by the new one.
+    let response = reqwest::get(first_links.text).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    let response = reqwest::get(fresh_links.text).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .expect("Failed to query for subscriber.");
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn resending_the_confirmation_email_to_an_unknown_address_sends_nothing() {
+    let test_app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    let response = test_app
+        .post_resend_confirmation("email=kvkenyon%40gmail.com".into())
+        .await;
+    // Give the background task a chance to send an email.
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn resending_the_confirmation_email_to_a_confirmed_subscriber_sends_nothing() {
+    let test_app = spawn_app().await;
+
+    let mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&test_app.email_server)
+        .await;
+
+    let body = "name=Kevin%20Kenyon&email=kvkenyon%40gmail.com";
+    test_app.post_subscriptions(body.into()).await;
+    let email_request = &test_app.wait_for_email_requests(1).await[0];
+    let confirmation_links = test_app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.text).await.unwrap();
+    drop(mock_guard);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    let response = test_app
+        .post_resend_confirmation("email=kvkenyon%40gmail.com".into())
+        .await;
+    // Give the background task a chance to send an email.
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn resending_to_a_pending_address_looks_the_same_when_the_email_provider_fails() {
+    let test_app = spawn_app().await;
+
+    let mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&test_app.email_server)
+        .await;
+    let body = "name=Kevin%20Kenyon&email=kvkenyon%40gmail.com";
+    test_app.post_subscriptions(body.into()).await;
+    test_app.wait_for_email_requests(1).await;
+    drop(mock_guard);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        // Permanent, a retry could still be running once the test is over.
+        .respond_with(ResponseTemplate::new(422))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let response = test_app
+        .post_resend_confirmation("email=kvkenyon%40gmail.com".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    // The send was attempted, after the response.
+    test_app.wait_for_email_requests(2).await;
+}
+
+#[tokio::test]
+async fn resending_the_confirmation_email_to_an_invalid_address_returns_a_400() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .post_resend_confirmation("email=definitely-not-an-email".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
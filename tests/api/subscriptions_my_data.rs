@@ -0,0 +1,374 @@
+//! tests/api/subscriptions_my_data.rs
+use crate::helpers::{TestApp, spawn_app};
+use chrono::{Duration, Utc};
+use reqwest::Url;
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::domain::DataRequestToken;
+
+const BODY: &str = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+const EMAIL: &str = "ursula_le_guin@gmail.com";
+
+/// Subscribe, then ask for a data request link and return it.
+async fn get_my_data_link(app: &TestApp) -> Url {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions(BODY.into())
+        .await
+        .error_for_status()
+        .unwrap();
+    app.wait_for_email_requests(1).await;
+    app.post_my_data_request("email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // The confirmation email, then the data request email.
+    let email_request = app.wait_for_email_requests(2).await.pop().unwrap();
+    app.get_confirmation_links(&email_request).html
+}
+
+fn with_path(link: &Url, path: &str) -> Url {
+    let mut link = link.clone();
+    link.set_path(path);
+    link
+}
+
+async fn audit_actions(app: &TestApp) -> Vec<String> {
+    sqlx::query_scalar!("SELECT action FROM audit_log ORDER BY created_at")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn an_address_that_is_not_on_the_list_gets_a_200_and_no_email() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_my_data_request("email=nobody%40example.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn a_failing_email_provider_does_not_reveal_that_the_address_is_on_the_list() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .up_to_n_times(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions(BODY.into())
+        .await
+        .error_for_status()
+        .unwrap();
+    app.wait_for_email_requests(1).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        // Permanent, a retry could still be running once the test is over.
+        .respond_with(ResponseTemplate::new(422))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_my_data_request("email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    // The send was attempted, after the response.
+    app.wait_for_email_requests(2).await;
+}
+
+#[tokio::test]
+async fn an_invalid_email_is_rejected_with_a_400() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.post_my_data_request("email=not-an-email".into()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn the_link_offers_to_download_or_erase_without_erasing_anything() {
+    // Arrange
+    let app = spawn_app().await;
+    let link = get_my_data_link(&app).await;
+
+    // Act
+    let response = reqwest::get(link).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains(EMAIL));
+    assert!(html.contains("/subscriptions/my-data/export?token="));
+    assert!(html.contains(r#"<form action="/subscriptions/my-data/erase?token="#));
+    let count = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+}
+
+#[tokio::test]
+async fn the_export_has_everything_we_store_about_the_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let link = get_my_data_link(&app).await;
+
+    // Act
+    let response = reqwest::get(with_path(&link, "/subscriptions/my-data/export"))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        response.headers()["content-disposition"]
+            .to_str()
+            .unwrap()
+            .contains("my-data.json")
+    );
+    let data: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(data["subscriber"]["email"], EMAIL);
+    assert_eq!(data["subscriber"]["name"], "le guin");
+    assert_eq!(data["subscriber"]["status"], "pending_confirmation");
+    assert_eq!(data["subscription_tokens"].as_array().unwrap().len(), 1);
+    assert!(data["deliveries"].as_array().unwrap().is_empty());
+    assert!(data["pending_deliveries"].as_array().unwrap().is_empty());
+    assert!(data["import_reports"].as_array().unwrap().is_empty());
+    assert_eq!(audit_actions(&app).await, vec!["subscriber_data_exported"]);
+}
+
+#[tokio::test]
+async fn the_export_and_the_erasure_cover_the_delivery_history() {
+    // Arrange - Confirm the subscriber and send them an issue
+    let app = spawn_app().await;
+    let link = get_my_data_link(&app).await;
+    let confirmation_request = &app.email_server.received_requests().await.unwrap()[0];
+    reqwest::get(app.get_confirmation_links(confirmation_request).html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    app.login_as(&app.user).await;
+    app.post_newsletters(serde_json::json!({
+        "title": "Newsletter title",
+        "content_text": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Act
+    let data: serde_json::Value = reqwest::get(with_path(&link, "/subscriptions/my-data/export"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    // Assert
+    let deliveries = data["deliveries"].as_array().unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0]["title"], "Newsletter title");
+    assert_eq!(deliveries[0]["outcome"], "delivered");
+
+    // Act - Part 2 - Erase
+    reqwest::Client::new()
+        .post(with_path(&link, "/subscriptions/my-data/erase"))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let count = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_deliveries"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+}
+
+#[tokio::test]
+async fn erasure_deletes_the_subscriber_and_keeps_a_suppression_record() {
+    // Arrange
+    let app = spawn_app().await;
+    let link = get_my_data_link(&app).await;
+    let erase_link = with_path(&link, "/subscriptions/my-data/erase");
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(erase_link.clone())
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let subscribers = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let tokens = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM subscription_tokens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let suppressed = sqlx::query_scalar!(r#"SELECT email_hash FROM suppressed_emails"#)
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscribers, 0);
+    assert_eq!(tokens, 0);
+    assert_eq!(suppressed.len(), 1);
+    assert!(!suppressed[0].contains(EMAIL));
+    assert_eq!(audit_actions(&app).await, vec!["subscriber_erased"]);
+
+    // Act - Part 2 - The link does not work anymore
+    let response = reqwest::Client::new()
+        .post(erase_link)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn an_erased_address_is_never_mailed_again() {
+    // Arrange
+    let app = spawn_app().await;
+    let link = get_my_data_link(&app).await;
+    reqwest::Client::new()
+        .post(with_path(&link, "/subscriptions/my-data/erase"))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let emails_sent = app.email_server.received_requests().await.unwrap().len();
+
+    // Act - Subscribing again looks like it worked
+    let subscribe = app.post_subscriptions(BODY.into()).await;
+    let request = app
+        .post_my_data_request("email=ursula_le_guin%40gmail.com".into())
+        .await;
+    // Give the background task a chance to send an email.
+    tokio::time::sleep(Duration::milliseconds(500).to_std().unwrap()).await;
+
+    // Assert
+    assert_eq!(subscribe.status().as_u16(), 200);
+    assert_eq!(request.status().as_u16(), 200);
+    assert_eq!(
+        app.email_server.received_requests().await.unwrap().len(),
+        emails_sent
+    );
+    let count = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+
+    // Act - Part 2 - Nor through an import
+    app.login_as(&app.user).await;
+    let response = app
+        .post_subscriber_import("name,email\nUrsula,URSULA_LE_GUIN@gmail.com\n", "confirmed")
+        .await;
+    let html = response.text().await.unwrap();
+    assert!(html.contains("<li>Imported: 0</li>"));
+}
+
+#[tokio::test]
+async fn erasure_removes_the_address_from_import_reports() {
+    // Arrange
+    let app = spawn_app().await;
+    let link = get_my_data_link(&app).await;
+    app.login_as(&app.user).await;
+    app.post_subscriber_import(
+        "name,email\nUrsula,ursula_le_guin@gmail.com\nOctavia,octavia@example.com\nOctavia,octavia@example.com\n",
+        "confirmed",
+    )
+    .await
+    .error_for_status()
+    .unwrap();
+    let data: serde_json::Value = reqwest::get(with_path(&link, "/subscriptions/my-data/export"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(data["import_reports"][0]["line"], "2");
+    assert_eq!(data["import_reports"][0]["outcome"], "skipped");
+
+    // Act
+    reqwest::Client::new()
+        .post(with_path(&link, "/subscriptions/my-data/erase"))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let report = sqlx::query_scalar!("SELECT report FROM subscriber_imports")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(!report.contains(EMAIL));
+    assert!(!report.contains("Ursula"));
+    assert!(report.contains("2,,,skipped"));
+    // The other rows are untouched.
+    assert!(report.contains("octavia@example.com"));
+}
+
+#[tokio::test]
+async fn invalid_or_expired_links_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let expired = DataRequestToken::generate(
+        Uuid::new_v4(),
+        Utc::now() - Duration::minutes(1),
+        &app.hmac_secret,
+    );
+    let forged = DataRequestToken::generate(
+        Uuid::new_v4(),
+        Utc::now() + Duration::days(1),
+        &secrecy::Secret::new("not-the-secret".to_string()),
+    );
+
+    for (token, status) in [(expired.as_ref(), 410), (forged.as_ref(), 401)] {
+        // Act
+        let response = reqwest::get(format!(
+            "{}/subscriptions/my-data/export?token={token}",
+            app.address
+        ))
+        .await
+        .unwrap();
+
+        // Assert
+        assert_eq!(response.status().as_u16(), status);
+    }
+}
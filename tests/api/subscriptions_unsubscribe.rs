@@ -0,0 +1,187 @@
+//! tests/api/subscriptions_unsubscribe.rs
+use crate::helpers::{TestApp, spawn_app};
+use reqwest::Url;
+use serde_json::json;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn create_confirmed_subscriber(app: &TestApp) {
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let email_request = &app.wait_for_email_requests(1).await[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+async fn publish_newsletter(app: &TestApp) {
+    app.post_login(&json!({
+        "username": app.user.username,
+        "password": app.user.password
+    }))
+    .await;
+    app.post_newsletters(json!({
+        "title": "Newsletter title",
+        "content_text": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+}
+
+/// Publish an issue to a single confirmed subscriber and return the
+/// unsubscribe link it was sent with.
+async fn get_unsubscribe_link(app: &TestApp) -> Url {
+    create_confirmed_subscriber(app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    publish_newsletter(app).await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    app.get_unsubscribe_link(&email_request)
+}
+
+#[tokio::test]
+async fn newsletters_carry_one_click_unsubscribe_headers_and_a_footer_link() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let link = get_unsubscribe_link(&app).await;
+
+    // Assert
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let headers = body["Headers"].as_array().unwrap();
+    assert!(headers.contains(&json!({
+        "Name": "List-Unsubscribe-Post",
+        "Value": "List-Unsubscribe=One-Click"
+    })));
+    assert_eq!(link.path(), "/subscriptions/unsubscribe");
+    let token = link.query_pairs().find(|(k, _)| k == "token").unwrap().1;
+    assert!(body["HtmlBody"].as_str().unwrap().contains(token.as_ref()));
+    assert!(body["TextBody"].as_str().unwrap().contains(token.as_ref()));
+}
+
+#[tokio::test]
+async fn one_click_unsubscribe_marks_the_subscriber_as_unsubscribed() {
+    // Arrange
+    let app = spawn_app().await;
+    let link = get_unsubscribe_link(&app).await;
+
+    // Act
+    let response = app.post_unsubscribe(&link).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "unsubscribed");
+}
+
+#[tokio::test]
+async fn the_unsubscribe_page_asks_for_confirmation_without_unsubscribing() {
+    // Arrange
+    let app = spawn_app().await;
+    let link = get_unsubscribe_link(&app).await;
+
+    // Act
+    let response = reqwest::get(link).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(r#"method="post""#));
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn unsubscribing_with_a_forged_token_is_rejected_with_a_401() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut link = get_unsubscribe_link(&app).await;
+    let token = link.query_pairs().find(|(k, _)| k == "token").unwrap().1;
+    let (_, tag) = token.split_once('.').unwrap();
+    let forged = format!("{}.{}", uuid::Uuid::new_v4().simple(), tag);
+    link.set_query(Some(&format!("token={forged}")));
+
+    // Act
+    let get_response = reqwest::get(link.clone()).await.unwrap();
+    let post_response = app.post_unsubscribe(&link).await;
+
+    // Assert
+    assert_eq!(get_response.status().as_u16(), 401);
+    assert_eq!(post_response.status().as_u16(), 401);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn unsubscribed_subscribers_do_not_receive_further_issues() {
+    // Arrange
+    let app = spawn_app().await;
+    let link = get_unsubscribe_link(&app).await;
+    app.post_unsubscribe(&link)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    publish_newsletter(&app).await;
+
+    // Assert
+    // The mock mounted by `get_unsubscribe_link` expects exactly one email.
+    let n_issues_sent = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|r| {
+            let body: serde_json::Value = serde_json::from_slice(&r.body).unwrap();
+            body["Subject"] == "Newsletter title"
+        })
+        .count();
+    assert_eq!(n_issues_sent, 1);
+}
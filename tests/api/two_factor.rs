@@ -0,0 +1,326 @@
+//! tests/api/two_factor.rs
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+use serde_json::json;
+use std::time::{SystemTime, UNIX_EPOCH};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+fn totp(secret: &str) -> TOTP {
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        Secret::Encoded(secret.to_string()).to_bytes().unwrap(),
+        None,
+        "test".to_string(),
+    )
+    .unwrap()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn extract_between<'a>(html: &'a str, start: &str, end: &str) -> Vec<&'a str> {
+    html.split(start)
+        .skip(1)
+        .map(|rest| rest.split(end).next().unwrap())
+        .collect()
+}
+
+struct Enrollment {
+    secret: String,
+    enrollment_code: String,
+    recovery_codes: Vec<String>,
+}
+
+/// Log in as the test user and enable two-factor authentication, the
+/// session is left logged in.
+async fn enroll(app: &TestApp) -> Enrollment {
+    app.login_as(&app.user).await;
+    let html_page = app.get_two_factor_enrollment_html().await;
+    let secret =
+        extract_between(&html_page, r#"<code id="totp-secret">"#, "</code>")[0].to_string();
+    let enrollment_code = totp(&secret).generate(now());
+
+    let response = app
+        .post_two_factor_enrollment(&json!({"code": enrollment_code}))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    let recovery_codes = extract_between(&html_page, "<li><code>", "</code></li>")
+        .into_iter()
+        .map(String::from)
+        .collect();
+    Enrollment {
+        secret,
+        enrollment_code,
+        recovery_codes,
+    }
+}
+
+#[tokio::test]
+async fn the_dashboard_links_to_the_two_factor_setup() {
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+
+    let html_page = app.get_admin_dashboard_html().await;
+
+    assert!(html_page.contains(r#"href="/admin/two-factor""#));
+}
+
+#[tokio::test]
+async fn the_enrollment_page_shows_a_qr_code_and_the_secret() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let first_page = app.get_two_factor_enrollment_html().await;
+    let second_page = app.get_two_factor_enrollment_html().await;
+
+    // Assert
+    assert!(first_page.contains("<svg"));
+    let secret = extract_between(&first_page, r#"<code id="totp-secret">"#, "</code>");
+    // Reloading the page keeps the secret the user may have already scanned.
+    assert_eq!(
+        secret,
+        extract_between(&second_page, r#"<code id="totp-secret">"#, "</code>")
+    );
+}
+
+#[tokio::test]
+async fn enrolling_issues_recovery_codes_and_stores_only_their_hashes() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let enrollment = enroll(&app).await;
+
+    // Assert
+    assert_eq!(enrollment.recovery_codes.len(), 10);
+    let saved = sqlx::query!(
+        "SELECT totp_secret FROM users WHERE user_id = $1",
+        app.user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.totp_secret, Some(enrollment.secret));
+    let hashes: Vec<String> = sqlx::query!("SELECT code_hash FROM recovery_codes")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.code_hash)
+        .collect();
+    assert_eq!(hashes.len(), 10);
+    for code in &enrollment.recovery_codes {
+        assert!(!hashes.contains(code));
+    }
+}
+
+#[tokio::test]
+async fn enrolling_with_a_wrong_code_does_not_enable_two_factor() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+    app.get_two_factor_enrollment_html().await;
+
+    // Act
+    let response = app
+        .post_two_factor_enrollment(&json!({"code": "000000"}))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    let html_page = app.get_two_factor_enrollment_html().await;
+    assert!(html_page.contains("The code is invalid"));
+    let saved = sqlx::query!(
+        "SELECT totp_secret FROM users WHERE user_id = $1",
+        app.user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(saved.totp_secret.is_none());
+}
+
+#[tokio::test]
+async fn replacing_an_enabled_second_factor_requires_the_current_one() {
+    // Arrange
+    let app = spawn_app().await;
+    let enrollment = enroll(&app).await;
+    let html_page = app.get_two_factor_enrollment_html().await;
+    assert!(html_page.contains(r#"name="current_code""#));
+    let new_secret =
+        extract_between(&html_page, r#"<code id="totp-secret">"#, "</code>")[0].to_string();
+    let saved_secret = || async {
+        sqlx::query!(
+            "SELECT totp_secret FROM users WHERE user_id = $1",
+            app.user.user_id
+        )
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .totp_secret
+    };
+
+    for current_code in [None, Some("000000")] {
+        // Act - Part 1 - Without a valid current code
+        let mut body = json!({"code": totp(&new_secret).generate(now())});
+        if let Some(current_code) = current_code {
+            body["current_code"] = json!(current_code);
+        }
+        let response = app.post_two_factor_enrollment(&body).await;
+
+        // Assert
+        assert_is_redirect_to(&response, "/admin/two-factor");
+        let html_page = app.get_two_factor_enrollment_html().await;
+        assert!(html_page.contains("The current code is invalid"));
+        assert_eq!(saved_secret().await, Some(enrollment.secret.clone()));
+    }
+
+    // Act - Part 2 - With a code from the current app
+    let response = app
+        .post_two_factor_enrollment(&json!({
+            "code": totp(&new_secret).generate(now()),
+            "current_code": totp(&enrollment.secret).generate(now() + 30),
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(saved_secret().await, Some(new_secret));
+}
+
+#[tokio::test]
+async fn users_without_two_factor_log_in_with_their_password_only() {
+    let app = spawn_app().await;
+
+    let response = app.login_as(&app.user).await;
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn logging_in_with_two_factor_enabled_requires_a_code() {
+    // Arrange
+    let app = spawn_app().await;
+    let enrollment = enroll(&app).await;
+    app.post_logout().await;
+
+    // Act - Part 1 - Password
+    let response = app.login_as(&app.user).await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+    let html_page = app.get_login_two_factor_html().await;
+    assert!(html_page.contains("Authentication code"));
+
+    // Act - Part 2 - The pending session is not authenticated yet
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 3 - Second factor, from the step after the enrollment code
+    let code = totp(&enrollment.secret).generate(now() + 30);
+    let response = app.post_login_two_factor(&json!({"code": code})).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    // Act - Part 4 - Follow the redirect
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn a_wrong_code_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    enroll(&app).await;
+    app.post_logout().await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let response = app
+        .post_login_two_factor(&json!({"code": "not-a-code"}))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/two-factor");
+    let html_page = app.get_login_two_factor_html().await;
+    assert!(html_page.contains("Invalid authentication code."));
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_enrollment_code_cannot_be_replayed_to_log_in() {
+    // Arrange
+    let app = spawn_app().await;
+    let enrollment = enroll(&app).await;
+    app.post_logout().await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let response = app
+        .post_login_two_factor(&json!({"code": enrollment.enrollment_code}))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn a_recovery_code_can_only_be_used_once() {
+    // Arrange
+    let app = spawn_app().await;
+    let enrollment = enroll(&app).await;
+    let recovery_code = &enrollment.recovery_codes[0];
+    app.post_logout().await;
+
+    // Act - Part 1 - First use
+    app.login_as(&app.user).await;
+    let response = app
+        .post_login_two_factor(&json!({"code": recovery_code}))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    app.post_logout().await;
+
+    // Act - Part 2 - Second use
+    app.login_as(&app.user).await;
+    let response = app
+        .post_login_two_factor(&json!({"code": recovery_code}))
+        .await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn too_many_wrong_codes_restart_the_login() {
+    // Arrange
+    let app = spawn_app().await;
+    enroll(&app).await;
+    app.post_logout().await;
+    app.login_as(&app.user).await;
+
+    // Act
+    for _ in 0..4 {
+        let response = app.post_login_two_factor(&json!({"code": "000000"})).await;
+        assert_is_redirect_to(&response, "/login/two-factor");
+    }
+    let response = app.post_login_two_factor(&json!({"code": "000000"})).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Too many invalid authentication codes"));
+}
+
+#[tokio::test]
+async fn a_code_without_a_verified_password_is_rejected() {
+    let app = spawn_app().await;
+
+    let response = app.post_login_two_factor(&json!({"code": "000000"})).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,274 @@
+//! tests/api/users.rs
+use crate::helpers::{TestUser, assert_is_redirect_to, spawn_app};
+use serde_json::json;
+
+#[tokio::test]
+async fn viewers_cannot_publish_newsletters() {
+    // Arrange
+    let app = spawn_app().await;
+    let viewer = TestUser::generate_with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    app.login_as(&viewer).await;
+
+    // Act
+    let form_response = app.get_newsletters_form().await;
+    let publish_response = app
+        .post_newsletters(json!({
+            "title": "Newsletter title",
+            "content_text": "Newsletter body as plain text",
+            "content_html": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(form_response.status().as_u16(), 403);
+    assert_eq!(publish_response.status().as_u16(), 403);
+    let n_issues = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_issues, 0);
+}
+
+#[tokio::test]
+async fn editors_can_publish_newsletters() {
+    // Arrange
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    app.login_as(&editor).await;
+
+    // Act
+    let response = app
+        .post_newsletters(json!({
+            "title": "Newsletter title",
+            "content_text": "Newsletter body as plain text",
+            "content_html": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+}
+
+#[tokio::test]
+async fn only_owners_can_manage_users() {
+    // Arrange
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    app.login_as(&editor).await;
+
+    // Act
+    let list_response = app.get_users().await;
+    let invite_response = app
+        .post_users("", &json!({"username": "mallory", "role": "owner"}))
+        .await;
+
+    // Assert
+    assert_eq!(list_response.status().as_u16(), 403);
+    assert_eq!(invite_response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn the_dashboard_only_links_to_allowed_actions() {
+    // Arrange
+    let app = spawn_app().await;
+    let viewer = TestUser::generate_with_role("viewer");
+    viewer.store(&app.db_pool).await;
+
+    // Act - Part 1 - Owner
+    app.login_as(&app.user).await;
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(r#"href="/admin/newsletters""#));
+    assert!(html_page.contains(r#"href="/admin/users""#));
+
+    // Act - Part 2 - Viewer
+    app.login_as(&viewer).await;
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(!html_page.contains(r#"href="/admin/newsletters""#));
+    assert!(!html_page.contains(r#"href="/admin/users""#));
+}
+
+#[tokio::test]
+async fn an_invited_user_can_log_in_with_the_temporary_password() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+
+    // Act - Part 1 - Invite
+    let response = app
+        .post_users("", &json!({"username": "ada", "role": "editor"}))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Cache-Control"], "no-store");
+
+    // Act - Part 2 - Read the temporary password, shown only once
+    let html_page = response.text().await.unwrap();
+    let temporary_password = html_page
+        .split("<code>")
+        .nth(1)
+        .and_then(|rest| rest.split("</code>").next())
+        .expect("The temporary password is not shown.")
+        .to_string();
+    assert!(html_page.contains("ada was invited as editor"));
+    assert!(!app.get_users_html().await.contains(&temporary_password));
+
+    // Act - Part 3 - Log in as the invited user
+    let response = app
+        .post_login(&json!({"username": "ada", "password": temporary_password}))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let saved = sqlx::query!("SELECT role FROM users WHERE username = 'ada'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.role, "editor");
+}
+
+#[tokio::test]
+async fn inviting_a_taken_username_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let response = app
+        .post_users(
+            "",
+            &json!({"username": app.user.username, "role": "viewer"}),
+        )
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("is already taken"));
+}
+
+#[tokio::test]
+async fn an_invalid_role_is_escaped_in_the_error_message() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let response = app
+        .post_users(
+            "",
+            &json!({"username": "new-user", "role": "<script>alert(1)</script>"}),
+        )
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("&lt;script&gt;alert(1)&lt;/script&gt; is not a valid role."));
+    assert!(!html_page.contains("<script>alert(1)</script>"));
+}
+
+#[tokio::test]
+async fn owners_can_change_the_role_of_other_users() {
+    // Arrange
+    let app = spawn_app().await;
+    let viewer = TestUser::generate_with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let response = app
+        .post_users(
+            &format!("/{}/role", viewer.user_id),
+            &json!({"role": "editor"}),
+        )
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/users");
+    let saved = sqlx::query!("SELECT role FROM users WHERE user_id = $1", viewer.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.role, "editor");
+}
+
+#[tokio::test]
+async fn owners_cannot_demote_or_deactivate_themselves() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login_as(&app.user).await;
+
+    // Act
+    app.post_users(
+        &format!("/{}/role", app.user.user_id),
+        &json!({"role": "viewer"}),
+    )
+    .await;
+    app.post_users(&format!("/{}/deactivate", app.user.user_id), &json!({}))
+        .await;
+
+    // Assert
+    let saved = sqlx::query!(
+        "SELECT role, is_active FROM users WHERE user_id = $1",
+        app.user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.role, "owner");
+    assert!(saved.is_active);
+}
+
+#[tokio::test]
+async fn deactivated_users_are_logged_out_and_cannot_log_back_in() {
+    // Arrange
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    app.login_as(&editor).await;
+
+    // Act - Part 1 - Deactivate the editor behind their back
+    sqlx::query!(
+        "UPDATE users SET is_active = FALSE WHERE user_id = $1",
+        editor.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act - Part 2 - The existing session is no longer accepted
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 3 - Logging in again fails
+    let response = app.login_as(&editor).await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn owners_can_deactivate_other_users() {
+    // Arrange
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    app.login_as(&app.user).await;
+
+    // Act
+    let response = app
+        .post_users(&format!("/{}/deactivate", editor.user_id), &json!({}))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/users");
+    let saved = sqlx::query!(
+        "SELECT is_active FROM users WHERE user_id = $1",
+        editor.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(!saved.is_active);
+}